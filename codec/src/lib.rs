@@ -12,8 +12,8 @@
 #![allow(clippy::range_plus_one)]
 
 use anyhow::{bail, Context as _, Error};
-use config::keyassignment::{PaneDirection, ScrollbackEraseMode};
-use mux::client::{ClientId, ClientInfo};
+use config::keyassignment::{PaneDirection, PaneOutputLog, PaneOutputPipe, ScrollbackEraseMode};
+use mux::client::{ClientId, ClientInfo, ClientPermission};
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PaneNode, SerdeUrl, SplitRequest, TabId};
@@ -385,6 +385,26 @@ macro_rules! pdu {
                 }
             }
 
+            /// The name and identifier of each type of Pdu
+            #[cfg(test)]
+            const VARIANTS: &'static [(&'static str, u64)] = &[$((stringify!($name), $vers),)*];
+
+            /// Returns the identifier and the serialized form of the Pdu,
+            /// without framing or compression
+            #[cfg(test)]
+            fn serialize_payload(&self) -> Result<(u64, Vec<u8>), Error> {
+                match self {
+                    Pdu::Invalid{..} => bail!("attempted to serialize Pdu::Invalid"),
+                    $(
+                        Pdu::$name(s) => {
+                            let mut data = Vec::new();
+                            s.serialize(&mut varbincode::Serializer::new(&mut data))?;
+                            Ok(($vers, data))
+                        }
+                    ,)*
+                }
+            }
+
             pub fn decode<R: std::io::Read>(r: R) -> Result<DecodedPdu, Error> {
                 let decoded = decode_raw(r).context("decoding a PDU")?;
                 match decoded.ident {
@@ -440,8 +460,32 @@ macro_rules! pdu {
 
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
-/// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 45;
+/// are made to the existing types and protocol.
+/// New types of Pdu should instead be associated with a new
+/// `Capabilities` flag, so that peers built from an older version
+/// of this code can continue to talk to us.
+pub const CODEC_VERSION: usize = 49;
+
+bitflags::bitflags! {
+    /// Optional protocol features, which are advertised by each peer
+    /// when it connects.  A Pdu that requires a capability is only
+    /// sent to a peer that has advertised it.  Flags that we don't
+    /// recognize are ignored, so a flag must never be reassigned.
+    #[derive(Default, Deserialize, Serialize)]
+    pub struct Capabilities: u64 {
+        /// The peer understands `SyncPane`
+        const SYNC_PANE = 1 << 0;
+        /// The peer understands `StartUdpSession`.  Servers only
+        /// advertise this when they are listening for datagrams.
+        const UDP_SESSIONS = 1 << 1;
+        /// The peer understands `GetMetrics`
+        const METRICS = 1 << 2;
+        /// The peer understands `SetPaneOutputLog`
+        const OUTPUT_LOG = 1 << 3;
+        /// The peer understands `SetPaneOutputPipe`
+        const OUTPUT_PIPE = 1 << 4;
+    }
+}
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -502,6 +546,17 @@ pdu! {
     GetPaneDirection: 60,
     GetPaneDirectionResponse: 61,
     AdjustPaneSize: 62,
+    SetClientIdResponse: 63,
+    SyncPane: 64,
+    SyncPaneResponse: 65,
+    StartUdpSession: 66,
+    StartUdpSessionResponse: 67,
+    NegotiateCapabilities: 68,
+    NegotiateCapabilitiesResponse: 69,
+    GetMetrics: 70,
+    GetMetricsResponse: 71,
+    SetPaneOutputLog: 72,
+    SetPaneOutputPipe: 73,
 }
 
 impl Pdu {
@@ -522,6 +577,51 @@ impl Pdu {
         }
     }
 
+    /// Returns true if this type of Pdu mutates the state of the
+    /// mux or its panes, and must therefore be refused when the
+    /// session is read-only
+    pub fn requires_write_permission(&self) -> bool {
+        match self {
+            Self::WriteToPane(_)
+            | Self::SendKeyDown(_)
+            | Self::SendMouseEvent(_)
+            | Self::SendPaste(_)
+            | Self::Resize(_)
+            | Self::KillPane(_)
+            | Self::SplitPane(_)
+            | Self::SpawnV2(_)
+            | Self::MovePaneToNewTab(_)
+            | Self::SetPaneZoomed(_)
+            | Self::AdjustPaneSize(_)
+            | Self::EraseScrollbackRequest(_)
+            | Self::SetPalette(_)
+            | Self::SetPaneOutputLog(_)
+            | Self::SetPaneOutputPipe(_)
+            | Self::SetWindowWorkspace(_)
+            | Self::RenameWorkspace(_)
+            | Self::WindowTitleChanged(_)
+            | Self::TabTitleChanged(_)
+            // The issued certificate grants a read-write session
+            | Self::GetTlsCreds(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the capabilities that the peer must have advertised
+    /// in order to understand this type of Pdu
+    pub fn required_capabilities(&self) -> Capabilities {
+        match self {
+            Self::SyncPane(_) | Self::SyncPaneResponse(_) => Capabilities::SYNC_PANE,
+            Self::StartUdpSession(_) | Self::StartUdpSessionResponse(_) => {
+                Capabilities::UDP_SESSIONS
+            }
+            Self::GetMetrics(_) | Self::GetMetricsResponse(_) => Capabilities::METRICS,
+            Self::SetPaneOutputLog(_) => Capabilities::OUTPUT_LOG,
+            Self::SetPaneOutputPipe(_) => Capabilities::OUTPUT_PIPE,
+            _ => Capabilities::empty(),
+        }
+    }
+
     pub fn stream_decode(buffer: &mut Vec<u8>) -> anyhow::Result<Option<DecodedPdu>> {
         let mut cursor = Cursor::new(buffer.as_slice());
         match Self::decode(&mut cursor) {
@@ -834,6 +934,15 @@ pub struct WindowWorkspaceChanged {
 pub struct SetClientId {
     pub client_id: ClientId,
     pub is_proxy: bool,
+    /// The permission that the client is requesting
+    pub permission: ClientPermission,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetClientIdResponse {
+    /// The permission granted to the client by the server,
+    /// which may be more restrictive than was requested
+    pub permission: ClientPermission,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -1109,6 +1218,92 @@ pub struct GetLinesResponse {
     pub lines: SerializedLines,
 }
 
+/// Requests a snapshot of a pane in a single round trip, so that a
+/// client can catch up after (re)connecting without fetching the
+/// lines one screenful at a time
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SyncPane {
+    pub pane_id: PaneId,
+    /// How many lines of scrollback above the viewport to include
+    pub scrollback_lines: usize,
+    /// If set, only the lines that have changed since this sequence
+    /// number are included, as the client already has the others
+    pub since_seqno: Option<SequenceNo>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SyncPaneResponse {
+    pub pane_id: PaneId,
+    pub mouse_grabbed: bool,
+    pub cursor_position: StableCursorPosition,
+    pub dimensions: RenderableDimensions,
+    pub title: String,
+    pub working_dir: Option<SerdeUrl>,
+    pub palette: ColorPalette,
+    /// The range of lines covered by this snapshot
+    pub rows: Range<StableRowIndex>,
+    /// True if `lines` holds only the lines from `rows` that changed
+    /// since the requested sequence number, rather than all of them
+    pub is_delta: bool,
+    pub lines: SerializedLines,
+    pub seqno: SequenceNo,
+}
+
+/// Asks the server to set up a session on its datagram transport.
+/// The session is granted the same access as the connection that
+/// requested it.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct StartUdpSession {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct StartUdpSessionResponse {
+    /// Identifies the session in each datagram
+    pub session_id: u64,
+    /// The key used to encrypt and authenticate the datagrams
+    pub key: [u8; 32],
+}
+
+/// Advertises the capabilities of the client.  Servers that predate
+/// capability negotiation respond with an error, in which case the
+/// client should assume that they have no capabilities.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct NegotiateCapabilities {
+    pub capabilities: Capabilities,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct NegotiateCapabilitiesResponse {
+    /// The capabilities of the server.  Each peer uses only those
+    /// capabilities that both of them have advertised.
+    pub capabilities: Capabilities,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetMetrics {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetMetricsResponse {
+    /// The metrics collected by the server, in the Prometheus
+    /// text exposition format
+    pub text: String,
+}
+
+/// Starts logging the output of a pane to a file on the server,
+/// or stops logging it if `log` is None
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneOutputLog {
+    pub pane_id: PaneId,
+    pub log: Option<PaneOutputLog>,
+}
+
+/// Starts piping the output of a pane through a command on the
+/// server, or stops piping it if `pipe` is None
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneOutputPipe {
+    pub pane_id: PaneId,
+    pub pipe: Option<PaneOutputPipe>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct EraseScrollbackRequest {
     pub pane_id: PaneId,
@@ -1142,6 +1337,9 @@ pub struct GetImageCellResponse {
     pub data: Option<Arc<ImageData>>,
 }
 
+#[cfg(test)]
+mod compat;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1250,6 +1448,68 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_pdu_set_client_id_response() {
+        let mut encoded = Vec::new();
+        Pdu::SetClientIdResponse(SetClientIdResponse {
+            permission: ClientPermission::ReadOnly,
+        })
+        .encode(&mut encoded, 0x43)
+        .unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x43,
+                pdu: Pdu::SetClientIdResponse(SetClientIdResponse {
+                    permission: ClientPermission::ReadOnly,
+                })
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pdu_sync_pane() {
+        let mut encoded = Vec::new();
+        let sync = SyncPane {
+            pane_id: 1,
+            scrollback_lines: 1000,
+            since_seqno: Some(42),
+        };
+        Pdu::SyncPane(sync).encode(&mut encoded, 0x44).unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x44,
+                pdu: Pdu::SyncPane(SyncPane {
+                    pane_id: 1,
+                    scrollback_lines: 1000,
+                    since_seqno: Some(42),
+                })
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pdu_start_udp_session() {
+        let mut encoded = Vec::new();
+        Pdu::StartUdpSessionResponse(StartUdpSessionResponse {
+            session_id: 0x1234_5678_9abc_def0,
+            key: [7; 32],
+        })
+        .encode(&mut encoded, 0x45)
+        .unwrap();
+        assert_eq!(
+            DecodedPdu {
+                serial: 0x45,
+                pdu: Pdu::StartUdpSessionResponse(StartUdpSessionResponse {
+                    session_id: 0x1234_5678_9abc_def0,
+                    key: [7; 32],
+                })
+            },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
     #[test]
     fn test_bogus_pdu() {
         let mut encoded = Vec::new();
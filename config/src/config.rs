@@ -169,6 +169,10 @@ pub struct Config {
     #[dynamic(default = "default_command_palette_bg_color")]
     pub command_palette_bg_color: RgbaColor,
 
+    /// Which sources contribute entries to ActivateCommandPalette
+    #[dynamic(default = "default_command_palette_providers")]
+    pub command_palette_providers: Vec<CommandPaletteProvider>,
+
     /// Font to use for PaneSelect
     #[dynamic(default)]
     pub pane_select_font: Option<TextStyle>,
@@ -385,6 +389,12 @@ pub struct Config {
     #[dynamic(default = "default_ratelimit_line_prefetches_per_second")]
     pub ratelimit_mux_line_prefetches_per_second: u32,
 
+    /// How many lines of scrollback the multiplexer client will
+    /// request, in addition to the viewport, when it synchronizes
+    /// a pane after attaching or reconnecting to the server.
+    #[dynamic(default = "default_mux_sync_scrollback_lines")]
+    pub mux_sync_scrollback_lines: usize,
+
     /// The buffer size used by parse_buffered_data in the mux module.
     /// This should not be too large, otherwise the processing cost
     /// of applying a batch of actions to the terminal will be too
@@ -395,6 +405,11 @@ pub struct Config {
     #[dynamic(default = "default_true")]
     pub mux_enable_ssh_agent: bool,
 
+    /// If set, wezterm-mux-server serves its metrics over HTTP,
+    /// in the Prometheus text format, on this unix domain socket
+    #[dynamic(default)]
+    pub mux_metrics_socket_path: Option<PathBuf>,
+
     #[dynamic(default)]
     pub default_ssh_auth_sock: Option<String>,
 
@@ -408,6 +423,12 @@ pub struct Config {
     #[dynamic(default = "default_mux_env_remove")]
     pub mux_env_remove: Vec<String>,
 
+    /// When multiple clients are attached to the same pane and
+    /// have differing sizes, specifies which of those sizes
+    /// the pane will adopt
+    #[dynamic(default)]
+    pub mux_client_size_policy: MuxClientSizePolicy,
+
     #[dynamic(default)]
     pub keys: Vec<Key>,
     #[dynamic(default)]
@@ -512,6 +533,12 @@ pub struct Config {
     #[dynamic(try_from = "crate::units::PixelUnit", default = "default_half_cell")]
     pub min_scroll_bar_height: Dimension,
 
+    /// If true, draw a marker in the left padding alongside each
+    /// shell-integration prompt, colored according to the exit
+    /// status of the command that was run at that prompt.
+    #[dynamic(default)]
+    pub show_command_gutter_markers: bool,
+
     /// If false, do not try to use a Wayland protocol connection
     /// when starting the gui frontend, and instead use X11.
     /// This option is only considered on X11/Wayland systems and
@@ -1652,10 +1679,26 @@ fn default_command_palette_bg_color() -> RgbaColor {
     (0x33, 0x33, 0x33).into()
 }
 
+fn default_command_palette_providers() -> Vec<CommandPaletteProvider> {
+    vec![
+        CommandPaletteProvider::Commands,
+        CommandPaletteProvider::LaunchMenu,
+        CommandPaletteProvider::Panes,
+        CommandPaletteProvider::Tabs,
+        CommandPaletteProvider::Workspaces,
+        CommandPaletteProvider::Domains,
+        CommandPaletteProvider::RecentCommands,
+    ]
+}
+
 fn default_swallow_mouse_click_on_window_focus() -> bool {
     cfg!(target_os = "macos")
 }
 
+fn default_mux_sync_scrollback_lines() -> usize {
+    1000
+}
+
 fn default_mux_output_parser_coalesce_delay_ms() -> u64 {
     3
 }
@@ -2028,6 +2071,19 @@ pub enum ExitBehavior {
     Hold,
 }
 
+/// How the multiplexer server resolves the sizes requested by
+/// multiple clients that are attached to the same pane
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MuxClientSizePolicy {
+    /// Use the size of the smallest client
+    Smallest,
+    /// Use the size of the largest client
+    Largest,
+    /// Use the size of the client that most recently sent input
+    #[default]
+    MostRecentlyActive,
+}
+
 #[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ExitBehaviorMessaging {
     #[default]
@@ -2421,6 +2477,76 @@ pub enum ImePreeditRendering {
     System,
 }
 
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommandPaletteProvider {
+    /// Key assignments plus entries from the augment-command-palette event
+    Commands,
+    /// Items from the launch_menu
+    LaunchMenu,
+    /// Panes in the tabs of the current window
+    Panes,
+    /// Tabs in the current window
+    Tabs,
+    /// Workspaces known to the mux
+    Workspaces,
+    /// Spawnable and attachable domains
+    Domains,
+    /// Command lines recovered from OSC 133 input zones in the active pane
+    RecentCommands,
+}
+
+impl CommandPaletteProvider {
+    /// The character that, when typed at the start of the palette
+    /// input, restricts the matches to this provider
+    pub fn prefix(self) -> char {
+        match self {
+            Self::Commands | Self::LaunchMenu => '>',
+            Self::Panes | Self::Tabs | Self::Workspaces | Self::Domains => '@',
+            Self::RecentCommands => '#',
+        }
+    }
+
+    /// Returns the provider scope selected by the leading character
+    /// of the palette input, along with the remaining query text
+    pub fn split_scope(input: &str) -> (Option<char>, &str) {
+        match input.chars().next() {
+            Some(c @ ('>' | '@' | '#')) => (Some(c), input[1..].trim_start()),
+            _ => (None, input),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_scope() {
+        assert_eq!(CommandPaletteProvider::split_scope("vim"), (None, "vim"));
+        assert_eq!(
+            CommandPaletteProvider::split_scope("@ vim"),
+            (Some('@'), "vim")
+        );
+        assert_eq!(
+            CommandPaletteProvider::split_scope("#make"),
+            (Some('#'), "make")
+        );
+        assert_eq!(CommandPaletteProvider::split_scope(">"), (Some('>'), ""));
+        assert_eq!(CommandPaletteProvider::split_scope(""), (None, ""));
+    }
+
+    #[test]
+    fn prefixes_are_recognized_scopes() {
+        for provider in default_command_palette_providers() {
+            let input = format!("{}x", provider.prefix());
+            assert_eq!(
+                CommandPaletteProvider::split_scope(&input),
+                (Some(provider.prefix()), "x")
+            );
+        }
+    }
+}
+
 #[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
 pub enum NotificationHandling {
     #[default]
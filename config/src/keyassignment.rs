@@ -569,6 +569,10 @@ pub enum KeyAssignment {
     ScrollByLine(isize),
     ScrollByCurrentEventWheelDelta,
     ScrollToPrompt(isize),
+    SelectCommandOutput,
+    CopyCommandOutput(ClipboardCopyDestination),
+    ToggleCommandOutputFold,
+    RerunCommand,
     ScrollToTop,
     ScrollToBottom,
     ShowTabNavigator,
@@ -582,6 +586,8 @@ pub enum KeyAssignment {
     ShowLauncher,
     ShowLauncherArgs(LauncherActionArgs),
     ClearScrollback(ScrollbackEraseMode),
+    StartPaneOutputLog(PaneOutputLog),
+    StopPaneOutputLog,
     Search(Pattern),
     ActivateCopyMode,
 
@@ -632,6 +638,10 @@ pub enum KeyAssignment {
     AttachDomain(String),
 
     CopyMode(CopyModeAssignment),
+    LauncherMode(LauncherModeAssignment),
+    PaletteMode(PaletteModeAssignment),
+    CharSelectorMode(CharSelectorModeAssignment),
+    PaneSelectorMode(PaneSelectorModeAssignment),
     RotatePanes(RotationDirection),
     SplitPane(SplitPane),
     PaneSelect(PaneSelectArguments),
@@ -660,6 +670,53 @@ pub struct SplitPane {
     pub top_level: bool,
 }
 
+/// Describes where and how the output of a pane is logged
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic, Serialize, Deserialize)]
+pub struct PaneOutputLog {
+    /// The file that the output is appended to
+    pub path: PathBuf,
+    /// If true, escape sequences are removed from the output,
+    /// leaving only printable text, tabs and line breaks
+    #[dynamic(default)]
+    pub strip_ansi: bool,
+    /// Rotate the file before it would grow larger than this
+    /// many bytes
+    #[dynamic(default)]
+    pub max_size: Option<u64>,
+    /// Rotate the file once it has been written to for this
+    /// many seconds
+    #[dynamic(default)]
+    pub rotate_interval_seconds: Option<u64>,
+    /// How many rotated files to keep.  They are named by
+    /// appending `.1`, `.2` and so on to `path`, with `.1`
+    /// being the most recent.
+    #[dynamic(default = "default_output_log_max_files")]
+    pub max_files: usize,
+}
+
+impl_lua_conversion_dynamic!(PaneOutputLog);
+
+fn default_output_log_max_files() -> usize {
+    5
+}
+
+/// Describes a command that the output of a pane is piped through
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic, Serialize, Deserialize)]
+pub struct PaneOutputPipe {
+    /// The program and its arguments.  The output of the pane is
+    /// written to its stdin.
+    pub args: Vec<String>,
+    /// The directory in which the command is run
+    #[dynamic(default)]
+    pub cwd: Option<PathBuf>,
+    /// If true, the stdout of the command is parsed and injected
+    /// into the pane as though the pane had output it
+    #[dynamic(default)]
+    pub inject_output: bool,
+}
+
+impl_lua_conversion_dynamic!(PaneOutputPipe);
+
 #[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum SplitSize {
     Cells(usize),
@@ -722,6 +779,56 @@ pub enum CopyModeAssignment {
     JumpReverse,
 }
 
+/// Actions that can be bound in the `launcher_mode` key table
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum LauncherModeAssignment {
+    Close,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    Accept,
+    StartFilter,
+    DeleteBackward,
+    ClearInput,
+}
+
+/// Actions that can be bound in the `palette_mode` key table
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum PaletteModeAssignment {
+    Close,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    Accept,
+    DeleteBackward,
+    ClearInput,
+}
+
+/// Actions that can be bound in the `char_selector_mode` key table
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum CharSelectorModeAssignment {
+    Close,
+    MoveUp,
+    MoveDown,
+    PageUp,
+    PageDown,
+    Accept,
+    DeleteBackward,
+    ClearInput,
+    NextGroup,
+    PriorGroup,
+}
+
+/// Actions that can be bound in the `pane_selector_mode` key table
+#[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum PaneSelectorModeAssignment {
+    Close,
+    DeleteBackward,
+    ClearInput,
+}
+
 pub type KeyTable = HashMap<(KeyCode, Modifiers), KeyTableEntry>;
 
 #[derive(Debug, Clone, Default)]
@@ -33,6 +33,7 @@ pub mod keyassignment;
 mod keys;
 pub mod lua;
 pub mod meta;
+mod mux_access;
 mod scheme_data;
 mod serial;
 mod ssh;
@@ -54,6 +55,7 @@ pub use exec_domain::*;
 pub use font::*;
 pub use frontend::*;
 pub use keys::*;
+pub use mux_access::*;
 pub use serial::*;
 pub use ssh::*;
 pub use terminal::*;
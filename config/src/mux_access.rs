@@ -0,0 +1,81 @@
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// Describes a class of peer that is permitted to connect to a
+/// multiplexer server listener, and the restrictions placed upon it.
+/// When a listener has a non-empty list of rules, the first rule that
+/// matches the connecting peer determines its access.
+#[derive(Default, Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub struct MuxAccessRule {
+    /// For unix domains, the uid of the connecting process.
+    /// If unspecified, the rule applies to any uid.
+    pub uid: Option<u32>,
+
+    /// For tls servers, the CN of the client certificate.
+    /// A trailing `*` matches any suffix.
+    /// If unspecified, the rule applies to any CN.
+    pub common_name: Option<String>,
+
+    /// If non-empty, the client may only see and interact with
+    /// windows in these workspaces.
+    #[dynamic(default)]
+    pub workspaces: Vec<String>,
+
+    /// If true, the client is only granted read-only access,
+    /// regardless of what it requests.
+    #[dynamic(default)]
+    pub read_only: bool,
+}
+
+impl MuxAccessRule {
+    /// Returns true if this rule applies to a unix domain peer
+    /// with the specified uid
+    pub fn matches_uid(&self, uid: u32) -> bool {
+        self.common_name.is_none() && self.uid.map(|u| u == uid).unwrap_or(true)
+    }
+
+    /// Returns true if this rule applies to a tls peer whose
+    /// certificate has the specified CN
+    pub fn matches_common_name(&self, cn: &str) -> bool {
+        if self.uid.is_some() {
+            return false;
+        }
+        match &self.common_name {
+            None => true,
+            Some(pattern) => match pattern.strip_suffix('*') {
+                Some(prefix) => cn.starts_with(prefix),
+                None => pattern == cn,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_mux_access_rule_matching() {
+    let any = MuxAccessRule::default();
+    assert!(any.matches_uid(1000));
+    assert!(any.matches_common_name("wez"));
+
+    let uid = MuxAccessRule {
+        uid: Some(1000),
+        ..Default::default()
+    };
+    assert!(uid.matches_uid(1000));
+    assert!(!uid.matches_uid(1001));
+    assert!(!uid.matches_common_name("wez"));
+
+    let exact = MuxAccessRule {
+        common_name: Some("wez".to_string()),
+        ..Default::default()
+    };
+    assert!(exact.matches_common_name("wez"));
+    assert!(!exact.matches_common_name("wezterm"));
+    assert!(!exact.matches_uid(1000));
+
+    let prefix = MuxAccessRule {
+        common_name: Some("user:wez/*".to_string()),
+        ..Default::default()
+    };
+    assert!(prefix.matches_common_name("user:wez/laptop"));
+    assert!(!prefix.matches_common_name("user:bob/laptop"));
+}
@@ -81,6 +81,11 @@ pub struct SshDomain {
     #[dynamic(default)]
     pub overlay_lag_indicator: bool,
 
+    /// Connect as a read-only client: panes can be observed but
+    /// input, resizing, spawning and killing are refused by the server.
+    #[dynamic(default)]
+    pub read_only: bool,
+
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
     /// Override the entire `wezterm cli proxy` invocation that would otherwise
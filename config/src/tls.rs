@@ -24,6 +24,18 @@ pub struct TlsDomainServer {
     /// to the trust store.
     #[dynamic(default)]
     pub pem_root_certs: Vec<PathBuf>,
+
+    /// Controls which client certificates are permitted to connect.
+    /// When empty, the certificate CN must match the user running
+    /// the server.
+    #[dynamic(default)]
+    pub access_rules: Vec<MuxAccessRule>,
+
+    /// If set, the address:port combination on which the server will
+    /// accept datagram sessions.  Clients authenticate over TLS and
+    /// then switch to the datagram transport, which survives changes
+    /// to the client's network address.
+    pub udp_bind_address: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
@@ -94,6 +106,17 @@ pub struct TlsDomainClient {
     /// instead.
     #[dynamic(default)]
     pub overlay_lag_indicator: bool,
+
+    /// Connect as a read-only client: panes can be observed but
+    /// input, resizing, spawning and killing are refused by the server.
+    #[dynamic(default)]
+    pub read_only: bool,
+
+    /// If set, identifies the host:port pair of the server's datagram
+    /// transport.  After authenticating over TLS, the client switches
+    /// to the datagram transport so that the session survives moving
+    /// between networks.
+    pub udp_remote_address: Option<String>,
 }
 
 impl TlsDomainClient {
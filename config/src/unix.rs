@@ -62,6 +62,17 @@ pub struct UnixDomain {
     /// instead.
     #[dynamic(default)]
     pub overlay_lag_indicator: bool,
+
+    /// Connect as a read-only client: panes can be observed but
+    /// input, resizing, spawning and killing are refused by the server.
+    #[dynamic(default)]
+    pub read_only: bool,
+
+    /// When acting as the server for this domain, controls which
+    /// peers may connect.  When empty, any process that can open
+    /// the socket is permitted.
+    #[dynamic(default)]
+    pub access_rules: Vec<MuxAccessRule>,
 }
 
 impl Default for UnixDomain {
@@ -78,6 +89,8 @@ impl Default for UnixDomain {
             local_echo_threshold_ms: None,
             proxy_command: None,
             overlay_lag_indicator: false,
+            read_only: false,
+            access_rules: vec![],
         }
     }
 }
@@ -1,4 +1,5 @@
 use super::*;
+use config::keyassignment::{PaneOutputLog, PaneOutputPipe};
 use luahelper::mlua::LuaSerdeExt;
 use luahelper::{dynamic_to_lua_value, from_lua, to_lua};
 use mlua::Value;
@@ -22,65 +23,7 @@ impl MuxPane {
     fn get_text_from_semantic_zone(&self, zone: SemanticZone) -> mlua::Result<String> {
         let mux = get_mux()?;
         let pane = self.resolve(&mux)?;
-
-        let mut last_was_wrapped = false;
-        let first_row = zone.start_y;
-        let last_row = zone.end_y;
-
-        fn cols_for_row(zone: &SemanticZone, row: StableRowIndex) -> std::ops::Range<usize> {
-            if row < zone.start_y || row > zone.end_y {
-                0..0
-            } else if zone.start_y == zone.end_y {
-                // A single line zone
-                if zone.start_x <= zone.end_x {
-                    zone.start_x..zone.end_x.saturating_add(1)
-                } else {
-                    zone.end_x..zone.start_x.saturating_add(1)
-                }
-            } else if row == zone.end_y {
-                // last line of multi-line
-                0..zone.end_x.saturating_add(1)
-            } else if row == zone.start_y {
-                // first line of multi-line
-                zone.start_x..usize::max_value()
-            } else {
-                // some "middle" line of multi-line
-                0..usize::max_value()
-            }
-        }
-
-        let mut s = String::new();
-        for line in pane.get_logical_lines(zone.start_y..zone.end_y + 1) {
-            if !s.is_empty() && !last_was_wrapped {
-                s.push('\n');
-            }
-            let last_idx = line.physical_lines.len().saturating_sub(1);
-            for (idx, phys) in line.physical_lines.iter().enumerate() {
-                let this_row = line.first_row + idx as StableRowIndex;
-                if this_row >= first_row && this_row <= last_row {
-                    let last_phys_idx = phys.len().saturating_sub(1);
-
-                    let cols = cols_for_row(&zone, this_row);
-                    let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
-                    let col_span = phys.columns_as_str(cols);
-                    // Only trim trailing whitespace if we are the last line
-                    // in a wrapped sequence
-                    if idx == last_idx {
-                        s.push_str(col_span.trim_end());
-                    } else {
-                        s.push_str(&col_span);
-                    }
-
-                    last_was_wrapped = last_col_idx == last_phys_idx
-                        && phys
-                            .get_cell(last_col_idx)
-                            .map(|c| c.attrs().wrapped())
-                            .unwrap_or(false);
-                }
-            }
-        }
-
-        Ok(s)
+        Ok(pane.get_text_from_semantic_zone(&zone))
     }
 }
 
@@ -123,6 +66,35 @@ impl UserData for MuxPane {
                 .map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
             Ok(())
         });
+
+        methods.add_method("start_output_log", |_, this, log: PaneOutputLog| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_output_log(Some(&log))
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        });
+
+        methods.add_method("stop_output_log", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_output_log(None)
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        });
+
+        methods.add_method("start_output_pipe", |_, this, pipe: PaneOutputPipe| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_output_pipe(Some(&pipe))
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        });
+
+        methods.add_method("stop_output_pipe", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_output_pipe(None)
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+        });
+
         methods.add_method("window", |_, this, _: ()| {
             let mux = get_mux()?;
             Ok(mux
@@ -320,6 +292,28 @@ impl UserData for MuxPane {
             Ok(zones)
         });
 
+        methods.add_method("get_command_blocks", |lua, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+
+            let blocks = pane
+                .get_command_blocks()
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+
+            let blocks = to_lua(lua, blocks)?;
+            Ok(blocks)
+        });
+
+        methods.add_method(
+            "set_command_output_folded",
+            |_, this, (row, folded): (StableRowIndex, Option<bool>)| {
+                let mux = get_mux()?;
+                let pane = this.resolve(&mux)?;
+                pane.set_command_output_folded(row, folded.unwrap_or(true))
+                    .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+            },
+        );
+
         methods.add_method(
             "get_semantic_zone_at",
             |lua, this, (x, y): (usize, StableRowIndex)| {
@@ -39,6 +39,31 @@ impl ClientId {
     }
 }
 
+/// Controls what a client session is allowed to do to the mux
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ClientPermission {
+    /// The client may send input to, resize, spawn and kill panes
+    #[default]
+    ReadWrite,
+    /// The client may only observe the output of panes
+    ReadOnly,
+}
+
+impl ClientPermission {
+    pub fn is_read_only(self) -> bool {
+        self == Self::ReadOnly
+    }
+}
+
+impl std::fmt::Display for ClientPermission {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::ReadWrite => write!(f, "read-write"),
+            Self::ReadOnly => write!(f, "read-only"),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct ClientInfo {
     pub client_id: Arc<ClientId>,
@@ -52,16 +77,20 @@ pub struct ClientInfo {
     pub last_input: DateTime<Utc>,
     /// The currently-focused pane
     pub focused_pane_id: Option<PaneId>,
+    /// What the client is permitted to do
+    #[serde(default)]
+    pub permission: ClientPermission,
 }
 
 impl ClientInfo {
-    pub fn new(client_id: Arc<ClientId>) -> Self {
+    pub fn new(client_id: Arc<ClientId>, permission: ClientPermission) -> Self {
         Self {
             client_id,
             connected_at: Utc::now(),
             active_workspace: None,
             last_input: Utc::now(),
             focused_pane_id: None,
+            permission,
         }
     }
 
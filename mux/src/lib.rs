@@ -1,4 +1,4 @@
-use crate::client::{ClientId, ClientInfo};
+use crate::client::{ClientId, ClientInfo, ClientPermission};
 use crate::pane::{CachePolicy, Pane, PaneId};
 use crate::ssh_agent::AgentProxy;
 use crate::tab::{SplitRequest, Tab, TabId};
@@ -37,7 +37,10 @@ pub mod activity;
 pub mod client;
 pub mod connui;
 pub mod domain;
+pub mod localecho;
 pub mod localpane;
+pub mod output_log;
+pub mod output_pipe;
 pub mod pane;
 pub mod renderable;
 pub mod ssh;
@@ -157,6 +160,7 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
                 break;
             }
             Ok(size) => {
+                let start = Instant::now();
                 parser.parse(&buf[0..size], |action| {
                     let mut flush = false;
                     match &action {
@@ -190,6 +194,7 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
                         action_size = 0;
                     }
                 });
+                histogram!("parse_buffered_data.parse.latency").record(start.elapsed());
                 action_size += size;
                 if !actions.is_empty() && !hold {
                     // If we haven't accumulated too much data,
@@ -316,6 +321,8 @@ fn read_from_pane_pty(
         tx.write_all(banner.as_bytes()).ok();
     }
 
+    let output_bytes = metrics::counter!("pane.output.bytes", "pane" => pane_id.to_string());
+
     while !dead.load(Ordering::Relaxed) {
         match reader.read(&mut buf) {
             Ok(size) if size == 0 => {
@@ -328,6 +335,9 @@ fn read_from_pane_pty(
             }
             Ok(size) => {
                 histogram!("read_from_pane_pty.bytes.rate").record(size as f64);
+                output_bytes.increment(size as u64);
+                output_log::log_output(pane_id, &buf[..size]);
+                output_pipe::pipe_output(pane_id, &buf[..size]);
                 log::trace!("read_pty pane {pane_id} read {size} bytes");
                 if let Err(err) = tx.write_all(&buf[..size]) {
                     error!(
@@ -564,10 +574,10 @@ impl Mux {
         Ok(())
     }
 
-    pub fn register_client(&self, client_id: Arc<ClientId>) {
-        self.clients
-            .write()
-            .insert((*client_id).clone(), ClientInfo::new(client_id));
+    pub fn register_client(&self, client_id: Arc<ClientId>, permission: ClientPermission) {
+        let mut clients = self.clients.write();
+        clients.insert((*client_id).clone(), ClientInfo::new(client_id, permission));
+        metrics::gauge!("mux.clients").set(clients.len() as f64);
     }
 
     pub fn iter_clients(&self) -> Vec<ClientInfo> {
@@ -686,7 +696,9 @@ impl Mux {
     }
 
     pub fn unregister_client(&self, client_id: &ClientId) {
-        self.clients.write().remove(client_id);
+        let mut clients = self.clients.write();
+        clients.remove(client_id);
+        metrics::gauge!("mux.clients").set(clients.len() as f64);
     }
 
     pub fn subscribe<F>(&self, subscriber: F)
@@ -815,6 +827,8 @@ impl Mux {
         if let Some(pane) = self.panes.write().remove(&pane_id).clone() {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
+            output_log::stop(pane_id);
+            output_pipe::stop(pane_id);
             self.notify(MuxNotification::PaneRemoved(pane_id));
             changed = true;
         }
@@ -0,0 +1,522 @@
+//! Speculative local echo for panes whose content is produced by a
+//! remote host.  When the round trip time to the host is high, we
+//! predict the effect of printable input and cursor motion at the
+//! prompt and render it immediately, underlined, until the host
+//! confirms it.  If a prediction turns out to be wrong, all of the
+//! outstanding predictions are discarded so that we fall back to
+//! showing exactly what the host sent.
+use crate::pane::WithPaneLines;
+use crate::renderable::StableCursorPosition;
+use std::time::{Duration, Instant};
+use termwiz::cell::{Cell, CellAttributes, SemanticType, Underline};
+use termwiz::surface::Line;
+use wezterm_term::{KeyCode, KeyModifiers, StableRowIndex};
+
+/// How long to wait for a prediction to be confirmed, in addition to
+/// twice the round trip time, before deciding that it was wrong
+const CONFIRMATION_GRACE: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+struct Prediction {
+    row: StableRowIndex,
+    col: usize,
+    /// The cell that we expect the host to place at row, col.
+    /// None for predictions that only move the cursor.
+    cell: Option<Cell>,
+    /// Where we expect the cursor to be after this prediction
+    cursor_x: usize,
+    made_at: Instant,
+}
+
+pub struct LocalEcho {
+    /// Predict only when the round trip time is at least this long.
+    /// If None, local echo is disabled.
+    threshold: Option<Duration>,
+    /// Smoothed round trip time
+    rtt: Option<Duration>,
+    /// When we sent input that we haven't yet seen a response to
+    input_sent_at: Option<Instant>,
+    /// Set when we sent input whose effect we cannot predict, such as
+    /// pressing enter.  We won't predict anything further until the
+    /// host has had a chance to respond to it.
+    suspended_at: Option<Instant>,
+    predictions: Vec<Prediction>,
+}
+
+impl LocalEcho {
+    pub fn new(threshold: Option<Duration>) -> Self {
+        Self {
+            threshold,
+            rtt: None,
+            input_sent_at: None,
+            suspended_at: None,
+            predictions: vec![],
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: Option<Duration>) {
+        self.threshold = threshold;
+        if threshold.is_none() {
+            self.predictions.clear();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold.is_some()
+    }
+
+    fn should_predict(&self) -> bool {
+        match (self.threshold, self.rtt) {
+            (Some(threshold), Some(rtt)) => rtt >= threshold,
+            _ => false,
+        }
+    }
+
+    /// How long a prediction may remain unconfirmed before we decide
+    /// that it was wrong.  Callers should arrange to render the pane
+    /// again after this long so that mispredictions get rolled back.
+    pub fn timeout(&self) -> Duration {
+        self.rtt.unwrap_or_default() * 2 + CONFIRMATION_GRACE
+    }
+
+    /// Incorporate a round trip time measured by the caller
+    pub fn record_rtt(&mut self, sample: Duration) {
+        self.rtt = Some(match self.rtt {
+            None => sample,
+            Some(rtt) => (rtt * 7 + sample) / 8,
+        });
+    }
+
+    /// Note that input was sent to the host.  The next call to
+    /// `output_received` will measure the round trip time from here.
+    pub fn input_sent(&mut self) {
+        if self.input_sent_at.is_none() {
+            self.input_sent_at.replace(Instant::now());
+        }
+    }
+
+    /// Note that output was received from the host
+    pub fn output_received(&mut self) {
+        if let Some(sent) = self.input_sent_at.take() {
+            self.record_rtt(sent.elapsed());
+        }
+    }
+
+    /// Returns true if there are any outstanding predictions
+    pub fn has_predictions(&self) -> bool {
+        !self.predictions.is_empty()
+    }
+
+    /// Returns true if there are predictions that need to be overlaid
+    /// on the content of `row`
+    pub fn has_predictions_for(&self, row: StableRowIndex) -> bool {
+        self.predictions.iter().any(|p| p.row == row)
+    }
+
+    /// Returns the rows that currently have predictions
+    pub fn predicted_rows(&self) -> Vec<StableRowIndex> {
+        let mut rows: Vec<StableRowIndex> = self.predictions.iter().map(|p| p.row).collect();
+        rows.dedup();
+        rows
+    }
+
+    /// Returns the position at which the cursor should be displayed,
+    /// given its actual position
+    pub fn cursor_position(&self, actual: &StableCursorPosition) -> StableCursorPosition {
+        match self.predictions.last() {
+            Some(p) => StableCursorPosition {
+                x: p.cursor_x,
+                y: p.row,
+                ..*actual
+            },
+            None => *actual,
+        }
+    }
+
+    /// Overlay the predictions for `row` onto `line`
+    pub fn apply_to_line(&self, row: StableRowIndex, line: &mut Line) {
+        // Bump the seqno so that anything caching the rendered form
+        // of this line notices that it differs from the original
+        let seqno = line.current_seqno() + 1;
+        for p in &self.predictions {
+            if p.row != row {
+                continue;
+            }
+            if let Some(cell) = &p.cell {
+                let mut cell = cell.clone();
+                if cell.str() != " " {
+                    cell.attrs_mut().set_underline(Underline::Double);
+                }
+                line.set_cell(p.col, cell, seqno);
+            }
+        }
+    }
+
+    /// Returns true if it looks like `col` is somewhere that the user
+    /// is entering input at a prompt.  When the shell uses OSC 133 to
+    /// mark up its prompt and input, we can tell precisely; otherwise
+    /// we assume that it is, unless the line looks like a password
+    /// prompt, as we don't want to reveal the password.
+    fn is_input_position(line: &Line, col: usize, allow_prompt: bool) -> bool {
+        let has_zones = line
+            .visible_cells()
+            .any(|cell| cell.attrs().semantic_type() != SemanticType::Output);
+        if !has_zones {
+            return !line.as_str().contains("sword");
+        }
+        match line.get_cell(col).map(|cell| cell.attrs().semantic_type()) {
+            Some(SemanticType::Input) => true,
+            Some(SemanticType::Prompt) => allow_prompt,
+            _ => false,
+        }
+    }
+
+    /// Returns true if there is nothing other than blanks at or to
+    /// the right of `col`
+    fn is_blank_from(line: &Line, col: usize) -> bool {
+        line.visible_cells()
+            .filter(|cell| cell.cell_index() >= col)
+            .all(|cell| cell.str() == " ")
+    }
+
+    /// Note that input whose effect we cannot predict was sent
+    fn suspend(&mut self) {
+        self.suspended_at.replace(Instant::now());
+    }
+
+    /// Predict the effect of a key press.  `cursor` is the actual
+    /// cursor position and `line` the actual content of the row that
+    /// it is on.  Returns true if a prediction was made, in which case
+    /// the caller should arrange to render the pane.
+    pub fn predict_key(
+        &mut self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        cursor: &StableCursorPosition,
+        line: &Line,
+        cols: usize,
+    ) -> bool {
+        if !self.should_predict() {
+            return false;
+        }
+        if self.suspended_at.is_some()
+            || (mods != KeyModifiers::NONE && mods != KeyModifiers::SHIFT)
+        {
+            self.suspend();
+            return false;
+        }
+
+        let row = cursor.y;
+        if self.predictions.iter().any(|p| p.row != row) {
+            // The host moved the cursor to another line; our
+            // predictions are no longer relevant
+            self.predictions.clear();
+        }
+
+        let mut line = line.clone();
+        self.apply_to_line(row, &mut line);
+        let x = self.cursor_position(cursor).x;
+
+        let (col, cell, cursor_x) = match key {
+            KeyCode::Char(c) if !c.is_control() => {
+                let cell = Cell::new(c, CellAttributes::default());
+                let width = cell.width();
+                if x + width > cols
+                    || !Self::is_blank_from(&line, x)
+                    || (x > 0 && !Self::is_input_position(&line, x - 1, true))
+                {
+                    self.suspend();
+                    return false;
+                }
+                (x, Some(cell), x + width)
+            }
+            KeyCode::Backspace
+                if x > 0
+                    && Self::is_blank_from(&line, x)
+                    && Self::is_input_position(&line, x - 1, false) =>
+            {
+                (x - 1, Some(Cell::blank()), x - 1)
+            }
+            KeyCode::LeftArrow if x > 0 && Self::is_input_position(&line, x - 1, false) => {
+                (x - 1, None, x - 1)
+            }
+            KeyCode::RightArrow
+                if !Self::is_blank_from(&line, x) && Self::is_input_position(&line, x, false) =>
+            {
+                (x + 1, None, x + 1)
+            }
+            _ => {
+                self.suspend();
+                return false;
+            }
+        };
+
+        if cell.is_some() {
+            // Supersede any earlier prediction for the same cell
+            self.predictions
+                .retain(|p| !(p.cell.is_some() && p.row == row && p.col == col));
+        }
+        self.predictions.push(Prediction {
+            row,
+            col,
+            cell,
+            cursor_x,
+            made_at: Instant::now(),
+        });
+        true
+    }
+
+    /// Predict the effect of pasting `text`.  Only pastes that fit
+    /// on the current line are predicted.
+    pub fn predict_paste(
+        &mut self,
+        text: &str,
+        cursor: &StableCursorPosition,
+        line: &Line,
+        cols: usize,
+    ) -> bool {
+        if !self.should_predict() {
+            return false;
+        }
+        if text.contains(|c: char| c.is_control()) {
+            self.suspend();
+            return false;
+        }
+        let mut predicted = false;
+        for c in text.chars() {
+            if !self.predict_key(KeyCode::Char(c), KeyModifiers::NONE, cursor, line, cols) {
+                break;
+            }
+            predicted = true;
+        }
+        predicted
+    }
+
+    /// Compare the outstanding predictions with the actual state of
+    /// the pane, as supplied by `line_for_row`, which should return
+    /// None for rows whose current content is not known.  Predictions
+    /// that have come true are dropped, as the actual content is now
+    /// the same.  If any prediction has been outstanding for too long
+    /// then we assume that we guessed wrong and discard them all.
+    /// Returns true if the predictions changed.
+    pub fn validate<'a, F>(&mut self, cursor: &StableCursorPosition, line_for_row: F) -> bool
+    where
+        F: Fn(StableRowIndex) -> Option<&'a Line>,
+    {
+        let timeout = self.timeout();
+        let before = self.predictions.len();
+        let mut expired = false;
+
+        self.predictions.retain(|p| {
+            let confirmed = match &p.cell {
+                Some(cell) => match line_for_row(p.row) {
+                    Some(line) => match line.get_cell(p.col) {
+                        Some(actual) => actual.str() == cell.str(),
+                        None => cell.str() == " ",
+                    },
+                    None => false,
+                },
+                None => cursor.y == p.row && cursor.x == p.cursor_x,
+            };
+            if !confirmed && p.made_at.elapsed() > timeout {
+                expired = true;
+            }
+            !confirmed
+        });
+
+        if expired {
+            log::trace!("local echo mispredicted; discarding {:?}", self.predictions);
+            self.predictions.clear();
+        }
+
+        if self.predictions.is_empty() {
+            if let Some(suspended) = self.suspended_at {
+                if suspended.elapsed() > self.rtt.unwrap_or_default() {
+                    self.suspended_at.take();
+                }
+            }
+        }
+
+        self.predictions.len() != before
+    }
+}
+
+/// Wraps a WithPaneLines implementation so that it sees the lines
+/// with the predictions from a LocalEcho overlaid
+pub struct WithLocalEcho<'a> {
+    pub echo: &'a LocalEcho,
+    pub inner: &'a mut dyn WithPaneLines,
+}
+
+impl<'a> WithPaneLines for WithLocalEcho<'a> {
+    fn with_lines_mut(&mut self, first_row: StableRowIndex, lines: &mut [&mut Line]) {
+        let mut overlaid: Vec<Option<Line>> = lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                let row = first_row + idx as StableRowIndex;
+                if self.echo.has_predictions_for(row) {
+                    let mut line = (**line).clone();
+                    self.echo.apply_to_line(row, &mut line);
+                    Some(line)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let mut lines: Vec<&mut Line> = lines
+            .iter_mut()
+            .zip(overlaid.iter_mut())
+            .map(|(line, overlaid)| match overlaid {
+                Some(overlaid) => overlaid,
+                None => &mut **line,
+            })
+            .collect();
+
+        self.inner.with_lines_mut(first_row, &mut lines);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn prompt(text: &str) -> Line {
+        Line::from_text(text, &CellAttributes::default(), SEQ_ZERO, None)
+    }
+
+    fn cursor_at(x: usize) -> StableCursorPosition {
+        StableCursorPosition {
+            x,
+            ..Default::default()
+        }
+    }
+
+    fn active_echo() -> LocalEcho {
+        let mut echo = LocalEcho::new(Some(Duration::from_millis(10)));
+        echo.record_rtt(Duration::from_millis(100));
+        echo
+    }
+
+    #[test]
+    fn predict_and_confirm() {
+        let mut echo = active_echo();
+        let line = prompt("$ ");
+        let cursor = cursor_at(2);
+
+        assert!(echo.predict_key(KeyCode::Char('l'), KeyModifiers::NONE, &cursor, &line, 80));
+        assert!(echo.predict_key(KeyCode::Char('s'), KeyModifiers::NONE, &cursor, &line, 80));
+        assert_eq!(echo.cursor_position(&cursor).x, 4);
+
+        let mut shown = line.clone();
+        echo.apply_to_line(0, &mut shown);
+        assert_eq!(shown.as_str(), "$ ls");
+
+        // The host has echoed the first character only
+        let echoed = prompt("$ l");
+        echo.validate(&cursor_at(3), |_| Some(&echoed));
+        assert_eq!(echo.predicted_rows(), vec![0]);
+        assert_eq!(echo.cursor_position(&cursor_at(3)).x, 4);
+
+        let echoed = prompt("$ ls");
+        echo.validate(&cursor_at(4), |_| Some(&echoed));
+        assert!(echo.predicted_rows().is_empty());
+    }
+
+    #[test]
+    fn no_prediction_below_threshold_or_in_password_prompt() {
+        let mut echo = LocalEcho::new(Some(Duration::from_millis(500)));
+        echo.record_rtt(Duration::from_millis(100));
+        let line = prompt("$ ");
+        assert!(!echo.predict_key(
+            KeyCode::Char('l'),
+            KeyModifiers::NONE,
+            &cursor_at(2),
+            &line,
+            80
+        ));
+
+        let mut echo = active_echo();
+        let line = prompt("Password: ");
+        assert!(!echo.predict_key(
+            KeyCode::Char('x'),
+            KeyModifiers::NONE,
+            &cursor_at(10),
+            &line,
+            80
+        ));
+    }
+
+    #[test]
+    fn only_predict_in_input_zone() {
+        let mut echo = active_echo();
+        let mut attrs = CellAttributes::default();
+        attrs.set_semantic_type(SemanticType::Prompt);
+        let line = Line::from_text("$ ", &attrs, SEQ_ZERO, None);
+
+        // Typing after the prompt is predicted, but erasing the prompt is not
+        assert!(!echo.predict_key(
+            KeyCode::Backspace,
+            KeyModifiers::NONE,
+            &cursor_at(2),
+            &line,
+            80
+        ));
+        echo.suspended_at.take();
+        assert!(echo.predict_key(
+            KeyCode::Char('x'),
+            KeyModifiers::NONE,
+            &cursor_at(2),
+            &line,
+            80
+        ));
+
+        // Output that doesn't follow a prompt is not input
+        let mut echo = active_echo();
+        let mut line = prompt("output");
+        line.set_cell(0, Cell::new('$', attrs.clone()), SEQ_ZERO);
+        assert!(!echo.predict_key(
+            KeyCode::Char('x'),
+            KeyModifiers::NONE,
+            &cursor_at(6),
+            &line,
+            80
+        ));
+    }
+
+    #[test]
+    fn unpredictable_input_suspends() {
+        let mut echo = active_echo();
+        let line = prompt("$ ");
+        assert!(!echo.predict_key(KeyCode::Enter, KeyModifiers::NONE, &cursor_at(2), &line, 80));
+        assert!(!echo.predict_key(
+            KeyCode::Char('x'),
+            KeyModifiers::NONE,
+            &cursor_at(2),
+            &line,
+            80
+        ));
+    }
+
+    #[test]
+    fn mismatch_rolls_back() {
+        let mut echo = active_echo();
+        let line = prompt("$ ");
+        assert!(echo.predict_key(
+            KeyCode::Char('j'),
+            KeyModifiers::NONE,
+            &cursor_at(2),
+            &line,
+            80
+        ));
+        for p in &mut echo.predictions {
+            p.made_at -= Duration::from_secs(5);
+        }
+        // The host didn't echo anything
+        assert!(echo.validate(&cursor_at(2), |_| Some(&line)));
+        assert!(echo.predicted_rows().is_empty());
+        assert_eq!(echo.cursor_position(&cursor_at(2)).x, 2);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::domain::DomainId;
+use crate::localecho::{LocalEcho, WithLocalEcho};
 use crate::pane::{
     CachePolicy, CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId, Pattern,
     SearchResult, WithPaneLines,
@@ -31,8 +32,9 @@ use url::Url;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Alert, AlertHandler, Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, Progress,
-    SemanticZone, StableRowIndex, Terminal, TerminalConfiguration, TerminalSize,
+    Alert, AlertHandler, Clipboard, CommandBlock, DownloadHandler, KeyCode, KeyModifiers,
+    MouseEvent, Progress, SemanticZone, StableRowIndex, Terminal, TerminalConfiguration,
+    TerminalSize,
 };
 
 const PROC_INFO_CACHE_TTL: Duration = Duration::from_millis(300);
@@ -133,6 +135,7 @@ pub struct LocalPane {
     #[cfg(unix)]
     leader: Arc<Mutex<Option<CachedLeaderInfo>>>,
     command_description: String,
+    local_echo: Mutex<LocalEcho>,
 }
 
 #[async_trait(?Send)]
@@ -164,7 +167,9 @@ impl Pane for LocalPane {
     }
 
     fn get_cursor_position(&self) -> StableCursorPosition {
-        let mut cursor = terminal_get_cursor_position(&mut self.terminal.lock());
+        let echo = self.local_echo.lock();
+        let mut cursor =
+            echo.cursor_position(&terminal_get_cursor_position(&mut self.terminal.lock()));
         if self.tmux_domain.lock().is_some() {
             cursor.visibility = termwiz::surface::CursorVisibility::Hidden;
         }
@@ -204,7 +209,18 @@ impl Pane for LocalPane {
     }
 
     fn with_lines_mut(&self, lines: Range<StableRowIndex>, with_lines: &mut dyn WithPaneLines) {
-        terminal_with_lines_mut(&mut self.terminal.lock(), lines, with_lines)
+        let mut echo = self.local_echo.lock();
+        let mut term = self.terminal.lock();
+        Self::validate_predictions(&mut echo, &mut term);
+        if echo.has_predictions() {
+            let mut with_echo = WithLocalEcho {
+                echo: &echo,
+                inner: with_lines,
+            };
+            terminal_with_lines_mut(&mut term, lines, &mut with_echo)
+        } else {
+            terminal_with_lines_mut(&mut term, lines, with_lines)
+        }
     }
 
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
@@ -388,7 +404,13 @@ impl Pane for LocalPane {
     }
 
     fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
-        self.terminal.lock().perform_actions(actions)
+        let mut echo = self.local_echo.lock();
+        let mut term = self.terminal.lock();
+        term.perform_actions(actions);
+        if echo.is_enabled() {
+            echo.output_received();
+            Self::validate_predictions(&mut echo, &mut term);
+        }
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
@@ -405,6 +427,9 @@ impl Pane for LocalPane {
             }
             return Ok(());
         } else {
+            self.predict_input(|echo, cursor, line, cols| {
+                echo.predict_key(key, mods, cursor, line, cols)
+            });
             self.terminal.lock().key_down(key, mods)
         }
     }
@@ -442,6 +467,9 @@ impl Pane for LocalPane {
         if self.tmux_domain.lock().is_some() {
             Ok(())
         } else {
+            self.predict_input(|echo, cursor, line, cols| {
+                echo.predict_paste(text, cursor, line, cols)
+            });
             self.terminal.lock().send_paste(text)
         }
     }
@@ -643,6 +671,23 @@ impl Pane for LocalPane {
         term.get_semantic_zones()
     }
 
+    fn get_command_blocks(&self) -> anyhow::Result<Vec<CommandBlock>> {
+        let mut term = self.terminal.lock();
+        term.get_command_blocks()
+    }
+
+    fn has_folded_command_output(&self) -> bool {
+        self.terminal.lock().has_folded_command_output()
+    }
+
+    fn set_command_output_folded(&self, row: StableRowIndex, folded: bool) -> anyhow::Result<()> {
+        self.terminal
+            .lock()
+            .set_command_output_folded(row, folded)?;
+        Mux::get().notify(MuxNotification::PaneOutput(self.pane_id));
+        Ok(())
+    }
+
     async fn search(
         &self,
         pattern: Pattern,
@@ -1019,7 +1064,86 @@ impl LocalPane {
             #[cfg(unix)]
             leader: Arc::new(Mutex::new(None)),
             command_description,
+            local_echo: Mutex::new(LocalEcho::new(None)),
+        }
+    }
+
+    /// Enables predictive local echo for this pane when the round trip
+    /// time to the host is at least `threshold`, or disables it if
+    /// None.  Only useful when the pty is connected to a remote host.
+    pub fn set_local_echo_threshold(&self, threshold: Option<Duration>) {
+        self.local_echo.lock().set_threshold(threshold);
+    }
+
+    /// Records that input is being sent to the host and, if the cursor
+    /// is at a prompt, predicts its effect using `predict`.
+    fn predict_input<F>(&self, predict: F)
+    where
+        F: FnOnce(&mut LocalEcho, &StableCursorPosition, &Line, usize) -> bool,
+    {
+        let timeout = {
+            let mut echo = self.local_echo.lock();
+            if !echo.is_enabled() {
+                return;
+            }
+            echo.input_sent();
+
+            let mut term = self.terminal.lock();
+            if term.is_alt_screen_active() {
+                // Full screen applications don't have a prompt
+                return;
+            }
+            let cursor = terminal_get_cursor_position(&mut term);
+            let cols = term.get_size().cols;
+            let mut predict = Some(predict);
+            let mut predicted = false;
+            terminal_with_lines(&mut term, cursor.y..cursor.y + 1, |first, lines| {
+                if let (true, Some(line), Some(predict)) =
+                    (first == cursor.y, lines.first(), predict.take())
+                {
+                    predicted = predict(&mut echo, &cursor, line, cols);
+                }
+            });
+            if !predicted {
+                return;
+            }
+            echo.timeout()
+        };
+
+        // Render the prediction now, and again once it should have been
+        // confirmed, so that it is rolled back if the host didn't do
+        // what we expected
+        let pane_id = self.pane_id;
+        if let Some(mux) = Mux::try_get() {
+            mux.notify(MuxNotification::PaneOutput(pane_id));
         }
+        promise::spawn::spawn(async move {
+            smol::Timer::after(timeout).await;
+            if let Some(mux) = Mux::try_get() {
+                mux.notify(MuxNotification::PaneOutput(pane_id));
+            }
+        })
+        .detach();
+    }
+
+    /// Drops predictions that have been confirmed by the content of the
+    /// terminal, or that have been outstanding for too long
+    fn validate_predictions(echo: &mut LocalEcho, term: &mut Terminal) {
+        if !echo.has_predictions() {
+            return;
+        }
+        let cursor = terminal_get_cursor_position(term);
+        let mut lines: Vec<(StableRowIndex, Line)> = vec![];
+        for row in echo.predicted_rows() {
+            terminal_with_lines(term, row..row + 1, |first, found| {
+                if let (true, Some(line)) = (first == row, found.first()) {
+                    lines.push((row, (*line).clone()));
+                }
+            });
+        }
+        echo.validate(&cursor, |row| {
+            lines.iter().find(|(r, _)| *r == row).map(|(_, line)| line)
+        });
     }
 
     #[cfg(unix)]
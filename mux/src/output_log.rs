@@ -0,0 +1,211 @@
+//! Logs the output of panes to files, as it is read from their pty
+//! by `read_from_pane_pty`, so that the output is captured even when
+//! no GUI is attached to the mux.
+use crate::pane::PaneId;
+use anyhow::Context;
+use config::keyassignment::PaneOutputLog;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::escape::parser::Parser;
+use termwiz::escape::{Action, ControlCode};
+
+lazy_static::lazy_static! {
+    static ref LOGS: Mutex<HashMap<PaneId, Arc<Mutex<OutputLog>>>> = Mutex::new(HashMap::new());
+}
+
+struct OutputLog {
+    config: PaneOutputLog,
+    file: File,
+    size: u64,
+    opened: Instant,
+    /// Used to strip escape sequences, when enabled.  It retains
+    /// its state between writes, as a sequence may span them.
+    parser: Option<Parser>,
+}
+
+impl OutputLog {
+    fn open(config: &PaneOutputLog) -> anyhow::Result<Self> {
+        let file = Self::open_file(config)?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            config: config.clone(),
+            file,
+            size,
+            opened: Instant::now(),
+            parser: if config.strip_ansi {
+                Some(Parser::new())
+            } else {
+                None
+            },
+        })
+    }
+
+    fn open_file(config: &PaneOutputLog) -> anyhow::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .with_context(|| format!("opening output log {}", config.path.display()))
+    }
+
+    /// Returns the name of the `n`th most recently rotated file
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.config.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn needs_rotation(&self, len: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let too_big = self
+            .config
+            .max_size
+            .map(|max| self.size + len as u64 > max)
+            .unwrap_or(false);
+        let too_old = self
+            .config
+            .rotate_interval_seconds
+            .map(|secs| self.opened.elapsed() >= Duration::from_secs(secs))
+            .unwrap_or(false);
+        too_big || too_old
+    }
+
+    fn rotate(&mut self) -> anyhow::Result<()> {
+        let path = &self.config.path;
+        if self.config.max_files == 0 {
+            std::fs::remove_file(path)
+                .with_context(|| format!("removing output log {}", path.display()))?;
+        } else {
+            for n in (1..self.config.max_files).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(n + 1))
+                        .with_context(|| format!("rotating output log {}", from.display()))?;
+                }
+            }
+            std::fs::rename(path, self.rotated_path(1))
+                .with_context(|| format!("rotating output log {}", path.display()))?;
+        }
+        self.file = Self::open_file(&self.config)?;
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let stripped;
+        let data = match self.parser.as_mut() {
+            Some(parser) => {
+                stripped = strip_escapes(parser, data);
+                stripped.as_slice()
+            }
+            None => data,
+        };
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.needs_rotation(data.len()) {
+            self.rotate()?;
+        }
+        self.file
+            .write_all(data)
+            .with_context(|| format!("writing output log {}", self.config.path.display()))?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Retains only the printable text, tabs and line breaks in the same
+/// way as the `strip-ansi-escapes` utility
+fn strip_escapes(parser: &mut Parser, data: &[u8]) -> Vec<u8> {
+    let mut text = String::new();
+    parser.parse(data, |action| match action {
+        Action::Print(c) => text.push(c),
+        Action::PrintString(s) => text.push_str(&s),
+        Action::Control(
+            c @ (ControlCode::HorizontalTab | ControlCode::LineFeed | ControlCode::CarriageReturn),
+        ) => text.push(c as u8 as char),
+        _ => {}
+    });
+    text.into_bytes()
+}
+
+/// Starts logging the output of the pane, replacing any log that
+/// was previously started for it
+pub fn start(pane_id: PaneId, config: &PaneOutputLog) -> anyhow::Result<()> {
+    let log = OutputLog::open(config)?;
+    LOGS.lock().insert(pane_id, Arc::new(Mutex::new(log)));
+    Ok(())
+}
+
+/// Stops logging the output of the pane
+pub fn stop(pane_id: PaneId) {
+    LOGS.lock().remove(&pane_id);
+}
+
+/// Called with each chunk of output read from the pty of the pane
+pub(crate) fn log_output(pane_id: PaneId, data: &[u8]) {
+    let log = match LOGS.lock().get(&pane_id) {
+        Some(log) => Arc::clone(log),
+        None => return,
+    };
+    let result = log.lock().write(data);
+    if let Err(err) = result {
+        log::error!("pane {}: {:#}; no longer logging its output", pane_id, err);
+        stop(pane_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read(path: PathBuf) -> String {
+        std::fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn rotate_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PaneOutputLog {
+            path: dir.path().join("pane.log"),
+            strip_ansi: false,
+            max_size: Some(10),
+            rotate_interval_seconds: None,
+            max_files: 2,
+        };
+        let mut log = OutputLog::open(&config).unwrap();
+        for chunk in ["one\n", "two\n", "three\n", "four\n", "five\n", "six\n"] {
+            log.write(chunk.as_bytes()).unwrap();
+        }
+
+        assert_eq!(read(config.path.clone()), "six\n");
+        assert_eq!(read(log.rotated_path(1)), "four\nfive\n");
+        assert_eq!(read(log.rotated_path(2)), "three\n");
+        assert!(!log.rotated_path(3).exists());
+    }
+
+    #[test]
+    fn strip_ansi_across_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PaneOutputLog {
+            path: dir.path().join("pane.log"),
+            strip_ansi: true,
+            max_size: None,
+            rotate_interval_seconds: None,
+            max_files: 0,
+        };
+        let mut log = OutputLog::open(&config).unwrap();
+        log.write(b"\x1b[1;3").unwrap();
+        log.write(b"1mred\x1b[0m\r\n\tok\x07").unwrap();
+
+        assert_eq!(read(config.path.clone()), "red\r\n\tok");
+    }
+}
@@ -0,0 +1,299 @@
+//! Pipes the output of panes through external commands, in the same
+//! way as the `pipe-pane` command of tmux.  The output is fed to the
+//! command as it is read from the pty by `read_from_pane_pty`, and the
+//! output of the command can optionally be injected back into the pane.
+use crate::pane::PaneId;
+use crate::{Mux, MuxNotification};
+use anyhow::{anyhow, Context};
+use config::keyassignment::PaneOutputPipe;
+use crossbeam::channel::{bounded, Sender, TrySendError};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use termwiz::escape::parser::Parser;
+
+lazy_static::lazy_static! {
+    static ref PIPES: Mutex<HashMap<PaneId, OutputPipe>> = Mutex::new(HashMap::new());
+}
+
+/// How many chunks of output may be queued for a command that is
+/// slow to read them before further output is discarded
+const MAX_QUEUED_CHUNKS: usize = 256;
+
+/// How long a command is given to exit after its stdin is closed
+/// before it is killed
+const EXIT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+struct OutputPipe {
+    tx: Sender<Vec<u8>>,
+    child: Option<Child>,
+    /// Cleared when the pipe is stopped or replaced, so that a command
+    /// that is still winding down no longer injects into the pane
+    active: Arc<AtomicBool>,
+}
+
+impl OutputPipe {
+    fn spawn(pane_id: PaneId, config: &PaneOutputPipe) -> anyhow::Result<Self> {
+        let program = config
+            .args
+            .first()
+            .ok_or_else(|| anyhow!("no command was specified to pipe the output through"))?;
+        let mut cmd = Command::new(program);
+        cmd.args(&config.args[1..])
+            .env("WEZTERM_PANE", pane_id.to_string())
+            .stdin(Stdio::piped())
+            .stdout(if config.inject_output {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            });
+        if let Some(cwd) = &config.cwd {
+            cmd.current_dir(cwd);
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("spawning {:?} for pane {}", config.args, pane_id))?;
+
+        let (tx, rx) = bounded::<Vec<u8>>(MAX_QUEUED_CHUNKS);
+        let stdin = child.stdin.take().expect("stdin is piped");
+        std::thread::spawn(move || write_to_command(pane_id, stdin, rx));
+
+        let active = Arc::new(AtomicBool::new(true));
+        if let Some(stdout) = child.stdout.take() {
+            let active = Arc::clone(&active);
+            std::thread::spawn(move || inject_from_command(pane_id, stdout, active));
+        }
+
+        Ok(Self {
+            tx,
+            child: Some(child),
+            active,
+        })
+    }
+}
+
+impl Drop for OutputPipe {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::SeqCst);
+        // Dropping our sender closes the stdin of the command once the
+        // queued output has been written; give it a chance to finish
+        // before killing it, so that it doesn't linger if it ignores EOF.
+        if let Some(mut child) = self.child.take() {
+            std::thread::spawn(move || {
+                let deadline = std::time::Instant::now() + EXIT_GRACE_PERIOD;
+                while std::time::Instant::now() < deadline {
+                    if let Ok(Some(_)) = child.try_wait() {
+                        return;
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                child.kill().ok();
+                child.wait().ok();
+            });
+        }
+    }
+}
+
+fn write_to_command(
+    pane_id: PaneId,
+    mut stdin: ChildStdin,
+    rx: crossbeam::channel::Receiver<Vec<u8>>,
+) {
+    for data in rx {
+        if let Err(err) = stdin.write_all(&data).and_then(|_| stdin.flush()) {
+            log::error!(
+                "pane {}: writing to output pipe: {:#}; no longer piping its output",
+                pane_id,
+                err
+            );
+            return;
+        }
+    }
+}
+
+fn inject_from_command(pane_id: PaneId, mut stdout: ChildStdout, active: Arc<AtomicBool>) {
+    let mut parser = Parser::new();
+    let mut buf = vec![0; 8192];
+    loop {
+        let size = match stdout.read(&mut buf) {
+            Ok(0) => return,
+            Ok(size) => size,
+            Err(err) => {
+                log::error!("pane {}: reading from output pipe: {:#}", pane_id, err);
+                return;
+            }
+        };
+        let mut actions = vec![];
+        parser.parse(&buf[..size], |action| actions.push(action));
+        if actions.is_empty() {
+            continue;
+        }
+        if !active.load(Ordering::SeqCst) {
+            return;
+        }
+        let pane = match Mux::try_get().and_then(|mux| mux.get_pane(pane_id)) {
+            Some(pane) => pane,
+            None => return,
+        };
+        pane.perform_actions(actions);
+        Mux::notify_from_any_thread(MuxNotification::PaneOutput(pane_id));
+    }
+}
+
+/// Starts piping the output of the pane through a command, replacing
+/// any pipe that was previously started for it
+pub fn start(pane_id: PaneId, config: &PaneOutputPipe) -> anyhow::Result<()> {
+    let pipe = OutputPipe::spawn(pane_id, config)?;
+    PIPES.lock().insert(pane_id, pipe);
+    Ok(())
+}
+
+/// Stops piping the output of the pane
+pub fn stop(pane_id: PaneId) {
+    PIPES.lock().remove(&pane_id);
+}
+
+/// Called with each chunk of output read from the pty of the pane
+pub(crate) fn pipe_output(pane_id: PaneId, data: &[u8]) {
+    let mut pipes = PIPES.lock();
+    let pipe = match pipes.get(&pane_id) {
+        Some(pipe) => pipe,
+        None => return,
+    };
+    match pipe.tx.try_send(data.to_vec()) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            log::warn!(
+                "pane {}: output pipe command is not keeping up; discarding output",
+                pane_id
+            );
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            // The command exited or failed; the error, if any,
+            // has already been logged by write_to_command
+            pipes.remove(&pane_id);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::pane::Pane;
+    use wezterm_term::TerminalSize;
+
+    fn line(pane: &Arc<dyn Pane>, idx: isize) -> String {
+        let (_, lines) = pane.get_lines(idx..idx + 1);
+        lines[0].as_str().trim_end().to_string()
+    }
+
+    #[test]
+    fn pipe_through_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("piped");
+        let config = PaneOutputPipe {
+            args: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "tr a-z A-Z > piped; echo $WEZTERM_PANE >> piped".to_string(),
+            ],
+            cwd: Some(dir.path().to_path_buf()),
+            inject_output: false,
+        };
+        let pipe = OutputPipe::spawn(42, &config).unwrap();
+        pipe.tx.send(b"hello ".to_vec()).unwrap();
+        pipe.tx.send(b"world\n".to_vec()).unwrap();
+        drop(pipe);
+
+        let expected = "HELLO WORLD\n42\n";
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let mut contents = String::new();
+        while std::time::Instant::now() < deadline {
+            contents = std::fs::read_to_string(&path).unwrap_or_default();
+            if contents == expected {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(contents, expected);
+    }
+
+    #[test]
+    fn inject_output_into_pane() {
+        let mut config = config::Config::default_config();
+        config.mux_enable_ssh_agent = false;
+        config::use_this_configuration(config);
+        // Notifications are queued to the main thread
+        let _executor = promise::spawn::ScopedExecutor::new();
+        let mux = Arc::new(Mux::new(None));
+        Mux::set_mux(&mux);
+
+        let (_term, pane) = crate::termwiztermtab::allocate(
+            TerminalSize::default(),
+            Arc::new(config::TermConfig::new()),
+        );
+        mux.add_pane(&pane).unwrap();
+        let pane_id = pane.pane_id();
+
+        let config = PaneOutputPipe {
+            args: vec!["sh".to_string(), "-c".to_string(), "sed s/a/b/".to_string()],
+            cwd: None,
+            inject_output: true,
+        };
+        start(pane_id, &config).unwrap();
+        pipe_output(pane_id, b"banana\r\n");
+        // sed only flushes its output when its stdin is closed, which
+        // we do without stopping the pipe
+        if let Some(pipe) = PIPES.lock().get_mut(&pane_id) {
+            pipe.tx = bounded(0).0;
+        }
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while line(&pane, 0) != "bbnana" {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "timed out waiting for injected output"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        // A command that is stopped no longer injects output, even
+        // while it is still running
+        let config = PaneOutputPipe {
+            args: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "sleep 0.2; echo late".to_string(),
+            ],
+            cwd: None,
+            inject_output: true,
+        };
+        start(pane_id, &config).unwrap();
+        stop(pane_id);
+        std::thread::sleep(Duration::from_millis(600));
+        assert_eq!(line(&pane, 1), "");
+
+        Mux::shutdown();
+    }
+
+    #[test]
+    fn requires_a_command() {
+        let config = PaneOutputPipe {
+            args: vec![],
+            cwd: None,
+            inject_output: false,
+        };
+        assert!(OutputPipe::spawn(1, &config).is_err());
+    }
+}
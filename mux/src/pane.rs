@@ -2,7 +2,7 @@ use crate::domain::DomainId;
 use crate::renderable::*;
 use crate::ExitBehavior;
 use async_trait::async_trait;
-use config::keyassignment::{KeyAssignment, ScrollbackEraseMode};
+use config::keyassignment::{KeyAssignment, PaneOutputLog, PaneOutputPipe, ScrollbackEraseMode};
 use downcast_rs::{impl_downcast, Downcast};
 use parking_lot::MappedMutexGuard;
 use rangeset::RangeSet;
@@ -17,8 +17,8 @@ use url::Url;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, Progress, SemanticZone,
-    StableRowIndex, TerminalConfiguration, TerminalSize,
+    Clipboard, CommandBlock, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, Progress,
+    SemanticZone, StableRowIndex, TerminalConfiguration, TerminalSize,
 };
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -264,6 +264,32 @@ pub trait Pane: Downcast + Send + Sync {
 
     fn erase_scrollback(&self, _erase_mode: ScrollbackEraseMode) {}
 
+    /// Starts logging the output of the pane to a file, or stops
+    /// logging it if `log` is None.  The default implementation
+    /// logs the output that the mux reads from the pty of the pane.
+    fn set_output_log(&self, log: Option<&PaneOutputLog>) -> anyhow::Result<()> {
+        match log {
+            Some(log) => crate::output_log::start(self.pane_id(), log),
+            None => {
+                crate::output_log::stop(self.pane_id());
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts piping the output of the pane through a command, or
+    /// stops piping it if `pipe` is None.  The default implementation
+    /// pipes the output that the mux reads from the pty of the pane.
+    fn set_output_pipe(&self, pipe: Option<&PaneOutputPipe>) -> anyhow::Result<()> {
+        match pipe {
+            Some(pipe) => crate::output_pipe::start(self.pane_id(), pipe),
+            None => {
+                crate::output_pipe::stop(self.pane_id());
+                Ok(())
+            }
+        }
+    }
+
     /// Called to advise on whether this tab has focus
     fn focus_changed(&self, _focused: bool) {}
 
@@ -304,6 +330,90 @@ pub trait Pane: Downcast + Send + Sync {
         Ok(vec![])
     }
 
+    /// Retrieve the set of command blocks, derived from the semantic zones
+    fn get_command_blocks(&self) -> anyhow::Result<Vec<CommandBlock>> {
+        Ok(CommandBlock::from_semantic_zones(
+            &self.get_semantic_zones()?,
+        ))
+    }
+
+    /// Returns true if the output of any command block is folded.
+    /// This is cheaper than computing the command blocks, which
+    /// the renderer would otherwise need to do on every frame.
+    fn has_folded_command_output(&self) -> bool {
+        false
+    }
+
+    /// Folds or unfolds the output of the command block that
+    /// contains the specified row
+    fn set_command_output_folded(&self, _row: StableRowIndex, _folded: bool) -> anyhow::Result<()> {
+        anyhow::bail!("this pane does not support folding command output");
+    }
+
+    /// Extract the text spanned by a semantic zone.
+    /// Wrapped lines are joined together and trailing whitespace
+    /// is trimmed from each logical line.
+    fn get_text_from_semantic_zone(&self, zone: &SemanticZone) -> String {
+        let mut last_was_wrapped = false;
+        let first_row = zone.start_y;
+        let last_row = zone.end_y;
+
+        fn cols_for_row(zone: &SemanticZone, row: StableRowIndex) -> Range<usize> {
+            if row < zone.start_y || row > zone.end_y {
+                0..0
+            } else if zone.start_y == zone.end_y {
+                // A single line zone
+                if zone.start_x <= zone.end_x {
+                    zone.start_x..zone.end_x.saturating_add(1)
+                } else {
+                    zone.end_x..zone.start_x.saturating_add(1)
+                }
+            } else if row == zone.end_y {
+                // last line of multi-line
+                0..zone.end_x.saturating_add(1)
+            } else if row == zone.start_y {
+                // first line of multi-line
+                zone.start_x..usize::MAX
+            } else {
+                // some "middle" line of multi-line
+                0..usize::MAX
+            }
+        }
+
+        let mut s = String::new();
+        for line in self.get_logical_lines(zone.start_y..zone.end_y + 1) {
+            if !s.is_empty() && !last_was_wrapped {
+                s.push('\n');
+            }
+            let last_idx = line.physical_lines.len().saturating_sub(1);
+            for (idx, phys) in line.physical_lines.iter().enumerate() {
+                let this_row = line.first_row + idx as StableRowIndex;
+                if this_row >= first_row && this_row <= last_row {
+                    let last_phys_idx = phys.len().saturating_sub(1);
+
+                    let cols = cols_for_row(zone, this_row);
+                    let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
+                    let col_span = phys.columns_as_str(cols);
+                    // Only trim trailing whitespace if we are the last line
+                    // in a wrapped sequence
+                    if idx == last_idx {
+                        s.push_str(col_span.trim_end());
+                    } else {
+                        s.push_str(&col_span);
+                    }
+
+                    last_was_wrapped = last_col_idx == last_phys_idx
+                        && phys
+                            .get_cell(last_col_idx)
+                            .map(|c| c.attrs().wrapped())
+                            .unwrap_or(false);
+                }
+            }
+        }
+
+        s
+    }
+
     /// Returns true if the terminal has grabbed the mouse and wants to
     /// give the embedded application a chance to process events.
     /// In practice this controls whether the gui will perform local
@@ -767,7 +767,7 @@ impl Domain for RemoteSshDomain {
             Box::new(writer.clone()),
         );
 
-        let pane: Arc<dyn Pane> = Arc::new(LocalPane::new(
+        let pane = LocalPane::new(
             pane_id,
             terminal,
             child,
@@ -775,7 +775,9 @@ impl Domain for RemoteSshDomain {
             Box::new(writer),
             self.id,
             "RemoteSshDomain".to_string(),
-        ));
+        );
+        pane.set_local_echo_threshold(self.dom.local_echo_threshold_ms.map(Duration::from_millis));
+        let pane: Arc<dyn Pane> = Arc::new(pane);
         let mux = Mux::get();
         mux.add_pane(&pane)?;
 
@@ -24,7 +24,9 @@ use std::io::{BufWriter, Write};
 use std::ops::Range;
 use std::sync::Arc;
 use std::time::Duration;
-use termwiz::input::{InputEvent, KeyEvent, Modifiers, MouseEvent as TermWizMouseEvent};
+use termwiz::input::{
+    InputEvent, KeyEvent, KeyEventType, Modifiers, MouseEvent as TermWizMouseEvent,
+};
 use termwiz::render::terminfo::TerminfoRenderer;
 use termwiz::surface::{Change, Line, SequenceNo};
 use termwiz::terminal::{ScreenSize, TerminalWaker};
@@ -214,6 +216,8 @@ impl Pane for TermWizTerminalPane {
         let event = InputEvent::Key(KeyEvent {
             key,
             modifiers: modifiers.remove_positional_mods(),
+            event_type: KeyEventType::Press,
+            kitty: None,
         });
         if let Err(e) = self.input_tx.send(event) {
             *self.dead.lock() = true;
@@ -428,9 +432,12 @@ impl termwiz::terminal::Terminal for TermWizTerminal {
                 Some(InputEvent::Key(KeyEvent {
                     key: KeyCode::Char(c),
                     modifiers: Modifiers::CTRL,
+                    ..
                 })) if c.is_ascii_lowercase() => Some(InputEvent::Key(KeyEvent {
                     key: KeyCode::Char(c.to_ascii_uppercase()),
                     modifiers: Modifiers::CTRL,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 })),
                 i @ _ => i,
             }
@@ -4,23 +4,8 @@
 //! widths in the code beforehand.
 use termwiz::cell::{unicode_column_width, CellAttributes};
 use termwiz::surface::Change;
-
-/// Describes the alignment of a column
-#[derive(Debug, Clone, Copy)]
-pub enum Alignment {
-    Left,
-    Center,
-    Right,
-}
-
-/// Describes a column
-#[derive(Debug)]
-pub struct Column {
-    /// The name of the column; this is the column header text
-    pub name: String,
-    /// How the column should be aligned
-    pub alignment: Alignment,
-}
+use termwiz::tabulate::{alignment_padding, compute_column_widths};
+pub use termwiz::tabulate::{Alignment, Column};
 
 fn emit_column<W: std::io::Write>(
     text: &str,
@@ -29,18 +14,7 @@ fn emit_column<W: std::io::Write>(
     output: &mut W,
 ) -> Result<(), std::io::Error> {
     let text_width = unicode_column_width(text, None);
-    let (left_pad, right_pad) = match alignment {
-        Alignment::Left => (0, max_width - text_width),
-        Alignment::Center => {
-            let left_pad = (max_width - text_width) / 2;
-            // for odd-length columns, take care to use the remaining
-            // length rather than just assuming that the right_pad
-            // will have the same value as the left_pad
-            let right_pad = max_width - (text_width + left_pad);
-            (left_pad, right_pad)
-        }
-        Alignment::Right => (max_width - text_width, 0),
-    };
+    let (left_pad, right_pad) = alignment_padding(text_width, max_width, alignment);
 
     for _ in 0..left_pad {
         write!(output, " ")?;
@@ -64,24 +38,16 @@ pub fn tabulate_output<S: std::string::ToString, W: std::io::Write>(
     rows: &[Vec<S>],
     output: &mut W,
 ) -> Result<(), std::io::Error> {
-    let mut col_widths: Vec<usize> = columns
+    let display_rows: Vec<Vec<String>> = rows
         .iter()
-        .map(|c| unicode_column_width(&c.name, None))
+        .map(|src_row| src_row.iter().map(|col| col.to_string()).collect())
         .collect();
-
-    let mut display_rows: Vec<Vec<String>> = vec![];
-    for src_row in rows {
-        let dest_row: Vec<String> = src_row.iter().map(|col| col.to_string()).collect();
-        for (idx, col) in dest_row.iter().enumerate() {
-            let col_width = unicode_column_width(col, None);
-            if let Some(width) = col_widths.get_mut(idx) {
-                *width = (*width).max(col_width);
-            } else {
-                col_widths.push(col_width);
-            }
-        }
-        display_rows.push(dest_row);
-    }
+    let col_widths = compute_column_widths(
+        columns,
+        display_rows
+            .iter()
+            .map(|row| row.iter().map(|col| unicode_column_width(col, None))),
+    );
 
     for (idx, col) in columns.iter().enumerate() {
         if idx > 0 {
@@ -144,18 +110,7 @@ fn emit_column_for_terminal(
     spacer: &CellAttributes,
 ) {
     let text_width = unicode_column_width_of_change_slice(s);
-    let (left_pad, right_pad) = match alignment {
-        Alignment::Left => (0, max_width - text_width),
-        Alignment::Center => {
-            let left_pad = (max_width - text_width) / 2;
-            // for odd-length columns, take care to use the remaining
-            // length rather than just assuming that the right_pad
-            // will have the same value as the left_pad
-            let right_pad = max_width - (text_width + left_pad);
-            (left_pad, right_pad)
-        }
-        Alignment::Right => (max_width - text_width, 0),
-    };
+    let (left_pad, right_pad) = alignment_padding(text_width, max_width, alignment);
 
     emit_padding_for_terminal(left_pad, spacer, output);
     output.extend_from_slice(s);
@@ -168,21 +123,13 @@ pub fn tabulate_for_terminal(
     spacer: CellAttributes,
     result: &mut Vec<Change>,
 ) {
-    let mut col_widths: Vec<usize> = columns
-        .iter()
-        .map(|c| unicode_column_width(&c.name, None))
-        .collect();
-
-    for row in rows {
-        for (idx, col) in row.iter().enumerate() {
-            let col_width = unicode_column_width_of_change_slice(col);
-            if let Some(width) = col_widths.get_mut(idx) {
-                *width = (*width).max(col_width);
-            } else {
-                col_widths.push(col_width);
-            }
-        }
-    }
+    let col_widths = compute_column_widths(
+        columns,
+        rows.iter().map(|row| {
+            row.iter()
+                .map(|col| unicode_column_width_of_change_slice(col))
+        }),
+    );
 
     for (idx, col) in columns.iter().enumerate() {
         if idx > 0 {
@@ -0,0 +1,387 @@
+//! A `termwiz::terminal::Terminal` implementation that is backed by
+//! the terminal model in this crate rather than by a tty.
+//!
+//! This is intended for testing termwiz applications: the output
+//! rendered by the application is fed through the real escape
+//! sequence parser into a `Terminal`, whose screen can then be
+//! inspected, while input events and resizes are injected by the test.
+use crate::terminalstate::DB;
+use crate::{
+    unicode_column_width, CellAttributes, Line, Terminal, TerminalConfiguration, TerminalSize,
+    VisibleRowIndex,
+};
+use std::collections::VecDeque;
+use std::io::Write;
+#[cfg(unix)]
+use std::io::{ErrorKind, Read};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use termwiz::caps::{Capabilities, ColorLevel, ProbeHints};
+use termwiz::input::{InputEvent, InputParser};
+use termwiz::render::terminfo::TerminfoRenderer;
+use termwiz::render::RenderTty;
+use termwiz::surface::Change;
+use termwiz::terminal::{ScreenSize, Terminal as TermwizTerminal, TerminalWaker};
+
+/// Collects the responses that the terminal model writes back to
+/// the application, such as device status reports
+#[derive(Clone, Default)]
+struct Responses(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Responses {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Passes the rendered output into the terminal model
+struct ModelWriter<'a> {
+    term: &'a mut Terminal,
+}
+
+impl<'a> Write for ModelWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.term.advance_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> RenderTty for ModelWriter<'a> {
+    fn get_size_in_cells(&mut self) -> termwiz::Result<(usize, usize)> {
+        let size = self.term.get_size();
+        Ok((size.cols, size.rows))
+    }
+
+    fn get_cell_size_in_pixels(&mut self) -> termwiz::Result<Option<(usize, usize)>> {
+        let size = self.term.get_size();
+        if size.cols == 0 || size.rows == 0 || size.pixel_width == 0 || size.pixel_height == 0 {
+            return Ok(None);
+        }
+        Ok(Some((
+            size.pixel_width / size.cols,
+            size.pixel_height / size.rows,
+        )))
+    }
+}
+
+/// Returns the capabilities of the terminal model in this crate.
+/// These don't depend upon the environment, so that tests behave
+/// the same way on every machine.
+pub fn headless_capabilities() -> Capabilities {
+    let hints = ProbeHints::default()
+        .term(Some("wezterm".to_string()))
+        .terminfo_db(Some(DB.clone()))
+        .color_level(Some(ColorLevel::TrueColor))
+        .hyperlinks(Some(true))
+        .sixel(Some(true))
+        .iterm2_image(Some(true))
+        .kitty_graphics(Some(true))
+        .kitty_keyboard(Some(true))
+        .synchronized_output(Some(true))
+        .bracketed_paste(Some(true))
+        .mouse_reporting(Some(true));
+    Capabilities::new_with_hints(hints).expect("headless capabilities are valid")
+}
+
+/// A `termwiz::terminal::Terminal` that renders into an in-process
+/// `Terminal` model.
+///
+/// Input is injected by the test using `send_input`, `send_bytes`
+/// and `resize`, and is returned from `poll_input` in the order
+/// that it was sent.  Responses that the model generates, such as
+/// cursor position reports, are parsed and returned as input too.
+pub struct HeadlessTerminal {
+    term: Terminal,
+    renderer: TerminfoRenderer,
+    responses: Responses,
+    input_parser: InputParser,
+    input_queue: VecDeque<InputEvent>,
+    #[cfg(unix)]
+    wake_pipe: UnixStream,
+    #[cfg(unix)]
+    wake_pipe_write: UnixStream,
+    #[cfg(windows)]
+    waker: TerminalWaker,
+}
+
+impl HeadlessTerminal {
+    /// Create a headless terminal with the specified size, using
+    /// `headless_capabilities` to render to it
+    pub fn new(
+        size: TerminalSize,
+        config: Arc<dyn TerminalConfiguration + Send + Sync>,
+    ) -> anyhow::Result<Self> {
+        Self::new_with_caps(size, config, headless_capabilities())
+    }
+
+    /// Create a headless terminal with the specified size, rendering
+    /// to it as though it had the specified capabilities
+    pub fn new_with_caps(
+        size: TerminalSize,
+        config: Arc<dyn TerminalConfiguration + Send + Sync>,
+        caps: Capabilities,
+    ) -> anyhow::Result<Self> {
+        let responses = Responses::default();
+        let term = Terminal::new(
+            size,
+            config,
+            "WezTerm",
+            env!("CARGO_PKG_VERSION"),
+            Box::new(responses.clone()),
+        );
+
+        #[cfg(unix)]
+        let (wake_pipe, wake_pipe_write) = {
+            let (read, write) = UnixStream::pair()?;
+            write.set_nonblocking(true)?;
+            (read, write)
+        };
+
+        Ok(Self {
+            term,
+            renderer: TerminfoRenderer::new(caps),
+            responses,
+            input_parser: InputParser::new(),
+            input_queue: VecDeque::new(),
+            #[cfg(unix)]
+            wake_pipe,
+            #[cfg(unix)]
+            wake_pipe_write,
+            #[cfg(windows)]
+            waker: TerminalWaker::new()?,
+        })
+    }
+
+    /// Returns the underlying terminal model
+    pub fn terminal(&self) -> &Terminal {
+        &self.term
+    }
+
+    /// Returns the underlying terminal model, mutably
+    pub fn terminal_mut(&mut self) -> &mut Terminal {
+        &mut self.term
+    }
+
+    /// Queue up an input event to be returned from `poll_input`
+    pub fn send_input(&mut self, event: InputEvent) {
+        self.input_queue.push_back(event);
+    }
+
+    /// Parse `bytes` as though they were typed into the terminal,
+    /// queueing up the resulting input events
+    pub fn send_bytes(&mut self, bytes: &[u8]) {
+        let queue = &mut self.input_queue;
+        self.input_parser
+            .parse(bytes, |event| queue.push_back(event), false);
+    }
+
+    /// Resize the terminal, as though its window had been resized,
+    /// and queue up the corresponding `InputEvent::Resized`
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let size = self.term.get_size();
+        let (cell_width, cell_height) = cell_size_in_pixels(&size);
+        self.term.resize(TerminalSize {
+            rows,
+            cols,
+            pixel_width: cols * cell_width,
+            pixel_height: rows * cell_height,
+            dpi: size.dpi,
+        });
+        self.input_queue
+            .push_back(InputEvent::Resized { cols, rows });
+    }
+
+    /// Returns the cursor position as (x, y)
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let cursor = self.term.cursor_pos();
+        (cursor.x, cursor.y as usize)
+    }
+
+    /// Returns the text of each of the visible lines, with any
+    /// trailing whitespace removed
+    pub fn screen_lines(&self) -> Vec<String> {
+        self.visible_lines()
+            .iter()
+            .map(|line| line.as_str().trim_end().to_string())
+            .collect()
+    }
+
+    /// Returns the text of the visible lines, separated by newlines
+    pub fn screen_text(&self) -> String {
+        self.screen_lines().join("\n")
+    }
+
+    /// Returns the attributes of the cell at the specified visible
+    /// position, or None if it is out of range
+    pub fn cell_attributes(&self, x: usize, y: usize) -> Option<CellAttributes> {
+        let lines = self.visible_lines();
+        let line = lines.get(y)?;
+        line.get_cell(x).map(|cell| cell.attrs().clone())
+    }
+
+    /// Returns a textual rendition of the state of the screen that is
+    /// suitable for comparing against an expected snapshot.
+    /// Each line is enclosed in `|` characters so that trailing spaces
+    /// are visible, and is preceded by the title and cursor position.
+    pub fn snapshot(&self) -> String {
+        let (x, y) = self.cursor_position();
+        let mut snapshot = format!("title: {}\ncursor: {},{}\n", self.term.get_title(), x, y);
+        let cols = self.term.get_size().cols;
+        for line in self.visible_lines() {
+            let text = line.as_str();
+            let width = unicode_column_width(&text, None);
+            snapshot.push('|');
+            snapshot.push_str(&text);
+            for _ in width..cols {
+                snapshot.push(' ');
+            }
+            snapshot.push_str("|\n");
+        }
+        snapshot
+    }
+
+    fn visible_lines(&self) -> Vec<Line> {
+        let screen = self.term.screen();
+        let rows = screen.physical_rows as VisibleRowIndex;
+        screen.lines_in_phys_range(screen.phys_range(&(0..rows)))
+    }
+
+    /// Parses any responses from the terminal model into input events
+    fn process_responses(&mut self) {
+        let responses = std::mem::take(&mut *self.responses.0.lock().unwrap());
+        if !responses.is_empty() {
+            let queue = &mut self.input_queue;
+            self.input_parser
+                .parse(&responses, |event| queue.push_back(event), false);
+        }
+    }
+
+    /// Waits up to `wait` for the waker to be used
+    #[cfg(unix)]
+    fn wait_for_wake(&mut self, wait: Option<Duration>) -> termwiz::Result<bool> {
+        match wait {
+            Some(duration) if duration.is_zero() => self.wake_pipe.set_nonblocking(true)?,
+            _ => {
+                self.wake_pipe.set_nonblocking(false)?;
+                self.wake_pipe.set_read_timeout(wait)?;
+            }
+        }
+        let mut buf = [0u8; 64];
+        match self.wake_pipe.read(&mut buf) {
+            Ok(n) => Ok(n > 0),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                Ok(false)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    #[cfg(windows)]
+    fn wait_for_wake(&mut self, wait: Option<Duration>) -> termwiz::Result<bool> {
+        Ok(self.waker.wait(wait)?)
+    }
+}
+
+/// Returns the (width, height) of a cell in pixels
+fn cell_size_in_pixels(size: &TerminalSize) -> (usize, usize) {
+    if size.cols == 0 || size.rows == 0 {
+        (0, 0)
+    } else {
+        (size.pixel_width / size.cols, size.pixel_height / size.rows)
+    }
+}
+
+impl TermwizTerminal for HeadlessTerminal {
+    fn set_raw_mode(&mut self) -> termwiz::Result<()> {
+        Ok(())
+    }
+
+    fn set_cooked_mode(&mut self) -> termwiz::Result<()> {
+        Ok(())
+    }
+
+    fn enter_alternate_screen(&mut self) -> termwiz::Result<()> {
+        self.term.advance_bytes("\x1b[?1049h");
+        Ok(())
+    }
+
+    fn exit_alternate_screen(&mut self) -> termwiz::Result<()> {
+        self.term.advance_bytes("\x1b[?1049l");
+        Ok(())
+    }
+
+    fn get_screen_size(&mut self) -> termwiz::Result<ScreenSize> {
+        let size = self.term.get_size();
+        let (xpixel, ypixel) = cell_size_in_pixels(&size);
+        Ok(ScreenSize {
+            rows: size.rows,
+            cols: size.cols,
+            xpixel,
+            ypixel,
+        })
+    }
+
+    fn set_screen_size(&mut self, size: ScreenSize) -> termwiz::Result<()> {
+        let dpi = self.term.get_size().dpi;
+        self.term.resize(TerminalSize {
+            rows: size.rows,
+            cols: size.cols,
+            pixel_width: size.cols * size.xpixel,
+            pixel_height: size.rows * size.ypixel,
+            dpi,
+        });
+        Ok(())
+    }
+
+    fn render(&mut self, changes: &[Change]) -> termwiz::Result<()> {
+        let mut out = ModelWriter {
+            term: &mut self.term,
+        };
+        self.renderer.render_to(changes, &mut out)
+    }
+
+    fn flush(&mut self) -> termwiz::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the next injected input event or response.
+    /// If there are none, waits up to `wait` for the waker to be
+    /// used; note that if `wait` is `None` and nothing wakes the
+    /// terminal, this blocks forever, just as it would for a real
+    /// terminal that received no input.
+    fn poll_input(&mut self, wait: Option<Duration>) -> termwiz::Result<Option<InputEvent>> {
+        self.process_responses();
+        if let Some(event) = self.input_queue.pop_front() {
+            return Ok(Some(event));
+        }
+        if self.wait_for_wake(wait)? {
+            return Ok(Some(InputEvent::Wake));
+        }
+        Ok(None)
+    }
+
+    #[cfg(unix)]
+    fn waker(&self) -> TerminalWaker {
+        let pipe = self
+            .wake_pipe_write
+            .try_clone()
+            .expect("failed to clone the wake pipe");
+        TerminalWaker::with_pipe(pipe)
+    }
+
+    #[cfg(windows)]
+    fn waker(&self) -> TerminalWaker {
+        self.waker.clone()
+    }
+}
@@ -18,7 +18,7 @@
 use anyhow::Error;
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
-use std::ops::{Deref, DerefMut, Range};
+use std::ops::{Deref, DerefMut, Range, RangeInclusive};
 use std::str;
 use wezterm_dynamic::{FromDynamic, ToDynamic};
 use wezterm_surface::SequenceNo;
@@ -26,6 +26,8 @@ use wezterm_surface::SequenceNo;
 pub mod config;
 pub use config::TerminalConfiguration;
 
+pub mod headless;
+
 pub mod input;
 pub use crate::input::*;
 
@@ -122,6 +124,116 @@ pub struct SemanticZone {
     pub semantic_type: SemanticType,
 }
 
+/// A command as delimited by OSC 133 shell integration sequences:
+/// the prompt, the command line that was entered at that prompt and
+/// the output that it produced.
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, FromDynamic, ToDynamic)]
+pub struct CommandBlock {
+    pub prompt: Option<SemanticZone>,
+    pub input: Option<SemanticZone>,
+    pub output: Option<SemanticZone>,
+    /// The exit status reported via OSC 133;D, if any
+    pub exit_status: Option<i32>,
+    /// The time between the start of the output (OSC 133;C)
+    /// and the exit status (OSC 133;D) being reported
+    pub duration_ms: Option<u64>,
+    /// True if the output has been folded away, leaving just
+    /// its first row visible
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    pub folded: bool,
+}
+
+impl CommandBlock {
+    /// The first row occupied by this block
+    pub fn start_y(&self) -> StableRowIndex {
+        self.prompt
+            .or(self.input)
+            .or(self.output)
+            .map(|zone| zone.start_y)
+            .unwrap_or(0)
+    }
+
+    /// The last row occupied by this block
+    pub fn end_y(&self) -> StableRowIndex {
+        self.output
+            .or(self.input)
+            .or(self.prompt)
+            .map(|zone| zone.end_y)
+            .unwrap_or(0)
+    }
+
+    /// Returns true if the specified row is part of this block
+    pub fn contains_row(&self, row: StableRowIndex) -> bool {
+        row >= self.start_y() && row <= self.end_y()
+    }
+
+    /// Returns the range of rows that are hidden when this
+    /// block is folded
+    pub fn folded_rows(&self) -> Option<RangeInclusive<StableRowIndex>> {
+        match self.output {
+            Some(zone) if self.folded && zone.end_y > zone.start_y => {
+                Some(zone.start_y + 1..=zone.end_y)
+            }
+            _ => None,
+        }
+    }
+
+    /// Groups a sequence of semantic zones, as returned by
+    /// `get_semantic_zones`, into command blocks.
+    /// Each Prompt zone begins a new block; Input and Output zones
+    /// are attached to the block that precedes them.
+    /// Output that precedes the first prompt or input is not part
+    /// of any block.
+    pub fn from_semantic_zones(zones: &[SemanticZone]) -> Vec<CommandBlock> {
+        fn extend(target: &mut Option<SemanticZone>, zone: &SemanticZone) {
+            match target {
+                Some(existing) => {
+                    existing.end_y = zone.end_y;
+                    existing.end_x = zone.end_x;
+                }
+                None => {
+                    target.replace(*zone);
+                }
+            }
+        }
+
+        let mut blocks = vec![];
+        let mut current: Option<CommandBlock> = None;
+
+        for zone in zones {
+            match zone.semantic_type {
+                SemanticType::Prompt => {
+                    blocks.extend(current.take());
+                    current.replace(CommandBlock {
+                        prompt: Some(*zone),
+                        ..Default::default()
+                    });
+                }
+                SemanticType::Input => match current.as_mut() {
+                    Some(block) if block.output.is_none() => extend(&mut block.input, zone),
+                    _ => {
+                        blocks.extend(current.take());
+                        current.replace(CommandBlock {
+                            input: Some(*zone),
+                            ..Default::default()
+                        });
+                    }
+                },
+                SemanticType::Output => {
+                    if let Some(block) = current.as_mut() {
+                        extend(&mut block.output, zone);
+                    }
+                }
+            }
+        }
+        blocks.extend(current.take());
+
+        blocks
+    }
+}
+
 pub mod color;
 
 #[cfg(test)]
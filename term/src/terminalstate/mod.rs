@@ -6,11 +6,12 @@ use crate::color::{ColorPalette, RgbColor};
 use crate::config::{BidiMode, NewlineCanon};
 use log::debug;
 use num_traits::ToPrimitive;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::io::{BufWriter, Write};
 use std::num::NonZeroUsize;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use terminfo::{Database, Value};
 use termwiz::input::KeyboardEncoding;
 use url::Url;
@@ -36,7 +37,7 @@ use crate::terminalstate::image::*;
 use crate::terminalstate::kitty::*;
 
 lazy_static::lazy_static! {
-    static ref DB: Database = {
+    pub(crate) static ref DB: Database = {
         let data = include_bytes!("../../../termwiz/data/wezterm");
         Database::from_buffer(&data[..]).unwrap()
     };
@@ -243,6 +244,20 @@ impl ScreenOrAlt {
     }
 }
 
+/// Tracks the timing and exit status of a command, as reported
+/// by OSC 133;C and OSC 133;D
+#[derive(Debug, Clone)]
+struct CommandStatusRecord {
+    /// The cursor row when the command started to produce output,
+    /// or when its status was reported if there was no output
+    start_y: StableRowIndex,
+    /// The row on which the prompt for the command began, if known
+    prompt_y: Option<StableRowIndex>,
+    started: Option<Instant>,
+    duration: Option<Duration>,
+    exit_status: Option<i32>,
+}
+
 /// Manages the state for the terminal
 pub struct TerminalState {
     config: Arc<dyn TerminalConfiguration>,
@@ -261,6 +276,13 @@ pub struct TerminalState {
 
     clear_semantic_attribute_on_newline: bool,
 
+    /// Timing and exit status of recent commands, ordered by row
+    command_status: VecDeque<CommandStatusRecord>,
+    /// The row on which the most recent prompt began
+    command_prompt_y: Option<StableRowIndex>,
+    /// The first rows of the command blocks whose output is folded
+    folded_command_output: BTreeSet<StableRowIndex>,
+
     /// If true, writing a character inserts a new cell
     insert: bool,
 
@@ -522,6 +544,9 @@ impl TerminalState {
             left_and_right_margin_mode: false,
             wrap_next: false,
             clear_semantic_attribute_on_newline: false,
+            command_status: VecDeque::new(),
+            command_prompt_y: None,
+            folded_command_output: BTreeSet::new(),
             // We default auto wrap to true even though the default for
             // a dec terminal is false, because it is more useful this way.
             dec_auto_wrap: true,
@@ -2749,6 +2774,132 @@ impl TerminalState {
         Ok(zones)
     }
 
+    /// Computes the set of `CommandBlock`s for the current terminal screen,
+    /// grouping the semantic zones by prompt and annotating them with the
+    /// exit status and duration reported by the shell, if any.
+    pub fn get_command_blocks(&mut self) -> anyhow::Result<Vec<CommandBlock>> {
+        let zones = self.get_semantic_zones()?;
+        let mut blocks = CommandBlock::from_semantic_zones(&zones);
+
+        // A record belongs to the block whose prompt it was reported
+        // for.  Records made before any prompt was seen fall back to
+        // the row on which they were reported, which can be the first
+        // row of the block when the prompt and output share a row.
+        let starts: Vec<StableRowIndex> = blocks.iter().map(|b| b.start_y()).collect();
+        for (idx, block) in blocks.iter_mut().enumerate() {
+            let start = starts[idx];
+            let end = starts.get(idx + 1).copied().unwrap_or(StableRowIndex::MAX);
+            if let Some(record) = self.command_status.iter().rev().find(|r| {
+                let y = r.prompt_y.unwrap_or(r.start_y);
+                y >= start && y < end
+            }) {
+                block.exit_status = record.exit_status;
+                block.duration_ms = record.duration.map(|d| d.as_millis() as u64);
+            }
+            block.folded =
+                block.output.is_some() && self.folded_command_output.contains(&block.start_y());
+        }
+
+        Ok(blocks)
+    }
+
+    /// Folds or unfolds the output of the command block that contains
+    /// the specified row.  The first row of the output remains visible
+    /// while the block is folded.
+    pub fn set_command_output_folded(
+        &mut self,
+        row: StableRowIndex,
+        folded: bool,
+    ) -> anyhow::Result<()> {
+        let block = self
+            .get_command_blocks()?
+            .into_iter()
+            .find(|block| block.contains_row(row))
+            .ok_or_else(|| anyhow::anyhow!("there is no command block at row {}", row))?;
+
+        // Discard folds for rows that have scrolled out of the scrollback
+        let first_row = self.screen().phys_to_stable_row_index(0);
+        self.folded_command_output.retain(|&y| y >= first_row);
+
+        if !folded {
+            self.folded_command_output.remove(&block.start_y());
+        } else if block.output.is_some() {
+            self.folded_command_output.insert(block.start_y());
+        } else {
+            anyhow::bail!("the command at row {} has no output to fold", row);
+        }
+        Ok(())
+    }
+
+    /// Returns true if the output of any command block is folded
+    pub fn has_folded_command_output(&self) -> bool {
+        !self.folded_command_output.is_empty()
+    }
+
+    /// Called when the shell indicates the start of a prompt
+    /// (OSC 133;A, OSC 133;N or OSC 133;P)
+    fn command_prompt_started(&mut self) {
+        self.command_prompt_y = Some(self.screen().visible_row_to_stable_row(self.cursor.y));
+    }
+
+    /// Called when the shell indicates the start of the command line
+    /// (OSC 133;B).  Shells that don't mark up their prompt begin the
+    /// block here.
+    fn start_command_input(&mut self) {
+        if self.pen.semantic_type() != SemanticType::Prompt {
+            self.command_prompt_started();
+        }
+        self.pen.set_semantic_type(SemanticType::Input);
+    }
+
+    /// Called when the shell indicates that a command has started
+    /// to produce output (OSC 133;C)
+    fn command_output_started(&mut self) {
+        let start_y = self.screen().visible_row_to_stable_row(self.cursor.y);
+
+        // Discard records for rows that have scrolled out of the scrollback
+        let first_row = self.screen().phys_to_stable_row_index(0);
+        while self
+            .command_status
+            .front()
+            .map(|r| r.start_y < first_row)
+            .unwrap_or(false)
+        {
+            self.command_status.pop_front();
+        }
+
+        self.command_status.push_back(CommandStatusRecord {
+            start_y,
+            prompt_y: self.command_prompt_y,
+            started: Some(Instant::now()),
+            duration: None,
+            exit_status: None,
+        });
+    }
+
+    /// Called when the shell reports the exit status of a command
+    /// (OSC 133;D)
+    fn command_finished(&mut self, status: i32) {
+        match self.command_status.back_mut() {
+            Some(record) if record.exit_status.is_none() && record.duration.is_none() => {
+                record.duration = record.started.map(|started| started.elapsed());
+                record.exit_status = Some(status);
+            }
+            _ => {
+                // No output was produced, but we can still record the
+                // status against the current prompt
+                let start_y = self.screen().visible_row_to_stable_row(self.cursor.y);
+                self.command_status.push_back(CommandStatusRecord {
+                    start_y,
+                    prompt_y: self.command_prompt_y,
+                    started: None,
+                    duration: None,
+                    exit_status: Some(status),
+                });
+            }
+        }
+    }
+
     #[inline]
     pub fn get_reverse_video(&self) -> bool {
         self.reverse_video_mode
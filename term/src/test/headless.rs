@@ -0,0 +1,131 @@
+//! Tests for the headless terminal harness
+use super::*;
+use crate::headless::HeadlessTerminal;
+use k9::assert_equal as assert_eq;
+use std::time::Duration;
+use termwiz::cell::AttributeChange;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, KeyEventType, Modifiers};
+use termwiz::surface::{Change, Position, Surface};
+use termwiz::terminal::buffered::BufferedTerminal;
+use termwiz::terminal::Terminal as _;
+
+fn headless(cols: usize, rows: usize) -> HeadlessTerminal {
+    HeadlessTerminal::new(
+        TerminalSize {
+            rows,
+            cols,
+            pixel_width: cols * 8,
+            pixel_height: rows * 16,
+            dpi: 0,
+        },
+        Arc::new(TestTermConfig { scrollback: 0 }),
+    )
+    .unwrap()
+}
+
+#[test]
+fn buffered_render() {
+    let mut buf = BufferedTerminal::new(headless(10, 3)).unwrap();
+    buf.add_change("hello ");
+    buf.add_change(AttributeChange::Intensity(Intensity::Bold));
+    buf.add_change("world");
+    buf.flush().unwrap();
+
+    let term = buf.terminal();
+    assert_eq!(term.screen_lines(), vec!["hello worl", "d", ""]);
+    assert_eq!(
+        term.cell_attributes(0, 0).unwrap().intensity(),
+        Intensity::Normal
+    );
+    assert_eq!(
+        term.cell_attributes(6, 0).unwrap().intensity(),
+        Intensity::Bold
+    );
+    assert_eq!(term.cursor_position(), (1, 1));
+}
+
+#[test]
+fn surface_diff_round_trip() {
+    let mut before = Surface::new(12, 3);
+    before.add_change("hello\r\nthere");
+
+    let mut after = Surface::new(12, 3);
+    after.add_change("help\r\n");
+    after.add_change(Change::CursorPosition {
+        x: Position::Absolute(4),
+        y: Position::Absolute(2),
+    });
+    after.add_change("world");
+
+    let mut term = headless(12, 3);
+    let (_seq, changes) = before.get_changes(0);
+    term.render(&changes).unwrap();
+    assert_eq!(term.screen_text(), "hello\nthere\n");
+
+    term.render(&before.diff_screens(&after)).unwrap();
+    let expect: Vec<String> = after
+        .screen_chars_to_string()
+        .lines()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    assert_eq!(term.screen_lines(), expect);
+}
+
+#[test]
+fn input_and_resize() {
+    let mut term = headless(10, 3);
+    term.send_bytes(b"\x1b[A");
+    term.send_input(InputEvent::Paste("pasted".to_string()));
+    term.resize(20, 5);
+
+    let mut events = vec![];
+    while let Some(event) = term.poll_input(Some(Duration::ZERO)).unwrap() {
+        events.push(event);
+    }
+    assert_eq!(
+        events,
+        vec![
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
+            }),
+            InputEvent::Paste("pasted".to_string()),
+            InputEvent::Resized { cols: 20, rows: 5 },
+        ]
+    );
+
+    let size = term.get_screen_size().unwrap();
+    assert_eq!((size.cols, size.rows), (20, 5));
+    assert_eq!((size.xpixel, size.ypixel), (8, 16));
+}
+
+#[test]
+#[cfg(unix)]
+fn waker() {
+    let mut term = headless(10, 3);
+    assert_eq!(term.poll_input(Some(Duration::ZERO)).unwrap(), None);
+    term.waker().wake().unwrap();
+    assert_eq!(
+        term.poll_input(Some(Duration::from_secs(5))).unwrap(),
+        Some(InputEvent::Wake)
+    );
+}
+
+#[test]
+fn snapshot() {
+    let mut term = headless(6, 2);
+    term.render(&[
+        Change::Title("demo".to_string()),
+        Change::Text("hi".to_string()),
+    ])
+    .unwrap();
+    assert_eq!(
+        term.snapshot(),
+        "title: demo\n\
+         cursor: 2,0\n\
+         |hi    |\n\
+         |      |\n"
+    );
+}
@@ -6,6 +6,7 @@ mod c0;
 use bitflags::bitflags;
 mod c1;
 mod csi;
+mod headless;
 // mod selection; FIXME: port to render layer
 use crate::color::ColorPalette;
 use k9::assert_equal as assert_eq;
@@ -479,6 +480,171 @@ fn test_semantic() {
     );
 }
 
+#[test]
+fn test_command_blocks() {
+    use wezterm_escape_parser::osc::FinalTermSemanticPrompt;
+    let mut term = TestTerm::new(8, 10, 0);
+
+    let prompt = |term: &mut TestTerm, command: &str| {
+        term.print(format!(
+            "{}> {}{}\r\n{}",
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+                    aid: None,
+                    cl: None
+                }
+            ),
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker
+            ),
+            command,
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None }
+            ),
+        ));
+    };
+    let status = |term: &mut TestTerm, status: i32| {
+        term.print(format!(
+            "{}",
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::CommandStatus { status, aid: None }
+            )
+        ));
+    };
+
+    term.print("motd\r\n");
+    prompt(&mut term, "ls");
+    term.print("a\r\nb\r\n");
+    status(&mut term, 0);
+    prompt(&mut term, "false");
+    status(&mut term, 1);
+    term.print(format!(
+        "{}> ",
+        OperatingSystemCommand::FinalTermSemanticPrompt(
+            FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+                aid: None,
+                cl: None
+            }
+        )
+    ));
+
+    let blocks = term.get_command_blocks().unwrap();
+    assert_eq!(blocks.len(), 3);
+
+    assert_eq!(blocks[0].input.map(|z| (z.start_y, z.end_y)), Some((1, 1)));
+    assert_eq!(blocks[0].output.map(|z| (z.start_y, z.end_y)), Some((2, 3)));
+    assert_eq!(blocks[0].exit_status, Some(0));
+    assert!(blocks[0].duration_ms.is_some());
+
+    assert_eq!(blocks[1].start_y(), 4);
+    assert_eq!(blocks[1].exit_status, Some(1));
+
+    assert!(blocks[2].input.is_none());
+    assert!(blocks[2].output.is_none());
+    assert_eq!(blocks[2].exit_status, None);
+}
+
+#[test]
+fn test_command_blocks_output_on_prompt_row() {
+    use wezterm_escape_parser::osc::FinalTermSemanticPrompt;
+    let mut term = TestTerm::new(8, 20, 0);
+
+    let osc = |prompt: FinalTermSemanticPrompt| {
+        OperatingSystemCommand::FinalTermSemanticPrompt(prompt).to_string()
+    };
+    let fresh_prompt = || {
+        osc(FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+            aid: None,
+            cl: None,
+        })
+    };
+
+    // The prompt, the command line and the start of the output all
+    // share the first row of the block
+    term.print(format!(
+        "{}> {}x{}a b\r\nc\r\n{}",
+        fresh_prompt(),
+        osc(FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker),
+        osc(FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None }),
+        osc(FinalTermSemanticPrompt::CommandStatus {
+            status: 2,
+            aid: None
+        }),
+    ));
+    // A command that produces no output before the next prompt
+    term.print(format!(
+        "{}> {}true\r\n{}{}",
+        fresh_prompt(),
+        osc(FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker),
+        osc(FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None }),
+        osc(FinalTermSemanticPrompt::CommandStatus {
+            status: 0,
+            aid: None
+        }),
+    ));
+    term.print(format!("{}> ", fresh_prompt()));
+
+    let blocks = term.get_command_blocks().unwrap();
+    assert_eq!(blocks.len(), 3);
+
+    assert_eq!(blocks[0].start_y(), 0);
+    assert_eq!(blocks[0].output.map(|z| (z.start_y, z.end_y)), Some((0, 1)));
+    assert_eq!(blocks[0].exit_status, Some(2));
+
+    assert_eq!(blocks[1].start_y(), 2);
+    assert_eq!(blocks[1].exit_status, Some(0));
+
+    assert_eq!(blocks[2].start_y(), 3);
+    assert_eq!(blocks[2].exit_status, None);
+}
+
+#[test]
+fn test_command_blocks_folding() {
+    use wezterm_escape_parser::osc::FinalTermSemanticPrompt;
+    let mut term = TestTerm::new(8, 20, 0);
+
+    let osc = |prompt: FinalTermSemanticPrompt| {
+        OperatingSystemCommand::FinalTermSemanticPrompt(prompt).to_string()
+    };
+    let command = |term: &mut TestTerm, command: &str, output: &str| {
+        term.print(format!(
+            "{}> {}{}\r\n{}{}",
+            osc(FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+                aid: None,
+                cl: None
+            }),
+            osc(FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker),
+            command,
+            osc(FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None }),
+            output,
+        ));
+    };
+
+    command(&mut term, "ls", "a\r\nb\r\nc\r\n");
+    command(&mut term, "true", "");
+    term.print(format!(
+        "{}> ",
+        osc(FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+            aid: None,
+            cl: None
+        })
+    ));
+
+    term.set_command_output_folded(2, true).unwrap();
+    let blocks = term.get_command_blocks().unwrap();
+    assert!(blocks[0].folded);
+    assert_eq!(blocks[0].folded_rows(), Some(2..=3));
+    assert!(!blocks[1].folded);
+
+    // There is nothing to fold in a command without output
+    assert!(term.set_command_output_folded(4, true).is_err());
+
+    term.set_command_output_folded(0, false).unwrap();
+    let blocks = term.get_command_blocks().unwrap();
+    assert!(!blocks[0].folded);
+    assert_eq!(blocks[0].folded_rows(), None);
+}
+
 #[test]
 fn issue_1161() {
     let mut term = TestTerm::new(1, 5, 0);
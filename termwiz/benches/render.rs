@@ -0,0 +1,73 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use termwiz::caps::{Capabilities, ColorLevel, ProbeHints};
+use termwiz::cell::AttributeChange;
+use termwiz::color::ColorAttribute;
+use termwiz::render::terminfo::TerminfoRenderer;
+use termwiz::render::RenderTty;
+use termwiz::surface::{Change, Surface};
+
+/// Discards the rendered output
+struct Sink;
+
+impl std::io::Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl RenderTty for Sink {
+    fn get_size_in_cells(&mut self) -> termwiz::Result<(usize, usize)> {
+        Ok((80, 24))
+    }
+}
+
+/// Returns a screen of colorful text, with lines starting at `first`
+fn screen(first: usize) -> Surface {
+    let mut surface = Surface::new(80, 24);
+    for line in first..first + 24 {
+        if line > first {
+            surface.add_change("\r\n");
+        }
+        surface.add_change(Change::Attribute(AttributeChange::Foreground(
+            ColorAttribute::PaletteIndex(line as u8 % 16),
+        )));
+        surface.add_change(format!("{:4} {}", line, "lorem ipsum ".repeat(6)));
+    }
+    surface
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let caps = Capabilities::new_with_hints(
+        ProbeHints::default()
+            .color_level(Some(ColorLevel::TrueColor))
+            .synchronized_output(Some(true)),
+    )
+    .unwrap();
+
+    let current = screen(0);
+    let scrolled = screen(1);
+    let (_, repaint) = scrolled.get_changes(0);
+    let mut diff = vec![current.detect_scroll(&scrolled).unwrap()];
+    let mut shifted = screen(0);
+    shifted.add_change(diff[0].clone());
+    diff.append(&mut shifted.diff_screens(&scrolled));
+
+    c.bench_function("TerminfoRenderer::render_to repaint", |b| {
+        let mut renderer = TerminfoRenderer::new(caps.clone());
+        b.iter(|| renderer.render_to(black_box(&repaint), &mut Sink))
+    });
+    c.bench_function("TerminfoRenderer::render_to scroll", |b| {
+        let mut renderer = TerminfoRenderer::new(caps.clone());
+        b.iter(|| renderer.render_to(black_box(&diff), &mut Sink))
+    });
+    c.bench_function("Surface::detect_scroll", |b| {
+        b.iter(|| current.detect_scroll(black_box(&scrolled)))
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
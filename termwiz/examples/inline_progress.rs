@@ -0,0 +1,52 @@
+//! This example shows how to use an inline `BufferedTerminal` to
+//! render a small progress display below the cursor, rather than
+//! taking over the whole screen.  The final state of the display
+//! remains in the scrollback once the example exits.
+use std::time::Duration;
+use termwiz::caps::Capabilities;
+use termwiz::cell::AttributeChange;
+use termwiz::color::AnsiColor;
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::buffered::BufferedTerminal;
+use termwiz::terminal::{new_terminal, Terminal};
+use termwiz::Error;
+
+fn main() -> Result<(), Error> {
+    let caps = Capabilities::new_from_env()?;
+    let mut terminal = new_terminal(caps)?;
+    terminal.set_raw_mode()?;
+
+    let mut buf = BufferedTerminal::new_inline(terminal, 2)?;
+    buf.add_change(Change::CursorVisibility(CursorVisibility::Hidden));
+
+    let steps = 40;
+    for step in 0..=steps {
+        buf.check_for_resize()?;
+        let width = buf.dimensions().0.saturating_sub(2).max(1);
+        let filled = width * step / steps;
+
+        buf.add_change(Change::ClearScreen(Default::default()));
+        buf.add_change(format!("Step {} of {}\r\n", step, steps));
+        buf.add_change(Change::Attribute(AttributeChange::Foreground(
+            AnsiColor::Green.into(),
+        )));
+        buf.add_change(format!(
+            "[{}{}]",
+            "#".repeat(filled),
+            " ".repeat(width - filled)
+        ));
+        buf.add_change(Change::AllAttributes(Default::default()));
+        buf.add_change(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        });
+        buf.flush()?;
+        buf.terminal().flush()?;
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    buf.finish_inline()?;
+    buf.terminal().set_cooked_mode()?;
+
+    Ok(())
+}
@@ -1,11 +1,13 @@
 use termwiz::caps::Capabilities;
-use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, KeyEventType, Modifiers};
 use termwiz::terminal::{new_terminal, Terminal};
 use termwiz::Error;
 
 const CTRL_C: KeyEvent = KeyEvent {
     key: KeyCode::Char('c'),
     modifiers: Modifiers::CTRL,
+    event_type: KeyEventType::Press,
+    kitty: None,
 };
 
 fn main() -> Result<(), Error> {
@@ -28,6 +28,23 @@ impl LineEditorHost for Host {
         &mut self.history
     }
 
+    /// Suggest the remainder of the most recent history entry that
+    /// starts with the current line; press Right to accept it
+    fn hint(&self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return None;
+        }
+        let mut idx = self.history.last()?;
+        loop {
+            if let Some(entry) = self.history.get(idx) {
+                if entry.len() > line.len() && entry.starts_with(line) {
+                    return Some(entry[line.len()..].to_string());
+                }
+            }
+            idx = idx.checked_sub(1)?;
+        }
+    }
+
     /// Demo of the completion API for words starting with "h" or "he"
     fn complete(&self, line: &str, cursor_position: usize) -> Vec<CompletionCandidate> {
         let mut candidates = vec![];
@@ -92,7 +109,10 @@ fn word_at_cursor(line: &str, cursor_position: usize) -> Option<(std::ops::Range
 }
 
 fn main() -> termwiz::Result<()> {
-    println!("Type `exit` to quit this example, or start a word with `h` and press Tab.");
+    println!(
+        "Type `exit` to quit this example, or start a word with `h` and press Tab. \
+         Previously entered lines are suggested as you type."
+    );
     let mut terminal = line_editor_terminal()?;
     let mut editor = LineEditor::new(&mut terminal);
 
@@ -0,0 +1,108 @@
+//! This example shows how to compose some of the standard widgets
+#[cfg(feature = "widgets")]
+mod inner {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use termwiz::caps::Capabilities;
+    use termwiz::input::*;
+    use termwiz::surface::Change;
+    use termwiz::terminal::buffered::BufferedTerminal;
+    use termwiz::terminal::{new_terminal, Terminal};
+    use termwiz::widgets::frame::Frame;
+    use termwiz::widgets::layout::ChildOrientation;
+    use termwiz::widgets::list::ListView;
+    use termwiz::widgets::progress::ProgressBar;
+    use termwiz::widgets::*;
+    use termwiz::Error;
+
+    /// The container for the app; it lays out its children vertically
+    struct MainScreen {}
+
+    impl Widget for MainScreen {
+        fn render(&mut self, _args: &mut RenderArgs) {}
+
+        fn get_size_constraints(&self) -> layout::Constraints {
+            layout::Constraints {
+                child_orientation: ChildOrientation::Vertical,
+                ..Default::default()
+            }
+        }
+    }
+
+    pub fn run() -> Result<(), Error> {
+        let items: Vec<String> = std::env::vars().map(|(name, _)| name).collect();
+        let count = items.len();
+        let list = Rc::new(RefCell::new(ListView::new(items).with_type_to_filter()));
+        let progress = Rc::new(RefCell::new(ProgressBar::new()));
+        let mut chosen = None;
+
+        {
+            let caps = Capabilities::new_from_env()?;
+            let mut buf = BufferedTerminal::new(new_terminal(caps)?)?;
+            buf.terminal().set_raw_mode()?;
+
+            let mut ui = Ui::new();
+            let root_id = ui.set_root(MainScreen {});
+            let list_id = ui.add_child(
+                root_id,
+                Frame::new(Rc::clone(&list)).with_title("Type to filter; Enter to choose"),
+            );
+            ui.add_child(root_id, Rc::clone(&progress));
+            ui.set_focus(list_id);
+
+            loop {
+                ui.process_event_queue()?;
+
+                // The widgets retain their state; query and update
+                // them in between processing events and rendering
+                if let Some(idx) = list.borrow_mut().take_activated() {
+                    chosen = Some(list.borrow().items()[idx].clone());
+                    break;
+                }
+                let selected = list.borrow().selected().map(|idx| idx + 1).unwrap_or(0);
+                progress
+                    .borrow_mut()
+                    .set_progress(selected as f64 / count.max(1) as f64);
+
+                if ui.render_to_screen(&mut buf)? {
+                    continue;
+                }
+                buf.flush()?;
+
+                match buf.terminal().poll_input(None) {
+                    Ok(Some(InputEvent::Resized { rows, cols })) => {
+                        buf.add_change(Change::ClearScreen(Default::default()));
+                        buf.resize(cols, rows);
+                    }
+                    Ok(Some(InputEvent::Key(KeyEvent {
+                        key: KeyCode::Char('c'),
+                        modifiers: Modifiers::CTRL,
+                        ..
+                    }))) => break,
+                    Ok(Some(input)) => ui.queue_event(WidgetEvent::Input(input)),
+                    Ok(None) => {}
+                    Err(e) => {
+                        print!("{:?}\r\n", e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        println!("You chose: {:?}", chosen);
+
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "widgets"))]
+mod inner {
+    pub fn run() -> termwiz::Result<()> {
+        println!("recompile with --features widgets");
+        Ok(())
+    }
+}
+
+fn main() -> termwiz::Result<()> {
+    inner::run()
+}
@@ -54,6 +54,7 @@
 //! implements some heuristics (a fancy word for guessing) to compute
 //! the terminal capabilities, but also offers a `ProbeHints`
 //! that can be used by the embedding application to override those choices.
+use crate::caps::probed::ProbedCapabilities;
 use crate::{builder, Result};
 use std::env::var;
 use terminfo::{self, capability as cap};
@@ -119,6 +120,20 @@ builder! {
         /// invisible and reset, and directly emit those sequences.
         /// This can improve rendered text compatibility with pagers.
         force_terminfo_render_to_use_ansi_sgr: Option<bool>,
+
+        /// Whether the kitty keyboard protocol is supported
+        /// See <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+        kitty_keyboard: Option<bool>,
+
+        /// Whether the kitty graphics protocol is supported
+        /// See <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+        kitty_graphics: Option<bool>,
+
+        /// Whether synchronized output (DEC private mode 2026) is supported
+        synchronized_output: Option<bool>,
+
+        /// Whether setting the clipboard via OSC 52 is permitted
+        osc52: Option<bool>,
     }
 }
 
@@ -179,6 +194,10 @@ pub struct Capabilities {
     bracketed_paste: bool,
     mouse_reporting: bool,
     force_terminfo_render_to_use_ansi_sgr: bool,
+    kitty_keyboard: bool,
+    kitty_graphics: bool,
+    synchronized_output: bool,
+    osc52: bool,
 }
 
 impl Capabilities {
@@ -313,6 +332,13 @@ impl Capabilities {
         let force_terminfo_render_to_use_ansi_sgr =
             hints.force_terminfo_render_to_use_ansi_sgr.unwrap_or(false);
 
+        // These can't be reliably inferred from the environment, so
+        // assume no unless told otherwise, or until probed
+        let kitty_keyboard = hints.kitty_keyboard.unwrap_or(false);
+        let kitty_graphics = hints.kitty_graphics.unwrap_or(false);
+        let synchronized_output = hints.synchronized_output.unwrap_or(false);
+        let osc52 = hints.osc52.unwrap_or(false);
+
         Ok(Self {
             color_level,
             sixel,
@@ -323,9 +349,38 @@ impl Capabilities {
             bracketed_paste,
             mouse_reporting,
             force_terminfo_render_to_use_ansi_sgr,
+            kitty_keyboard,
+            kitty_graphics,
+            synchronized_output,
+            osc52,
         })
     }
 
+    /// Update the capabilities to reflect the outcome of probing
+    /// the terminal with `ProbeCapabilities::probe`.
+    /// Capabilities that the terminal didn't respond about keep the
+    /// values that were derived from the environment and hints.
+    pub fn apply_probed(&mut self, probed: &ProbedCapabilities) {
+        if probed.true_color == Some(true) && self.color_level != ColorLevel::MonoChrome {
+            self.color_level = ColorLevel::TrueColor;
+        }
+        if probed.sixel() {
+            self.sixel = true;
+        }
+        if probed.kitty_keyboard_flags.is_some() {
+            self.kitty_keyboard = true;
+        }
+        if probed.kitty_graphics {
+            self.kitty_graphics = true;
+        }
+        if let Some(synchronized_output) = probed.synchronized_output {
+            self.synchronized_output = synchronized_output;
+        }
+        if probed.osc52() {
+            self.osc52 = true;
+        }
+    }
+
     /// Indicates how many colors are supported
     pub fn color_level(&self) -> ColorLevel {
         self.color_level
@@ -374,6 +429,28 @@ impl Capabilities {
     pub fn force_terminfo_render_to_use_ansi_sgr(&self) -> bool {
         self.force_terminfo_render_to_use_ansi_sgr
     }
+
+    /// Does the terminal support the kitty keyboard protocol?
+    /// See <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    pub fn kitty_keyboard(&self) -> bool {
+        self.kitty_keyboard
+    }
+
+    /// Does the terminal support the kitty graphics protocol?
+    /// See <https://sw.kovidgoyal.net/kitty/graphics-protocol/>
+    pub fn kitty_graphics(&self) -> bool {
+        self.kitty_graphics
+    }
+
+    /// Does the terminal support synchronized output?
+    pub fn synchronized_output(&self) -> bool {
+        self.synchronized_output
+    }
+
+    /// Is setting the clipboard via OSC 52 permitted?
+    pub fn osc52(&self) -> bool {
+        self.osc52
+    }
 }
 
 /// Returns true if the version string `a` is >= `b`
@@ -1,13 +1,22 @@
-use crate::escape::csi::{Device, Window};
+use crate::escape::csi::{
+    CsiParam, DecPrivateMode, DecPrivateModeCode, Device, DeviceAttribute, DeviceAttributes,
+    Keyboard, KittyKeyboardFlags, Mode, Window, XtSmGraphics, XtSmGraphicsItem,
+};
 use crate::escape::parser::Parser;
 use crate::escape::{Action, DeviceControlMode, Esc, EscCode, CSI};
 use crate::terminal::ScreenSize;
 use crate::{bail, Result};
+use num_traits::ToPrimitive;
 use std::io::{Read, Write};
+use std::time::{Duration, Instant};
 
 const TMUX_BEGIN: &str = "\u{1b}Ptmux;\u{1b}";
 const TMUX_END: &str = "\u{1b}\\";
 
+/// The image id used for the kitty graphics protocol query, so that
+/// its response can be recognized
+const KITTY_GRAPHICS_QUERY_ID: u32 = 31;
+
 /// Represents a terminal name and version.
 /// The name XtVersion is because this value is produced
 /// by querying the terminal using the XTVERSION escape
@@ -40,6 +49,88 @@ impl XtVersion {
     }
 }
 
+/// The outcome of `ProbeCapabilities::probe`.
+/// Terminals generally don't respond to queries that they don't
+/// recognize, so the corresponding fields will be `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProbedCapabilities {
+    /// The XTVERSION response
+    pub xt_version: Option<XtVersion>,
+    /// The primary device attributes (DA1); the conformance level
+    /// followed by the supported extensions
+    pub primary_device_attributes: Option<Vec<i64>>,
+    /// The secondary device attributes (DA2); the terminal type,
+    /// firmware version and ROM cartridge registration number
+    pub secondary_device_attributes: Option<Vec<i64>>,
+    /// The kitty keyboard protocol flags that are currently in effect,
+    /// if the protocol is supported
+    pub kitty_keyboard_flags: Option<KittyKeyboardFlags>,
+    /// Whether the kitty graphics protocol is supported
+    pub kitty_graphics: bool,
+    /// The maximum sixel graphics geometry as (width, height) in pixels,
+    /// as reported by XTSMGRAPHICS
+    pub sixel_geometry: Option<(u32, u32)>,
+    /// The size of a cell as (width, height) in pixels
+    pub cell_pixel_size: Option<(usize, usize)>,
+    /// Whether synchronized output (DEC private mode 2026) is supported
+    pub synchronized_output: Option<bool>,
+    /// Whether 24-bit color is supported, as determined by reading
+    /// back an RGB foreground color using DECRQSS
+    pub true_color: Option<bool>,
+}
+
+impl ProbedCapabilities {
+    fn has_device_attribute(&self, attribute: i64) -> bool {
+        match &self.primary_device_attributes {
+            // The first value is the conformance level
+            Some(attributes) => attributes.iter().skip(1).any(|&a| a == attribute),
+            None => false,
+        }
+    }
+
+    /// Whether sixel graphics are supported, according to either the
+    /// device attributes or the XTSMGRAPHICS response
+    pub fn sixel(&self) -> bool {
+        self.sixel_geometry.is_some() || self.has_device_attribute(4)
+    }
+
+    /// Whether the terminal allows the clipboard to be set using OSC 52.
+    /// xterm and compatible terminals advertise this via attribute 52
+    /// in their primary device attributes.
+    pub fn osc52(&self) -> bool {
+        self.has_device_attribute(52)
+    }
+}
+
+/// Returns the integer values of a set of device attributes
+fn device_attribute_values(attributes: &DeviceAttributes) -> Vec<i64> {
+    let (level, flags) = match attributes {
+        DeviceAttributes::Vt100WithAdvancedVideoOption => return vec![1, 2],
+        DeviceAttributes::Vt101WithNoOptions => return vec![1, 0],
+        DeviceAttributes::Vt102 => return vec![6],
+        DeviceAttributes::Vt220(flags) => (62, flags),
+        DeviceAttributes::Vt320(flags) => (63, flags),
+        DeviceAttributes::Vt420(flags) => (64, flags),
+    };
+    std::iter::once(level)
+        .chain(flags.attributes.iter().filter_map(|attr| match attr {
+            DeviceAttribute::Code(code) => code.to_i64(),
+            DeviceAttribute::Unspecified(CsiParam::Integer(n)) => Some(*n),
+            DeviceAttribute::Unspecified(_) => None,
+        }))
+        .collect()
+}
+
+fn integer_params(params: &[CsiParam]) -> Vec<i64> {
+    params
+        .iter()
+        .filter_map(|p| match p {
+            CsiParam::Integer(n) => Some(*n),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -55,6 +146,65 @@ mod test {
             assert_eq!(version.name_and_version(), result, "{input}");
         }
     }
+
+    fn probe_responses(responses: &[u8]) -> ProbedCapabilities {
+        let mut read = responses;
+        let mut write = vec![];
+        let mut probe = ProbeCapabilities::new(&mut read, &mut write);
+        probe.probe(Duration::from_secs(1)).unwrap()
+    }
+
+    #[test]
+    fn probe_all() {
+        let probed = probe_responses(
+            concat!(
+                "\x1bP>|WezTerm 20240203\x1b\\",
+                "\x1b[>1;277;0c",
+                "\x1b[?1u",
+                "\x1b[?2;0;1000;800S",
+                "\x1b[6;16;8t",
+                "\x1b[?2026;2$y",
+                "\x1b_Gi=31;OK\x1b\\",
+                "\x1bP1$r0;38:2::1:2:3m\x1b\\",
+                "\x1b[?65;4;6;18;22;52c",
+            )
+            .as_bytes(),
+        );
+
+        assert_eq!(
+            probed,
+            ProbedCapabilities {
+                xt_version: Some(XtVersion("WezTerm 20240203".to_string())),
+                primary_device_attributes: Some(vec![65, 4, 6, 18, 22, 52]),
+                secondary_device_attributes: Some(vec![1, 277, 0]),
+                kitty_keyboard_flags: Some(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES),
+                kitty_graphics: true,
+                sixel_geometry: Some((1000, 800)),
+                cell_pixel_size: Some((8, 16)),
+                synchronized_output: Some(true),
+                true_color: Some(true),
+            }
+        );
+        assert!(probed.sixel());
+        assert!(probed.osc52());
+    }
+
+    #[test]
+    fn probe_minimal() {
+        let probed = probe_responses(b"\x1bP0$r\x1b\\\x1b[?2026;0$y\x1b[?62;22c");
+
+        assert_eq!(
+            probed,
+            ProbedCapabilities {
+                primary_device_attributes: Some(vec![62, 22]),
+                synchronized_output: Some(false),
+                true_color: Some(false),
+                ..Default::default()
+            }
+        );
+        assert!(!probed.sixel());
+        assert!(!probed.osc52());
+    }
 }
 
 /// This struct is a helper that uses probing to determine specific capabilities
@@ -63,6 +213,7 @@ mod test {
 pub struct ProbeCapabilities<'a> {
     read: Box<&'a mut dyn Read>,
     write: Box<&'a mut dyn Write>,
+    poll: Option<Box<dyn FnMut(Duration) -> Result<bool>>>,
 }
 
 impl<'a> ProbeCapabilities<'a> {
@@ -70,9 +221,174 @@ impl<'a> ProbeCapabilities<'a> {
         Self {
             read: Box::new(read),
             write: Box::new(write),
+            poll: None,
         }
     }
 
+    /// Provides a function that waits up to the specified duration for
+    /// data to become available to read, returning true if it did.
+    /// Without it, `probe` cannot time out when the terminal doesn't
+    /// respond.
+    pub fn with_poll<F: FnMut(Duration) -> Result<bool> + 'static>(mut self, poll: F) -> Self {
+        self.poll = Some(Box::new(poll));
+        self
+    }
+
+    /// Read a single byte, or return None if the deadline passes first
+    fn read_byte(&mut self, deadline: Instant) -> Result<Option<u8>> {
+        if let Some(poll) = self.poll.as_mut() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !poll(remaining)? {
+                return Ok(None);
+            }
+        }
+        let mut byte = [0u8];
+        if self.read.read(&mut byte)? == 0 {
+            bail!("EOF while reading probe responses");
+        }
+        Ok(Some(byte[0]))
+    }
+
+    /// Probe for a number of capabilities at once.
+    /// All of the queries are sent together, followed by a request for
+    /// the primary device attributes, which every terminal responds to,
+    /// so that only a single round trip is required.
+    /// Responses are collected until the device attributes arrive or
+    /// `timeout` has elapsed, whichever happens first.
+    /// The terminal should be in raw mode while probing.
+    pub fn probe(&mut self, timeout: Duration) -> Result<ProbedCapabilities> {
+        let xt_version = CSI::Device(Box::new(Device::RequestTerminalNameAndVersion));
+        let secondary_attributes = CSI::Device(Box::new(Device::RequestSecondaryDeviceAttributes));
+        let kitty_keyboard = CSI::Keyboard(Keyboard::QueryKittySupport);
+        let sixel_geometry = CSI::Device(Box::new(Device::XtSmGraphics(XtSmGraphics {
+            item: XtSmGraphicsItem::SixelGraphicsGeometry,
+            // Read attribute
+            action_or_status: 1,
+            value: vec![],
+        })));
+        let query_pixels = CSI::Window(Box::new(Window::ReportCellSizePixels));
+        let synchronized_output = CSI::Mode(Mode::QueryDecPrivateMode(DecPrivateMode::Code(
+            DecPrivateModeCode::SynchronizedOutput,
+        )));
+        let dev_attributes = CSI::Device(Box::new(Device::RequestPrimaryDeviceAttributes));
+
+        write!(
+            self.write,
+            "{xt_version}{secondary_attributes}{kitty_keyboard}{sixel_geometry}\
+             {query_pixels}{synchronized_output}"
+        )?;
+        // Ask the terminal to validate, but not store, a 1x1 RGB image
+        write!(
+            self.write,
+            "\x1b_Gi={KITTY_GRAPHICS_QUERY_ID},s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\"
+        )?;
+        // Set an RGB foreground color and read it back with DECRQSS.
+        // Terminals without 24-bit color support either ignore it or
+        // report it as the nearest palette entry.
+        write!(self.write, "\x1b[38;2;1;2;3m\x1bP$qm\x1b\\\x1b[m")?;
+        write!(self.write, "{dev_attributes}")?;
+        self.write.flush()?;
+
+        let deadline = Instant::now() + timeout;
+        let mut probed = ProbedCapabilities::default();
+        let mut parser = Parser::new();
+        let mut raw = vec![];
+        let mut dcs: Option<(u8, Vec<i64>, Vec<u8>)> = None;
+        let mut done = false;
+
+        while !done {
+            let byte = match self.read_byte(deadline)? {
+                Some(byte) => byte,
+                None => break,
+            };
+            raw.push(byte);
+
+            parser.parse(&[byte], |action| match action {
+                Action::DeviceControl(DeviceControlMode::Enter(mode)) => {
+                    dcs = Some((mode.byte, mode.params.clone(), vec![]));
+                }
+                Action::DeviceControl(DeviceControlMode::Data(b)) => {
+                    if let Some((_, _, data)) = dcs.as_mut() {
+                        data.push(b);
+                    }
+                }
+                Action::DeviceControl(DeviceControlMode::Exit) => match dcs.take() {
+                    Some((b'|', _, data)) => {
+                        probed.xt_version =
+                            Some(XtVersion(String::from_utf8_lossy(&data).into()));
+                    }
+                    Some((b'r', params, data)) => {
+                        // A DECRQSS response; `1$r` indicates a valid request
+                        let sgr = String::from_utf8_lossy(&data);
+                        probed.true_color = Some(
+                            params == [1] && (sgr.contains("1;2;3") || sgr.contains("1:2:3")),
+                        );
+                    }
+                    _ => {}
+                },
+                Action::CSI(CSI::Device(dev)) => match *dev {
+                    Device::DeviceAttributes(attributes) => {
+                        probed.primary_device_attributes =
+                            Some(device_attribute_values(&attributes));
+                        done = true;
+                    }
+                    // A status of 0 indicates success
+                    Device::XtSmGraphics(g)
+                        if g.item == XtSmGraphicsItem::SixelGraphicsGeometry
+                            && g.action_or_status == 0
+                            && g.value.len() >= 2 =>
+                    {
+                        probed.sixel_geometry = Some((g.value[0] as u32, g.value[1] as u32));
+                    }
+                    _ => {}
+                },
+                Action::CSI(CSI::Keyboard(Keyboard::ReportKittyState(flags))) => {
+                    probed.kitty_keyboard_flags = Some(flags);
+                }
+                Action::CSI(CSI::Window(win)) => {
+                    if let Window::ReportCellSizePixelsResponse {
+                        width: Some(width),
+                        height: Some(height),
+                    } = *win
+                    {
+                        if width > 0 && height > 0 {
+                            probed.cell_pixel_size = Some((width as usize, height as usize));
+                        }
+                    }
+                }
+                Action::CSI(CSI::Unspecified(unspec)) => match (unspec.control, &unspec.params[..]) {
+                    // Primary device attributes that don't map to one of
+                    // the known conformance levels
+                    ('c', [CsiParam::P(b'?'), params @ ..]) => {
+                        probed.primary_device_attributes = Some(integer_params(params));
+                        done = true;
+                    }
+                    ('c', [CsiParam::P(b'>'), params @ ..]) => {
+                        probed.secondary_device_attributes = Some(integer_params(params));
+                    }
+                    // DECRPM; 0 means that the mode is not recognized
+                    (
+                        'y',
+                        [CsiParam::P(b'?'), CsiParam::Integer(mode), CsiParam::P(b';'), CsiParam::Integer(status), CsiParam::P(b'$')],
+                    ) if *mode == DecPrivateModeCode::SynchronizedOutput as i64 => {
+                        probed.synchronized_output = Some(*status != 0);
+                    }
+                    _ => {}
+                },
+                _ => {}
+            });
+        }
+
+        let kitty_response = format!("\x1b_Gi={KITTY_GRAPHICS_QUERY_ID};");
+        probed.kitty_graphics = raw
+            .windows(kitty_response.len())
+            .position(|w| w == kitty_response.as_bytes())
+            .map(|idx| raw[idx + kitty_response.len()..].starts_with(b"OK"))
+            .unwrap_or(false);
+
+        Ok(probed)
+    }
+
     /// Probe for the XTVERSION response
     pub fn xt_version(&mut self) -> Result<XtVersion> {
         self.xt_version_impl(false)
@@ -89,6 +89,42 @@ pub struct KeyEvent {
 
     /// Which modifiers are down
     pub modifiers: Modifiers,
+
+    /// Whether the key was pressed, repeated or released.
+    /// Only terminals that implement the kitty keyboard protocol,
+    /// with `REPORT_EVENT_TYPES` enabled, will report repeats and
+    /// releases; everything else is reported as a press.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub event_type: KeyEventType,
+
+    /// Additional information reported by the kitty keyboard
+    /// protocol, if it was enabled with the appropriate flags
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    pub kitty: Option<KittyKeyDetails>,
+}
+
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum KeyEventType {
+    #[default]
+    Press,
+    Repeat,
+    Release,
+}
+
+/// Holds the optional parts of a kitty keyboard protocol key report
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct KittyKeyDetails {
+    /// The key produced when shift is held, reported when
+    /// `REPORT_ALTERNATE_KEYS` is enabled
+    pub shifted_key: Option<char>,
+    /// The key in the standard PC-101 layout that is in the same
+    /// physical position, reported when `REPORT_ALTERNATE_KEYS` is enabled
+    pub base_layout_key: Option<char>,
+    /// The text generated by the key, reported when
+    /// `REPORT_ASSOCIATED_TEXT` is enabled
+    pub text: Option<String>,
 }
 
 /// Which key is pressed.  Not all of these are probable to appear
@@ -623,6 +659,101 @@ enum InputState {
     Pasting(usize),
 }
 
+/// Maps the key code of a kitty `CSI u` key report to a KeyCode.
+/// The codes for functional keys that have no unicode representation
+/// are allocated from the private use area.
+fn kitty_key_code(code: u32) -> Option<KeyCode> {
+    Some(match code {
+        8 | 127 => KeyCode::Backspace,
+        9 => KeyCode::Tab,
+        10 | 13 => KeyCode::Enter,
+        27 => KeyCode::Escape,
+        57358 => KeyCode::CapsLock,
+        57359 => KeyCode::ScrollLock,
+        57360 => KeyCode::NumLock,
+        57361 => KeyCode::PrintScreen,
+        57362 => KeyCode::Pause,
+        57363 => KeyCode::Menu,
+        57376..=57398 => KeyCode::Function((code - 57376 + 13) as u8),
+        57399 => KeyCode::Numpad0,
+        57400 => KeyCode::Numpad1,
+        57401 => KeyCode::Numpad2,
+        57402 => KeyCode::Numpad3,
+        57403 => KeyCode::Numpad4,
+        57404 => KeyCode::Numpad5,
+        57405 => KeyCode::Numpad6,
+        57406 => KeyCode::Numpad7,
+        57407 => KeyCode::Numpad8,
+        57408 => KeyCode::Numpad9,
+        57409 => KeyCode::Decimal,
+        57410 => KeyCode::Divide,
+        57411 => KeyCode::Multiply,
+        57412 => KeyCode::Subtract,
+        57413 => KeyCode::Add,
+        57414 => KeyCode::Enter,
+        57415 => KeyCode::Char('='),
+        57416 => KeyCode::Separator,
+        57417 => KeyCode::LeftArrow,
+        57418 => KeyCode::RightArrow,
+        57419 => KeyCode::UpArrow,
+        57420 => KeyCode::DownArrow,
+        57421 => KeyCode::KeyPadPageUp,
+        57422 => KeyCode::KeyPadPageDown,
+        57423 => KeyCode::KeyPadHome,
+        57424 => KeyCode::KeyPadEnd,
+        57425 => KeyCode::Insert,
+        57426 => KeyCode::Delete,
+        57427 => KeyCode::KeyPadBegin,
+        57428..=57430 => KeyCode::MediaPlayPause,
+        57432 => KeyCode::MediaStop,
+        57435 => KeyCode::MediaNextTrack,
+        57436 => KeyCode::MediaPrevTrack,
+        57438 => KeyCode::VolumeDown,
+        57439 => KeyCode::VolumeUp,
+        57440 => KeyCode::VolumeMute,
+        57441 => KeyCode::LeftShift,
+        57442 => KeyCode::LeftControl,
+        57443 => KeyCode::LeftAlt,
+        57444 => KeyCode::LeftWindows,
+        57445 | 57451 => KeyCode::Hyper,
+        57446 | 57452 => KeyCode::Meta,
+        57447 => KeyCode::RightShift,
+        57448 => KeyCode::RightControl,
+        57449 => KeyCode::RightAlt,
+        57450 => KeyCode::RightWindows,
+        _ => KeyCode::Char(char::from_u32(code)?),
+    })
+}
+
+/// Maps the number of a `CSI number ~` key report to a KeyCode
+fn kitty_tilde_key_code(code: u32) -> Option<KeyCode> {
+    Some(match code {
+        2 => KeyCode::Insert,
+        3 => KeyCode::Delete,
+        5 => KeyCode::PageUp,
+        6 => KeyCode::PageDown,
+        7 => KeyCode::Home,
+        8 => KeyCode::End,
+        11..=15 => KeyCode::Function((code - 10) as u8),
+        17..=21 => KeyCode::Function((code - 11) as u8),
+        23 | 24 => KeyCode::Function((code - 12) as u8),
+        29 => KeyCode::Menu,
+        57427 => KeyCode::KeyPadBegin,
+        _ => return None,
+    })
+}
+
+/// The outcome of attempting to decode a kitty keyboard protocol report
+#[derive(Debug)]
+enum KittyDecode {
+    /// A key event and the number of bytes that it consumed
+    Key(KeyEvent, usize),
+    /// The buffer holds an incomplete sequence
+    NeedData,
+    /// The buffer doesn't start with a kitty key report
+    NotKitty,
+}
+
 #[derive(Debug)]
 pub struct InputParser {
     key_map: KeyMap<InputEvent>,
@@ -784,6 +915,8 @@ mod windows {
             let input_event = InputEvent::Key(KeyEvent {
                 key: key_code,
                 modifiers,
+                event_type: KeyEventType::Press,
+                kitty: None,
             });
             for _ in 0..event.wRepeatCount {
                 callback(input_event.clone());
@@ -933,6 +1066,8 @@ impl InputParser {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char((alpha as char).to_ascii_lowercase()),
                     modifiers: Modifiers::CTRL,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             );
 
@@ -943,23 +1078,15 @@ impl InputParser {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char(alpha as char),
                     modifiers: Modifiers::ALT,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             );
         }
 
+        // `CSI u` encodings are handled by decode_kitty_key
         for c in 0..=0x7fu8 {
             for (suffix, modifiers) in modifier_combos {
-                // `CSI u` encodings for the ascii range;
-                // see http://www.leonerd.org.uk/hacks/fixterms/
-                let key = format!("\x1b[{}{}u", c, suffix);
-                map.insert(
-                    key,
-                    InputEvent::Key(KeyEvent {
-                        key: KeyCode::Char(c as char),
-                        modifiers: *modifiers,
-                    }),
-                );
-
                 if !suffix.is_empty() {
                     // xterm modifyOtherKeys sequences
                     let key = format!("\x1b[27{};{}~", suffix, c);
@@ -974,6 +1101,8 @@ impl InputParser {
                                 _ => KeyCode::Char(c as char),
                             },
                             modifiers: *modifiers,
+                            event_type: KeyEventType::Press,
+                            kitty: None,
                         }),
                     );
                 }
@@ -996,6 +1125,8 @@ impl InputParser {
                 InputEvent::Key(KeyEvent {
                     key: *keycode,
                     modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             );
             for (suffix, modifiers) in modifier_combos_including_meta() {
@@ -1005,6 +1136,8 @@ impl InputParser {
                     InputEvent::Key(KeyEvent {
                         key: *keycode,
                         modifiers: *modifiers,
+                        event_type: KeyEventType::Press,
+                        kitty: None,
                     }),
                 );
             }
@@ -1025,6 +1158,8 @@ impl InputParser {
                     InputEvent::Key(KeyEvent {
                         key: keycode,
                         modifiers: mods,
+                        event_type: KeyEventType::Press,
+                        kitty: None,
                     }),
                 );
             }
@@ -1043,6 +1178,8 @@ impl InputParser {
                 InputEvent::Key(KeyEvent {
                     key: *keycode,
                     modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             );
             for (suffix, modifiers) in modifier_combos {
@@ -1052,6 +1189,8 @@ impl InputParser {
                     InputEvent::Key(KeyEvent {
                         key: *keycode,
                         modifiers: *modifiers,
+                        event_type: KeyEventType::Press,
+                        kitty: None,
                     }),
                 );
             }
@@ -1070,6 +1209,8 @@ impl InputParser {
                 InputEvent::Key(KeyEvent {
                     key: *keycode,
                     modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             );
         }
@@ -1088,6 +1229,8 @@ impl InputParser {
                     InputEvent::Key(KeyEvent {
                         key: *keycode,
                         modifiers: *modifiers,
+                        event_type: KeyEventType::Press,
+                        kitty: None,
                     }),
                 );
             }
@@ -1115,6 +1258,8 @@ impl InputParser {
                         InputEvent::Key(KeyEvent {
                             key: KeyCode::Function(n),
                             modifiers: *modifiers,
+                            event_type: KeyEventType::Press,
+                            kitty: None,
                         }),
                     );
                 }
@@ -1144,6 +1289,8 @@ impl InputParser {
                     InputEvent::Key(KeyEvent {
                         key: *keycode,
                         modifiers: *modifiers,
+                        event_type: KeyEventType::Press,
+                        kitty: None,
                     }),
                 );
             }
@@ -1154,6 +1301,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Backspace,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
 
@@ -1162,6 +1311,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Backspace,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
 
@@ -1170,6 +1321,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Escape,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
 
@@ -1178,6 +1331,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Tab,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
         map.insert(
@@ -1185,6 +1340,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Tab,
                 modifiers: Modifiers::SHIFT,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
 
@@ -1193,6 +1350,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Enter,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
         map.insert(
@@ -1200,6 +1359,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Enter,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
 
@@ -1208,6 +1369,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::InternalPasteStart,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
         map.insert(
@@ -1215,6 +1378,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::InternalPasteEnd,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
         map.insert(
@@ -1222,6 +1387,8 @@ impl InputParser {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('['),
                 modifiers: Modifiers::ALT,
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),
         );
 
@@ -1263,6 +1430,124 @@ impl InputParser {
         }
     }
 
+    /// Attempts to decode a key report of the form
+    /// `CSI key-code:shifted-key:base-layout-key ; modifiers:event-type ; text u`
+    /// that is produced by terminals that implement the kitty keyboard
+    /// protocol <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>.
+    /// The `~` and letter terminated forms are only decoded here if
+    /// they carry information that the legacy encodings cannot express;
+    /// the others are left for the keymap.
+    fn decode_kitty_key(bytes: &[u8]) -> KittyDecode {
+        if !bytes.starts_with(b"\x1b[") {
+            return KittyDecode::NotKitty;
+        }
+        let params_len = match bytes[2..]
+            .iter()
+            .position(|&b| !matches!(b, b'0'..=b'9' | b';' | b':'))
+        {
+            Some(len) => len,
+            None => return KittyDecode::NeedData,
+        };
+        let final_byte = bytes[2 + params_len];
+        // The params are all ascii, so this cannot fail
+        let params = std::str::from_utf8(&bytes[2..2 + params_len]).unwrap_or("");
+
+        let fields: Vec<Vec<Option<u32>>> = params
+            .split(';')
+            .map(|field| field.split(':').map(|n| n.parse().ok()).collect())
+            .collect();
+        let param = |field: usize, sub: usize| -> Option<u32> {
+            fields
+                .get(field)
+                .and_then(|f| f.get(sub))
+                .copied()
+                .flatten()
+        };
+
+        let mods = param(1, 0).unwrap_or(1).saturating_sub(1);
+        let event_type = param(1, 1);
+        // Only the kitty protocol reports event types, or modifiers
+        // beyond shift, alt and ctrl
+        let beyond_legacy = event_type.is_some() || mods > 7;
+
+        let key = match final_byte {
+            b'u' => match param(0, 0).and_then(kitty_key_code) {
+                Some(key) => key,
+                None => return KittyDecode::NotKitty,
+            },
+            b'~' if beyond_legacy && fields.len() <= 2 => {
+                match param(0, 0).and_then(kitty_tilde_key_code) {
+                    Some(key) => key,
+                    None => return KittyDecode::NotKitty,
+                }
+            }
+            b'A' | b'B' | b'C' | b'D' | b'E' | b'F' | b'H' | b'P' | b'Q' | b'S'
+                if beyond_legacy && fields.len() <= 2 && param(0, 0).unwrap_or(1) == 1 =>
+            {
+                match final_byte {
+                    b'A' => KeyCode::UpArrow,
+                    b'B' => KeyCode::DownArrow,
+                    b'C' => KeyCode::RightArrow,
+                    b'D' => KeyCode::LeftArrow,
+                    b'E' => KeyCode::KeyPadBegin,
+                    b'F' => KeyCode::End,
+                    b'H' => KeyCode::Home,
+                    b'P' => KeyCode::Function(1),
+                    b'Q' => KeyCode::Function(2),
+                    _ => KeyCode::Function(4),
+                }
+            }
+            _ => return KittyDecode::NotKitty,
+        };
+
+        let mut modifiers = Modifiers::NONE;
+        if mods & 1 != 0 {
+            modifiers |= Modifiers::SHIFT;
+        }
+        if mods & 2 != 0 {
+            modifiers |= Modifiers::ALT;
+        }
+        if mods & 4 != 0 {
+            modifiers |= Modifiers::CTRL;
+        }
+        if mods & 8 != 0 {
+            modifiers |= Modifiers::SUPER;
+        }
+
+        let text: String = fields
+            .get(2)
+            .map(|codepoints| {
+                codepoints
+                    .iter()
+                    .filter_map(|c| c.and_then(char::from_u32))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let details = KittyKeyDetails {
+            shifted_key: param(0, 1).and_then(char::from_u32),
+            base_layout_key: param(0, 2).and_then(char::from_u32),
+            text: if text.is_empty() { None } else { Some(text) },
+        };
+
+        KittyDecode::Key(
+            KeyEvent {
+                key,
+                modifiers,
+                event_type: match event_type {
+                    Some(2) => KeyEventType::Repeat,
+                    Some(3) => KeyEventType::Release,
+                    _ => KeyEventType::Press,
+                },
+                kitty: if details == KittyKeyDetails::default() {
+                    None
+                } else {
+                    Some(details)
+                },
+            },
+            2 + params_len + 1,
+        )
+    }
+
     fn dispatch_callback<F: FnMut(InputEvent)>(&mut self, mut callback: F, event: InputEvent) {
         match (self.state, event) {
             (
@@ -1286,15 +1571,19 @@ impl InputParser {
                 callback(InputEvent::Key(KeyEvent {
                     key: KeyCode::Escape,
                     modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }));
                 self.state = InputState::Pasting(0);
             }
-            (InputState::EscapeMaybeAlt, InputEvent::Key(KeyEvent { key, modifiers })) => {
+            (InputState::EscapeMaybeAlt, InputEvent::Key(KeyEvent { key, modifiers, .. })) => {
                 // Treat this as ALT-key
                 self.state = InputState::Normal;
                 callback(InputEvent::Key(KeyEvent {
                     key,
                     modifiers: modifiers | Modifiers::ALT,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }));
             }
             (InputState::EscapeMaybeAlt, event) => {
@@ -1303,6 +1592,8 @@ impl InputParser {
                 callback(InputEvent::Key(KeyEvent {
                     key: KeyCode::Escape,
                     modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }));
                 callback(event);
             }
@@ -1375,6 +1666,16 @@ impl InputParser {
                             }
                             continue;
                         }
+
+                        match Self::decode_kitty_key(self.buf.as_slice()) {
+                            KittyDecode::Key(event, len) => {
+                                self.buf.advance(len);
+                                callback(InputEvent::Key(event));
+                                continue;
+                            }
+                            KittyDecode::NeedData if maybe_more => return,
+                            KittyDecode::NeedData | KittyDecode::NotKitty => {}
+                        }
                     }
 
                     match (
@@ -1392,6 +1693,7 @@ impl InputParser {
                                 InputEvent::Key(KeyEvent {
                                     key: KeyCode::Escape,
                                     modifiers: Modifiers::NONE,
+                                    ..
                                 }),
                             ),
                             _,
@@ -1415,6 +1717,8 @@ impl InputParser {
                                     InputEvent::Key(KeyEvent {
                                         key: KeyCode::Char(c),
                                         modifiers: Modifiers::NONE,
+                                        event_type: KeyEventType::Press,
+                                        kitty: None,
                                     }),
                                 );
                             } else {
@@ -1478,22 +1782,32 @@ mod test {
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('h'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('e'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('l'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('l'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('o'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             ],
             inputs
@@ -1509,14 +1823,20 @@ mod test {
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::CTRL,
                     key: KeyCode::Char('c'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::ALT,
                     key: KeyCode::Char('J'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Backspace,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             ],
             inputs
@@ -1532,18 +1852,26 @@ mod test {
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::ApplicationUpArrow,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::ApplicationDownArrow,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::ApplicationRightArrow,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::ApplicationLeftArrow,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             ],
             inputs
@@ -1562,6 +1890,8 @@ mod test {
             vec![InputEvent::Key(KeyEvent {
                 modifiers: Modifiers::NONE,
                 key: KeyCode::Function(1),
+                event_type: KeyEventType::Press,
+                kitty: None,
             })],
             inputs
         );
@@ -1575,6 +1905,8 @@ mod test {
             vec![InputEvent::Key(KeyEvent {
                 key: KeyCode::Escape,
                 modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
             })],
             p.parse_as_vec(b"\x1b", false)
         );
@@ -1590,14 +1922,20 @@ mod test {
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::ALT,
                     key: KeyCode::Char('['),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('1'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     modifiers: Modifiers::NONE,
                     key: KeyCode::Char('1'),
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             ],
             inputs
@@ -1617,6 +1955,8 @@ mod test {
             vec![InputEvent::Key(KeyEvent {
                 modifiers: Modifiers::ALT,
                 key: KeyCode::Char('['),
+                event_type: KeyEventType::Press,
+                kitty: None,
             }),],
             inputs
         );
@@ -1634,28 +1974,136 @@ mod test {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Enter,
                     modifiers: Modifiers::CTRL,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Tab,
                     modifiers: Modifiers::CTRL,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Backspace,
                     modifiers: Modifiers::CTRL | Modifiers::SHIFT,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Backspace,
                     modifiers: Modifiers::SHIFT,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Escape,
                     modifiers: Modifiers::CTRL | Modifiers::SHIFT,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
                 }),
             ],
             inputs
         );
     }
 
+    #[test]
+    fn kitty_keyboard_parse() {
+        let mut p = InputParser::new();
+        let inputs = p.parse_as_vec(
+            b"\x1b[105;5u\x1b[9u\x1b[97;1:2u\x1b[97;1:3u\x1b[27u\x1b[57399;9u",
+            NO_MORE,
+        );
+        assert_eq!(
+            vec![
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char('i'),
+                    modifiers: Modifiers::CTRL,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Tab,
+                    modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char('a'),
+                    modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Repeat,
+                    kitty: None,
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char('a'),
+                    modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Release,
+                    kitty: None,
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Escape,
+                    modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Numpad0,
+                    modifiers: Modifiers::SUPER,
+                    event_type: KeyEventType::Press,
+                    kitty: None,
+                }),
+            ],
+            inputs
+        );
+    }
+
+    #[test]
+    fn kitty_keyboard_parse_alternates_and_text() {
+        let mut p = InputParser::new();
+        let inputs = p.parse_as_vec(b"\x1b[97:65:113;2;65u\x1b[1;1:3A\x1b[5;3:2~", NO_MORE);
+        assert_eq!(
+            vec![
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char('a'),
+                    modifiers: Modifiers::SHIFT,
+                    event_type: KeyEventType::Press,
+                    kitty: Some(KittyKeyDetails {
+                        shifted_key: Some('A'),
+                        base_layout_key: Some('q'),
+                        text: Some("A".to_string()),
+                    }),
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::UpArrow,
+                    modifiers: Modifiers::NONE,
+                    event_type: KeyEventType::Release,
+                    kitty: None,
+                }),
+                InputEvent::Key(KeyEvent {
+                    key: KeyCode::PageUp,
+                    modifiers: Modifiers::ALT,
+                    event_type: KeyEventType::Repeat,
+                    kitty: None,
+                }),
+            ],
+            inputs
+        );
+    }
+
+    #[test]
+    fn kitty_keyboard_partial() {
+        let mut p = InputParser::new();
+        assert_eq!(p.parse_as_vec(b"\x1b[97;1", MAYBE_MORE), vec![]);
+        assert_eq!(
+            p.parse_as_vec(b":3u", NO_MORE),
+            vec![InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('a'),
+                modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Release,
+                kitty: None,
+            })]
+        );
+    }
+
     #[test]
     fn modify_other_keys_encode() {
         let mode = KeyCodeEncodeModes {
@@ -37,8 +37,11 @@
 //!
 //! ## Features
 //!
-//! * `widgets` - enables the widget layout and related traits
+//! * `widgets` - enables the widget layout and related traits, along with
+//!   a library of standard widgets
 //! * `use_serde` - makes a number of structs serde serializable
+//! * `frecency` - ranks `lineedit::FileHistory` search results by how
+//!   frequently and recently the entries were used
 
 pub mod caps;
 pub use wezterm_cell as cell;
@@ -57,6 +60,7 @@ pub use wezterm_char_props::nerdfonts;
 mod readbuf;
 pub mod render;
 pub use wezterm_surface as surface;
+pub mod tabulate;
 pub mod terminal;
 #[cfg(feature = "tmux_cc")]
 pub use wezterm_escape_parser::tmux_cc;
@@ -1,17 +1,84 @@
 pub type RepeatCount = usize;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Movement {
     BackwardChar(RepeatCount),
+    /// Move to the start of the previous word, where words are
+    /// delimited by Unicode word boundaries
     BackwardWord(RepeatCount),
     ForwardChar(RepeatCount),
+    /// Move to the start of the next word, where words are
+    /// delimited by Unicode word boundaries
     ForwardWord(RepeatCount),
+    /// Move to the last grapheme of the current or next word
+    ForwardWordEnd(RepeatCount),
+    /// Like `BackwardWord`, but words are delimited only by whitespace
+    BackwardBigWord(RepeatCount),
+    /// Like `ForwardWord`, but words are delimited only by whitespace
+    ForwardBigWord(RepeatCount),
+    /// Like `ForwardWordEnd`, but words are delimited only by whitespace
+    ForwardBigWordEnd(RepeatCount),
+    /// Move to the Nth occurrence of the char after the cursor
+    ForwardToChar(RepeatCount, char),
+    /// Move to the grapheme before the Nth occurrence of the char
+    /// after the cursor
+    ForwardTillChar(RepeatCount, char),
+    /// Move to the Nth occurrence of the char before the cursor
+    BackwardToChar(RepeatCount, char),
+    /// Move to the grapheme after the Nth occurrence of the char
+    /// before the cursor
+    BackwardTillChar(RepeatCount, char),
+    /// Move to the start of the current line of the buffer
     StartOfLine,
+    /// Move to the first non-whitespace grapheme of the current line
+    FirstNonBlank,
+    /// Move to the end of the current line of the buffer
     EndOfLine,
+    /// Move up to the previous line of a multi-line buffer
+    PreviousLine(RepeatCount),
+    /// Move down to the next line of a multi-line buffer
+    NextLine(RepeatCount),
     None,
 }
 
-#[derive(Debug, Clone)]
+impl Movement {
+    /// Returns true if a kill or copy using this movement includes
+    /// the grapheme at the destination, as is the case for the vi
+    /// `e` and `f` motions.
+    pub fn is_inclusive(&self) -> bool {
+        matches!(
+            self,
+            Movement::ForwardWordEnd(_)
+                | Movement::ForwardBigWordEnd(_)
+                | Movement::ForwardToChar(..)
+                | Movement::ForwardTillChar(..)
+        )
+    }
+
+    /// Returns true if this movement is towards the start of the buffer
+    pub fn is_backward(&self) -> bool {
+        matches!(
+            self,
+            Movement::BackwardChar(_)
+                | Movement::BackwardWord(_)
+                | Movement::BackwardBigWord(_)
+                | Movement::BackwardToChar(..)
+                | Movement::BackwardTillChar(..)
+                | Movement::StartOfLine
+                | Movement::FirstNonBlank
+                | Movement::PreviousLine(_)
+        )
+    }
+}
+
+/// Identifies the editing mode of the vi keymap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViMode {
+    Insert,
+    Normal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Action {
     AcceptLine,
     Cancel,
@@ -22,10 +89,45 @@ pub enum Action {
     Move(Movement),
     Kill(Movement),
     KillAndMove(Movement, Movement),
+    /// Copy the text between the cursor and the movement
+    /// destination to the kill ring, without changing the buffer
+    Copy(Movement),
+    /// Insert the most recently killed text
+    Yank(RepeatCount),
+    /// Immediately after a `Yank` or `YankPop`, replace the yanked
+    /// text with the previous entry from the kill ring
+    YankPop,
+    Undo,
+    Redo,
     HistoryPrevious,
     HistoryNext,
     Complete,
+    /// Like `Complete`, but cycles backwards through the candidates
+    CompletePrevious,
     NoAction,
     HistoryIncSearchBackwards,
     HistoryIncSearchForwards,
+    /// Switch the vi keymap to the specified mode
+    SetViMode(ViMode),
+    /// Apply a series of actions as a single undoable step
+    Sequence(Vec<Action>),
+}
+
+impl Action {
+    /// Returns true if the action may change the content of the
+    /// line buffer, and should therefore be undoable.
+    pub fn is_edit(&self) -> bool {
+        match self {
+            Action::InsertChar(..)
+            | Action::InsertText(..)
+            | Action::Kill(_)
+            | Action::KillAndMove(..)
+            | Action::Yank(_)
+            | Action::YankPop
+            | Action::Complete
+            | Action::CompletePrevious => true,
+            Action::Sequence(actions) => actions.iter().any(Action::is_edit),
+            _ => false,
+        }
+    }
 }
@@ -1,12 +1,21 @@
-use unicode_segmentation::GraphemeCursor;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
 
 use super::actions::Movement;
 
+/// A snapshot of the line and cursor position, used to implement
+/// undo and redo
+struct UndoState {
+    line: String,
+    cursor: usize,
+}
+
 pub struct LineEditBuffer {
     line: String,
     /// byte index into the UTF-8 string data of the insertion
     /// point.  This is NOT the number of graphemes!
     cursor: usize,
+    undo_stack: Vec<UndoState>,
+    redo_stack: Vec<UndoState>,
 }
 
 impl Default for LineEditBuffer {
@@ -14,10 +23,23 @@ impl Default for LineEditBuffer {
         Self {
             line: String::new(),
             cursor: 0,
+            undo_stack: vec![],
+            redo_stack: vec![],
         }
     }
 }
 
+/// Classifies a segment of text for the purposes of word movement
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WordClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+/// The maximum number of entries retained on the undo stack
+const MAX_UNDO: usize = 256;
+
 impl LineEditBuffer {
     pub fn new(line: &str, cursor: usize) -> Self {
         let mut buffer = Self::default();
@@ -60,22 +82,51 @@ impl LineEditBuffer {
         self.cursor = cursor;
     }
 
+    /// Replaces the text in the specified byte range with `text`,
+    /// leaving the cursor positioned after the replacement.
+    /// Panics: the range must lie on char boundaries of the line.
+    pub fn replace_range(&mut self, range: std::ops::Range<usize>, text: &str) {
+        let start = range.start;
+        self.line.replace_range(range, text);
+        self.cursor = start + text.len();
+    }
+
     pub fn kill_text(&mut self, kill_movement: Movement, move_movement: Movement) {
-        let kill_pos = self.eval_movement(kill_movement);
+        let range = self.movement_range(kill_movement);
         let new_cursor = self.eval_movement(move_movement);
 
-        let (lower, upper) = if kill_pos < self.cursor {
-            (kill_pos, self.cursor)
-        } else {
-            (self.cursor, kill_pos)
-        };
-
-        self.line.replace_range(lower..upper, "");
+        self.line.replace_range(range.clone(), "");
 
         // Clamp to the line length, otherwise a kill to end of line
         // command will leave the cursor way off beyond the end of
         // the line.
-        self.cursor = new_cursor.min(self.line.len());
+        self.cursor = if new_cursor > range.start {
+            new_cursor.saturating_sub(range.len()).max(range.start)
+        } else {
+            new_cursor
+        }
+        .min(self.line.len());
+    }
+
+    /// Returns the byte range spanned by the cursor and the destination
+    /// of the movement.  If the movement is inclusive, the range is
+    /// extended to include the grapheme at the destination.
+    pub fn movement_range(&self, movement: Movement) -> std::ops::Range<usize> {
+        let mut pos = self.eval_movement(movement);
+        if movement.is_inclusive() && pos >= self.cursor {
+            pos = self.next_grapheme(pos).unwrap_or(pos);
+        }
+        if pos < self.cursor {
+            pos..self.cursor
+        } else {
+            self.cursor..pos
+        }
+    }
+
+    /// Returns the text that a kill or copy using the specified
+    /// movement would operate upon
+    pub fn text_for_movement(&self, movement: Movement) -> &str {
+        &self.line[self.movement_range(movement)]
     }
 
     pub fn clear(&mut self) {
@@ -87,85 +138,241 @@ impl LineEditBuffer {
         self.cursor = self.eval_movement(movement);
     }
 
-    /// Compute the cursor position after applying movement
-    fn eval_movement(&self, movement: Movement) -> usize {
-        match movement {
-            Movement::BackwardChar(rep) => {
-                let mut position = self.cursor;
-                for _ in 0..rep {
-                    let mut cursor = GraphemeCursor::new(position, self.line.len(), false);
-                    if let Ok(Some(pos)) = cursor.prev_boundary(&self.line, 0) {
-                        position = pos;
-                    } else {
-                        break;
+    /// Records the current line and cursor position so that a subsequent
+    /// call to `undo` can restore them.  This clears the redo stack.
+    pub fn save_undo_state(&mut self) {
+        let unchanged = self
+            .undo_stack
+            .last()
+            .map(|state| state.line == self.line)
+            .unwrap_or(false);
+        if !unchanged {
+            if self.undo_stack.len() == MAX_UNDO {
+                self.undo_stack.remove(0);
+            }
+            self.undo_stack.push(self.current_state());
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Restores the line to the most recently saved state that differs
+    /// from the current line.  Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        while let Some(state) = self.undo_stack.pop() {
+            if state.line != self.line {
+                self.redo_stack.push(self.current_state());
+                self.restore_state(state);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Reverts the most recent `undo`.
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(state) => {
+                self.undo_stack.push(self.current_state());
+                self.restore_state(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Discards the undo and redo history
+    pub fn clear_undo_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    fn current_state(&self) -> UndoState {
+        UndoState {
+            line: self.line.clone(),
+            cursor: self.cursor,
+        }
+    }
+
+    fn restore_state(&mut self, state: UndoState) {
+        self.line = state.line;
+        self.cursor = state.cursor;
+    }
+
+    fn next_grapheme(&self, position: usize) -> Option<usize> {
+        let mut cursor = GraphemeCursor::new(position, self.line.len(), false);
+        cursor.next_boundary(&self.line, 0).ok().flatten()
+    }
+
+    fn prev_grapheme(&self, position: usize) -> Option<usize> {
+        let mut cursor = GraphemeCursor::new(position, self.line.len(), false);
+        cursor.prev_boundary(&self.line, 0).ok().flatten()
+    }
+
+    /// Returns the byte index of the start of the line that
+    /// contains `position`
+    fn line_start(&self, position: usize) -> usize {
+        self.line[..position]
+            .rfind('\n')
+            .map(|idx| idx + 1)
+            .unwrap_or(0)
+    }
+
+    /// Returns the byte index of the end of the line that
+    /// contains `position`, excluding its newline
+    fn line_end(&self, position: usize) -> usize {
+        self.line[position..]
+            .find('\n')
+            .map(|idx| idx + position)
+            .unwrap_or(self.line.len())
+    }
+
+    /// Returns the (start, end) byte ranges of the words in the line.
+    /// When `big` is true, words are runs of non-whitespace.  Otherwise
+    /// the line is split at Unicode word boundaries, and words are
+    /// either word segments or runs of punctuation.
+    fn words(&self, big: bool) -> Vec<(usize, usize)> {
+        let mut words: Vec<(usize, usize)> = vec![];
+        let mut prior = WordClass::Whitespace;
+        for (idx, segment) in self.line.split_word_bound_indices() {
+            let class = if segment.chars().all(char::is_whitespace) {
+                WordClass::Whitespace
+            } else if big {
+                WordClass::Punctuation
+            } else if segment.chars().any(char::is_alphanumeric) {
+                WordClass::Word
+            } else {
+                WordClass::Punctuation
+            };
+            let end = idx + segment.len();
+            match class {
+                WordClass::Whitespace => {}
+                WordClass::Punctuation if prior == WordClass::Punctuation => {
+                    if let Some(word) = words.last_mut() {
+                        word.1 = end;
                     }
                 }
-                position
+                _ => words.push((idx, end)),
             }
-            Movement::BackwardWord(rep) => {
-                let char_indices: Vec<(usize, char)> = self.line.char_indices().collect();
-                if char_indices.is_empty() {
-                    return self.cursor;
-                }
-                let mut char_position = char_indices
-                    .iter()
-                    .position(|(idx, _)| *idx == self.cursor)
-                    .unwrap_or(char_indices.len() - 1);
+            prior = class;
+        }
+        words
+    }
 
-                for _ in 0..rep {
-                    if char_position == 0 {
-                        break;
-                    }
+    fn backward_word(&self, rep: usize, big: bool) -> usize {
+        let words = self.words(big);
+        let mut position = self.cursor;
+        for _ in 0..rep {
+            match words.iter().rev().find(|(start, _)| *start < position) {
+                Some((start, _)) => position = *start,
+                None => return 0,
+            }
+        }
+        position
+    }
 
-                    let mut found = None;
-                    for prev in (0..char_position - 1).rev() {
-                        if char_indices[prev].1.is_whitespace() {
-                            found = Some(prev + 1);
-                            break;
-                        }
-                    }
+    fn forward_word(&self, rep: usize, big: bool) -> usize {
+        let words = self.words(big);
+        let mut position = self.cursor;
+        for _ in 0..rep {
+            match words.iter().find(|(start, _)| *start > position) {
+                Some((start, _)) => position = *start,
+                None => return self.line.len(),
+            }
+        }
+        position
+    }
 
-                    char_position = found.unwrap_or(0);
-                }
-                char_indices[char_position].0
+    fn forward_word_end(&self, rep: usize, big: bool) -> usize {
+        let words = self.words(big);
+        let mut position = self.cursor;
+        for _ in 0..rep {
+            let last_grapheme = |(_, end): &(usize, usize)| self.prev_grapheme(*end);
+            match words
+                .iter()
+                .filter_map(last_grapheme)
+                .find(|last| *last > position)
+            {
+                Some(last) => position = last,
+                None => break,
             }
-            Movement::ForwardWord(rep) => {
-                let char_indices: Vec<(usize, char)> = self.line.char_indices().collect();
-                if char_indices.is_empty() {
-                    return self.cursor;
+        }
+        position
+    }
+
+    /// Finds the Nth occurrence of `c` on the current line, searching
+    /// forwards or backwards from the cursor
+    fn find_char(&self, rep: usize, c: char, forward: bool) -> Option<usize> {
+        if forward {
+            let start = self.next_grapheme(self.cursor)?;
+            let end = self.line_end(self.cursor).max(start);
+            self.line[start..end]
+                .match_indices(c)
+                .nth(rep.saturating_sub(1))
+                .map(|(idx, _)| start + idx)
+        } else {
+            let start = self.line_start(self.cursor);
+            self.line[start..self.cursor]
+                .rmatch_indices(c)
+                .nth(rep.saturating_sub(1))
+                .map(|(idx, _)| start + idx)
+        }
+    }
+
+    /// Moves vertically by `delta` lines, retaining the column
+    /// of the cursor as closely as possible
+    fn vertical(&self, delta: isize) -> usize {
+        let start = self.line_start(self.cursor);
+        let column = self.line[start..self.cursor].graphemes(true).count();
+
+        let mut line_start = start;
+        for _ in 0..delta.unsigned_abs() {
+            if delta < 0 {
+                if line_start == 0 {
+                    break;
+                }
+                line_start = self.line_start(line_start - 1);
+            } else {
+                let end = self.line_end(line_start);
+                if end == self.line.len() {
+                    break;
                 }
-                let mut char_position = char_indices
-                    .iter()
-                    .position(|(idx, _)| *idx == self.cursor)
-                    .unwrap_or_else(|| char_indices.len());
+                line_start = end + 1;
+            }
+        }
 
-                for _ in 0..rep {
-                    // Skip any non-whitespace characters
-                    while char_position < char_indices.len()
-                        && !char_indices[char_position].1.is_whitespace()
-                    {
-                        char_position += 1;
-                    }
+        let line_end = self.line_end(line_start);
+        self.line[line_start..line_end]
+            .grapheme_indices(true)
+            .nth(column)
+            .map(|(idx, _)| line_start + idx)
+            .unwrap_or(line_end)
+    }
 
-                    // Skip any whitespace characters
-                    while char_position < char_indices.len()
-                        && char_indices[char_position].1.is_whitespace()
-                    {
-                        char_position += 1;
+    /// Compute the cursor position after applying movement
+    fn eval_movement(&self, movement: Movement) -> usize {
+        match movement {
+            Movement::BackwardChar(rep) => {
+                let mut position = self.cursor;
+                for _ in 0..rep {
+                    if let Some(pos) = self.prev_grapheme(position) {
+                        position = pos;
+                    } else {
+                        break;
                     }
-
-                    // We are now on the start of the next word
                 }
-                char_indices
-                    .get(char_position)
-                    .map(|(i, _)| *i)
-                    .unwrap_or_else(|| self.line.len())
+                position
             }
+            Movement::BackwardWord(rep) => self.backward_word(rep, false),
+            Movement::BackwardBigWord(rep) => self.backward_word(rep, true),
+            Movement::ForwardWord(rep) => self.forward_word(rep, false),
+            Movement::ForwardBigWord(rep) => self.forward_word(rep, true),
+            Movement::ForwardWordEnd(rep) => self.forward_word_end(rep, false),
+            Movement::ForwardBigWordEnd(rep) => self.forward_word_end(rep, true),
             Movement::ForwardChar(rep) => {
                 let mut position = self.cursor;
                 for _ in 0..rep {
-                    let mut cursor = GraphemeCursor::new(position, self.line.len(), false);
-                    if let Ok(Some(pos)) = cursor.next_boundary(&self.line, 0) {
+                    if let Some(pos) = self.next_grapheme(position) {
                         position = pos;
                     } else {
                         break;
@@ -173,17 +380,134 @@ impl LineEditBuffer {
                 }
                 position
             }
-            Movement::StartOfLine => 0,
-            Movement::EndOfLine => {
-                let mut cursor =
-                    GraphemeCursor::new(self.line.len().saturating_sub(1), self.line.len(), false);
-                if let Ok(Some(pos)) = cursor.next_boundary(&self.line, 0) {
-                    pos
-                } else {
-                    self.cursor
-                }
+            Movement::ForwardToChar(rep, c) => self.find_char(rep, c, true).unwrap_or(self.cursor),
+            Movement::ForwardTillChar(rep, c) => self
+                .find_char(rep, c, true)
+                .and_then(|pos| self.prev_grapheme(pos))
+                .unwrap_or(self.cursor),
+            Movement::BackwardToChar(rep, c) => {
+                self.find_char(rep, c, false).unwrap_or(self.cursor)
             }
+            Movement::BackwardTillChar(rep, c) => self
+                .find_char(rep, c, false)
+                .and_then(|pos| self.next_grapheme(pos))
+                .unwrap_or(self.cursor),
+            Movement::StartOfLine => self.line_start(self.cursor),
+            Movement::FirstNonBlank => {
+                let start = self.line_start(self.cursor);
+                let end = self.line_end(self.cursor);
+                self.line[start..end]
+                    .find(|c: char| !c.is_whitespace())
+                    .map(|idx| start + idx)
+                    .unwrap_or(end)
+            }
+            Movement::EndOfLine => self.line_end(self.cursor),
+            Movement::PreviousLine(rep) => self.vertical(-(rep as isize)),
+            Movement::NextLine(rep) => self.vertical(rep as isize),
             Movement::None => self.cursor,
         }
     }
+
+    /// Returns true if the cursor is on the first line of the buffer
+    pub fn is_on_first_line(&self) -> bool {
+        self.line_start(self.cursor) == 0
+    }
+
+    /// Returns true if the cursor is on the last line of the buffer
+    pub fn is_on_last_line(&self) -> bool {
+        self.line_end(self.cursor) == self.line.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn moved(line: &str, cursor: usize, movement: Movement) -> usize {
+        let mut buffer = LineEditBuffer::new(line, cursor);
+        buffer.exec_movement(movement);
+        buffer.get_cursor()
+    }
+
+    #[test]
+    fn word_movement() {
+        let line = "let x = foo(bar, ünïcode);";
+        assert_eq!(moved(line, 0, Movement::ForwardWord(1)), 4);
+        assert_eq!(moved(line, 8, Movement::ForwardWord(1)), 11);
+        assert_eq!(moved(line, 8, Movement::ForwardWord(3)), 15);
+        assert_eq!(moved(line, 8, Movement::ForwardBigWord(1)), 17);
+        assert_eq!(moved(line, 17, Movement::BackwardWord(1)), 15);
+        assert_eq!(moved(line, 17, Movement::BackwardBigWord(1)), 8);
+        assert_eq!(moved(line, 8, Movement::ForwardWordEnd(1)), 10);
+        assert_eq!(moved(line, 17, Movement::ForwardWordEnd(1)), 25);
+        assert_eq!(moved(line, 3, Movement::BackwardWord(5)), 0);
+
+        // CJK ideographs are each their own word
+        assert_eq!(moved("漢字 ok", 0, Movement::ForwardWord(1)), 3);
+    }
+
+    #[test]
+    fn char_search_and_lines() {
+        let line = "  a(b, c)\n  d\nend";
+        assert_eq!(moved(line, 0, Movement::FirstNonBlank), 2);
+        assert_eq!(moved(line, 2, Movement::ForwardToChar(1, ',')), 5);
+        // there is no second occurrence, so the cursor doesn't move
+        assert_eq!(moved(line, 2, Movement::ForwardToChar(2, ',')), 2);
+        assert_eq!(moved(line, 2, Movement::ForwardTillChar(1, ')')), 7);
+        assert_eq!(moved(line, 8, Movement::BackwardToChar(1, '(')), 3);
+        assert_eq!(moved(line, 8, Movement::BackwardTillChar(1, '(')), 4);
+        // searches don't cross lines
+        assert_eq!(moved(line, 2, Movement::ForwardToChar(1, 'd')), 2);
+
+        assert_eq!(moved(line, 12, Movement::StartOfLine), 10);
+        assert_eq!(moved(line, 12, Movement::EndOfLine), 13);
+        assert_eq!(moved(line, 12, Movement::PreviousLine(1)), 2);
+        assert_eq!(moved(line, 12, Movement::NextLine(1)), 16);
+        assert_eq!(moved(line, 8, Movement::NextLine(5)), 17);
+    }
+
+    #[test]
+    fn inclusive_kill() {
+        let mut buffer = LineEditBuffer::new("hello world", 0);
+        assert_eq!(
+            buffer.text_for_movement(Movement::ForwardWordEnd(1)),
+            "hello"
+        );
+        buffer.kill_text(Movement::ForwardToChar(1, 'o'), Movement::None);
+        assert_eq!(buffer.get_line(), " world");
+        assert_eq!(buffer.get_cursor(), 0);
+
+        let mut buffer = LineEditBuffer::new("hello world", 11);
+        buffer.kill_text(Movement::BackwardWord(1), Movement::BackwardWord(1));
+        assert_eq!(buffer.get_line(), "hello ");
+        assert_eq!(buffer.get_cursor(), 6);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut buffer = LineEditBuffer::default();
+        buffer.save_undo_state();
+        buffer.insert_text("hello");
+        buffer.save_undo_state();
+        buffer.insert_text(" world");
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.get_line(), "hello");
+        assert!(buffer.undo());
+        assert_eq!(buffer.get_line(), "");
+        assert!(!buffer.undo());
+
+        assert!(buffer.redo());
+        assert!(buffer.redo());
+        assert_eq!(buffer.get_line(), "hello world");
+        assert_eq!(buffer.get_cursor(), 11);
+        assert!(!buffer.redo());
+
+        buffer.undo();
+        buffer.save_undo_state();
+        buffer.insert_char('!');
+        assert!(!buffer.redo());
+        assert!(buffer.undo());
+        assert_eq!(buffer.get_line(), "hello");
+    }
 }
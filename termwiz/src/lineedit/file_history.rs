@@ -0,0 +1,576 @@
+use crate::lineedit::history::{fuzzy_match, History, HistoryIndex};
+use crate::lineedit::{SearchDirection, SearchResult, SearchStyle};
+use crate::Result;
+#[cfg(feature = "frecency")]
+use frecency::Frecency;
+use std::borrow::Cow;
+#[cfg(feature = "frecency")]
+use std::collections::HashMap;
+use std::collections::{HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The default value for `FileHistory::set_max_entries`
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// An entry in a `FileHistory`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub line: String,
+    /// When the line was most recently added
+    pub timestamp: SystemTime,
+}
+
+/// A history implementation that persists its entries to a file.
+///
+/// Each call to `add` appends a single record to the file while
+/// holding a lock on it (`flock` on unix, `LockFileEx` on Windows),
+/// so several processes may safely share the same history file.
+/// Records appended by other processes are merged in the next time
+/// this process adds an entry, or when `reload` is called.
+///
+/// Adding a line that is already present moves it to the end of
+/// the history, so each distinct line appears just once.  When the
+/// number of records in the file grows to twice the maximum number
+/// of entries, the file is rewritten to remove duplicates and the
+/// oldest entries.  The rewritten file begins with a new generation
+/// number, which tells the other processes that they need to load
+/// it again from the start.
+pub struct FileHistory {
+    path: PathBuf,
+    entries: VecDeque<HistoryEntry>,
+    max_entries: usize,
+    /// The length of the portion of the file that has been loaded
+    loaded_len: u64,
+    /// The generation of the file that has been loaded; None if the
+    /// file has never been compacted
+    generation: Option<u64>,
+    /// The number of records in the file, including duplicates
+    file_records: usize,
+    #[cfg(feature = "frecency")]
+    frecency: HashMap<String, Frecency>,
+}
+
+impl FileHistory {
+    /// Loads the history from the specified file.  The file is created
+    /// when the first entry is added, if it doesn't already exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut history = Self {
+            path: path.as_ref().to_path_buf(),
+            entries: VecDeque::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            loaded_len: 0,
+            generation: None,
+            file_records: 0,
+            #[cfg(feature = "frecency")]
+            frecency: HashMap::new(),
+        };
+        history.reload()?;
+        Ok(history)
+    }
+
+    /// Sets the maximum number of entries that are retained; the
+    /// oldest entries are discarded when the limit is exceeded.
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries.max(1);
+        self.trim();
+    }
+
+    /// Returns the entry at the specified index, including its timestamp
+    pub fn entry(&self, idx: HistoryIndex) -> Option<&HistoryEntry> {
+        self.entries.get(idx)
+    }
+
+    /// Merges in any records that have been appended to the file
+    /// by other processes since it was last read.
+    pub fn reload(&mut self) -> Result<()> {
+        match File::open(&self.path) {
+            Ok(mut file) => self.read_new_records(&mut file),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns the entries that fuzzy match `pattern`, best match first.
+    /// Ties are broken in favor of more frequently and recently used
+    /// entries when the `frecency` feature is enabled, and then in
+    /// favor of the most recently added entries.
+    pub fn ranked_search(&self, pattern: &str) -> Vec<SearchResult<'_>> {
+        let mut results: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| {
+                let (cursor, score) = fuzzy_match(pattern, &entry.line)?;
+                Some((score, self.frecency_score(&entry.line), idx, cursor))
+            })
+            .collect();
+        results.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| b.1.total_cmp(&a.1))
+                .then_with(|| b.2.cmp(&a.2))
+        });
+        results
+            .into_iter()
+            .map(|(_score, _frecency, idx, cursor)| SearchResult {
+                line: Cow::Borrowed(self.entries[idx].line.as_str()),
+                idx,
+                cursor,
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "frecency")]
+    fn frecency_score(&self, line: &str) -> f64 {
+        self.frecency.get(line).map(Frecency::score).unwrap_or(0.)
+    }
+
+    #[cfg(not(feature = "frecency"))]
+    fn frecency_score(&self, _line: &str) -> f64 {
+        0.
+    }
+
+    /// Adds an entry to the in-memory history, removing any prior
+    /// occurrence of the same line
+    fn insert(&mut self, entry: HistoryEntry) {
+        self.register_access(&entry);
+        if let Some(idx) = self.entries.iter().rposition(|e| e.line == entry.line) {
+            self.entries.remove(idx);
+        }
+        self.entries.push_back(entry);
+    }
+
+    #[cfg(feature = "frecency")]
+    fn register_access(&mut self, entry: &HistoryEntry) {
+        let when = chrono::DateTime::<chrono::Utc>::from(entry.timestamp);
+        self.frecency
+            .entry(entry.line.clone())
+            .or_insert_with(|| Frecency::new_at_time(when))
+            .register_access_at_time(when);
+    }
+
+    #[cfg(not(feature = "frecency"))]
+    fn register_access(&mut self, _entry: &HistoryEntry) {}
+
+    fn trim(&mut self) {
+        while self.entries.len() > self.max_entries {
+            if let Some(_entry) = self.entries.pop_front() {
+                #[cfg(feature = "frecency")]
+                self.frecency.remove(&_entry.line);
+            }
+        }
+    }
+
+    fn read_new_records(&mut self, file: &mut File) -> Result<()> {
+        let len = file.metadata()?.len();
+        let generation = read_generation(file)?;
+        if len < self.loaded_len || generation != self.generation {
+            // The file was rewritten by another process; start over
+            self.entries.clear();
+            #[cfg(feature = "frecency")]
+            self.frecency.clear();
+            self.loaded_len = 0;
+            self.file_records = 0;
+            self.generation = generation;
+        }
+        file.seek(SeekFrom::Start(self.loaded_len))?;
+        let mut data = vec![];
+        file.take(len - self.loaded_len).read_to_end(&mut data)?;
+
+        // Only consume complete records; a partial record may be
+        // in the process of being written by another process
+        let complete = match data.iter().rposition(|&b| b == b'\n') {
+            Some(idx) => idx + 1,
+            None => return Ok(()),
+        };
+        for record in String::from_utf8_lossy(&data[..complete]).lines() {
+            if record.starts_with('#') {
+                // The generation header
+                continue;
+            }
+            self.file_records += 1;
+            match decode_record(record) {
+                Some(entry) => {
+                    self.register_access(&entry);
+                    self.entries.push_back(entry);
+                }
+                None => log::warn!(
+                    "ignoring malformed record in history file {}",
+                    self.path.display()
+                ),
+            }
+        }
+        self.loaded_len += complete as u64;
+
+        // Keep only the most recent occurrence of each line
+        let mut seen = HashSet::new();
+        let mut entries: VecDeque<_> = self
+            .entries
+            .drain(..)
+            .rev()
+            .filter(|e| seen.insert(e.line.clone()))
+            .collect();
+        entries.make_contiguous().reverse();
+        self.entries = entries;
+        self.trim();
+        Ok(())
+    }
+
+    fn append(&mut self, line: &str) -> Result<()> {
+        let mut file = lock_file(&self.path)?;
+        self.read_new_records(&mut file)?;
+
+        let entry = HistoryEntry {
+            line: line.to_string(),
+            timestamp: SystemTime::now(),
+        };
+        // The record is written with a single call, so that
+        // it cannot be interleaved with records from other processes
+        let record = encode_record(&entry);
+        file.write_all(record.as_bytes())?;
+        self.loaded_len += record.len() as u64;
+        self.file_records += 1;
+        self.insert(entry);
+        self.trim();
+
+        if self.file_records >= self.max_entries * 2 {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the file so that it contains just the current entries.
+    /// Must be called while holding the lock.
+    fn compact(&mut self) -> Result<()> {
+        let generation = new_generation(self.generation);
+        let mut data = format!("#{}\n", generation);
+        data.extend(self.entries.iter().map(encode_record));
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, &data)?;
+        std::fs::rename(&temp_path, &self.path)?;
+        self.loaded_len = data.len() as u64;
+        self.generation = Some(generation);
+        self.file_records = self.entries.len();
+        Ok(())
+    }
+}
+
+impl History for FileHistory {
+    fn get(&self, idx: HistoryIndex) -> Option<Cow<'_, str>> {
+        self.entries
+            .get(idx)
+            .map(|e| Cow::Borrowed(e.line.as_str()))
+    }
+
+    fn last(&self) -> Option<HistoryIndex> {
+        if self.entries.is_empty() {
+            None
+        } else {
+            Some(self.entries.len() - 1)
+        }
+    }
+
+    fn add(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        if let Err(err) = self.append(line) {
+            log::error!(
+                "failed to add entry to history file {}: {:#}",
+                self.path.display(),
+                err
+            );
+            // Retain the entry for the remainder of this session
+            self.insert(HistoryEntry {
+                line: line.to_string(),
+                timestamp: SystemTime::now(),
+            });
+            self.trim();
+        }
+    }
+
+    fn search(
+        &self,
+        idx: HistoryIndex,
+        style: SearchStyle,
+        direction: SearchDirection,
+        pattern: &str,
+    ) -> Option<SearchResult<'_>> {
+        let mut idx = idx;
+
+        loop {
+            let line = &self.entries.get(idx)?.line;
+
+            if let Some(cursor) = style.match_against(pattern, line) {
+                return Some(SearchResult {
+                    line: Cow::Borrowed(line.as_str()),
+                    idx,
+                    cursor,
+                });
+            }
+
+            idx = direction.next(idx)?;
+        }
+    }
+}
+
+/// Opens the file for appending, holding an exclusive advisory lock.
+/// If another process replaced the file while we were waiting for the
+/// lock, the replacement is opened instead.
+#[cfg(unix)]
+fn lock_file(path: &Path) -> Result<File> {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // The lock is released when the file is closed
+        let locked = file.metadata()?;
+        match std::fs::metadata(path) {
+            Ok(current) if current.ino() == locked.ino() && current.dev() == locked.dev() => {
+                return Ok(file)
+            }
+            _ => continue,
+        }
+    }
+}
+
+/// Opens the file for appending, holding an exclusive lock.
+/// If another process replaced the file while we were waiting for the
+/// lock, the replacement is opened instead.
+#[cfg(windows)]
+fn lock_file(path: &Path) -> Result<File> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, OVERLAPPED};
+
+    loop {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        // Windows locks are mandatory, so rather than locking the
+        // content, which would prevent other processes from reading
+        // it, lock a byte far beyond the end of the file.
+        // The lock is released when the file is closed.
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        unsafe {
+            let offset = overlapped.u.s_mut();
+            offset.Offset = u32::MAX;
+            offset.OffsetHigh = i32::MAX as u32;
+        }
+        if unsafe {
+            LockFileEx(
+                file.as_raw_handle() as _,
+                LOCKFILE_EXCLUSIVE_LOCK,
+                0,
+                1,
+                0,
+                &mut overlapped,
+            )
+        } == 0
+        {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // A replacement begins with a different generation
+        let mut current = File::open(path)?;
+        if read_generation(&mut file)? == read_generation(&mut current)? {
+            return Ok(file);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_file(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?)
+}
+
+/// Reads the generation number from the header that `compact`
+/// writes at the start of the file, if any
+fn read_generation(file: &mut File) -> Result<Option<u64>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut header = vec![];
+    file.take(24).read_to_end(&mut header)?;
+    if header.first() != Some(&b'#') {
+        return Ok(None);
+    }
+    let end = match header.iter().position(|&b| b == b'\n') {
+        Some(end) => end,
+        None => return Ok(None),
+    };
+    Ok(std::str::from_utf8(&header[1..end])
+        .ok()
+        .and_then(|generation| generation.parse().ok()))
+}
+
+/// Picks a generation number for a compacted file that differs
+/// from the generation of the file that it replaces
+fn new_generation(previous: Option<u64>) -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let generation = nanos ^ ((std::process::id() as u64) << 32);
+    if Some(generation) == previous {
+        generation.wrapping_add(1)
+    } else {
+        generation
+    }
+}
+
+/// Encodes an entry as a line of the form `TIMESTAMP\tLINE\n`, where
+/// newlines and backslashes in the line are escaped
+fn encode_record(entry: &HistoryEntry) -> String {
+    let secs = entry
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut record = format!("{}\t", secs);
+    for c in entry.line.chars() {
+        match c {
+            '\\' => record.push_str("\\\\"),
+            '\n' => record.push_str("\\n"),
+            c => record.push(c),
+        }
+    }
+    record.push('\n');
+    record
+}
+
+fn decode_record(record: &str) -> Option<HistoryEntry> {
+    let (secs, escaped) = record.split_once('\t')?;
+    let timestamp = UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?);
+    let mut line = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => line.push('\n'),
+                c => line.push(c),
+            },
+            c => line.push(c),
+        }
+    }
+    Some(HistoryEntry { line, timestamp })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lines(history: &FileHistory) -> Vec<&str> {
+        history.entries.iter().map(|e| e.line.as_str()).collect()
+    }
+
+    #[test]
+    fn persist_and_dedup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut first = FileHistory::open(&path).unwrap();
+        let mut second = FileHistory::open(&path).unwrap();
+        first.add("ls");
+        first.add("echo 'multi\nline' \\");
+        second.add("cargo build");
+        first.add("ls");
+
+        // `first` picked up the entry from `second` when it added "ls"
+        assert_eq!(
+            lines(&first),
+            vec!["echo 'multi\nline' \\", "cargo build", "ls"]
+        );
+        assert_eq!(first.last(), Some(2));
+
+        let reopened = FileHistory::open(&path).unwrap();
+        assert_eq!(lines(&reopened), lines(&first));
+        assert!(reopened.entry(0).unwrap().timestamp <= SystemTime::now());
+    }
+
+    #[test]
+    fn reload_after_compaction_by_another_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut first = FileHistory::open(&path).unwrap();
+        first.set_max_entries(3);
+        for line in ["a", "b", "c", "d", "e"] {
+            first.add(line);
+        }
+        let mut second = FileHistory::open(&path).unwrap();
+        assert_eq!(lines(&second), vec!["a", "b", "c", "d", "e"]);
+
+        // Compact the file, and then grow it beyond the length
+        // that `second` has already loaded
+        first.add("f");
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 4);
+        first.add("g");
+        assert!(std::fs::metadata(&path).unwrap().len() > second.loaded_len);
+
+        second.reload().unwrap();
+        assert_eq!(lines(&second), vec!["d", "e", "f", "g"]);
+
+        // Entries added by `second` land in the compacted file
+        second.add("h");
+        first.reload().unwrap();
+        assert_eq!(lines(&first), vec!["f", "g", "h"]);
+    }
+
+    #[test]
+    fn trim_and_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut history = FileHistory::open(&path).unwrap();
+        history.set_max_entries(3);
+        for line in ["a", "b", "c", "d", "e", "f"] {
+            history.add(line);
+        }
+        assert_eq!(lines(&history), vec!["d", "e", "f"]);
+
+        // The file was compacted when it reached 6 records
+        let data = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(data.lines().filter(|l| !l.starts_with('#')).count(), 3);
+
+        let mut reopened = FileHistory::open(&path).unwrap();
+        reopened.set_max_entries(3);
+        assert_eq!(lines(&reopened), vec!["d", "e", "f"]);
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = FileHistory::open(dir.path().join("history")).unwrap();
+        for line in ["git status", "cargo test", "git commit", "grep tests"] {
+            history.add(line);
+        }
+
+        let last = history.last().unwrap();
+        let result = history
+            .search(last, SearchStyle::Fuzzy, SearchDirection::Backwards, "gt")
+            .unwrap();
+        assert_eq!(result.line, "grep tests");
+        assert_eq!(result.cursor, 0);
+
+        let ranked: Vec<_> = history
+            .ranked_search("gst")
+            .into_iter()
+            .map(|r| r.line.to_string())
+            .collect();
+        assert_eq!(ranked, vec!["git status", "grep tests", "cargo test"]);
+    }
+}
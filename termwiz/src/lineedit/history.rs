@@ -37,6 +37,9 @@ pub struct SearchResult<'a> {
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum SearchStyle {
     Substring,
+    /// Matches lines that contain all of the characters of the
+    /// pattern, in order, ignoring case
+    Fuzzy,
 }
 
 impl SearchStyle {
@@ -45,10 +48,57 @@ impl SearchStyle {
     pub fn match_against(&self, pattern: &str, line: &str) -> Option<usize> {
         match self {
             Self::Substring => line.find(pattern),
+            Self::Fuzzy => fuzzy_match(pattern, line).map(|(idx, _score)| idx),
         }
     }
 }
 
+/// Returns a score for how well `pattern` fuzzy matches `candidate`,
+/// or None if it doesn't match at all.
+/// A candidate matches if it contains all of the characters of the
+/// pattern, in order, ignoring case.  Matches that are contiguous or
+/// that start at a word boundary score higher.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<i64> {
+    fuzzy_match(pattern, candidate).map(|(_idx, score)| score)
+}
+
+/// Implements `fuzzy_score`, additionally returning the byte index
+/// of the first matching character
+pub(crate) fn fuzzy_match(pattern: &str, candidate: &str) -> Option<(usize, i64)> {
+    let mut score = 0;
+    let mut first = None;
+    let mut pattern_chars = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut prior: Option<char> = None;
+    let mut prior_matched = false;
+
+    for (idx, c) in candidate.char_indices() {
+        let next = match pattern_chars.peek() {
+            Some(next) => *next,
+            None => break,
+        };
+        let matched = c.to_lowercase().eq(std::iter::once(next));
+        if matched {
+            pattern_chars.next();
+            first.get_or_insert(idx);
+            score += 1;
+            if prior_matched {
+                score += 4;
+            }
+            if prior.map(|p| !p.is_alphanumeric()).unwrap_or(true) {
+                score += 2;
+            }
+        }
+        prior_matched = matched;
+        prior = Some(c);
+    }
+
+    if pattern_chars.peek().is_some() {
+        None
+    } else {
+        Some((first.unwrap_or(0), score))
+    }
+}
+
 /// Encodes the direction the search should take, relative to the
 /// current HistoryIndex.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
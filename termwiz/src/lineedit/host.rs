@@ -1,4 +1,4 @@
-use crate::cell::{AttributeChange, CellAttributes};
+use crate::cell::{AttributeChange, CellAttributes, Intensity};
 use crate::input::InputEvent;
 use crate::lineedit::actions::Action;
 use crate::lineedit::{BasicHistory, History, LineEditor};
@@ -71,6 +71,44 @@ pub trait LineEditorHost {
         (vec![OutputElement::Text(line.to_owned())], cursor_x_pos)
     }
 
+    /// Given the text entered so far, return true if it is incomplete
+    /// and pressing Enter should start a new line of input rather than
+    /// accepting it; for example, when a quote or bracket is unbalanced.
+    /// The default implementation always returns false.
+    fn needs_continuation(&self, _buffer: &str) -> bool {
+        false
+    }
+
+    /// Return the rendered form of the prompt that is shown before
+    /// the second and subsequent lines of multi-line input.
+    /// `line_index` is the zero-based index of the line within the
+    /// input, and is always at least 1.
+    /// The default implementation returns `... `.
+    fn render_continuation_prompt(&self, _prompt: &str, _line_index: usize) -> Vec<OutputElement> {
+        vec![OutputElement::Text("... ".to_owned())]
+    }
+
+    /// Given the current line, return text that would plausibly follow
+    /// it, such as the remainder of a matching history entry.  The hint
+    /// is shown after the input when the cursor is at the end of the
+    /// line, and moving the cursor forwards accepts it.
+    /// Only the first line of the hint is used.
+    /// The default implementation returns None.
+    fn hint(&self, _line: &str) -> Option<String> {
+        None
+    }
+
+    /// Return the rendered form of the hint returned by `hint`.
+    /// The text must be output as-is, so that the hint occupies the
+    /// same columns that it would once accepted.
+    /// The default implementation renders the hint with half intensity.
+    fn render_hint(&self, hint: &str) -> Vec<OutputElement> {
+        vec![
+            OutputElement::Attribute(AttributeChange::Intensity(Intensity::Half)),
+            OutputElement::Text(hint.to_owned()),
+        ]
+    }
+
     /// Returns the history implementation
     fn history(&mut self) -> &mut dyn History;
 
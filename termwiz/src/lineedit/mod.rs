@@ -37,19 +37,60 @@
 //! Ctrl-W        | Delete word leading up to cursor
 //! Alt-b, Alt-Left | Move the cursor backwards one word
 //! Alt-f, Alt-Right | Move the cursor forwards one word
+//! Ctrl-Y        | Insert the most recently killed text
+//! Alt-y         | Replace the text inserted by Ctrl-Y with older killed text
+//! Ctrl-_        | Undo the most recent change
+//! Alt-_         | Redo the most recently undone change
+//! Alt-Enter     | Insert a newline, starting a new line of input
+//! Up, Down      | Move between the lines of multi-line input, then through history
+//! Tab, Shift-Tab | Cycle forwards or backwards through the completion candidates
+//! Ctrl-F, Right, End | At the end of the input, accept the hint provided by `LineEditorHost::hint`
+//!
+//! Text that is deleted by the word and line deletion commands is
+//! saved in a kill ring that persists across calls to `read_line`.
+//!
+//! ## Vi Mode
+//!
+//! `LineEditor::set_edit_mode(EditMode::Vi)` selects a vi style keymap.
+//! Each line starts in insert mode, which uses the bindings shown
+//! above; Escape switches to normal mode, which supports:
+//!
+//! * Counts, such as `3w`
+//! * The motions `h`, `l`, `w`, `b`, `e`, `W`, `B`, `E`, `0`, `^`, `$`,
+//!   `f`, `F`, `t`, `T`, `;` and `,`
+//! * The operators `d`, `c` and `y`, which may be followed by a motion,
+//!   or doubled up to apply to the whole line
+//! * `x`, `X`, `D`, `C`, `s`, `S`, `Y`, `p` and `P`
+//! * `i`, `a`, `I` and `A` to return to insert mode
+//! * `u` to undo and Ctrl-R to redo
+//! * `j` and `k` to move through history
+//!
+//! ## Multi-line Input
+//!
+//! When Enter is pressed, the editor asks `LineEditorHost::needs_continuation`
+//! whether the input is complete.  If it is not, a newline is inserted
+//! and editing continues on the next line, which is shown with the
+//! prompt from `LineEditorHost::render_continuation_prompt`.
 use crate::caps::{Capabilities, ProbeHints};
-use crate::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use crate::cell::{unicode_column_width, AttributeChange};
+use crate::input::{InputEvent, KeyCode, KeyEvent, KeyEventType, Modifiers};
 use crate::surface::change::ChangeSequence;
 use crate::surface::{Change, Position};
 use crate::terminal::{new_terminal, Terminal};
 use crate::{bail, ensure, Result};
+use std::collections::VecDeque;
+use unicode_segmentation::UnicodeSegmentation;
+use vi::ViState;
 
 mod actions;
 mod buffer;
+mod file_history;
 mod history;
 mod host;
-pub use actions::{Action, Movement, RepeatCount};
+mod vi;
+pub use actions::{Action, Movement, RepeatCount, ViMode};
 pub use buffer::LineEditBuffer;
+pub use file_history::*;
 pub use history::*;
 pub use host::*;
 
@@ -83,6 +124,48 @@ pub struct LineEditor<'term> {
     move_to_editor_end: Option<Change>,
 
     state: EditorState,
+
+    edit_mode: EditMode,
+    search_style: SearchStyle,
+    vi: ViState,
+    kill_ring: VecDeque<String>,
+    /// The range and kill ring index of the text inserted by the
+    /// previous action, if it was a yank
+    last_yank: Option<(std::ops::Range<usize>, usize)>,
+    /// Set while subsequent edits should be merged into the current
+    /// undo step, such as when typing a run of characters
+    undo_group_open: bool,
+}
+
+/// Selects the keymap used by the `LineEditor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+    /// Bindings similar to those of emacs and readline; the default
+    Emacs,
+    /// Bindings similar to those of vi, with separate insert and
+    /// normal modes
+    Vi,
+}
+
+/// The maximum number of entries retained by the kill ring
+const MAX_KILL_RING: usize = 32;
+
+/// The maximum number of completion candidates shown at once
+const MAX_COMPLETION_MENU_ROWS: usize = 8;
+
+/// Truncates text so that it occupies no more than `width` columns
+fn truncate_to_width(text: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut used = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = unicode_column_width(grapheme, None);
+        if used + grapheme_width > width {
+            break;
+        }
+        used += grapheme_width;
+        result.push_str(grapheme);
+    }
+    result
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -114,6 +197,13 @@ impl CompletionState {
         }
     }
 
+    fn prev(&mut self) {
+        if self.index == 0 {
+            self.index = self.candidates.len();
+        }
+        self.index -= 1;
+    }
+
     fn current(&self) -> (usize, String) {
         let mut line = self.original_line.clone();
         let candidate = &self.candidates[self.index];
@@ -160,6 +250,12 @@ impl<'term> LineEditor<'term> {
             move_to_editor_start: None,
             move_to_editor_end: None,
             state: EditorState::Inactive,
+            edit_mode: EditMode::Emacs,
+            search_style: SearchStyle::Substring,
+            vi: ViState::new(),
+            kill_ring: VecDeque::new(),
+            last_yank: None,
+            undo_group_open: false,
         }
     }
 
@@ -186,10 +282,6 @@ impl<'term> LineEditor<'term> {
             _ => (self.line.get_line(), self.line.get_cursor()),
         };
 
-        let cursor_position_after_printing_prompt = changes.current_cursor_position();
-
-        let (elements, cursor_x_pos) = host.highlight_line(line_to_display, cursor);
-
         // Calculate what the cursor position would be after printing X columns
         // of text from the specified location.
         // Returns (x, y) of the resultant cursor position.
@@ -207,15 +299,58 @@ impl<'term> LineEditor<'term> {
 
             (col, row)
         }
-        let cursor_position = compute_cursor_after_printing_x_columns(
-            cursor_position_after_printing_prompt.0,
-            cursor_position_after_printing_prompt.1,
-            cursor_x_pos,
-            screen_size.cols,
-        );
 
-        for ele in elements {
-            changes.add(ele);
+        // Multi-line input is rendered one line at a time, with each
+        // line after the first being preceded by the continuation prompt
+        let mut cursor_position = None;
+        let mut line_start = 0;
+        for (line_index, text) in line_to_display.split('\n').enumerate() {
+            if line_index > 0 {
+                changes.add("\r\n");
+                changes.add(Change::AllAttributes(Default::default()));
+                for ele in host.render_continuation_prompt(&self.prompt, line_index) {
+                    changes.add(ele);
+                }
+                changes.add(Change::AllAttributes(Default::default()));
+            }
+
+            let line_end = line_start + text.len();
+            let cursor_is_on_line =
+                cursor_position.is_none() && cursor >= line_start && cursor <= line_end;
+            let cursor_position_after_printing_prompt = changes.current_cursor_position();
+
+            let (elements, cursor_x_pos) = host.highlight_line(
+                text,
+                if cursor_is_on_line {
+                    cursor - line_start
+                } else {
+                    0
+                },
+            );
+            if cursor_is_on_line {
+                cursor_position = Some(compute_cursor_after_printing_x_columns(
+                    cursor_position_after_printing_prompt.0,
+                    cursor_position_after_printing_prompt.1,
+                    cursor_x_pos,
+                    screen_size.cols,
+                ));
+            }
+
+            for ele in elements {
+                changes.add(ele);
+            }
+            line_start = line_end + 1;
+        }
+        let cursor_position = cursor_position.unwrap_or_else(|| changes.current_cursor_position());
+
+        // Show the hint, if any, after the end of the input
+        if self.state == EditorState::Editing && cursor == line_to_display.len() {
+            if let Some(hint) = self.current_hint(host) {
+                for ele in host.render_hint(&hint) {
+                    changes.add(ele);
+                }
+                changes.add(Change::AllAttributes(Default::default()));
+            }
         }
 
         let cursor_after_line_render = changes.current_cursor_position();
@@ -240,6 +375,29 @@ impl<'term> LineEditor<'term> {
             }
         }
 
+        if let (EditorState::Editing, Some(completion)) = (&self.state, &self.completion) {
+            // The menu of completion candidates is shown below the input,
+            // scrolled so that the current candidate is visible
+            let count = completion.candidates.len();
+            let rows = count.min(MAX_COMPLETION_MENU_ROWS);
+            let first = completion.index.saturating_sub(rows - 1).min(count - rows);
+            for (idx, candidate) in completion.candidates[first..first + rows]
+                .iter()
+                .enumerate()
+            {
+                changes.add("\r\n");
+                changes.add(Change::AllAttributes(Default::default()));
+                if first + idx == completion.index {
+                    changes.add(Change::Attribute(AttributeChange::Reverse(true)));
+                }
+                changes.add(truncate_to_width(
+                    &candidate.text,
+                    screen_size.cols.saturating_sub(1),
+                ));
+            }
+            changes.add(Change::AllAttributes(Default::default()));
+        }
+
         if let EditorState::Searching {
             style, direction, ..
         } = &self.state
@@ -248,6 +406,8 @@ impl<'term> LineEditor<'term> {
             let label = match (style, direction) {
                 (SearchStyle::Substring, SearchDirection::Backwards) => "bck-i-search",
                 (SearchStyle::Substring, SearchDirection::Forwards) => "fwd-i-search",
+                (SearchStyle::Fuzzy, SearchDirection::Backwards) => "bck-fuzzy-search",
+                (SearchStyle::Fuzzy, SearchDirection::Forwards) => "fwd-fuzzy-search",
             };
             // Do not be affected by attributes set by previous lines.
             changes.add(Change::AllAttributes(Default::default()));
@@ -290,10 +450,48 @@ impl<'term> LineEditor<'term> {
         Ok(())
     }
 
+    /// Returns the hint for the current line, if the host provides
+    /// one.  Only the first line of the hint is used.
+    fn current_hint(&self, host: &dyn LineEditorHost) -> Option<String> {
+        let hint = host.hint(self.line.get_line())?;
+        let hint = hint.split('\n').next().unwrap_or("");
+        if hint.is_empty() {
+            None
+        } else {
+            Some(hint.to_string())
+        }
+    }
+
     pub fn set_prompt(&mut self, prompt: &str) {
         self.prompt = prompt.to_owned();
     }
 
+    /// Selects the keymap that is used to interpret key presses
+    pub fn set_edit_mode(&mut self, mode: EditMode) {
+        self.edit_mode = mode;
+    }
+
+    pub fn get_edit_mode(&self) -> EditMode {
+        self.edit_mode
+    }
+
+    /// Selects how the incremental history search matches the
+    /// pattern against history entries; the default is substring
+    /// matching.
+    pub fn set_history_search_style(&mut self, style: SearchStyle) {
+        self.search_style = style;
+    }
+
+    /// Returns the current mode of the vi keymap, or None if the
+    /// vi keymap is not in use.  This is useful for hosts that
+    /// want to reflect the mode in the prompt.
+    pub fn vi_mode(&self) -> Option<ViMode> {
+        match self.edit_mode {
+            EditMode::Emacs => None,
+            EditMode::Vi => Some(self.vi.mode),
+        }
+    }
+
     /// Enter line editing mode.
     /// Control is not returned to the caller until a line has been
     /// accepted, or until an error is detected.
@@ -341,45 +539,93 @@ impl<'term> LineEditor<'term> {
             return Some(action);
         }
 
+        // Key releases are only reported if the kitty keyboard protocol
+        // has been enabled; the editor acts only on presses and repeats
+        if let InputEvent::Key(KeyEvent {
+            event_type: KeyEventType::Release,
+            ..
+        }) = event
+        {
+            return None;
+        }
+
+        if self.edit_mode == EditMode::Vi {
+            match self.vi.mode {
+                ViMode::Normal => {
+                    if let Some(action) = self.vi.resolve_normal(event, &self.line) {
+                        return Some(action);
+                    }
+                }
+                ViMode::Insert => {
+                    if let InputEvent::Key(KeyEvent {
+                        key: KeyCode::Escape,
+                        ..
+                    }) = event
+                    {
+                        return Some(Action::SetViMode(ViMode::Normal));
+                    }
+                }
+            }
+        }
+
         match event {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('C'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::Cancel),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Tab,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::Complete),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Tab,
+                modifiers: Modifiers::SHIFT,
+                ..
+            }) => Some(Action::CompletePrevious),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('D'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::EndOfFile),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('J'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('M'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::Enter,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::AcceptLine),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                modifiers: Modifiers::ALT,
+                ..
+            }) => Some(Action::InsertChar(1, '\n')),
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('H'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::Backspace,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::Kill(Movement::BackwardChar(1))),
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Delete,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::KillAndMove(
                 Movement::ForwardChar(1),
                 Movement::None,
@@ -388,122 +634,182 @@ impl<'term> LineEditor<'term> {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('P'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::UpArrow,
                 modifiers: Modifiers::NONE,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationUpArrow,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::HistoryPrevious),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('N'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::DownArrow,
                 modifiers: Modifiers::NONE,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationDownArrow,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::HistoryNext),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('B'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationLeftArrow,
                 modifiers: Modifiers::NONE,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::LeftArrow,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::Move(Movement::BackwardChar(1))),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('W'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::Kill(Movement::BackwardWord(1))),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('b'),
                 modifiers: Modifiers::ALT,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::LeftArrow,
                 modifiers: Modifiers::ALT,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationLeftArrow,
                 modifiers: Modifiers::ALT,
+                ..
             }) => Some(Action::Move(Movement::BackwardWord(1))),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('f'),
                 modifiers: Modifiers::ALT,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::RightArrow,
                 modifiers: Modifiers::ALT,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationRightArrow,
                 modifiers: Modifiers::ALT,
+                ..
             }) => Some(Action::Move(Movement::ForwardWord(1))),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('A'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::Home,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::Move(Movement::StartOfLine)),
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('E'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::End,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::Move(Movement::EndOfLine)),
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('F'),
                 modifiers: Modifiers::CTRL,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::RightArrow,
                 modifiers: Modifiers::NONE,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::ApplicationRightArrow,
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::Move(Movement::ForwardChar(1))),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('Y'),
+                modifiers: Modifiers::CTRL,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('y'),
+                modifiers: Modifiers::CTRL,
+                ..
+            }) => Some(Action::Yank(1)),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('y'),
+                modifiers: Modifiers::ALT,
+                ..
+            }) => Some(Action::YankPop),
+
+            // Ctrl-_ is sent as 0x1f by most terminals
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('_'),
+                modifiers: Modifiers::CTRL,
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('\u{1f}'),
+                ..
+            }) => Some(Action::Undo),
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('_'),
+                modifiers: Modifiers::ALT,
+                ..
+            }) => Some(Action::Redo),
+
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char(c),
                 modifiers: Modifiers::SHIFT,
+                ..
             })
             | InputEvent::Key(KeyEvent {
                 key: KeyCode::Char(c),
                 modifiers: Modifiers::NONE,
+                ..
             }) => Some(Action::InsertChar(1, *c)),
             InputEvent::Paste(text) => Some(Action::InsertText(1, text.clone())),
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('L'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::Repaint),
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('K'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::Kill(Movement::EndOfLine)),
 
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('R'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::HistoryIncSearchBackwards),
 
             // This is the common binding for forwards, but it is usually
@@ -511,6 +817,7 @@ impl<'term> LineEditor<'term> {
             InputEvent::Key(KeyEvent {
                 key: KeyCode::Char('S'),
                 modifiers: Modifiers::CTRL,
+                ..
             }) => Some(Action::HistoryIncSearchForwards),
 
             _ => None,
@@ -519,6 +826,16 @@ impl<'term> LineEditor<'term> {
 
     fn kill_text(&mut self, kill_movement: Movement, move_movement: Movement) {
         self.clear_completion();
+        // Deleting single characters doesn't displace the content of
+        // the kill ring, except for the vi `x` and `X` commands
+        let single_char = matches!(
+            kill_movement,
+            Movement::BackwardChar(_) | Movement::ForwardChar(_)
+        );
+        if !single_char || self.vi_mode() == Some(ViMode::Normal) {
+            let text = self.line.text_for_movement(kill_movement).to_string();
+            self.push_kill(text);
+        }
         self.line.kill_text(kill_movement, move_movement);
     }
 
@@ -667,27 +984,67 @@ impl<'term> LineEditor<'term> {
         };
     }
 
+    /// Adds text to the front of the kill ring
+    fn push_kill(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.kill_ring.push_front(text);
+        self.kill_ring.truncate(MAX_KILL_RING);
+    }
+
     /// Applies the effect of the specified action to the line editor.
     /// You don't normally need to call this unless you are defining
     /// custom key mapping or custom actions in your embedding application.
     pub fn apply_action(&mut self, host: &mut dyn LineEditorHost, action: Action) -> Result<()> {
+        let is_edit = action.is_edit();
+        let is_insert = matches!(action, Action::InsertChar(..));
+        // Outside of vi insert mode, only typing continues the
+        // current undo step; any other edit begins a new one
+        let continues_group =
+            self.undo_group_open && (is_insert || self.vi_mode() == Some(ViMode::Insert));
+        if is_edit && !continues_group {
+            self.line.save_undo_state();
+        }
+        let group_was_open = self.undo_group_open;
+        let is_undo = matches!(action, Action::Undo | Action::Redo);
+        let last_yank = self.last_yank.take();
+
+        self.apply_action_impl(host, action, last_yank)?;
+
+        self.undo_group_open = match self.vi_mode() {
+            _ if is_undo => false,
+            // Everything typed in a vi insert session is undone as
+            // a single step
+            Some(ViMode::Insert) => {
+                if !group_was_open && !is_edit {
+                    self.line.save_undo_state();
+                }
+                true
+            }
+            Some(ViMode::Normal) => false,
+            // A run of typed characters is undone as a single step
+            None => is_insert,
+        };
+
+        Ok(())
+    }
+
+    fn apply_action_impl(
+        &mut self,
+        host: &mut dyn LineEditorHost,
+        action: Action,
+        last_yank: Option<(std::ops::Range<usize>, usize)>,
+    ) -> Result<()> {
         // When searching, reinterpret history next/prev as repeated
         // search actions in the appropriate direction
         let action = match (action, &self.state) {
-            (
-                Action::HistoryPrevious,
-                EditorState::Searching {
-                    style: SearchStyle::Substring,
-                    ..
-                },
-            ) => Action::HistoryIncSearchBackwards,
-            (
-                Action::HistoryNext,
-                EditorState::Searching {
-                    style: SearchStyle::Substring,
-                    ..
-                },
-            ) => Action::HistoryIncSearchForwards,
+            (Action::HistoryPrevious, EditorState::Searching { .. }) => {
+                Action::HistoryIncSearchBackwards
+            }
+            (Action::HistoryNext, EditorState::Searching { .. }) => {
+                Action::HistoryIncSearchForwards
+            }
             (action, _) => action,
         };
 
@@ -700,7 +1057,15 @@ impl<'term> LineEditor<'term> {
                 // line to be accepted, rather than the search pattern!
                 self.cancel_search_state();
 
-                self.state = EditorState::Accepted;
+                if host.needs_continuation(self.line.get_line()) {
+                    if !self.undo_group_open {
+                        self.line.save_undo_state();
+                    }
+                    self.clear_completion();
+                    self.line.insert_char('\n');
+                } else {
+                    self.state = EditorState::Accepted;
+                }
             }
             Action::EndOfFile => {
                 return Err(
@@ -719,7 +1084,24 @@ impl<'term> LineEditor<'term> {
             Action::Move(movement) => {
                 self.clear_completion();
                 self.cancel_search_state();
-                self.line.exec_movement(movement);
+                // Moving forwards from the end of the input accepts
+                // the hint, as in fish
+                let at_end = self.line.get_cursor() == self.line.get_line().len();
+                let hint = match movement {
+                    Movement::ForwardChar(_) | Movement::EndOfLine if at_end => {
+                        self.current_hint(host)
+                    }
+                    _ => None,
+                };
+                match hint {
+                    Some(hint) => {
+                        if !self.undo_group_open {
+                            self.line.save_undo_state();
+                        }
+                        self.line.insert_text(&hint);
+                    }
+                    None => self.line.exec_movement(movement),
+                }
             }
 
             Action::InsertChar(rep, c) => {
@@ -740,6 +1122,14 @@ impl<'term> LineEditor<'term> {
                 self.terminal
                     .render(&[Change::ClearScreen(Default::default())])?;
             }
+            Action::HistoryPrevious if !self.line.is_on_first_line() => {
+                self.clear_completion();
+                self.line.exec_movement(Movement::PreviousLine(1));
+            }
+            Action::HistoryNext if !self.line.is_on_last_line() => {
+                self.clear_completion();
+                self.line.exec_movement(Movement::NextLine(1));
+            }
             Action::HistoryPrevious => {
                 self.clear_completion();
                 self.cancel_search_state();
@@ -777,14 +1167,74 @@ impl<'term> LineEditor<'term> {
                 }
             }
 
+            Action::Copy(movement) => {
+                let text = self.line.text_for_movement(movement).to_string();
+                self.push_kill(text);
+            }
+            Action::Yank(rep) => {
+                self.clear_completion();
+                if let Some(text) = self.kill_ring.front().cloned() {
+                    let start = self.line.get_cursor();
+                    for _ in 0..rep {
+                        self.line.insert_text(&text);
+                    }
+                    self.last_yank = Some((start..self.line.get_cursor(), 0));
+                }
+                self.reapply_search_pattern(host);
+            }
+            Action::YankPop => {
+                // Only valid immediately after a yank
+                if let Some((range, index)) = last_yank {
+                    self.clear_completion();
+                    let index = (index + 1) % self.kill_ring.len();
+                    let text = self.kill_ring[index].clone();
+                    let start = range.start;
+                    self.line.replace_range(range, &text);
+                    self.last_yank = Some((start..start + text.len(), index));
+                    self.reapply_search_pattern(host);
+                }
+            }
+            Action::Undo => {
+                self.clear_completion();
+                self.cancel_search_state();
+                self.line.undo();
+            }
+            Action::Redo => {
+                self.clear_completion();
+                self.cancel_search_state();
+                self.line.redo();
+            }
+            Action::SetViMode(mode) => {
+                self.clear_completion();
+                self.cancel_search_state();
+                // As in vi, leaving insert mode moves the cursor back
+                // onto the last character that was inserted
+                if mode == ViMode::Normal
+                    && self.vi.mode == ViMode::Insert
+                    && !self
+                        .line
+                        .text_for_movement(Movement::StartOfLine)
+                        .is_empty()
+                {
+                    self.line.exec_movement(Movement::BackwardChar(1));
+                }
+                self.vi.mode = mode;
+                self.vi.reset();
+            }
+            Action::Sequence(actions) => {
+                for action in actions {
+                    self.apply_action_impl(host, action, None)?;
+                }
+            }
+
             Action::HistoryIncSearchBackwards => {
-                self.trigger_search(SearchStyle::Substring, SearchDirection::Backwards, host);
+                self.trigger_search(self.search_style, SearchDirection::Backwards, host);
             }
             Action::HistoryIncSearchForwards => {
-                self.trigger_search(SearchStyle::Substring, SearchDirection::Forwards, host);
+                self.trigger_search(self.search_style, SearchDirection::Forwards, host);
             }
 
-            Action::Complete => {
+            Action::Complete | Action::CompletePrevious => {
                 self.cancel_search_state();
 
                 if self.completion.is_none() {
@@ -808,7 +1258,11 @@ impl<'term> LineEditor<'term> {
                         }
                     }
                 } else if let Some(state) = self.completion.as_mut() {
-                    state.next();
+                    if let Action::CompletePrevious = action {
+                        state.prev();
+                    } else {
+                        state.next();
+                    }
                     let (cursor, line) = state.current();
                     self.line.set_line_and_cursor(&line, cursor);
                 }
@@ -824,6 +1278,11 @@ impl<'term> LineEditor<'term> {
         initial_value: Option<&str>,
     ) -> Result<Option<String>> {
         self.line.clear();
+        self.line.clear_undo_history();
+        self.undo_group_open = false;
+        self.last_yank = None;
+        self.vi.mode = ViMode::Insert;
+        self.vi.reset();
         if let Some(value) = initial_value {
             self.line.set_line_and_cursor(value, value.len());
         }
@@ -859,3 +1318,303 @@ pub fn line_editor_terminal() -> Result<impl Terminal> {
     let caps = Capabilities::new_with_hints(hints)?;
     new_terminal(caps)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::{KeyEventType, Modifiers};
+    use crate::surface::Surface;
+    use crate::terminal::{ScreenSize, TerminalWaker};
+    use std::time::Duration;
+
+    /// A terminal that replays a script of input events and applies
+    /// the rendered changes to a surface, recording the screen as it
+    /// was before each event was read
+    struct ScriptedTerminal {
+        input: VecDeque<InputEvent>,
+        screen: Surface,
+        screens: Vec<String>,
+    }
+
+    impl ScriptedTerminal {
+        fn new(input: Vec<InputEvent>) -> Self {
+            Self {
+                input: input.into(),
+                screen: Surface::new(20, 6),
+                screens: vec![],
+            }
+        }
+    }
+
+    impl Terminal for ScriptedTerminal {
+        fn set_raw_mode(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn set_cooked_mode(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn enter_alternate_screen(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+        fn exit_alternate_screen(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+        fn get_screen_size(&mut self) -> Result<ScreenSize> {
+            let (cols, rows) = self.screen.dimensions();
+            Ok(ScreenSize {
+                cols,
+                rows,
+                xpixel: 0,
+                ypixel: 0,
+            })
+        }
+        fn set_screen_size(&mut self, size: ScreenSize) -> Result<()> {
+            self.screen.resize(size.cols, size.rows);
+            Ok(())
+        }
+        fn render(&mut self, changes: &[Change]) -> Result<()> {
+            self.screen.add_changes(changes.to_vec());
+            Ok(())
+        }
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn poll_input(&mut self, _wait: Option<Duration>) -> Result<Option<InputEvent>> {
+            self.screens.push(self.screen.screen_chars_to_string());
+            Ok(self.input.pop_front())
+        }
+        fn waker(&self) -> TerminalWaker {
+            unimplemented!();
+        }
+    }
+
+    /// A host that treats input with an unbalanced double quote
+    /// as incomplete, hints from a single fixed line and completes
+    /// words from a fixed list
+    #[derive(Default)]
+    struct TestHost {
+        history: BasicHistory,
+    }
+
+    const HINT_LINE: &str = "git status";
+    const WORDS: &[&str] = &["hello", "help", "helm"];
+
+    impl LineEditorHost for TestHost {
+        fn history(&mut self) -> &mut dyn History {
+            &mut self.history
+        }
+
+        fn needs_continuation(&self, buffer: &str) -> bool {
+            buffer.matches('"').count() % 2 == 1
+        }
+
+        fn hint(&self, line: &str) -> Option<String> {
+            if line.is_empty() {
+                return None;
+            }
+            HINT_LINE.strip_prefix(line).map(|rest| rest.to_string())
+        }
+
+        fn complete(&self, line: &str, cursor_position: usize) -> Vec<CompletionCandidate> {
+            let start = line[..cursor_position]
+                .rfind(' ')
+                .map(|idx| idx + 1)
+                .unwrap_or(0);
+            let word = &line[start..cursor_position];
+            WORDS
+                .iter()
+                .filter(|candidate| candidate.starts_with(word))
+                .map(|candidate| CompletionCandidate {
+                    range: start..cursor_position,
+                    text: candidate.to_string(),
+                })
+                .collect()
+        }
+    }
+
+    fn key(key: KeyCode, modifiers: Modifiers) -> InputEvent {
+        InputEvent::Key(KeyEvent {
+            key,
+            modifiers,
+            event_type: KeyEventType::Press,
+            kitty: None,
+        })
+    }
+
+    fn text(text: &str) -> Vec<InputEvent> {
+        text.chars()
+            .map(|c| key(KeyCode::Char(c), Modifiers::NONE))
+            .collect()
+    }
+
+    fn enter() -> InputEvent {
+        key(KeyCode::Enter, Modifiers::NONE)
+    }
+
+    /// Runs the line editor over the scripted input, returning
+    /// the accepted line and the screens that were rendered
+    fn read_line(host: &mut dyn LineEditorHost, input: Vec<InputEvent>) -> (String, Vec<String>) {
+        let mut terminal = ScriptedTerminal::new(input);
+        let line = LineEditor::new(&mut terminal)
+            .read_line(host)
+            .unwrap()
+            .unwrap();
+        (line, terminal.screens)
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut input = text("hello world");
+        input.extend([
+            key(KeyCode::Char('W'), Modifiers::CTRL),
+            key(KeyCode::Char('_'), Modifiers::CTRL),
+            key(KeyCode::Char('_'), Modifiers::CTRL),
+            key(KeyCode::Char('_'), Modifiers::ALT),
+            key(KeyCode::Char('_'), Modifiers::ALT),
+            enter(),
+        ]);
+        let (line, screens) = read_line(&mut TestHost::default(), input);
+        assert_eq!(line, "hello ");
+
+        // The typed text is undone as a single step
+        let lines: Vec<&str> = screens[11..]
+            .iter()
+            .map(|screen| screen.lines().next().unwrap().trim_end())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "> hello world",
+                "> hello",
+                "> hello world",
+                ">",
+                "> hello world",
+                "> hello",
+            ]
+        );
+    }
+
+    #[test]
+    fn kill_ring() {
+        let mut host = TestHost::default();
+        let mut input = text("one two");
+        input.extend([
+            key(KeyCode::Char('W'), Modifiers::CTRL),
+            key(KeyCode::Char('W'), Modifiers::CTRL),
+            key(KeyCode::Char('Y'), Modifiers::CTRL),
+            key(KeyCode::Char('y'), Modifiers::ALT),
+            enter(),
+            key(KeyCode::Char('Y'), Modifiers::CTRL),
+            enter(),
+        ]);
+
+        let mut terminal = ScriptedTerminal::new(input);
+        let mut editor = LineEditor::new(&mut terminal);
+        // Yanking inserts the most recent kill, and yank-pop
+        // replaces it with the one before
+        assert_eq!(editor.read_line(&mut host).unwrap().unwrap(), "two");
+        // The kill ring persists across calls to read_line
+        assert_eq!(editor.read_line(&mut host).unwrap().unwrap(), "one ");
+    }
+
+    #[test]
+    fn multi_line_input() {
+        let mut input = text("echo \"a");
+        input.push(enter());
+        input.extend(text("b\""));
+        input.push(key(KeyCode::Enter, Modifiers::ALT));
+        input.extend(text("c"));
+        input.push(enter());
+        let (line, screens) = read_line(&mut TestHost::default(), input);
+
+        // Enter continues the input while the quote is unbalanced,
+        // and Alt-Enter always starts a new line
+        assert_eq!(line, "echo \"a\nb\"\nc");
+        let screen: Vec<&str> = screens
+            .last()
+            .unwrap()
+            .lines()
+            .map(|line| line.trim_end())
+            .collect();
+        assert_eq!(screen[..3], ["> echo \"a", "... b\"", "... c"]);
+    }
+
+    #[test]
+    fn completion_cycling() {
+        let mut state = CompletionState {
+            candidates: ["hello", "help"]
+                .iter()
+                .map(|text| CompletionCandidate {
+                    range: 0..2,
+                    text: text.to_string(),
+                })
+                .collect(),
+            index: 0,
+            original_line: "he there".to_string(),
+            original_cursor: 2,
+        };
+        state.prev();
+        assert_eq!(state.current(), (4, "help there".to_string()));
+        state.next();
+        assert_eq!(state.current(), (5, "hello there".to_string()));
+
+        assert_eq!(truncate_to_width("漢字ok", 3), "漢");
+    }
+
+    #[test]
+    fn accept_hint_at_end_of_line() {
+        let mut input = text("git");
+        input.extend([
+            // Moving forwards from the end of the input accepts the hint
+            key(KeyCode::RightArrow, Modifiers::NONE),
+            enter(),
+        ]);
+        let (line, screens) = read_line(&mut TestHost::default(), input);
+        assert_eq!(line, HINT_LINE);
+        // The hint was displayed after the input before it was accepted
+        assert!(screens[3].starts_with("> git status"));
+
+        // Moving within the line doesn't accept the hint
+        let mut input = text("git");
+        input.extend([
+            key(KeyCode::LeftArrow, Modifiers::NONE),
+            key(KeyCode::RightArrow, Modifiers::NONE),
+            enter(),
+        ]);
+        let (line, _) = read_line(&mut TestHost::default(), input);
+        assert_eq!(line, "git");
+    }
+
+    #[test]
+    fn completion_menu() {
+        let mut input = text("say hel");
+        input.extend([
+            key(KeyCode::Tab, Modifiers::NONE),
+            key(KeyCode::Tab, Modifiers::NONE),
+            // Typing dismisses the menu, keeping the current candidate
+            key(KeyCode::Char('!'), Modifiers::NONE),
+            enter(),
+        ]);
+        let (line, screens) = read_line(&mut TestHost::default(), input);
+        assert_eq!(line, "say help!");
+
+        let rows = |screen: &str| -> Vec<String> {
+            screen
+                .lines()
+                .map(|line| line.trim_end().to_string())
+                .filter(|line| !line.is_empty())
+                .collect()
+        };
+
+        // The menu lists the candidates below the input
+        assert_eq!(
+            rows(&screens[8]),
+            vec!["> say hello", "hello", "help", "helm"]
+        );
+        assert_eq!(
+            rows(&screens[9]),
+            vec!["> say help", "hello", "help", "helm"]
+        );
+        assert_eq!(rows(&screens[10]), vec!["> say help!"]);
+    }
+}
@@ -0,0 +1,372 @@
+//! Key resolution for the normal mode of the vi keymap
+use crate::input::{InputEvent, KeyCode, KeyEvent, Modifiers};
+use crate::lineedit::actions::{Action, Movement, RepeatCount, ViMode};
+use crate::lineedit::LineEditBuffer;
+
+/// The largest count that may be typed.  Larger counts are clamped
+/// to this, as some commands repeat an action `count` times.
+const MAX_COUNT: RepeatCount = 9999;
+
+/// Tracks the mode of the vi keymap, along with any partially
+/// entered command
+pub(crate) struct ViState {
+    pub mode: ViMode,
+    /// The count that has been typed so far
+    count: Option<RepeatCount>,
+    /// The operator (`d`, `c` or `y`) that is waiting for a motion,
+    /// along with the count that preceded it
+    operator: Option<(char, RepeatCount)>,
+    /// The `f`, `F`, `t` or `T` motion that is waiting for its
+    /// target char, along with its count
+    pending_find: Option<(char, RepeatCount)>,
+    /// The most recent `f`, `F`, `t` or `T` motion and its target,
+    /// for use by `;` and `,`
+    last_find: Option<(char, char)>,
+}
+
+impl ViState {
+    pub fn new() -> Self {
+        Self {
+            mode: ViMode::Insert,
+            count: None,
+            operator: None,
+            pending_find: None,
+            last_find: None,
+        }
+    }
+
+    /// Abandons any partially entered command
+    pub fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.pending_find = None;
+    }
+
+    /// Resolves a key event that was received in normal mode.
+    /// Returns None for keys that should use the default bindings,
+    /// such as Enter, the arrow keys and CTRL-C.
+    pub fn resolve_normal(
+        &mut self,
+        event: &InputEvent,
+        buffer: &LineEditBuffer,
+    ) -> Option<Action> {
+        let key = match event {
+            InputEvent::Key(key) => key,
+            _ => return None,
+        };
+
+        if let Some((kind, count)) = self.pending_find.take() {
+            return match printable_char(key) {
+                Some(c) => {
+                    self.last_find = Some((kind, c));
+                    Some(self.motion(find_movement(kind, c, count), buffer))
+                }
+                None => {
+                    self.reset();
+                    Some(Action::NoAction)
+                }
+            };
+        }
+
+        let c = match (&key.key, key.modifiers) {
+            (KeyCode::Char('r'), Modifiers::CTRL) | (KeyCode::Char('R'), Modifiers::CTRL) => {
+                self.reset();
+                return Some(Action::Redo);
+            }
+            (KeyCode::Escape, _) => {
+                self.reset();
+                return Some(Action::NoAction);
+            }
+            (KeyCode::Backspace, Modifiers::NONE)
+            | (KeyCode::LeftArrow, Modifiers::NONE)
+            | (KeyCode::ApplicationLeftArrow, Modifiers::NONE) => 'h',
+            (KeyCode::RightArrow, Modifiers::NONE)
+            | (KeyCode::ApplicationRightArrow, Modifiers::NONE) => 'l',
+            (KeyCode::Home, Modifiers::NONE) => '0',
+            (KeyCode::End, Modifiers::NONE) => '$',
+            _ => match printable_char(key) {
+                Some(c) => c,
+                None => {
+                    self.reset();
+                    return None;
+                }
+            },
+        };
+
+        if let Some(digit) = c.to_digit(10) {
+            if digit != 0 || self.count.is_some() {
+                let count = self
+                    .count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(digit as usize);
+                self.count = Some(count.min(MAX_COUNT));
+                return Some(Action::NoAction);
+            }
+        }
+        let count = self.count.take().unwrap_or(1);
+
+        if let Some((op, op_count)) = self.operator {
+            let count = count.saturating_mul(op_count).min(MAX_COUNT);
+            if c == op {
+                self.operator = None;
+                return Some(whole_line(op));
+            }
+            if let Some(movement) = self.movement_for(c, count) {
+                return Some(self.motion(movement, buffer));
+            }
+            if matches!(c, 'f' | 'F' | 't' | 'T') {
+                self.pending_find = Some((c, count));
+                return Some(Action::NoAction);
+            }
+            self.reset();
+            return Some(Action::NoAction);
+        }
+
+        if let Some(movement) = self.movement_for(c, count) {
+            return Some(Action::Move(movement));
+        }
+
+        Some(match c {
+            'f' | 'F' | 't' | 'T' => {
+                self.pending_find = Some((c, count));
+                Action::NoAction
+            }
+            'd' | 'c' | 'y' => {
+                self.operator = Some((c, count));
+                Action::NoAction
+            }
+            'x' => kill(Movement::ForwardChar(count)),
+            'X' => kill(Movement::BackwardChar(count)),
+            'D' => kill(Movement::EndOfLine),
+            'C' => Action::Sequence(vec![
+                kill(Movement::EndOfLine),
+                Action::SetViMode(ViMode::Insert),
+            ]),
+            's' => Action::Sequence(vec![
+                kill(Movement::ForwardChar(count)),
+                Action::SetViMode(ViMode::Insert),
+            ]),
+            'S' => whole_line('c'),
+            'Y' => whole_line('y'),
+            'i' => Action::SetViMode(ViMode::Insert),
+            'a' => Action::Sequence(vec![
+                Action::Move(Movement::ForwardChar(1)),
+                Action::SetViMode(ViMode::Insert),
+            ]),
+            'I' => Action::Sequence(vec![
+                Action::Move(Movement::FirstNonBlank),
+                Action::SetViMode(ViMode::Insert),
+            ]),
+            'A' => Action::Sequence(vec![
+                Action::Move(Movement::EndOfLine),
+                Action::SetViMode(ViMode::Insert),
+            ]),
+            'p' => Action::Sequence(vec![
+                Action::Move(Movement::ForwardChar(1)),
+                Action::Yank(count),
+                Action::Move(Movement::BackwardChar(1)),
+            ]),
+            'P' => Action::Sequence(vec![
+                Action::Yank(count),
+                Action::Move(Movement::BackwardChar(1)),
+            ]),
+            'u' => Action::Sequence(vec![Action::Undo; count]),
+            'j' => Action::HistoryNext,
+            'k' => Action::HistoryPrevious,
+            _ => Action::NoAction,
+        })
+    }
+
+    /// Returns the movement for a motion key
+    fn movement_for(&self, c: char, count: RepeatCount) -> Option<Movement> {
+        Some(match c {
+            'h' => Movement::BackwardChar(count),
+            'l' | ' ' => Movement::ForwardChar(count),
+            'w' => Movement::ForwardWord(count),
+            'W' => Movement::ForwardBigWord(count),
+            'b' => Movement::BackwardWord(count),
+            'B' => Movement::BackwardBigWord(count),
+            'e' => Movement::ForwardWordEnd(count),
+            'E' => Movement::ForwardBigWordEnd(count),
+            '0' => Movement::StartOfLine,
+            '^' => Movement::FirstNonBlank,
+            '$' => Movement::EndOfLine,
+            ';' => {
+                let (kind, target) = self.last_find?;
+                find_movement(kind, target, count)
+            }
+            ',' => {
+                let (kind, target) = self.last_find?;
+                let reversed = match kind {
+                    'f' => 'F',
+                    'F' => 'f',
+                    't' => 'T',
+                    _ => 't',
+                };
+                find_movement(reversed, target, count)
+            }
+            _ => return None,
+        })
+    }
+
+    /// Produces the action for a motion, applying the pending
+    /// operator, if any
+    fn motion(&mut self, movement: Movement, buffer: &LineEditBuffer) -> Action {
+        match self.operator.take() {
+            None => Action::Move(movement),
+            Some(('d', _)) => kill(movement),
+            Some(('c', _)) => {
+                // As in vi, `cw` changes to the end of the word rather
+                // than to the start of the next word
+                let on_word = buffer.get_line()[buffer.get_cursor()..]
+                    .chars()
+                    .next()
+                    .map(|c| !c.is_whitespace())
+                    .unwrap_or(false);
+                let movement = match movement {
+                    Movement::ForwardWord(n) if on_word => Movement::ForwardWordEnd(n),
+                    Movement::ForwardBigWord(n) if on_word => Movement::ForwardBigWordEnd(n),
+                    movement => movement,
+                };
+                Action::Sequence(vec![kill(movement), Action::SetViMode(ViMode::Insert)])
+            }
+            Some(_) => Action::Copy(movement),
+        }
+    }
+}
+
+/// Returns the char produced by a key press without modifiers
+/// other than SHIFT
+fn printable_char(key: &KeyEvent) -> Option<char> {
+    match (&key.key, key.modifiers) {
+        (KeyCode::Char(c), Modifiers::NONE) | (KeyCode::Char(c), Modifiers::SHIFT) => Some(*c),
+        _ => None,
+    }
+}
+
+fn find_movement(kind: char, c: char, count: RepeatCount) -> Movement {
+    match kind {
+        'f' => Movement::ForwardToChar(count, c),
+        'F' => Movement::BackwardToChar(count, c),
+        't' => Movement::ForwardTillChar(count, c),
+        _ => Movement::BackwardTillChar(count, c),
+    }
+}
+
+/// Deletes the text spanned by the movement, leaving the cursor at
+/// the start of the deleted region
+fn kill(movement: Movement) -> Action {
+    if movement.is_backward() {
+        Action::Kill(movement)
+    } else {
+        Action::KillAndMove(movement, Movement::None)
+    }
+}
+
+/// Applies the operator to the whole of the current line, as for
+/// `dd`, `cc` and `yy`
+fn whole_line(op: char) -> Action {
+    let mut actions = vec![Action::Move(Movement::StartOfLine)];
+    match op {
+        'd' => actions.push(kill(Movement::EndOfLine)),
+        'c' => {
+            actions.push(kill(Movement::EndOfLine));
+            actions.push(Action::SetViMode(ViMode::Insert));
+        }
+        _ => actions.push(Action::Copy(Movement::EndOfLine)),
+    }
+    Action::Sequence(actions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::KeyEventType;
+
+    /// Feeds the keys to a normal mode ViState, returning the
+    /// final resolved action
+    fn resolve(keys: &str, line: &str, cursor: usize) -> Option<Action> {
+        let mut state = ViState::new();
+        state.mode = ViMode::Normal;
+        let buffer = LineEditBuffer::new(line, cursor);
+        let mut result = None;
+        for c in keys.chars() {
+            let event = InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+                event_type: KeyEventType::Press,
+                kitty: None,
+            });
+            result = state.resolve_normal(&event, &buffer);
+        }
+        result
+    }
+
+    #[test]
+    fn motions_and_operators() {
+        use Movement::*;
+        assert_eq!(
+            resolve("3w", "a b c d", 0),
+            Some(Action::Move(ForwardWord(3)))
+        );
+        assert_eq!(
+            resolve("d2w", "a b c d", 0),
+            Some(Action::KillAndMove(ForwardWord(2), None))
+        );
+        assert_eq!(
+            resolve("2db", "a b c d", 6),
+            Some(Action::Kill(BackwardWord(2)))
+        );
+        assert_eq!(
+            resolve("cw", "hello world", 0),
+            Some(Action::Sequence(vec![
+                Action::KillAndMove(ForwardWordEnd(1), None),
+                Action::SetViMode(ViMode::Insert),
+            ]))
+        );
+        assert_eq!(
+            resolve("dt)", "f(a, b)", 2),
+            Some(Action::KillAndMove(ForwardTillChar(1, ')'), None))
+        );
+        assert_eq!(
+            resolve("fa;", "banana", 0),
+            Some(Action::Move(ForwardToChar(1, 'a')))
+        );
+        assert_eq!(
+            resolve("yy", "hello", 2),
+            Some(Action::Sequence(vec![
+                Action::Move(StartOfLine),
+                Action::Copy(EndOfLine),
+            ]))
+        );
+        assert_eq!(resolve("d", "hello", 0), Some(Action::NoAction));
+        assert_eq!(resolve("dz", "hello", 0), Some(Action::NoAction));
+        assert_eq!(resolve("0", "hello", 3), Some(Action::Move(StartOfLine)));
+    }
+
+    #[test]
+    fn long_counts_are_clamped() {
+        use Movement::*;
+        let digits = "9".repeat(40);
+        assert_eq!(
+            resolve(&format!("{digits}w"), "a b c d", 0),
+            Some(Action::Move(ForwardWord(MAX_COUNT)))
+        );
+        assert_eq!(
+            resolve(&format!("{digits}d{digits}w"), "a b c d", 0),
+            Some(Action::KillAndMove(ForwardWord(MAX_COUNT), None))
+        );
+        assert_eq!(
+            resolve(&format!("{digits}u"), "hello", 0),
+            Some(Action::Sequence(vec![Action::Undo; MAX_COUNT]))
+        );
+        assert_eq!(
+            resolve(&format!("{digits}P"), "hello", 0),
+            Some(Action::Sequence(vec![
+                Action::Yank(MAX_COUNT),
+                Action::Move(BackwardChar(1)),
+            ]))
+        );
+    }
+}
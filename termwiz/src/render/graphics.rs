@@ -0,0 +1,394 @@
+//! Helpers for rendering images with the kitty graphics protocol,
+//! as sixels or as half-block characters
+use crate::bail;
+use crate::color::{ColorAttribute, SrgbaTuple};
+use crate::escape::{Sixel, SixelData};
+use crate::image::ImageDataType;
+use crate::surface::Image;
+use crate::{Context, Result};
+use image::{imageops, ImageFormat, RgbaImage};
+use std::io::Cursor;
+
+/// Pixels with an alpha value below this are treated as transparent
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// The largest number of colors that we put into a sixel palette
+const MAX_SIXEL_COLORS: usize = 256;
+
+type Rgb = (u8, u8, u8);
+
+/// Returns the RGBA pixels of the image data.
+/// For an animated image, the first frame is used.
+pub(crate) fn decode(image: &Image) -> Result<RgbaImage> {
+    let data = match &*image.image.data() {
+        ImageDataType::EncodedLease(lease) => ImageDataType::EncodedFile(lease.get_data()?),
+        data => data.clone(),
+    };
+    let (width, height, pixels) = match data.decode() {
+        ImageDataType::Rgba8 {
+            width,
+            height,
+            data,
+            ..
+        } => (width, height, data),
+        ImageDataType::AnimRgba8 {
+            width,
+            height,
+            mut frames,
+            ..
+        } if !frames.is_empty() => (width, height, frames.swap_remove(0)),
+        _ => bail!("unable to decode image data"),
+    };
+    match RgbaImage::from_raw(width, height, pixels) {
+        Some(pixels) => Ok(pixels),
+        None => bail!(
+            "image data doesn't match its {}x{} dimensions",
+            width,
+            height
+        ),
+    }
+}
+
+/// Returns the (x, y, width, height) in pixels of the region of an
+/// image with the specified dimensions that is selected by the
+/// texture coordinates of `image`.  The region is at least one pixel
+/// in size.
+pub(crate) fn source_rect(image: &Image, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let scale = |coord: f32, size: u32| (coord.clamp(0., 1.) * size as f32).round() as u32;
+    let x = scale(*image.top_left.x, width).min(width.saturating_sub(1));
+    let y = scale(*image.top_left.y, height).min(height.saturating_sub(1));
+    let right = scale(*image.bottom_right.x, width).max(x + 1);
+    let bottom = scale(*image.bottom_right.y, height).max(y + 1);
+    (x, y, right - x, bottom - y)
+}
+
+/// Returns true if the texture coordinates of `image` select the
+/// whole of the image data
+pub(crate) fn is_whole_image(image: &Image) -> bool {
+    *image.top_left.x == 0.
+        && *image.top_left.y == 0.
+        && *image.bottom_right.x == 1.
+        && *image.bottom_right.y == 1.
+}
+
+/// Crops `pixels` to the region selected by `image` and scales the
+/// result to the specified size in pixels
+pub(crate) fn region(image: &Image, pixels: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let (x, y, w, h) = source_rect(image, pixels.width(), pixels.height());
+    let cropped = imageops::crop_imm(pixels, x, y, w, h).to_image();
+    imageops::resize(
+        &cropped,
+        width.max(1),
+        height.max(1),
+        imageops::FilterType::Triangle,
+    )
+}
+
+/// Returns the image data encoded as PNG, along with its dimensions.
+/// PNG data is passed through as-is, while other formats are decoded
+/// and re-encoded.
+pub(crate) fn png_data(image: &Image) -> Result<(Vec<u8>, u32, u32)> {
+    let data = match &*image.image.data() {
+        ImageDataType::EncodedFile(data) => Some(data.clone()),
+        ImageDataType::EncodedLease(lease) => Some(lease.get_data()?),
+        _ => None,
+    };
+    if let Some(data) = data {
+        if image::guess_format(&data).ok() == Some(ImageFormat::Png) {
+            let (width, height) = ImageDataType::EncodedFile(data.clone()).dimensions()?;
+            return Ok((data, width, height));
+        }
+    }
+
+    encode_png(&decode(image)?)
+}
+
+/// Encodes the pixels as PNG, returning the data and its dimensions
+pub(crate) fn encode_png(pixels: &RgbaImage) -> Result<(Vec<u8>, u32, u32)> {
+    let mut png = Cursor::new(vec![]);
+    pixels
+        .write_to(&mut png, ImageFormat::Png)
+        .context("encoding image as PNG")?;
+    Ok((png.into_inner(), pixels.width(), pixels.height()))
+}
+
+/// Encodes the pixels as a sixel image with a palette of up to 256
+/// colors chosen by median cut quantization.
+/// Transparent pixels are left unpainted.
+pub(crate) fn encode_sixel(pixels: &RgbaImage) -> Sixel {
+    let (palette, indices) = quantize(pixels);
+    let width = pixels.width() as usize;
+    let height = pixels.height() as usize;
+
+    let mut data: Vec<SixelData> = palette
+        .iter()
+        .enumerate()
+        .map(|(color_number, &(r, g, b))| SixelData::DefineColorMapRGB {
+            color_number: color_number as u16,
+            rgb: crate::color::RgbColor::new_8bpc(r, g, b),
+        })
+        .collect();
+
+    for band in (0..height).step_by(6) {
+        let band_height = (height - band).min(6);
+        // Compute the sixel values for each color that appears in the band
+        let mut bands: Vec<Option<Vec<u8>>> = vec![None; palette.len()];
+        for row in 0..band_height {
+            for x in 0..width {
+                if let Some(idx) = indices[(band + row) * width + x] {
+                    let values = bands[idx as usize].get_or_insert_with(|| vec![0; width]);
+                    values[x] |= 1 << row;
+                }
+            }
+        }
+
+        let mut first = true;
+        for (color_number, values) in bands.into_iter().enumerate() {
+            let Some(values) = values else { continue };
+            if !first {
+                data.push(SixelData::CarriageReturn);
+            }
+            first = false;
+            data.push(SixelData::SelectColorMapEntry(color_number as u16));
+
+            // Trailing blank sixels needn't be sent
+            let len = values.iter().rposition(|&v| v != 0).map_or(0, |p| p + 1);
+            let mut x = 0;
+            while x < len {
+                let value = values[x];
+                let run = values[x..len].iter().take_while(|&&v| v == value).count();
+                data.push(if run > 3 {
+                    SixelData::Repeat {
+                        repeat_count: run as u32,
+                        data: value,
+                    }
+                } else {
+                    SixelData::Data(value)
+                });
+                x += if run > 3 { run } else { 1 };
+            }
+        }
+        if band + 6 < height {
+            data.push(SixelData::NewLine);
+        }
+    }
+
+    Sixel {
+        pan: 1,
+        pad: 1,
+        pixel_width: Some(width as u32),
+        pixel_height: Some(height as u32),
+        background_is_transparent: true,
+        horizontal_grid_size: None,
+        data,
+    }
+}
+
+/// Reduces the opaque pixels to a palette of at most 256 colors by
+/// recursively splitting the set of colors at the median of its
+/// widest channel.
+/// Returns the palette and the palette index of each pixel, which
+/// is None for transparent pixels.
+fn quantize(pixels: &RgbaImage) -> (Vec<Rgb>, Vec<Option<u8>>) {
+    // Each entry is the color of an opaque pixel along with its index
+    let mut colors: Vec<([u8; 3], usize)> = pixels
+        .pixels()
+        .enumerate()
+        .filter(|(_, p)| p.0[3] >= ALPHA_THRESHOLD)
+        .map(|(idx, p)| ([p.0[0], p.0[1], p.0[2]], idx))
+        .collect();
+
+    // Returns the widest channel of the colors and its range
+    fn widest_channel(colors: &[([u8; 3], usize)]) -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let min = colors.iter().map(|c| c.0[channel]).min().unwrap_or(0);
+                let max = colors.iter().map(|c| c.0[channel]).max().unwrap_or(0);
+                (channel, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    }
+
+    let mut boxes = Vec::with_capacity(MAX_SIXEL_COLORS);
+    boxes.push(0..colors.len());
+    while boxes.len() < MAX_SIXEL_COLORS {
+        // Split the box with the greatest range of colors
+        let Some((idx, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| range.len() > 1)
+            .map(|(idx, range)| {
+                let (channel, extent) = widest_channel(&colors[range.clone()]);
+                (idx, channel, extent)
+            })
+            .filter(|&(_, _, extent)| extent > 0)
+            .max_by_key(|&(_, _, extent)| extent)
+            .map(|(idx, channel, _)| (idx, channel))
+        else {
+            break;
+        };
+        let range = boxes.swap_remove(idx);
+        let sorted = &mut colors[range.clone()];
+        sorted.sort_unstable_by_key(|c| c.0[channel]);
+        // Split where the channel value changes, so that a color
+        // never ends up in both halves
+        let median = sorted[sorted.len() / 2].0[channel];
+        let below = sorted.partition_point(|c| c.0[channel] < median);
+        let mid = range.start
+            + if below > 0 {
+                below
+            } else {
+                sorted.partition_point(|c| c.0[channel] <= median)
+            };
+        boxes.push(range.start..mid);
+        boxes.push(mid..range.end);
+    }
+
+    let mut palette = vec![];
+    let mut indices = vec![None; (pixels.width() * pixels.height()) as usize];
+    for range in boxes.into_iter().filter(|range| !range.is_empty()) {
+        let len = range.len() as u32;
+        let mut sum = [0u32; 3];
+        for (color, idx) in &colors[range] {
+            for channel in 0..3 {
+                sum[channel] += color[channel] as u32;
+            }
+            indices[*idx] = Some(palette.len() as u8);
+        }
+        palette.push((
+            (sum[0] / len) as u8,
+            (sum[1] / len) as u8,
+            (sum[2] / len) as u8,
+        ));
+    }
+    (palette, indices)
+}
+
+/// Returns the character, foreground and background colors for
+/// rendering each cell of the pixels using half block characters.
+/// The upper half block is used with the top pixel as its foreground
+/// and the bottom pixel as its background, while the lower half
+/// block or a space is used when pixels are transparent so that the
+/// terminal background shows through.
+/// `pixels` must be twice as tall as the number of rows.
+pub(crate) fn half_blocks(pixels: &RgbaImage) -> Vec<Vec<(char, ColorAttribute, ColorAttribute)>> {
+    let color = |x: u32, y: u32| {
+        let [r, g, b, a] = pixels.get_pixel(x, y).0;
+        if a < ALPHA_THRESHOLD {
+            None
+        } else {
+            Some(ColorAttribute::TrueColorWithPaletteFallback(
+                SrgbaTuple::from((r, g, b)),
+                nearest_palette_index(r, g, b),
+            ))
+        }
+    };
+    (0..pixels.height() / 2)
+        .map(|row| {
+            (0..pixels.width())
+                .map(|x| match (color(x, row * 2), color(x, row * 2 + 1)) {
+                    (Some(top), bottom) => {
+                        ('\u{2580}', top, bottom.unwrap_or(ColorAttribute::Default))
+                    }
+                    (None, Some(bottom)) => ('\u{2584}', bottom, ColorAttribute::Default),
+                    (None, None) => (' ', ColorAttribute::Default, ColorAttribute::Default),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the index of the color in the xterm 256 color palette,
+/// excluding the 16 ANSI colors, that best approximates the color
+fn nearest_palette_index(r: u8, g: u8, b: u8) -> u8 {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |v: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap_or(0)
+    };
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let dr = r as i32 - r2 as i32;
+        let dg = g as i32 - g2 as i32;
+        let db = b as i32 - b2 as i32;
+        dr * dr + dg * dg + db * db
+    };
+
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // The grayscale ramp runs from 8 to 238 in steps of 10
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + gray_step * 10;
+    let gray_index = 232 + gray_step as usize;
+
+    if distance((gray_level, gray_level, gray_level)) < distance(cube) {
+        gray_index as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::{ImageData, TextureCoordinate};
+    use std::sync::Arc;
+
+    fn image(width: u32, height: u32, pixels: Vec<u8>) -> Image {
+        Image {
+            width: 1,
+            height: 1,
+            top_left: TextureCoordinate::new_f32(0., 0.),
+            bottom_right: TextureCoordinate::new_f32(1., 1.),
+            image: Arc::new(ImageData::with_data(ImageDataType::new_single_frame(
+                width, height, pixels,
+            ))),
+        }
+    }
+
+    #[test]
+    fn sixel() {
+        // A 2x7 image: red and green columns, with a transparent
+        // pixel at the bottom right
+        let mut pixels = vec![];
+        for y in 0..7 {
+            pixels.extend_from_slice(&[255, 0, 0, 255]);
+            pixels.extend_from_slice(if y == 6 {
+                &[0, 0, 0, 0]
+            } else {
+                &[0, 255, 0, 255]
+            });
+        }
+        let image = image(2, 7, pixels);
+        let sixel = encode_sixel(&decode(&image).unwrap());
+        assert_eq!(
+            format!("{sixel}"),
+            "\x1bP;1q\"1;1;2;7\
+             #0;2;100;0;0#1;2;0;100;0\
+             #0~$#1?~-\
+             #0@"
+        );
+    }
+
+    #[test]
+    fn source_rects() {
+        let mut image = image(1, 1, vec![0; 4]);
+        assert!(is_whole_image(&image));
+        image.top_left = TextureCoordinate::new_f32(0.5, 0.25);
+        image.bottom_right = TextureCoordinate::new_f32(0.75, 0.5);
+        assert!(!is_whole_image(&image));
+        assert_eq!(source_rect(&image, 100, 40), (50, 10, 25, 10));
+    }
+
+    #[test]
+    fn palette_index() {
+        assert_eq!(nearest_palette_index(0, 0, 0), 16);
+        assert_eq!(nearest_palette_index(255, 0, 0), 196);
+        assert_eq!(nearest_palette_index(128, 128, 128), 244);
+        assert_eq!(nearest_palette_index(255, 255, 255), 231);
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "use_image")]
+mod graphics;
 pub mod terminfo;
 #[cfg(windows)]
 pub mod windows;
@@ -5,4 +7,20 @@ pub mod windows;
 pub trait RenderTty: std::io::Write {
     /// Returns the (cols, rows) for the terminal
     fn get_size_in_cells(&mut self) -> crate::Result<(usize, usize)>;
+
+    /// Returns the (width, height) in pixels of a cell, if known.
+    /// This is used to size images that are rendered as pixels.
+    fn get_cell_size_in_pixels(&mut self) -> crate::Result<Option<(usize, usize)>> {
+        Ok(None)
+    }
+}
+
+/// Counters that describe the output produced by a renderer;
+/// these are useful when benchmarking or tuning rendering.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    /// The number of non-empty batches of changes that were rendered
+    pub frames: usize,
+    /// The total number of bytes that were written to the terminal
+    pub bytes: usize,
 }
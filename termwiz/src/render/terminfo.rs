@@ -2,24 +2,86 @@
 use crate::caps::{Capabilities, ColorLevel};
 use crate::cell::{AttributeChange, Blink, CellAttributes, Intensity, Underline};
 use crate::color::{ColorAttribute, ColorSpec};
-use crate::escape::csi::{Cursor, Edit, EraseInDisplay, EraseInLine, Sgr, CSI};
+#[cfg(feature = "use_image")]
+use crate::escape::apc::{
+    KittyImage, KittyImageCompression, KittyImageData, KittyImageDelete, KittyImageFormat,
+    KittyImagePlacement, KittyImageTransmit, KittyImageVerbosity,
+};
+use crate::escape::csi::{
+    Cursor, DecPrivateMode, DecPrivateModeCode, Edit, EraseInDisplay, EraseInLine, Mode, Sgr, CSI,
+};
 use crate::escape::esc::EscCode;
 use crate::escape::osc::OperatingSystemCommand;
 #[cfg(feature = "use_image")]
 use crate::escape::osc::{ITermDimension, ITermFileData, ITermProprietary};
 use crate::escape::{Esc, OneBased};
 #[cfg(feature = "use_image")]
-use crate::image::{ImageDataType, TextureCoordinate};
-use crate::render::RenderTty;
+use crate::image::ImageDataType;
+#[cfg(feature = "use_image")]
+use crate::render::graphics;
+use crate::render::{RenderStats, RenderTty};
+#[cfg(feature = "use_image")]
+use crate::surface::Image;
 use crate::surface::{Change, CursorShape, CursorVisibility, LineAttribute, Position};
 use crate::Result;
+#[cfg(feature = "use_image")]
+use base64::Engine;
+#[cfg(feature = "use_image")]
+use lru::LruCache;
 use std::io::Write;
+#[cfg(feature = "use_image")]
+use std::num::NonZeroUsize;
 use terminfo::{capability as cap, Capability as TermInfoCapability};
 
+/// (image_id, x, y, width, height, columns, rows)
+#[cfg(feature = "use_image")]
+type KittyPlacementKey = (u32, u32, u32, u32, u32, u32, u32);
+
+/// The cell size in pixels that is assumed when the tty doesn't report it
+#[cfg(feature = "use_image")]
+const DEFAULT_CELL_SIZE: (usize, usize) = (10, 20);
+
+/// The largest chunk of base64 data that is sent in a single kitty
+/// graphics escape sequence
+#[cfg(feature = "use_image")]
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// The number of transmitted kitty images that are remembered.
+/// The least recently used image is deleted from the terminal
+/// when another one is transmitted.
+#[cfg(feature = "use_image")]
+const MAX_KITTY_IMAGES: usize = 64;
+
+/// The number of kitty placements that are remembered.
+/// The least recently used placement is deleted from the terminal
+/// when another one is added.
+#[cfg(feature = "use_image")]
+const MAX_KITTY_PLACEMENTS: usize = 256;
+
 pub struct TerminfoRenderer {
     caps: Capabilities,
     current_attr: CellAttributes,
     pending_attr: Option<CellAttributes>,
+    stats: RenderStats,
+    /// Maps the hash of image data that has been transmitted using
+    /// the kitty graphics protocol to its (image_id, width, height)
+    #[cfg(feature = "use_image")]
+    kitty_images: LruCache<[u8; 32], (u32, u32, u32)>,
+    /// Maps the image id, source rect and size in cells of a kitty
+    /// placement to its placement id, so that redrawing a region of
+    /// an image replaces the earlier placement rather than adding to it
+    #[cfg(feature = "use_image")]
+    kitty_placements: LruCache<KittyPlacementKey, u32>,
+    /// The placement id to use for the next new placement
+    #[cfg(feature = "use_image")]
+    next_kitty_placement_id: u32,
+    /// true if kitty placements may be on screen
+    #[cfg(feature = "use_image")]
+    kitty_placed: bool,
+    /// The most recently decoded image, which saves decoding it again
+    /// for each of the cells that display it
+    #[cfg(feature = "use_image")]
+    decoded: Option<([u8; 32], image::RgbaImage)>,
     /* TODO: we should record cursor position, shape and color here
      * so that we can optimize updating them on screen. */
 }
@@ -30,9 +92,30 @@ impl TerminfoRenderer {
             caps,
             current_attr: CellAttributes::default(),
             pending_attr: None,
+            stats: RenderStats::default(),
+            #[cfg(feature = "use_image")]
+            kitty_images: LruCache::new(NonZeroUsize::new(MAX_KITTY_IMAGES).unwrap()),
+            #[cfg(feature = "use_image")]
+            kitty_placements: LruCache::new(NonZeroUsize::new(MAX_KITTY_PLACEMENTS).unwrap()),
+            #[cfg(feature = "use_image")]
+            next_kitty_placement_id: 1,
+            #[cfg(feature = "use_image")]
+            kitty_placed: false,
+            #[cfg(feature = "use_image")]
+            decoded: None,
         }
     }
 
+    /// Returns the statistics accumulated since the renderer was
+    /// created, or since the last call to `reset_stats`
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = RenderStats::default();
+    }
+
     fn get_capability<'a, T: TermInfoCapability<'a>>(&'a self) -> Option<T> {
         self.caps.terminfo_db().and_then(|db| db.get::<T>())
     }
@@ -53,6 +136,10 @@ impl TerminfoRenderer {
 
     #[allow(clippy::cognitive_complexity)]
     fn flush_pending_attr<W: RenderTty + Write>(&mut self, out: &mut W) -> Result<()> {
+        // SGR changes that don't come from terminfo are batched up so
+        // that they can be sent as a single escape sequence
+        let mut sgr_batch = vec![];
+
         macro_rules! attr_on {
             ($cap:ident, $sgr:expr) => {{
                 let cap = if self.caps.force_terminfo_render_to_use_ansi_sgr() {
@@ -61,17 +148,21 @@ impl TerminfoRenderer {
                     self.get_capability::<cap::$cap>()
                 };
                 if let Some(attr) = cap {
+                    flush_sgr(&mut sgr_batch, out)?;
                     attr.expand().to(out.by_ref())?;
                 } else {
-                    write!(out, "{}", CSI::Sgr($sgr))?;
+                    sgr_batch.push($sgr);
                 }
             }};
             ($sgr:expr) => {
-                write!(out, "{}", CSI::Sgr($sgr))?;
+                sgr_batch.push($sgr);
             };
         }
 
         if let Some(attr) = self.pending_attr.take() {
+            if attr == self.current_attr {
+                return Ok(());
+            }
             let mut current_foreground = self.current_attr.foreground();
             let mut current_background = self.current_attr.background();
 
@@ -87,6 +178,7 @@ impl TerminfoRenderer {
                 };
                 // The SetAttributes capability can only handle single underline and slow blink.
                 if let Some(sgr) = sgr {
+                    flush_sgr(&mut sgr_batch, out)?;
                     sgr.expand()
                         .bold(attr.intensity() == Intensity::Bold)
                         .dim(attr.intensity() == Intensity::Half)
@@ -158,30 +250,23 @@ impl TerminfoRenderer {
                 match (has_true_color, attr.foreground()) {
                     (true, ColorAttribute::TrueColorWithPaletteFallback(tc, _))
                     | (true, ColorAttribute::TrueColorWithDefaultFallback(tc)) => {
-                        write!(
-                            out,
-                            "{}",
-                            CSI::Sgr(Sgr::Foreground(ColorSpec::TrueColor(tc)))
-                        )?;
+                        sgr_batch.push(Sgr::Foreground(ColorSpec::TrueColor(tc)));
                     }
                     (false, ColorAttribute::TrueColorWithDefaultFallback(_))
                     | (_, ColorAttribute::Default) => {
                         // Terminfo doesn't define a reset color to default, so
                         // we use the ANSI code.
-                        write!(out, "{}", CSI::Sgr(Sgr::Foreground(ColorSpec::Default)))?;
+                        sgr_batch.push(Sgr::Foreground(ColorSpec::Default));
                     }
                     (false, ColorAttribute::TrueColorWithPaletteFallback(_, idx))
                     | (_, ColorAttribute::PaletteIndex(idx)) => {
                         match self.get_capability::<cap::SetAForeground>() {
                             Some(set) if (idx as i32) < terminfo_256_color => {
+                                flush_sgr(&mut sgr_batch, out)?;
                                 set.expand().color(idx).to(out.by_ref())?;
                             }
                             _ => {
-                                write!(
-                                    out,
-                                    "{}",
-                                    CSI::Sgr(Sgr::Foreground(ColorSpec::PaletteIndex(idx)))
-                                )?;
+                                sgr_batch.push(Sgr::Foreground(ColorSpec::PaletteIndex(idx)));
                             }
                         }
                     }
@@ -194,37 +279,32 @@ impl TerminfoRenderer {
                 match (has_true_color, attr.background()) {
                     (true, ColorAttribute::TrueColorWithPaletteFallback(tc, _))
                     | (true, ColorAttribute::TrueColorWithDefaultFallback(tc)) => {
-                        write!(
-                            out,
-                            "{}",
-                            CSI::Sgr(Sgr::Background(ColorSpec::TrueColor(tc)))
-                        )?;
+                        sgr_batch.push(Sgr::Background(ColorSpec::TrueColor(tc)));
                     }
                     (false, ColorAttribute::TrueColorWithDefaultFallback(_))
                     | (_, ColorAttribute::Default) => {
                         // Terminfo doesn't define a reset color to default, so
                         // we use the ANSI code.
-                        write!(out, "{}", CSI::Sgr(Sgr::Background(ColorSpec::Default)))?;
+                        sgr_batch.push(Sgr::Background(ColorSpec::Default));
                     }
                     (false, ColorAttribute::TrueColorWithPaletteFallback(_, idx))
                     | (_, ColorAttribute::PaletteIndex(idx)) => {
                         match self.get_capability::<cap::SetABackground>() {
                             Some(set) if (idx as i32) < terminfo_256_color => {
+                                flush_sgr(&mut sgr_batch, out)?;
                                 set.expand().color(idx).to(out.by_ref())?;
                             }
                             _ => {
-                                write!(
-                                    out,
-                                    "{}",
-                                    CSI::Sgr(Sgr::Background(ColorSpec::PaletteIndex(idx)))
-                                )?;
+                                sgr_batch.push(Sgr::Background(ColorSpec::PaletteIndex(idx)));
                             }
                         }
                     }
                 }
             }
 
-            if self.caps.hyperlinks() {
+            flush_sgr(&mut sgr_batch, out)?;
+
+            if self.caps.hyperlinks() && attr.hyperlink() != self.current_attr.hyperlink() {
                 if let Some(link) = attr.hyperlink() {
                     let osc = OperatingSystemCommand::SetHyperlink(Some((**link).clone()));
                     write!(out, "{}", osc)?;
@@ -333,11 +413,387 @@ impl TerminfoRenderer {
         Ok(())
     }
 
-    #[allow(clippy::cyclomatic_complexity, clippy::cognitive_complexity)]
+    /// Renders an image at the cursor position, using the best
+    /// protocol supported by the terminal and falling back to half
+    /// block characters or blank cells.
+    /// The cursor is left at the top right of the image.
+    #[cfg(feature = "use_image")]
+    fn render_image<W: RenderTty + Write>(&mut self, image: &Image, out: &mut W) -> Result<()> {
+        if image.width == 0 || image.height == 0 {
+            return Ok(());
+        }
+        let result = if self.caps.kitty_graphics() {
+            self.render_kitty_image(image, out)
+        } else if self.caps.iterm2_image() {
+            self.render_iterm2_image(image, out)
+        } else if self.caps.sixel() {
+            self.render_sixel_image(image, out)
+        } else if self.caps.color_level() != ColorLevel::MonoChrome {
+            self.render_half_block_image(image, out)
+        } else {
+            self.blank_cells(image.width, image.height, out)
+        };
+        if let Err(err) = result {
+            log::error!("failed to render image: {:#}", err);
+            self.blank_cells(image.width, image.height, out)?;
+        }
+        Ok(())
+    }
+
+    /// Writes spaces over the specified number of cells, leaving the
+    /// cursor at the top right of the region
+    #[cfg(feature = "use_image")]
+    fn blank_cells<W: RenderTty + Write>(
+        &mut self,
+        width: usize,
+        height: usize,
+        out: &mut W,
+    ) -> Result<()> {
+        for y in 0..height {
+            for _ in 0..width {
+                write!(out, " ")?;
+            }
+
+            if y != height - 1 {
+                writeln!(out)?;
+                self.cursor_left(width as u32, out)?;
+            }
+        }
+        self.cursor_up(height.saturating_sub(1) as u32, out)
+    }
+
+    /// Returns the region of the image selected by its texture
+    /// coordinates, scaled to `size` pixels or left at its natural
+    /// size if `size` is None
+    #[cfg(feature = "use_image")]
+    fn image_region(
+        &mut self,
+        image: &Image,
+        size: Option<(u32, u32)>,
+    ) -> Result<image::RgbaImage> {
+        let hash = image.image.hash();
+        let pixels = match self.decoded.take() {
+            Some((decoded_hash, pixels)) if decoded_hash == hash => pixels,
+            _ => graphics::decode(image)?,
+        };
+        let (width, height) = size.unwrap_or_else(|| {
+            let (_, _, width, height) =
+                graphics::source_rect(image, pixels.width(), pixels.height());
+            (width, height)
+        });
+        let region = graphics::region(image, &pixels, width, height);
+        self.decoded = Some((hash, pixels));
+        Ok(region)
+    }
+
+    /// Returns the size of the image in pixels, based on the size
+    /// of the cells reported by the tty
+    #[cfg(feature = "use_image")]
+    fn image_size_in_pixels<W: RenderTty + Write>(
+        image: &Image,
+        out: &mut W,
+    ) -> Result<(u32, u32)> {
+        let (cell_width, cell_height) = out.get_cell_size_in_pixels()?.unwrap_or(DEFAULT_CELL_SIZE);
+        Ok((
+            (image.width * cell_width) as u32,
+            (image.height * cell_height) as u32,
+        ))
+    }
+
+    /// Renders the image using the kitty graphics protocol.
+    /// The image data is transmitted once and then displayed using
+    /// a placement id that is derived from the region of the image,
+    /// so that redrawing the same region replaces the old placement.
+    #[cfg(feature = "use_image")]
+    fn render_kitty_image<W: RenderTty + Write>(
+        &mut self,
+        image: &Image,
+        out: &mut W,
+    ) -> Result<()> {
+        let hash = image.image.hash();
+        let (image_id, width, height) = match self.kitty_images.get(&hash) {
+            Some(&entry) => entry,
+            None => {
+                let (data, width, height) = graphics::png_data(image)?;
+                let mut image_id = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]).max(1);
+                while self
+                    .kitty_images
+                    .iter()
+                    .any(|(_, &(id, _, _))| id == image_id)
+                {
+                    image_id = image_id.wrapping_add(1).max(1);
+                }
+                Self::transmit_kitty_image(image_id, &data, out)?;
+                if let Some((_, (evicted_id, _, _))) =
+                    self.kitty_images.push(hash, (image_id, width, height))
+                {
+                    self.delete_kitty_image(evicted_id, out)?;
+                }
+                (image_id, width, height)
+            }
+        };
+
+        let (x, y, w, h) = graphics::source_rect(image, width, height);
+        let key = (
+            image_id,
+            x,
+            y,
+            w,
+            h,
+            image.width as u32,
+            image.height as u32,
+        );
+        let placement_id = match self.kitty_placements.get(&key) {
+            Some(&id) => id,
+            None => {
+                let id = self.next_kitty_placement_id;
+                self.next_kitty_placement_id = id.wrapping_add(1).max(1);
+                if let Some((evicted, evicted_id)) = self.kitty_placements.push(key, id) {
+                    let delete = KittyImage::Delete {
+                        what: KittyImageDelete::ByImageId {
+                            image_id: evicted.0,
+                            placement_id: Some(evicted_id),
+                            delete: false,
+                        },
+                        verbosity: KittyImageVerbosity::Quiet,
+                    };
+                    write!(out, "{}\x1b\\", delete)?;
+                }
+                id
+            }
+        };
+
+        self.blank_cells(image.width, image.height, out)?;
+        self.cursor_left(image.width as u32, out)?;
+        let display = KittyImage::Display {
+            image_id: Some(image_id),
+            image_number: None,
+            placement: KittyImagePlacement {
+                x: Some(x),
+                y: Some(y),
+                w: Some(w),
+                h: Some(h),
+                x_offset: None,
+                y_offset: None,
+                columns: Some(image.width as u32),
+                rows: Some(image.height as u32),
+                do_not_move_cursor: true,
+                placement_id: Some(placement_id),
+                z_index: None,
+            },
+            verbosity: KittyImageVerbosity::Quiet,
+        };
+        write!(out, "{}\x1b\\", display)?;
+        self.cursor_right(image.width as u32, out)?;
+        self.kitty_placed = true;
+        Ok(())
+    }
+
+    /// Deletes an image that was evicted from the cache, along with
+    /// its placements, and frees its data in the terminal
+    #[cfg(feature = "use_image")]
+    fn delete_kitty_image<W: RenderTty + Write>(
+        &mut self,
+        image_id: u32,
+        out: &mut W,
+    ) -> Result<()> {
+        let placements: Vec<KittyPlacementKey> = self
+            .kitty_placements
+            .iter()
+            .filter(|(key, _)| key.0 == image_id)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in placements {
+            self.kitty_placements.pop(&key);
+        }
+        let delete = KittyImage::Delete {
+            what: KittyImageDelete::ByImageId {
+                image_id,
+                placement_id: None,
+                delete: true,
+            },
+            verbosity: KittyImageVerbosity::Quiet,
+        };
+        write!(out, "{}\x1b\\", delete)?;
+        Ok(())
+    }
+
+    /// Transmits PNG data using the kitty graphics protocol, splitting
+    /// the base64 encoded data into chunks as the protocol requires
+    #[cfg(feature = "use_image")]
+    fn transmit_kitty_image<W: RenderTty + Write>(
+        image_id: u32,
+        data: &[u8],
+        out: &mut W,
+    ) -> Result<()> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+        let mut chunks = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE).peekable();
+        let first = chunks.next().unwrap_or_default();
+        let transmit = KittyImage::TransmitData {
+            transmit: KittyImageTransmit {
+                format: Some(KittyImageFormat::Png),
+                data: KittyImageData::Direct(String::from_utf8_lossy(first).into_owned()),
+                width: None,
+                height: None,
+                image_id: Some(image_id),
+                image_number: None,
+                compression: KittyImageCompression::None,
+                more_data_follows: chunks.peek().is_some(),
+            },
+            verbosity: KittyImageVerbosity::Quiet,
+        };
+        write!(out, "{}\x1b\\", transmit)?;
+
+        // Subsequent chunks carry only the m key
+        while let Some(chunk) = chunks.next() {
+            let more = if chunks.peek().is_some() { 1 } else { 0 };
+            write!(out, "\x1b_Gm={};", more)?;
+            out.write_all(chunk)?;
+            write!(out, "\x1b\\")?;
+        }
+        Ok(())
+    }
+
+    /// Renders the image using the iTerm2 inline image protocol
+    #[cfg(feature = "use_image")]
+    fn render_iterm2_image<W: RenderTty + Write>(
+        &mut self,
+        image: &Image,
+        out: &mut W,
+    ) -> Result<()> {
+        let data = if graphics::is_whole_image(image) {
+            // The whole image is requested, so we can send the
+            // original image bytes over
+            match &*image.image.data() {
+                ImageDataType::EncodedFile(data) => Some(data.to_vec()),
+                ImageDataType::EncodedLease(lease) => Some(lease.get_data()?),
+                ImageDataType::AnimRgba8 { .. } | ImageDataType::Rgba8 { .. } => None,
+            }
+        } else {
+            None
+        };
+        let data = match data {
+            Some(data) => data,
+            None => graphics::encode_png(&self.image_region(image, None)?)?.0,
+        };
+
+        let file = ITermFileData {
+            name: None,
+            size: Some(data.len()),
+            width: ITermDimension::Cells(image.width as i64),
+            height: ITermDimension::Cells(image.height as i64),
+            preserve_aspect_ratio: true,
+            inline: true,
+            do_not_move_cursor: false,
+            data,
+        };
+
+        let osc = OperatingSystemCommand::ITermProprietary(ITermProprietary::File(Box::new(file)));
+
+        write!(out, "{}", osc)?;
+        Ok(())
+    }
+
+    /// Renders the image as a sixel image scaled to fill its cells
+    #[cfg(feature = "use_image")]
+    fn render_sixel_image<W: RenderTty + Write>(
+        &mut self,
+        image: &Image,
+        out: &mut W,
+    ) -> Result<()> {
+        let size = Self::image_size_in_pixels(image, out)?;
+        let sixel = graphics::encode_sixel(&self.image_region(image, Some(size))?);
+
+        self.blank_cells(image.width, image.height, out)?;
+        self.cursor_left(image.width as u32, out)?;
+        write!(
+            out,
+            "{}{}\x1b\\{}",
+            Esc::Code(EscCode::DecSaveCursorPosition),
+            sixel,
+            Esc::Code(EscCode::DecRestoreCursorPosition)
+        )?;
+        self.cursor_right(image.width as u32, out)
+    }
+
+    /// Renders the image using half block characters, which gives two
+    /// pixels per cell
+    #[cfg(feature = "use_image")]
+    fn render_half_block_image<W: RenderTty + Write>(
+        &mut self,
+        image: &Image,
+        out: &mut W,
+    ) -> Result<()> {
+        let size = (image.width as u32, image.height as u32 * 2);
+        let rows = graphics::half_blocks(&self.image_region(image, Some(size))?);
+
+        let saved_attr = self
+            .pending_attr
+            .take()
+            .unwrap_or_else(|| self.current_attr.clone());
+        for (y, row) in rows.iter().enumerate() {
+            for &(c, fg, bg) in row {
+                self.attr_apply(|attr| {
+                    attr.set_foreground(fg);
+                    attr.set_background(bg);
+                    attr.set_reverse(false);
+                });
+                self.flush_pending_attr(out)?;
+                write!(out, "{}", c)?;
+            }
+
+            if y != rows.len() - 1 {
+                writeln!(out)?;
+                self.cursor_left(image.width as u32, out)?;
+            }
+        }
+        self.cursor_up(rows.len().saturating_sub(1) as u32, out)?;
+        self.pending_attr = Some(saved_attr);
+        Ok(())
+    }
+
+    /// Renders `changes` to `out`.
+    /// If the terminal supports synchronized output, the changes are
+    /// wrapped in DEC private mode 2026 so that the terminal displays
+    /// them in one go rather than showing a partially drawn frame.
     pub fn render_to<W: RenderTty + Write>(
         &mut self,
         changes: &[Change],
         out: &mut W,
+    ) -> Result<()> {
+        let mut out = CountingWriter { out, bytes: 0 };
+        let sync = self.caps.synchronized_output() && !changes.is_empty();
+        let sync_mode = DecPrivateMode::Code(DecPrivateModeCode::SynchronizedOutput);
+
+        if sync {
+            write!(
+                out,
+                "{}",
+                CSI::Mode(Mode::SetDecPrivateMode(sync_mode.clone()))
+            )?;
+        }
+        let result = self.render_changes(changes, &mut out);
+        if sync {
+            // End the update even if rendering failed part way through,
+            // otherwise the terminal would stop updating its display
+            // until its synchronization timeout expires
+            write!(out, "{}", CSI::Mode(Mode::ResetDecPrivateMode(sync_mode)))?;
+        }
+
+        if !changes.is_empty() {
+            self.stats.frames += 1;
+        }
+        self.stats.bytes += out.bytes;
+        result?;
+        out.flush()?;
+        Ok(())
+    }
+
+    #[allow(clippy::cyclomatic_complexity, clippy::cognitive_complexity)]
+    fn render_changes<W: RenderTty + Write>(
+        &mut self,
+        changes: &[Change],
+        out: &mut W,
     ) -> Result<()> {
         macro_rules! record {
             ($accesor:ident, $value:expr) => {
@@ -350,6 +806,15 @@ impl TerminfoRenderer {
         for change in changes {
             match change {
                 Change::ClearScreen(color) => {
+                    #[cfg(feature = "use_image")]
+                    if self.kitty_placed {
+                        let delete = KittyImage::Delete {
+                            what: KittyImageDelete::All { delete: false },
+                            verbosity: KittyImageVerbosity::Quiet,
+                        };
+                        write!(out, "{}\x1b\\", delete)?;
+                        self.kitty_placed = false;
+                    }
                     // ClearScreen implicitly resets all to default
                     let defaults = CellAttributes::default().set_background(*color).clone();
                     if self.current_attr != defaults {
@@ -470,6 +935,24 @@ impl TerminfoRenderer {
                 }
                 Change::Text(text) => {
                     self.flush_pending_attr(out)?;
+                    #[cfg(feature = "use_image")]
+                    if let Some(mut images) = self.current_attr.images() {
+                        // Cells that display part of an image are
+                        // rendered as that part of the topmost image
+                        if let Some(cell) = images.pop() {
+                            let image = Image {
+                                width: 1,
+                                height: 1,
+                                top_left: cell.top_left(),
+                                bottom_right: cell.bottom_right(),
+                                image: cell.image_data().clone(),
+                            };
+                            for _ in text.chars() {
+                                self.render_image(&image, out)?;
+                            }
+                            continue;
+                        }
+                    }
                     out.by_ref().write_all(text.as_bytes())?;
                 }
 
@@ -585,57 +1068,7 @@ impl TerminfoRenderer {
                 },
                 #[cfg(feature = "use_image")]
                 Change::Image(image) => {
-                    if self.caps.iterm2_image() {
-                        let data = if image.top_left == TextureCoordinate::new_f32(0.0, 0.0)
-                            && image.bottom_right == TextureCoordinate::new_f32(1.0, 1.0)
-                        {
-                            // The whole image is requested, so we can send the
-                            // original image bytes over
-                            match &*image.image.data() {
-                                ImageDataType::EncodedFile(data) => data.to_vec(),
-                                ImageDataType::EncodedLease(lease) => lease.get_data()?,
-                                ImageDataType::AnimRgba8 { .. } | ImageDataType::Rgba8 { .. } => {
-                                    unimplemented!()
-                                }
-                            }
-                        } else {
-                            // TODO: slice out the requested region of the image,
-                            // and encode as a PNG.
-                            unimplemented!();
-                        };
-
-                        let file = ITermFileData {
-                            name: None,
-                            size: Some(data.len()),
-                            width: ITermDimension::Cells(image.width as i64),
-                            height: ITermDimension::Cells(image.height as i64),
-                            preserve_aspect_ratio: true,
-                            inline: true,
-                            do_not_move_cursor: false,
-                            data,
-                        };
-
-                        let osc = OperatingSystemCommand::ITermProprietary(ITermProprietary::File(
-                            Box::new(file),
-                        ));
-
-                        write!(out, "{}", osc)?;
-
-                    // TODO: } else if self.caps.sixel() {
-                    } else {
-                        // Blank out the cells and move the cursor to the right spot
-                        for y in 0..image.height {
-                            for _ in 0..image.width {
-                                write!(out, " ")?;
-                            }
-
-                            if y != image.height - 1 {
-                                writeln!(out)?;
-                                self.cursor_left(image.width as u32, out)?;
-                            }
-                        }
-                        self.cursor_up(image.height as u32, out)?;
-                    }
+                    self.render_image(image, out)?;
                 }
                 Change::ScrollRegionUp {
                     first_row,
@@ -664,6 +1097,20 @@ impl TerminfoRenderer {
                                     }
                                 }
                             }
+                        } else {
+                            write!(
+                                out,
+                                "{}",
+                                CSI::Cursor(Cursor::SetTopAndBottomMargins {
+                                    top: OneBased::from_zero_based(*first_row as u32),
+                                    bottom: OneBased::from_zero_based(
+                                        (*first_row + *region_size - 1) as u32
+                                    ),
+                                })
+                            )?;
+                            if *scroll_count > 0 {
+                                write!(out, "{}", CSI::Edit(Edit::ScrollUp(*scroll_count as u32)))?;
+                            }
                         }
                     }
                 }
@@ -694,6 +1141,24 @@ impl TerminfoRenderer {
                                     }
                                 }
                             }
+                        } else {
+                            write!(
+                                out,
+                                "{}",
+                                CSI::Cursor(Cursor::SetTopAndBottomMargins {
+                                    top: OneBased::from_zero_based(*first_row as u32),
+                                    bottom: OneBased::from_zero_based(
+                                        (*first_row + *region_size - 1) as u32
+                                    ),
+                                })
+                            )?;
+                            if *scroll_count > 0 {
+                                write!(
+                                    out,
+                                    "{}",
+                                    CSI::Edit(Edit::ScrollDown(*scroll_count as u32))
+                                )?;
+                            }
                         }
                     }
                 }
@@ -720,11 +1185,57 @@ impl TerminfoRenderer {
         }
 
         self.flush_pending_attr(out)?;
-        out.flush()?;
         Ok(())
     }
 }
 
+/// Writes the batched SGR changes as a single escape sequence
+fn flush_sgr<W: Write>(batch: &mut Vec<Sgr>, out: &mut W) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let params: Vec<String> = batch
+        .drain(..)
+        .map(|sgr| {
+            // Sgr renders as its parameters followed by the final `m`
+            let mut encoded = sgr.to_string();
+            encoded.pop();
+            encoded
+        })
+        .collect();
+    write!(out, "\x1b[{}m", params.join(";"))?;
+    Ok(())
+}
+
+/// Passes writes through to the underlying output while counting
+/// the bytes, for the benefit of `RenderStats`
+struct CountingWriter<'a, W> {
+    out: &'a mut W,
+    bytes: usize,
+}
+
+impl<'a, W: Write> Write for CountingWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.out.write(buf)?;
+        self.bytes += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<'a, W: RenderTty + Write> RenderTty for CountingWriter<'a, W> {
+    fn get_size_in_cells(&mut self) -> Result<(usize, usize)> {
+        self.out.get_size_in_cells()
+    }
+
+    fn get_cell_size_in_pixels(&mut self) -> Result<Option<(usize, usize)>> {
+        self.out.get_cell_size_in_pixels()
+    }
+}
+
 #[cfg(all(test, unix))]
 mod test {
     use super::*;
@@ -1294,4 +1805,230 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn synchronized_output() {
+        let caps = Capabilities::new_with_hints(
+            ProbeHints::default()
+                .color_level(Some(ColorLevel::TrueColor))
+                .synchronized_output(Some(true)),
+        )
+        .unwrap();
+        let mut out = FakeTerm::new(caps);
+        out.render(&[]).unwrap();
+        assert!(out.write.buf.is_empty());
+
+        out.render(&[Change::Text("A".into())]).unwrap();
+        assert_eq!(
+            String::from_utf8(out.write.buf.clone()).unwrap(),
+            "\x1b[?2026hA\x1b[?2026l"
+        );
+    }
+
+    #[test]
+    fn coalesced_sgr_and_stats() {
+        let mut out = FakeTerm::new(no_terminfo_all_enabled());
+        out.render(&[
+            Change::Attribute(AttributeChange::Intensity(Intensity::Bold)),
+            Change::Attribute(AttributeChange::Italic(true)),
+            Change::Attribute(AttributeChange::Foreground(AnsiColor::Maroon.into())),
+            Change::Text("A".into()),
+            // Setting the same attributes again produces no output
+            Change::Attribute(AttributeChange::Italic(true)),
+            Change::Text("B".into()),
+        ])
+        .unwrap();
+
+        let output = String::from_utf8(out.write.buf.clone()).unwrap();
+        assert_eq!(output, "\x1b[0;1;3;31mAB");
+        assert_eq!(
+            out.renderer.stats(),
+            RenderStats {
+                frames: 1,
+                bytes: output.len(),
+            }
+        );
+
+        out.renderer.reset_stats();
+        assert_eq!(out.renderer.stats(), RenderStats::default());
+    }
+
+    #[test]
+    fn scroll_region_no_terminfo() {
+        let mut out = FakeTerm::new(no_terminfo_all_enabled());
+        out.render(&[Change::ScrollRegionUp {
+            first_row: 0,
+            region_size: 24,
+            scroll_count: 2,
+        }])
+        .unwrap();
+
+        let result = out.parse();
+        assert_eq!(
+            result,
+            vec![
+                Action::CSI(CSI::Cursor(Cursor::SetTopAndBottomMargins {
+                    top: OneBased::new(1),
+                    bottom: OneBased::new(24),
+                })),
+                Action::CSI(CSI::Edit(Edit::ScrollUp(2))),
+            ]
+        );
+    }
+
+    /// Returns true color capabilities with only the image protocols
+    /// that are enabled by `hints`, so that the image tests don't depend
+    /// on the terminal that runs them
+    #[cfg(feature = "use_image")]
+    fn image_caps(hints: ProbeHints) -> Capabilities {
+        Capabilities::new_with_hints(
+            hints
+                .color_level(Some(ColorLevel::TrueColor))
+                .iterm2_image(Some(false)),
+        )
+        .unwrap()
+    }
+
+    /// Returns an image with a red pixel above a blue one,
+    /// to be displayed in the specified number of cells
+    #[cfg(feature = "use_image")]
+    fn test_image(width: usize, height: usize) -> Image {
+        test_image_with_green(width, height, 0)
+    }
+
+    /// Like `test_image`, but the green channel of the pixels is set
+    /// to `green` so that the image data hashes differently
+    #[cfg(feature = "use_image")]
+    fn test_image_with_green(width: usize, height: usize, green: u8) -> Image {
+        use crate::image::{ImageData, ImageDataType, TextureCoordinate};
+        let pixels = vec![255, green, 0, 255, 0, green, 255, 255];
+        Image {
+            width,
+            height,
+            top_left: TextureCoordinate::new_f32(0., 0.),
+            bottom_right: TextureCoordinate::new_f32(1., 1.),
+            image: std::sync::Arc::new(ImageData::with_data(ImageDataType::new_single_frame(
+                1, 2, pixels,
+            ))),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "use_image")]
+    fn kitty_image() {
+        let mut out = FakeTerm::new(image_caps(ProbeHints::default().kitty_graphics(Some(true))));
+        let image = test_image(2, 2);
+        out.render(&[Change::Image(image.clone())]).unwrap();
+        out.render(&[Change::Image(image)]).unwrap();
+
+        let kitty: Vec<KittyImage> = out
+            .parse()
+            .into_iter()
+            .filter_map(|action| match action {
+                Action::KittyImage(image) => Some(*image),
+                _ => None,
+            })
+            .collect();
+        // The data is transmitted once, and the same placement is
+        // used to display it both times
+        assert_eq!(kitty.len(), 3);
+        assert!(matches!(kitty[0], KittyImage::TransmitData { .. }));
+        for display in &kitty[1..] {
+            match display {
+                KittyImage::Display { placement, .. } => {
+                    assert_eq!(placement.placement_id, Some(1));
+                    assert_eq!(placement.columns, Some(2));
+                    assert_eq!(placement.rows, Some(2));
+                    assert!(placement.do_not_move_cursor);
+                }
+                _ => panic!("expected a display action, got {:?}", display),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "use_image")]
+    fn kitty_image_eviction() {
+        let mut out = FakeTerm::new(image_caps(ProbeHints::default().kitty_graphics(Some(true))));
+        for green in 0..=MAX_KITTY_IMAGES {
+            out.render(&[Change::Image(test_image_with_green(1, 1, green as u8))])
+                .unwrap();
+        }
+
+        let kitty: Vec<KittyImage> = out
+            .parse()
+            .into_iter()
+            .filter_map(|action| match action {
+                Action::KittyImage(image) => Some(*image),
+                _ => None,
+            })
+            .collect();
+        let first_id = match &kitty[0] {
+            KittyImage::TransmitData { transmit, .. } => transmit.image_id,
+            other => panic!("expected a transmit action, got {:?}", other),
+        };
+        // Only the least recently used image is deleted, and its data
+        // is freed in the terminal
+        let deletes: Vec<&KittyImage> = kitty
+            .iter()
+            .filter(|image| matches!(image, KittyImage::Delete { .. }))
+            .collect();
+        assert_eq!(deletes.len(), 1);
+        match deletes[0] {
+            KittyImage::Delete {
+                what:
+                    KittyImageDelete::ByImageId {
+                        image_id,
+                        placement_id: None,
+                        delete: true,
+                    },
+                ..
+            } => assert_eq!(Some(*image_id), first_id),
+            other => panic!("expected an image delete, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "use_image")]
+    fn sixel_image() {
+        let mut out = FakeTerm::new(image_caps(ProbeHints::default().sixel(Some(true))));
+        out.render(&[Change::Image(test_image(1, 1))]).unwrap();
+
+        let result = out.parse();
+        let sixel = result
+            .iter()
+            .find_map(|action| match action {
+                Action::Sixel(sixel) => Some(sixel),
+                _ => None,
+            })
+            .expect("a sixel image");
+        assert_eq!(sixel.pixel_width, Some(10));
+        assert_eq!(sixel.pixel_height, Some(20));
+        assert!(result.contains(&Action::Esc(Esc::Code(EscCode::DecSaveCursorPosition))));
+        assert!(result.contains(&Action::Esc(Esc::Code(EscCode::DecRestoreCursorPosition))));
+    }
+
+    #[test]
+    #[cfg(feature = "use_image")]
+    fn half_block_image() {
+        let mut out = FakeTerm::new(image_caps(ProbeHints::default()));
+        out.render(&[Change::Image(test_image(1, 1))]).unwrap();
+
+        let result = out.parse();
+        assert_eq!(
+            result,
+            vec![
+                Action::CSI(CSI::Sgr(Sgr::Foreground(ColorSpec::TrueColor(
+                    (255, 0, 0).into()
+                ),))),
+                Action::CSI(CSI::Sgr(Sgr::Background(ColorSpec::TrueColor(
+                    (0, 0, 255).into()
+                ),))),
+                Action::Print('\u{2580}'),
+                // The attributes that were in effect are restored
+                Action::CSI(CSI::Sgr(Sgr::Foreground(ColorSpec::Default))),
+                Action::CSI(CSI::Sgr(Sgr::Background(ColorSpec::Default))),
+            ]
+        );
+    }
 }
@@ -0,0 +1,95 @@
+//! This module holds the column width and alignment logic used to
+//! present tabulated data.  It is shared by the `tabout` crate and
+//! the table widget.
+
+/// Describes the alignment of a column
+#[derive(Debug, Clone, Copy)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Describes a column
+#[derive(Debug)]
+pub struct Column {
+    /// The name of the column; this is the column header text
+    pub name: String,
+    /// How the column should be aligned
+    pub alignment: Alignment,
+}
+
+/// Given the column headers and the display width of each cell
+/// in each row, compute the width of each column.
+/// If a given row has more cells than are defined in the
+/// columns slice, then the returned vector is extended to
+/// cover those additional columns.
+pub fn compute_column_widths<R, C>(columns: &[Column], rows: R) -> Vec<usize>
+where
+    R: IntoIterator<Item = C>,
+    C: IntoIterator<Item = usize>,
+{
+    let mut col_widths: Vec<usize> = columns
+        .iter()
+        .map(|c| crate::cell::unicode_column_width(&c.name, None))
+        .collect();
+
+    for row in rows {
+        for (idx, col_width) in row.into_iter().enumerate() {
+            if let Some(width) = col_widths.get_mut(idx) {
+                *width = (*width).max(col_width);
+            } else {
+                col_widths.push(col_width);
+            }
+        }
+    }
+
+    col_widths
+}
+
+/// Returns the `(left, right)` padding required to align text that
+/// occupies `text_width` cells within a column `max_width` cells wide.
+pub fn alignment_padding(
+    text_width: usize,
+    max_width: usize,
+    alignment: Alignment,
+) -> (usize, usize) {
+    let available = max_width.saturating_sub(text_width);
+    match alignment {
+        Alignment::Left => (0, available),
+        Alignment::Center => {
+            let left_pad = available / 2;
+            // for odd-length columns, take care to use the remaining
+            // length rather than just assuming that the right_pad
+            // will have the same value as the left_pad
+            (left_pad, available - left_pad)
+        }
+        Alignment::Right => (available, 0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn widths_and_padding() {
+        let columns = vec![
+            Column {
+                name: "name".to_string(),
+                alignment: Alignment::Left,
+            },
+            Column {
+                name: "n".to_string(),
+                alignment: Alignment::Right,
+            },
+        ];
+        let widths = compute_column_widths(&columns, vec![vec![2, 3], vec![6, 1, 4]]);
+        assert_eq!(widths, vec![6, 3, 4]);
+
+        assert_eq!(alignment_padding(2, 6, Alignment::Left), (0, 4));
+        assert_eq!(alignment_padding(2, 7, Alignment::Center), (2, 3));
+        assert_eq!(alignment_padding(2, 6, Alignment::Right), (4, 0));
+        assert_eq!(alignment_padding(8, 6, Alignment::Right), (0, 0));
+    }
+}
@@ -1,8 +1,9 @@
 //! A Terminal buffered with a Surface
 
-use crate::surface::{SequenceNo, Surface};
+use crate::surface::{Change, Position, SequenceNo, Surface};
 use crate::terminal::Terminal;
 use crate::Result;
+use std::borrow::Cow;
 use std::ops::{Deref, DerefMut};
 
 /// `BufferedTerminal` is a convenience wrapper around both
@@ -14,10 +15,36 @@ use std::ops::{Deref, DerefMut};
 /// The `flush` method is used to compute the optimized set
 /// of changes and actually render them to the underlying
 /// `Terminal`.  No output will be visible until it is flushed!
+///
+/// If scroll detection is enabled with `set_scroll_detection`, then
+/// when the surface has changed so much that a full repaint would be
+/// needed, `flush` checks whether the content has scrolled and, if so,
+/// scrolls what is already displayed and then updates only the lines
+/// that differ.
+///
+/// By default the surface covers the whole screen.  A terminal
+/// created with `new_inline` instead renders into a region of
+/// lines starting at the cursor position, leaving the rest of
+/// the screen and its scrollback intact.
 pub struct BufferedTerminal<T: Terminal> {
     terminal: T,
     surface: Surface,
     seqno: SequenceNo,
+    /// The content that is currently displayed, used to detect
+    /// scrolling.  Only present when scroll detection is enabled;
+    /// an inline terminal tracks this in its `InlineState`.
+    shadow: Option<Surface>,
+    inline: Option<InlineState>,
+}
+
+/// Tracks the region used by an inline `BufferedTerminal`
+struct InlineState {
+    /// The row of the terminal cursor, relative to the top of the region
+    cursor_row: usize,
+    /// The content that is currently displayed in the region
+    shadow: Surface,
+    /// Set once `finish_inline` has moved the cursor below the region
+    finished: bool,
 }
 
 impl<T: Terminal> BufferedTerminal<T> {
@@ -30,9 +57,174 @@ impl<T: Terminal> BufferedTerminal<T> {
             terminal,
             surface,
             seqno: 0,
+            shadow: None,
+            inline: None,
         })
     }
 
+    /// Create a new `BufferedTerminal` that renders inline, into a
+    /// region of `height` lines that starts at the line containing
+    /// the cursor, rather than taking over the whole screen.
+    /// This is useful for progress displays and pickers that should
+    /// appear in the flow of a shell session.
+    ///
+    /// The region is reserved by emitting newlines, so the terminal
+    /// scrolls if there are fewer than `height` lines below the cursor.
+    /// All subsequent cursor movement is relative to the region, so
+    /// the remainder of the screen is left untouched.
+    /// Use `finish_inline` to move the cursor below the region when
+    /// you're done with it; the rendered content remains on the screen
+    /// and in the scrollback.
+    pub fn new_inline(mut terminal: T, height: usize) -> Result<Self> {
+        let size = terminal.get_screen_size()?;
+        let height = height.clamp(1, size.rows.max(1));
+        let mut buffered = Self {
+            terminal,
+            surface: Surface::new(size.cols, height),
+            seqno: 0,
+            shadow: None,
+            inline: Some(InlineState {
+                cursor_row: 0,
+                shadow: Surface::new(size.cols, height),
+                finished: false,
+            }),
+        };
+        buffered.terminal.render(&[
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Relative(0),
+            },
+            Change::ClearToEndOfScreen(Default::default()),
+        ])?;
+        buffered.reserve_lines(0, height)?;
+        Ok(buffered)
+    }
+
+    /// Enables or disables scroll detection.  When enabled, `flush`
+    /// keeps a copy of what is displayed so that it can scroll the
+    /// existing content rather than repaint it in full.  The copy is as
+    /// large as the surface and is updated by every flush, so this is
+    /// disabled by default.  Enabling it causes the next flush to
+    /// repaint in full, so that the copy is known to be accurate.
+    /// Has no effect if the terminal is inline, as it always keeps
+    /// such a copy of its region.
+    pub fn set_scroll_detection(&mut self, enable: bool) {
+        if self.inline.is_some() || enable == self.shadow.is_some() {
+            return;
+        }
+        self.shadow = if enable {
+            let (width, height) = self.surface.dimensions();
+            Some(Surface::new(width, height))
+        } else {
+            None
+        };
+        self.seqno = 0;
+    }
+
+    /// Returns true if this terminal was created by `new_inline`
+    pub fn is_inline(&self) -> bool {
+        self.inline.is_some()
+    }
+
+    /// Changes the number of lines in the region of an inline terminal.
+    /// Growing the region emits newlines below it, scrolling the terminal
+    /// if required, while shrinking it clears the lines that are no longer
+    /// part of the region.  The surface is resized to match; as with
+    /// `Surface::resize`, its content is retained where possible.
+    /// Has no effect if the terminal isn't inline.
+    pub fn set_inline_height(&mut self, height: usize) -> Result<()> {
+        let rows = self.terminal.get_screen_size()?.rows;
+        let (width, old_height) = self.surface.dimensions();
+        let height = height.clamp(1, rows.max(1));
+        let inline = match self.inline.as_mut() {
+            Some(inline) if height != old_height => inline,
+            _ => return Ok(()),
+        };
+
+        if height > old_height {
+            self.reserve_lines(old_height, height)?;
+        } else {
+            let changes = [
+                inline.move_to_row(height),
+                Change::ClearToEndOfScreen(Default::default()),
+            ];
+            self.terminal.render(&changes)?;
+        }
+
+        let inline = self.inline.as_mut().expect("inline was checked above");
+        self.surface.resize(width, height);
+        inline.shadow.resize(width, height);
+        // The newly revealed lines are blank; make sure that the shadow
+        // agrees, as `Surface::resize` may retain content there
+        for row in old_height.min(height)..height {
+            inline.shadow.add_changes(vec![
+                Change::CursorPosition {
+                    x: Position::Absolute(0),
+                    y: Position::Absolute(row),
+                },
+                Change::ClearToEndOfLine(Default::default()),
+            ]);
+        }
+        Ok(())
+    }
+
+    /// Moves the cursor to the start of the line below the region of an
+    /// inline terminal, so that subsequent output follows the content of
+    /// the region.  The region is left on the screen.
+    /// This is performed automatically when the `BufferedTerminal` is
+    /// dropped, but calling it explicitly allows errors to be reported.
+    /// Has no effect if the terminal isn't inline.
+    pub fn finish_inline(&mut self) -> Result<()> {
+        let height = self.surface.dimensions().1;
+        if let Some(inline) = self.inline.as_mut() {
+            if !inline.finished {
+                inline.finished = true;
+                let changes = [
+                    inline.move_to_row(height - 1),
+                    Change::AllAttributes(Default::default()),
+                    Change::CursorVisibility(crate::surface::CursorVisibility::Visible),
+                    Change::Text("\r\n".to_string()),
+                ];
+                self.terminal.render(&changes)?;
+                self.terminal.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits newlines to extend the inline region from `from` lines
+    /// to `to` lines, then returns the cursor to the top of the region
+    fn reserve_lines(&mut self, from: usize, to: usize) -> Result<()> {
+        let inline = self.inline.as_mut().expect("only called when inline");
+        let mut changes = vec![inline.move_to_row(from.saturating_sub(1))];
+        changes.push(Change::Text("\r\n".repeat(to - from.max(1))));
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Relative(-(to as isize - 1)),
+        });
+        inline.cursor_row = 0;
+        self.terminal.render(&changes)
+    }
+
+    /// The inline counterpart to `flush`: computes the difference
+    /// between the surface and what is displayed in the region, and
+    /// renders it using cursor movement relative to the region.
+    fn flush_inline(&mut self) -> Result<()> {
+        let inline = self.inline.as_mut().expect("only called when inline");
+        let mut changes = inline.shadow.diff_screens(&self.surface);
+        inline.shadow.apply_changes_unlogged(&changes);
+        push_cursor_state(&self.surface, &mut changes);
+
+        let changes: Vec<Change> = changes
+            .into_iter()
+            .map(|change| inline.translate(change))
+            .collect();
+        self.terminal.render(&changes)?;
+        self.seqno = self.surface.current_seqno();
+        self.surface.flush_changes_older_than(self.seqno);
+        Ok(())
+    }
+
     /// Get a mutable reference to the underlying terminal instance
     pub fn terminal(&mut self) -> &mut T {
         &mut self.terminal
@@ -48,12 +240,53 @@ impl<T: Terminal> BufferedTerminal<T> {
     /// is common for unix applications) to request a repaint.
     /// You can use the `repaint` function for that situation.
     pub fn flush(&mut self) -> Result<()> {
+        if self.inline.is_some() {
+            return self.flush_inline();
+        }
         {
-            let (seq, changes) = self.surface.get_changes(self.seqno);
+            let previous_seqno = self.seqno;
+            let (seq, mut changes) = self.surface.get_changes(previous_seqno);
             // If we encounter an error during rendering, we want to
             // reset the sequence number so that a subsequent paint
             // renders all.
             self.seqno = 0;
+
+            if let Some(shadow) = self.shadow.as_mut() {
+                // The screen is about to be repainted in full, either because
+                // get_changes decided that is cheaper than replaying the changes,
+                // or because the application cleared and redrew it.  See if we
+                // can scroll instead.  A previous_seqno of 0 means that the
+                // display can't be trusted, so we must repaint in that case.
+                let full_repaint = match &changes {
+                    Cow::Owned(_) => true,
+                    Cow::Borrowed(changes) => changes
+                        .iter()
+                        .any(|change| matches!(change, Change::ClearScreen(_))),
+                };
+                let scroll = if previous_seqno != 0
+                    && full_repaint
+                    && changes.iter().all(captured_by_diff)
+                {
+                    shadow.detect_scroll(&self.surface)
+                } else {
+                    None
+                };
+                match scroll {
+                    Some(scroll) => {
+                        shadow.add_change(scroll.clone());
+                        let mut scrolled = shadow.diff_screens(&self.surface);
+                        if shadow.title() != self.surface.title() {
+                            scrolled.push(Change::Title(self.surface.title().to_owned()));
+                        }
+                        push_cursor_state(&self.surface, &mut scrolled);
+                        shadow.apply_changes_unlogged(&scrolled);
+                        scrolled.insert(0, scroll);
+                        changes = Cow::Owned(scrolled);
+                    }
+                    None => shadow.apply_changes_unlogged(&changes),
+                }
+            }
+
             self.terminal.render(&changes)?;
             //self.terminal.flush()?;
             self.seqno = seq;
@@ -65,6 +298,15 @@ impl<T: Terminal> BufferedTerminal<T> {
     /// Clears the screen and re-draws the surface contents onto
     /// the Terminal.
     pub fn repaint(&mut self) -> Result<()> {
+        if let Some(inline) = self.inline.as_mut() {
+            let (width, height) = self.surface.dimensions();
+            let changes = [
+                inline.move_to_row(0),
+                Change::ClearToEndOfScreen(Default::default()),
+            ];
+            inline.shadow = Surface::new(width, height);
+            self.terminal.render(&changes)?;
+        }
         self.seqno = 0;
         self.flush()
     }
@@ -93,12 +335,64 @@ impl<T: Terminal> BufferedTerminal<T> {
     /// consume the input records.  Such a thing is possible, but is
     /// better suited for a higher level abstraction than this basic
     /// `BufferedTerminal` interface.
+    ///
+    /// For an inline terminal, the region keeps its height unless the
+    /// screen becomes too short to hold it.  If the width changes, the
+    /// region is cleared and will be repainted in full by the next
+    /// `flush`; this assumes that the terminal reflows wrapped lines
+    /// when it is resized, as most modern terminals do.
     pub fn check_for_resize(&mut self) -> Result<bool> {
         let size = self.terminal.get_screen_size()?;
         let (width, height) = self.surface.dimensions();
 
+        if let Some(inline) = self.inline.as_mut() {
+            let new_height = height.min(size.rows.max(1));
+            if width == size.cols && height == new_height {
+                return Ok(false);
+            }
+
+            // Work out how many rows the region occupies above the
+            // cursor once the terminal has reflowed it to the new width
+            let cols = size.cols.max(1);
+            let lines = inline.shadow.screen_lines();
+            let mut rows_above: usize = lines
+                .iter()
+                .take(inline.cursor_row)
+                .map(|line| {
+                    let used = line
+                        .visible_cells()
+                        .filter(|cell| cell.str() != " ")
+                        .map(|cell| cell.cell_index() + cell.width())
+                        .max()
+                        .unwrap_or(0);
+                    used.max(1).div_ceil(cols)
+                })
+                .sum();
+            rows_above += self.surface.cursor_position().0.min(width) / cols;
+            drop(lines);
+
+            let changes = [
+                Change::CursorPosition {
+                    x: Position::Absolute(0),
+                    y: Position::Relative(-(rows_above as isize)),
+                },
+                Change::ClearToEndOfScreen(Default::default()),
+            ];
+            self.terminal.render(&changes)?;
+            inline.cursor_row = 0;
+            inline.shadow = Surface::new(size.cols, new_height);
+            self.surface.resize(size.cols, new_height);
+            return Ok(true);
+        }
+
         if (width != size.cols) || (height != size.rows) {
             self.surface.resize(size.cols, size.rows);
+            // The terminal may have reflowed its content, so what it
+            // displays is no longer known
+            if self.shadow.is_some() {
+                self.shadow = Some(Surface::new(size.cols, size.rows));
+            }
+            self.seqno = 0;
             Ok(true)
         } else {
             Ok(false)
@@ -106,6 +400,70 @@ impl<T: Terminal> BufferedTerminal<T> {
     }
 }
 
+/// Returns true if the effect of `change` is reproduced by diffing
+/// the screen contents and then restoring the cursor state and title
+fn captured_by_diff(change: &Change) -> bool {
+    !matches!(change, Change::CursorColor(_) | Change::LineAttribute(_))
+}
+
+/// Appends the changes that place the cursor and set its appearance
+/// to match `surface`
+fn push_cursor_state(surface: &Surface, changes: &mut Vec<Change>) {
+    let (x, y) = surface.cursor_position();
+    changes.push(Change::CursorPosition {
+        x: Position::Absolute(x),
+        y: Position::Absolute(y),
+    });
+    changes.push(Change::CursorVisibility(surface.cursor_visibility()));
+    if let Some(shape) = surface.cursor_shape() {
+        changes.push(Change::CursorShape(shape));
+    }
+}
+
+impl InlineState {
+    /// Returns the change that moves the cursor to the start of
+    /// the specified row of the region
+    fn move_to_row(&mut self, row: usize) -> Change {
+        let delta = row as isize - self.cursor_row as isize;
+        self.cursor_row = row;
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Relative(delta),
+        }
+    }
+
+    /// Converts cursor movement relative to the surface into movement
+    /// relative to the current cursor position
+    fn translate(&mut self, change: Change) -> Change {
+        match change {
+            Change::CursorPosition { x, y } => {
+                let height = self.shadow.dimensions().1;
+                let row = match y {
+                    Position::Absolute(row) => row,
+                    Position::Relative(delta) => (self.cursor_row as isize + delta).max(0) as usize,
+                    Position::EndRelative(row) => height.saturating_sub(row + 1),
+                }
+                .min(height - 1);
+                let delta = row as isize - self.cursor_row as isize;
+                self.cursor_row = row;
+                Change::CursorPosition {
+                    x,
+                    y: Position::Relative(delta),
+                }
+            }
+            change => change,
+        }
+    }
+}
+
+impl<T: Terminal> Drop for BufferedTerminal<T> {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish_inline() {
+            log::error!("failed to finish inline terminal: {:#}", err);
+        }
+    }
+}
+
 impl<T: Terminal> Deref for BufferedTerminal<T> {
     type Target = Surface;
 
@@ -119,3 +477,183 @@ impl<T: Terminal> DerefMut for BufferedTerminal<T> {
         &mut self.surface
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bail;
+    use crate::input::InputEvent;
+    use crate::terminal::{ScreenSize, TerminalWaker};
+    use std::time::Duration;
+
+    /// A terminal that applies the rendered changes to a surface,
+    /// so that the effect on the screen can be examined
+    struct SurfaceTerminal {
+        screen: Surface,
+        inline: bool,
+        /// The changes passed to the most recent call to render
+        rendered: Vec<Change>,
+    }
+
+    impl SurfaceTerminal {
+        fn new(screen: Surface, inline: bool) -> Self {
+            Self {
+                screen,
+                inline,
+                rendered: vec![],
+            }
+        }
+    }
+
+    impl Terminal for SurfaceTerminal {
+        fn set_raw_mode(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn set_cooked_mode(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn enter_alternate_screen(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+        fn exit_alternate_screen(&mut self) -> Result<()> {
+            bail!("not implemented");
+        }
+        fn get_screen_size(&mut self) -> Result<ScreenSize> {
+            let (cols, rows) = self.screen.dimensions();
+            Ok(ScreenSize {
+                cols,
+                rows,
+                xpixel: 0,
+                ypixel: 0,
+            })
+        }
+        fn set_screen_size(&mut self, size: ScreenSize) -> Result<()> {
+            self.screen.resize(size.cols, size.rows);
+            Ok(())
+        }
+        fn render(&mut self, changes: &[Change]) -> Result<()> {
+            for change in changes {
+                if let Change::CursorPosition {
+                    y: Position::Absolute(_),
+                    ..
+                } = change
+                {
+                    if self.inline {
+                        bail!("inline rendering used absolute positioning");
+                    }
+                }
+            }
+            self.rendered = changes.to_vec();
+            self.screen.add_changes(changes.to_vec());
+            Ok(())
+        }
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn poll_input(&mut self, _wait: Option<Duration>) -> Result<Option<InputEvent>> {
+            Ok(None)
+        }
+        fn waker(&self) -> TerminalWaker {
+            unimplemented!();
+        }
+    }
+
+    fn screen_text(buf: &mut BufferedTerminal<SurfaceTerminal>) -> String {
+        buf.terminal().screen.screen_chars_to_string()
+    }
+
+    #[test]
+    fn inline_region() {
+        let mut screen = Surface::new(6, 4);
+        screen.add_change("line1\r\nline2\r\nline3\r\n");
+        let mut buf = BufferedTerminal::new_inline(SurfaceTerminal::new(screen, true), 2).unwrap();
+
+        // Reserving the region scrolled the screen by one line
+        buf.add_change("hello\r\nworld");
+        buf.flush().unwrap();
+        assert_eq!(screen_text(&mut buf), "line2 \nline3 \nhello \nworld \n");
+
+        // Only the changed cells are rendered
+        buf.add_change(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        });
+        buf.add_change("J");
+        buf.flush().unwrap();
+        assert_eq!(screen_text(&mut buf), "line2 \nline3 \nJello \nworld \n");
+
+        buf.set_inline_height(3).unwrap();
+        assert_eq!(buf.dimensions(), (6, 3));
+        buf.add_change(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(2),
+        });
+        buf.add_change("!");
+        buf.flush().unwrap();
+        assert_eq!(screen_text(&mut buf), "line3 \nJello \nworld \n!     \n");
+
+        buf.set_inline_height(1).unwrap();
+        assert_eq!(screen_text(&mut buf), "line3 \nJello \n      \n      \n");
+
+        // The content is retained once we're done, and the cursor
+        // is placed on the line that follows it
+        buf.finish_inline().unwrap();
+        assert_eq!(buf.terminal().screen.cursor_position(), (0, 2));
+        assert_eq!(screen_text(&mut buf), "line3 \nJello \n      \n      \n");
+    }
+
+    #[test]
+    fn scrolled_repaint() {
+        let mut buf =
+            BufferedTerminal::new(SurfaceTerminal::new(Surface::new(6, 4), false)).unwrap();
+        buf.set_scroll_detection(true);
+        buf.add_change("line1\r\nline2\r\nline3\r\nline4");
+        buf.flush().unwrap();
+        assert_eq!(screen_text(&mut buf), "line1 \nline2 \nline3 \nline4 \n");
+
+        // Clear and redraw everything, as many applications do
+        buf.add_change(Change::ClearScreen(Default::default()));
+        for (idx, line) in ["line2", "line3", "line4", "line5"].iter().enumerate() {
+            if idx > 0 {
+                buf.add_change("\r\n");
+            }
+            buf.add_change(*line);
+        }
+        buf.flush().unwrap();
+        assert_eq!(screen_text(&mut buf), "line2 \nline3 \nline4 \nline5 \n");
+
+        // The display was scrolled and only the new line was painted
+        let rendered = &buf.terminal().rendered;
+        assert_eq!(
+            rendered[0],
+            Change::ScrollRegionUp {
+                first_row: 0,
+                region_size: 4,
+                scroll_count: 1,
+            }
+        );
+        assert!(rendered.contains(&Change::Text("line5".into())));
+        assert!(!rendered.contains(&Change::Text("line2".into())));
+    }
+
+    #[test]
+    fn repaint_without_scroll_detection() {
+        let mut buf =
+            BufferedTerminal::new(SurfaceTerminal::new(Surface::new(6, 2), false)).unwrap();
+        assert!(buf.shadow.is_none());
+        buf.add_change("line1\r\nline2");
+        buf.flush().unwrap();
+
+        buf.add_change(Change::ClearScreen(Default::default()));
+        buf.add_change("line2\r\nline3");
+        buf.flush().unwrap();
+        assert_eq!(screen_text(&mut buf), "line2 \nline3 \n");
+
+        // The changes are rendered as they are, without keeping a copy
+        // of the display to detect the scroll
+        assert!(buf.shadow.is_none());
+        let rendered = &buf.terminal().rendered;
+        assert_eq!(rendered[0], Change::ClearScreen(Default::default()));
+        assert!(rendered.contains(&Change::Text("line2\r\nline3".into())));
+    }
+}
@@ -1,10 +1,11 @@
 //! An abstraction over a terminal device
 
-use crate::caps::probed::ProbeCapabilities;
+use crate::caps::probed::{ProbeCapabilities, ProbedCapabilities};
 use crate::caps::Capabilities;
+use crate::escape::csi::KittyKeyboardFlags;
 use crate::input::InputEvent;
 use crate::surface::Change;
-use crate::{format_err, Result};
+use crate::{bail, format_err, Result};
 use num_traits::NumCast;
 use std::fmt::Display;
 use std::time::Duration;
@@ -66,6 +67,22 @@ pub trait Terminal {
     /// Exit the alternate screen.
     fn exit_alternate_screen(&mut self) -> Result<()>;
 
+    /// Push the specified kitty keyboard protocol flags onto the
+    /// terminal's stack of keyboard modes, enabling the associated
+    /// progressive enhancements to key reporting.
+    /// Any flags that remain pushed will be popped automatically
+    /// when the `Terminal` is dropped.
+    /// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    fn push_kitty_keyboard_flags(&mut self, _flags: KittyKeyboardFlags) -> Result<()> {
+        bail!("this terminal does not support the kitty keyboard protocol");
+    }
+
+    /// Pop the flags most recently pushed by `push_kitty_keyboard_flags`,
+    /// restoring the keyboard mode that was in effect before them.
+    fn pop_kitty_keyboard_flags(&mut self) -> Result<()> {
+        bail!("this terminal does not support the kitty keyboard protocol");
+    }
+
     /// Queries the current screen size, returning width, height.
     fn get_screen_size(&mut self) -> Result<ScreenSize>;
 
@@ -75,6 +92,14 @@ pub trait Terminal {
         None
     }
 
+    /// Probes the terminal using `ProbeCapabilities::probe`, waiting up
+    /// to `timeout` for the responses, and updates the capabilities
+    /// used to render to this terminal to match.
+    /// The terminal should be in raw mode while probing.
+    fn probe_and_apply_capabilities(&mut self, _timeout: Duration) -> Result<ProbedCapabilities> {
+        bail!("this terminal does not support capability probing");
+    }
+
     /// Sets the current screen size
     fn set_screen_size(&mut self, size: ScreenSize) -> Result<()>;
 
@@ -1,4 +1,5 @@
-use crate::render::RenderTty;
+use crate::caps::probed::ProbedCapabilities;
+use crate::render::{RenderStats, RenderTty};
 use crate::terminal::ProbeCapabilities;
 use crate::{bail, Context, Result};
 use filedescriptor::{poll, pollfd, FileDescriptor, POLLIN};
@@ -19,7 +20,10 @@ use termios::{
 };
 
 use crate::caps::Capabilities;
-use crate::escape::csi::{DecPrivateMode, DecPrivateModeCode, Mode, XtermKeyModifierResource, CSI};
+use crate::escape::csi::{
+    DecPrivateMode, DecPrivateModeCode, Keyboard, KittyKeyboardFlags, KittyKeyboardMode, Mode,
+    XtermKeyModifierResource, CSI,
+};
 use crate::input::{InputEvent, InputParser};
 use crate::render::terminfo::TerminfoRenderer;
 use crate::surface::Change;
@@ -138,6 +142,17 @@ impl RenderTty for TtyWriteHandle {
         let size = self.get_size()?;
         Ok((size.ws_col as usize, size.ws_row as usize))
     }
+
+    fn get_cell_size_in_pixels(&mut self) -> Result<Option<(usize, usize)>> {
+        let size = self.get_size()?;
+        if size.ws_xpixel == 0 || size.ws_ypixel == 0 || size.ws_col == 0 || size.ws_row == 0 {
+            return Ok(None);
+        }
+        Ok(Some((
+            size.ws_xpixel as usize / size.ws_col as usize,
+            size.ws_ypixel as usize / size.ws_row as usize,
+        )))
+    }
 }
 
 impl UnixTty for TtyWriteHandle {
@@ -208,6 +223,7 @@ pub struct UnixTerminal {
     wake_pipe_write: Arc<Mutex<UnixStream>>,
     caps: Capabilities,
     in_alternate_screen: bool,
+    kitty_keyboard_depth: u32,
 }
 
 impl UnixTerminal {
@@ -255,6 +271,7 @@ impl UnixTerminal {
             wake_pipe,
             wake_pipe_write: Arc::new(Mutex::new(wake_pipe_write)),
             in_alternate_screen: false,
+            kitty_keyboard_depth: 0,
         })
     }
 
@@ -267,6 +284,17 @@ impl UnixTerminal {
         Self::new_with(caps, &file, &file)
     }
 
+    /// Returns the statistics for the output that has been rendered
+    /// to the terminal; see `TerminfoRenderer::stats`.
+    /// Probing capabilities replaces the renderer, resetting them.
+    pub fn render_stats(&self) -> RenderStats {
+        self.renderer.stats()
+    }
+
+    pub fn reset_render_stats(&mut self) {
+        self.renderer.reset_stats();
+    }
+
     /// Test whether we caught delivery of SIGWINCH.
     /// If so, yield an `InputEvent` with the current size of the tty.
     fn caught_sigwinch(&mut self) -> Result<Option<InputEvent>> {
@@ -297,6 +325,16 @@ pub struct UnixTerminalWaker {
 }
 
 impl UnixTerminalWaker {
+    /// Creates a waker that writes to `pipe` when woken.
+    /// This allows `Terminal` implementations outside of this crate
+    /// to wake up their `poll_input` by watching the other end of
+    /// the pipe.  `pipe` should be in non-blocking mode.
+    pub fn with_pipe(pipe: UnixStream) -> Self {
+        Self {
+            pipe: Arc::new(Mutex::new(pipe)),
+        }
+    }
+
     pub fn wake(&self) -> std::result::Result<(), IoError> {
         let mut pipe = self.pipe.lock().unwrap();
         match pipe.write(b"W") {
@@ -376,6 +414,28 @@ impl Terminal for UnixTerminal {
         Ok(())
     }
 
+    fn push_kitty_keyboard_flags(&mut self, flags: KittyKeyboardFlags) -> Result<()> {
+        write!(
+            self.write,
+            "{}",
+            CSI::Keyboard(Keyboard::PushKittyState {
+                flags,
+                mode: KittyKeyboardMode::AssignAll,
+            })
+        )?;
+        self.kitty_keyboard_depth += 1;
+        Ok(())
+    }
+
+    fn pop_kitty_keyboard_flags(&mut self) -> Result<()> {
+        if self.kitty_keyboard_depth == 0 {
+            bail!("no kitty keyboard flags have been pushed");
+        }
+        write!(self.write, "{}", CSI::Keyboard(Keyboard::PopKittyState(1)))?;
+        self.kitty_keyboard_depth -= 1;
+        Ok(())
+    }
+
     fn get_screen_size(&mut self) -> Result<ScreenSize> {
         let size = self.write.get_size()?;
         Ok(ScreenSize {
@@ -387,7 +447,27 @@ impl Terminal for UnixTerminal {
     }
 
     fn probe_capabilities(&mut self) -> Option<ProbeCapabilities> {
-        Some(ProbeCapabilities::new(&mut self.read, &mut self.write))
+        let fd = self.read.fd.as_raw_fd();
+        Some(
+            ProbeCapabilities::new(&mut self.read, &mut self.write).with_poll(move |timeout| {
+                let mut pfd = [pollfd {
+                    fd,
+                    events: POLLIN,
+                    revents: 0,
+                }];
+                Ok(poll(&mut pfd, Some(timeout))? > 0)
+            }),
+        )
+    }
+
+    fn probe_and_apply_capabilities(&mut self, timeout: Duration) -> Result<ProbedCapabilities> {
+        let probed = match self.probe_capabilities() {
+            Some(mut probe) => probe.probe(timeout)?,
+            None => bail!("capability probing is not available"),
+        };
+        self.caps.apply_probed(&probed);
+        self.renderer = TerminfoRenderer::new(self.caps.clone());
+        Ok(probed)
     }
 
     fn set_screen_size(&mut self, size: ScreenSize) -> Result<()> {
@@ -535,6 +615,14 @@ impl Drop for UnixTerminal {
             decreset!(AnyEventMouse);
         }
         self.write.modify_other_keys(0).unwrap();
+        if self.kitty_keyboard_depth > 0 {
+            write!(
+                self.write,
+                "{}",
+                CSI::Keyboard(Keyboard::PopKittyState(self.kitty_keyboard_depth))
+            )
+            .unwrap();
+        }
         self.exit_alternate_screen().unwrap();
         self.write.flush().unwrap();
 
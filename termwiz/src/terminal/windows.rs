@@ -1,4 +1,7 @@
-use crate::escape::csi::{DecPrivateMode, DecPrivateModeCode, Mode, CSI};
+use crate::caps::probed::ProbedCapabilities;
+use crate::escape::csi::{
+    DecPrivateMode, DecPrivateModeCode, Keyboard, KittyKeyboardFlags, KittyKeyboardMode, Mode, CSI,
+};
 use crate::istty::IsTty;
 use crate::terminal::ProbeCapabilities;
 use crate::{bail, ensure, format_err, Result};
@@ -13,7 +16,7 @@ use std::time::Duration;
 use std::{mem, ptr};
 use winapi::shared::winerror::WAIT_TIMEOUT;
 use winapi::um::consoleapi;
-use winapi::um::synchapi::{CreateEventW, SetEvent, WaitForMultipleObjects};
+use winapi::um::synchapi::{CreateEventW, SetEvent, WaitForMultipleObjects, WaitForSingleObject};
 use winapi::um::winbase::{INFINITE, WAIT_FAILED, WAIT_OBJECT_0};
 use winapi::um::wincon::{
     FillConsoleOutputAttribute, FillConsoleOutputCharacterW, GetConsoleScreenBufferInfo,
@@ -491,6 +494,7 @@ pub struct WindowsTerminal {
     saved_input_cp: u32,
     saved_output_cp: u32,
     in_alternate_screen: bool,
+    kitty_keyboard_depth: u32,
     caps: Capabilities,
 }
 
@@ -516,6 +520,14 @@ impl Drop for WindowsTerminal {
             decreset!(BracketedPaste);
             decreset!(SGRMouse);
             decreset!(AnyEventMouse);
+            if self.kitty_keyboard_depth > 0 {
+                write!(
+                    self.output_handle,
+                    "{}",
+                    CSI::Keyboard(Keyboard::PopKittyState(self.kitty_keyboard_depth))
+                )
+                .unwrap();
+            }
         }
 
         self.exit_alternate_screen().unwrap();
@@ -608,6 +620,7 @@ impl WindowsTerminal {
             input_parser,
             input_queue: VecDeque::new(),
             in_alternate_screen: false,
+            kitty_keyboard_depth: 0,
             caps,
         };
 
@@ -656,6 +669,33 @@ pub struct WindowsTerminalWaker {
 }
 
 impl WindowsTerminalWaker {
+    /// Creates a waker that isn't associated with a console.
+    /// This allows `Terminal` implementations outside of this crate
+    /// to wake up their `poll_input` by calling `wait`.
+    pub fn new() -> IoResult<Self> {
+        Ok(Self {
+            handle: Arc::new(EventHandle::new()?),
+        })
+    }
+
+    /// Waits up to `wait` for `wake` to be called, returning true
+    /// if it was called.  `None` waits indefinitely.
+    pub fn wait(&self, wait: Option<Duration>) -> IoResult<bool> {
+        let result = unsafe {
+            WaitForSingleObject(
+                self.handle.handle.as_raw_handle() as *mut _,
+                wait.map(|wait| wait.as_millis() as u32).unwrap_or(INFINITE),
+            )
+        };
+        if result == WAIT_OBJECT_0 {
+            Ok(true)
+        } else if result == WAIT_TIMEOUT {
+            Ok(false)
+        } else {
+            Err(IoError::last_os_error())
+        }
+    }
+
     pub fn wake(&self) -> IoResult<()> {
         self.handle.set()?;
         Ok(())
@@ -758,6 +798,35 @@ impl Terminal for WindowsTerminal {
         Ok(())
     }
 
+    fn push_kitty_keyboard_flags(&mut self, flags: KittyKeyboardFlags) -> Result<()> {
+        if !matches!(&self.renderer, Renderer::Terminfo(_)) {
+            bail!("the windows console does not support the kitty keyboard protocol");
+        }
+        write!(
+            self.output_handle,
+            "{}",
+            CSI::Keyboard(Keyboard::PushKittyState {
+                flags,
+                mode: KittyKeyboardMode::AssignAll,
+            })
+        )?;
+        self.kitty_keyboard_depth += 1;
+        Ok(())
+    }
+
+    fn pop_kitty_keyboard_flags(&mut self) -> Result<()> {
+        if self.kitty_keyboard_depth == 0 {
+            bail!("no kitty keyboard flags have been pushed");
+        }
+        write!(
+            self.output_handle,
+            "{}",
+            CSI::Keyboard(Keyboard::PopKittyState(1))
+        )?;
+        self.kitty_keyboard_depth -= 1;
+        Ok(())
+    }
+
     fn get_screen_size(&mut self) -> Result<ScreenSize> {
         let info = self.output_handle.get_buffer_info()?;
         let (cols, rows) = dimensions_from_buffer_info(info);
@@ -771,10 +840,35 @@ impl Terminal for WindowsTerminal {
     }
 
     fn probe_capabilities(&mut self) -> Option<ProbeCapabilities> {
-        Some(ProbeCapabilities::new(
-            &mut self.input_handle,
-            &mut self.output_handle,
-        ))
+        let handle = self.input_handle.handle.as_raw_handle();
+        Some(
+            ProbeCapabilities::new(&mut self.input_handle, &mut self.output_handle).with_poll(
+                move |timeout| {
+                    let mut handles = [handle as *mut _];
+                    let result = unsafe {
+                        WaitForMultipleObjects(
+                            1,
+                            handles.as_mut_ptr(),
+                            0,
+                            timeout.as_millis() as u32,
+                        )
+                    };
+                    Ok(result == WAIT_OBJECT_0)
+                },
+            ),
+        )
+    }
+
+    fn probe_and_apply_capabilities(&mut self, timeout: Duration) -> Result<ProbedCapabilities> {
+        let probed = match self.probe_capabilities() {
+            Some(mut probe) => probe.probe(timeout)?,
+            None => bail!("capability probing is not available"),
+        };
+        self.caps.apply_probed(&probed);
+        if let Renderer::Terminfo(_) = &self.renderer {
+            self.renderer = Renderer::Terminfo(TerminfoRenderer::new(self.caps.clone()));
+        }
+        Ok(probed)
     }
 
     fn set_screen_size(&mut self, size: ScreenSize) -> Result<()> {
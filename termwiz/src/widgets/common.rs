@@ -0,0 +1,218 @@
+//! Drawing and input helpers shared by the widgets in this module
+use crate::cell::{grapheme_column_width, CellAttributes};
+use crate::input::{KeyCode, KeyEvent, KeyEventType, MouseButtons, MouseEvent};
+use crate::surface::{Change, Position, Surface};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Returns the longest prefix of `text` that fits within `width`
+/// cells, along with the number of cells that it occupies.
+pub(crate) fn truncate_to_width(text: &str, width: usize) -> (&str, usize) {
+    let mut used = 0;
+    let mut end = 0;
+    for (idx, grapheme) in text.grapheme_indices(true) {
+        let grapheme_width = grapheme_column_width(grapheme, None);
+        if used + grapheme_width > width {
+            break;
+        }
+        used += grapheme_width;
+        end = idx + grapheme.len();
+    }
+    (&text[..end], used)
+}
+
+/// Writes `text` at the specified position using the provided attributes.
+/// The text is truncated to `width` cells and padded with spaces
+/// so that it fills them.
+pub(crate) fn put_text(
+    surface: &mut Surface,
+    x: usize,
+    y: usize,
+    width: usize,
+    text: &str,
+    attrs: &CellAttributes,
+) {
+    let (text, used) = truncate_to_width(text, width);
+    surface.add_changes(vec![
+        Change::CursorPosition {
+            x: Position::Absolute(x),
+            y: Position::Absolute(y),
+        },
+        Change::AllAttributes(attrs.clone()),
+        Change::Text(format!("{}{}", text, " ".repeat(width - used))),
+        Change::AllAttributes(CellAttributes::default()),
+    ]);
+}
+
+/// Draws a single line box that occupies the specified region,
+/// with an optional title embedded in its top edge.
+/// The interior of the box is left untouched.
+pub(crate) fn draw_box(
+    surface: &mut Surface,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    title: Option<&str>,
+) {
+    if width < 2 || height < 2 {
+        return;
+    }
+    let horizontal = "─".repeat(width - 2);
+    let attrs = CellAttributes::default();
+
+    put_text(surface, x, y, width, &format!("┌{}┐", horizontal), &attrs);
+    for row in y + 1..y + height - 1 {
+        put_text(surface, x, row, 1, "│", &attrs);
+        put_text(surface, x + width - 1, row, 1, "│", &attrs);
+    }
+    put_text(
+        surface,
+        x,
+        y + height - 1,
+        width,
+        &format!("└{}┘", horizontal),
+        &attrs,
+    );
+
+    if let Some(title) = title {
+        if width > 4 {
+            let title = format!(" {} ", title);
+            let (title, used) = truncate_to_width(&title, width - 4);
+            put_text(surface, x + 2, y, used, title, &attrs);
+        }
+    }
+}
+
+/// Returns true if the key event is a press or a repeat.
+/// Widgets act on presses and ignore the key releases that
+/// are reported by the kitty keyboard protocol.
+pub(crate) fn is_key_press(key: &KeyEvent) -> bool {
+    key.event_type != KeyEventType::Release
+}
+
+/// Returns true if `event` represents the left button being pressed,
+/// as opposed to being held down while the mouse is dragged.
+/// `last_buttons` holds the button state from the previous event
+/// delivered to the widget and is updated by this function.
+pub(crate) fn is_left_press(event: &MouseEvent, last_buttons: &mut MouseButtons) -> bool {
+    let pressed = event.mouse_buttons.contains(MouseButtons::LEFT)
+        && !last_buttons.contains(MouseButtons::LEFT);
+    *last_buttons = event.mouse_buttons.clone();
+    pressed
+}
+
+/// Returns the number of rows to scroll for a vertical wheel event;
+/// negative values scroll towards the top.
+pub(crate) fn wheel_delta(event: &MouseEvent) -> isize {
+    if !event.mouse_buttons.contains(MouseButtons::VERT_WHEEL) {
+        0
+    } else if event.mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+        -1
+    } else {
+        1
+    }
+}
+
+/// Tracks the selected row and the first visible row of a
+/// vertically scrollable collection of rows.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ScrollState {
+    pub selected: usize,
+    pub top: usize,
+    /// The number of rows that were visible when last rendered
+    pub page: usize,
+}
+
+impl ScrollState {
+    /// Moves the selection by `delta` rows, clamping it to
+    /// the range of `len` rows.
+    pub fn move_by(&mut self, delta: isize, len: usize) {
+        let selected = self.selected as isize + delta;
+        self.select(selected.max(0) as usize, len);
+    }
+
+    pub fn select(&mut self, idx: usize, len: usize) {
+        self.selected = idx.min(len.saturating_sub(1));
+    }
+
+    /// Adjusts the first visible row so that the selection is
+    /// visible in a viewport that is `height` rows tall, and so that
+    /// the viewport isn't scrolled beyond the last of the `len` rows.
+    pub fn ensure_visible(&mut self, height: usize, len: usize) {
+        self.page = height;
+        self.top = self.top.min(len.saturating_sub(height));
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if height > 0 && self.selected >= self.top + height {
+            self.top = self.selected + 1 - height;
+        }
+    }
+
+    /// Applies the navigation keys to the selection.
+    /// Returns true if the key was consumed.
+    pub fn process_key(&mut self, key: &KeyEvent, len: usize) -> bool {
+        let page = self.page.max(1) as isize;
+        match key.key {
+            KeyCode::UpArrow | KeyCode::ApplicationUpArrow => self.move_by(-1, len),
+            KeyCode::DownArrow | KeyCode::ApplicationDownArrow => self.move_by(1, len),
+            KeyCode::PageUp => self.move_by(-page, len),
+            KeyCode::PageDown => self.move_by(page, len),
+            KeyCode::Home => self.select(0, len),
+            KeyCode::End => self.select(len.saturating_sub(1), len),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+    use crate::surface::Surface;
+    use crate::widgets::{Ui, WidgetEvent};
+
+    /// Renders the ui to a surface of the specified size, repeating
+    /// until the layout has settled, and returns the lines of text
+    /// that are displayed.
+    pub fn render(ui: &mut Ui, surface: &mut Surface) -> Vec<String> {
+        for _ in 0..3 {
+            if !ui.render_to_screen(surface).unwrap() {
+                break;
+            }
+        }
+        surface
+            .screen_chars_to_string()
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .collect()
+    }
+
+    pub fn key(ui: &mut Ui, key: KeyCode, modifiers: Modifiers) {
+        ui.queue_event(WidgetEvent::Input(InputEvent::Key(KeyEvent {
+            key,
+            modifiers,
+            event_type: Default::default(),
+            kitty: None,
+        })));
+        ui.process_event_queue().unwrap();
+    }
+
+    pub fn type_text(ui: &mut Ui, text: &str) {
+        for c in text.chars() {
+            key(ui, KeyCode::Char(c), Modifiers::NONE);
+        }
+    }
+
+    /// Clicks the left button at the specified screen coordinates
+    pub fn click(ui: &mut Ui, x: u16, y: u16) {
+        for mouse_buttons in [MouseButtons::LEFT, MouseButtons::NONE] {
+            ui.queue_event(WidgetEvent::Input(InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                modifiers: Modifiers::NONE,
+            })));
+        }
+        ui.process_event_queue().unwrap();
+    }
+}
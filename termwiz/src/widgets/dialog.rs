@@ -0,0 +1,275 @@
+//! A modal dialog widget
+use crate::cell::{unicode_column_width, CellAttributes};
+use crate::input::{InputEvent, KeyCode, Modifiers, MouseButtons};
+use crate::surface::{Change, CursorVisibility};
+use crate::widgets::common::{draw_box, is_key_press, is_left_press, put_text};
+use crate::widgets::layout::{Constraints, HorizontalAlignment, VerticalAlignment};
+use crate::widgets::{RenderArgs, UpdateArgs, Widget, WidgetEvent};
+
+/// Describes how a `Dialog` was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogChoice {
+    /// The button with the specified index was chosen
+    Button(usize),
+    /// The dialog was dismissed by pressing Escape
+    Dismissed,
+}
+
+/// A framed box holding a message and a row of buttons, centered
+/// within its parent.
+///
+/// The dialog is modal in the sense that it consumes all key presses
+/// while it has the focus, so the app should add it to the `Ui` and
+/// give it the focus while it is displayed.  Tab and the arrow keys
+/// move between the buttons, Enter or Space chooses the focused button
+/// and Escape dismisses the dialog; buttons can also be clicked.
+/// The outcome is available via `Dialog::take_choice`.
+pub struct Dialog {
+    title: String,
+    message: String,
+    buttons: Vec<String>,
+    focused_button: usize,
+    choice: Option<DialogChoice>,
+    last_buttons: MouseButtons,
+    /// The dimensions of the dialog when it was last rendered
+    size: (usize, usize),
+}
+
+impl Dialog {
+    pub fn new<T: Into<String>, M: Into<String>, B: Into<String>>(
+        title: T,
+        message: M,
+        buttons: Vec<B>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            message: message.into(),
+            buttons: buttons.into_iter().map(Into::into).collect(),
+            focused_button: 0,
+            choice: None,
+            last_buttons: MouseButtons::NONE,
+            size: (0, 0),
+        }
+    }
+
+    /// Returns the index of the button that has the focus
+    pub fn focused_button(&self) -> usize {
+        self.focused_button
+    }
+
+    /// Returns how the dialog was closed, if it has been, and clears it.
+    pub fn take_choice(&mut self) -> Option<DialogChoice> {
+        self.choice.take()
+    }
+
+    fn button_label(button: &str) -> String {
+        format!("[ {} ]", button)
+    }
+
+    /// Returns the width of the row of buttons
+    fn buttons_width(&self) -> usize {
+        let labels: usize = self
+            .buttons
+            .iter()
+            .map(|b| unicode_column_width(&Self::button_label(b), None))
+            .sum();
+        labels + 2 * self.buttons.len().saturating_sub(1)
+    }
+
+    /// Returns the starting column and width of each button when
+    /// the dialog is `width` cells wide
+    fn button_positions(&self, width: usize) -> Vec<(usize, usize)> {
+        let mut x = width.saturating_sub(self.buttons_width()) / 2;
+        self.buttons
+            .iter()
+            .map(|b| {
+                let label_width = unicode_column_width(&Self::button_label(b), None);
+                let start = x;
+                x += label_width + 2;
+                (start, label_width)
+            })
+            .collect()
+    }
+
+    fn move_focus(&mut self, delta: isize) {
+        let len = self.buttons.len() as isize;
+        if len > 0 {
+            self.focused_button = (self.focused_button as isize + delta).rem_euclid(len) as usize;
+        }
+    }
+}
+
+impl Widget for Dialog {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, height) = args.surface.dimensions();
+        self.size = (width, height);
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+        args.cursor.visibility = CursorVisibility::Hidden;
+        draw_box(args.surface, 0, 0, width, height, Some(&self.title));
+        if width <= 4 || height <= 2 {
+            return;
+        }
+
+        let attrs = CellAttributes::default();
+        for (idx, line) in self.message.lines().enumerate().take(height - 2) {
+            put_text(args.surface, 2, idx + 1, width - 4, line, &attrs);
+        }
+
+        let button_row = height - 2;
+        for (idx, (x, label_width)) in self.button_positions(width).into_iter().enumerate() {
+            if x + label_width >= width {
+                break;
+            }
+            let mut attrs = CellAttributes::default();
+            if idx == self.focused_button {
+                attrs.set_reverse(true);
+            }
+            put_text(
+                args.surface,
+                x,
+                button_row,
+                label_width,
+                &Self::button_label(&self.buttons[idx]),
+                &attrs,
+            );
+        }
+    }
+
+    fn get_size_constraints(&self) -> Constraints {
+        let message_width = self
+            .message
+            .lines()
+            .map(|line| unicode_column_width(line, None))
+            .max()
+            .unwrap_or(0);
+        let title_width = unicode_column_width(&self.title, None) + 2;
+        let width = message_width.max(title_width).max(self.buttons_width()) + 4;
+        // The border, the message, a blank line and the buttons
+        let height = self.message.lines().count() + 4;
+
+        let mut constraints = Constraints::with_fixed_width_height(width as u16, height as u16);
+        constraints
+            .set_halign(HorizontalAlignment::Center)
+            .set_valign(VerticalAlignment::Middle);
+        constraints
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, _args: &mut UpdateArgs) -> bool {
+        match event {
+            WidgetEvent::Input(InputEvent::Key(key)) => {
+                if is_key_press(key) {
+                    match (&key.key, key.modifiers) {
+                        (KeyCode::Tab, Modifiers::SHIFT)
+                        | (KeyCode::LeftArrow, _)
+                        | (KeyCode::ApplicationLeftArrow, _) => self.move_focus(-1),
+                        (KeyCode::Tab, _)
+                        | (KeyCode::RightArrow, _)
+                        | (KeyCode::ApplicationRightArrow, _) => self.move_focus(1),
+                        (KeyCode::Enter, _) | (KeyCode::Char(' '), _)
+                            if self.focused_button < self.buttons.len() =>
+                        {
+                            self.choice = Some(DialogChoice::Button(self.focused_button));
+                        }
+                        (KeyCode::Escape, _) => self.choice = Some(DialogChoice::Dismissed),
+                        _ => {}
+                    }
+                }
+                // Consume all keys so that they don't reach the widgets
+                // behind the dialog
+                true
+            }
+            WidgetEvent::Input(InputEvent::Paste(_)) => true,
+            WidgetEvent::Input(InputEvent::Mouse(mouse)) => {
+                if is_left_press(mouse, &mut self.last_buttons) {
+                    let (width, height) = self.size;
+                    let button_row = height.saturating_sub(2);
+                    if mouse.y as usize == button_row {
+                        let x = mouse.x as usize;
+                        if let Some(idx) = self
+                            .button_positions(width)
+                            .into_iter()
+                            .position(|(start, len)| x >= start && x < start + len)
+                        {
+                            self.focused_button = idx;
+                            self.choice = Some(DialogChoice::Button(idx));
+                        }
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::{click, key, render};
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Background {}
+
+    impl Widget for Background {
+        fn render(&mut self, args: &mut RenderArgs) {
+            args.surface
+                .add_change(Change::ClearScreen(Default::default()));
+        }
+    }
+
+    #[test]
+    fn modal_dialog() {
+        let dialog = Rc::new(RefCell::new(Dialog::new(
+            "Quit",
+            "Really quit?",
+            vec!["Yes", "No"],
+        )));
+        let mut ui = Ui::new();
+        let root = ui.set_root(Background {});
+        let id = ui.add_child(root, Rc::clone(&dialog));
+        ui.set_focus(id);
+        let mut surface = Surface::new(24, 7);
+
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "",
+    "  ┌─ Quit ──────────┐",
+    "  │ Really quit?    │",
+    "  │                 │",
+    "  │ [ Yes ]  [ No ] │",
+    "  └─────────────────┘",
+    "",
+]
+"#
+        );
+
+        key(&mut ui, KeyCode::Tab, Modifiers::NONE);
+        assert_eq!(dialog.borrow().focused_button(), 1);
+        key(&mut ui, KeyCode::Char('x'), Modifiers::NONE);
+        assert_eq!(dialog.borrow_mut().take_choice(), None);
+        key(&mut ui, KeyCode::Enter, Modifiers::NONE);
+        assert_eq!(
+            dialog.borrow_mut().take_choice(),
+            Some(DialogChoice::Button(1))
+        );
+
+        key(&mut ui, KeyCode::Escape, Modifiers::NONE);
+        assert_eq!(
+            dialog.borrow_mut().take_choice(),
+            Some(DialogChoice::Dismissed)
+        );
+
+        click(&mut ui, 6, 4);
+        assert_eq!(
+            dialog.borrow_mut().take_choice(),
+            Some(DialogChoice::Button(0))
+        );
+    }
+}
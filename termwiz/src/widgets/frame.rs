@@ -0,0 +1,183 @@
+//! A widget that draws a border around another widget
+use crate::input::InputEvent;
+use crate::surface::{Change, SequenceNo, Surface};
+use crate::widgets::common::draw_box;
+use crate::widgets::layout::{Constraints, Dimension, DimensionSpec};
+use crate::widgets::{RenderArgs, UpdateArgs, Widget, WidgetEvent};
+
+/// Draws a single line border, with an optional title, around
+/// another widget.  The inner widget is rendered into the interior
+/// of the frame; its size constraints are grown to make room for the
+/// border, and mouse events are translated to its coordinate space.
+pub struct Frame<W> {
+    inner: W,
+    title: Option<String>,
+    surface: Surface,
+}
+
+impl<W: Widget> Frame<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            title: None,
+            surface: Surface::new(1, 1),
+        }
+    }
+
+    pub fn with_title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn set_title<S: Into<String>>(&mut self, title: Option<S>) {
+        self.title = title.map(Into::into);
+    }
+
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+/// Grows a fixed dimension, and its bounds, by the space
+/// occupied by the border
+fn grow(dim: Dimension) -> Dimension {
+    Dimension {
+        spec: match dim.spec {
+            DimensionSpec::Fixed(n) => DimensionSpec::Fixed(n.saturating_add(2)),
+            spec => spec,
+        },
+        maximum: dim.maximum.map(|n| n.saturating_add(2)),
+        minimum: dim.minimum.map(|n| n.saturating_add(2)),
+    }
+}
+
+impl<W: Widget> Widget for Frame<W> {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, height) = args.surface.dimensions();
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+        draw_box(args.surface, 0, 0, width, height, self.title.as_deref());
+
+        if width <= 2 || height <= 2 {
+            return;
+        }
+
+        if self.surface.dimensions() != (width - 2, height - 2) {
+            self.surface.resize(width - 2, height - 2);
+        }
+        let mut cursor = args.cursor.clone();
+        cursor.coords.x = cursor.coords.x.saturating_sub(1);
+        cursor.coords.y = cursor.coords.y.saturating_sub(1);
+        self.inner.render(&mut RenderArgs {
+            id: args.id,
+            is_focused: args.is_focused,
+            cursor: &mut cursor,
+            surface: &mut self.surface,
+        });
+        args.surface.draw_from_screen(&self.surface, 1, 1);
+        self.surface.flush_changes_older_than(SequenceNo::MAX);
+
+        cursor.coords.x += 1;
+        cursor.coords.y += 1;
+        *args.cursor = cursor;
+    }
+
+    fn get_size_constraints(&self) -> Constraints {
+        let mut constraints = self.inner.get_size_constraints();
+        constraints.width = grow(constraints.width);
+        constraints.height = grow(constraints.height);
+        constraints
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, args: &mut UpdateArgs) -> bool {
+        match event {
+            WidgetEvent::Input(InputEvent::Mouse(mouse)) => {
+                let (width, height) = self.surface.dimensions();
+                let (x, y) = (mouse.x as usize, mouse.y as usize);
+                // Events that land on the border are not forwarded
+                if x == 0 || y == 0 || x > width || y > height {
+                    return false;
+                }
+                let mut mouse = mouse.clone();
+                mouse.x -= 1;
+                mouse.y -= 1;
+                self.inner
+                    .process_event(&WidgetEvent::Input(InputEvent::Mouse(mouse)), args)
+            }
+            _ => self.inner.process_event(event, args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::{KeyCode, Modifiers};
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::{click, key, render, type_text};
+    use crate::widgets::list::ListView;
+    use crate::widgets::text_input::TextInput;
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn frame_around_list() {
+        let list = Rc::new(RefCell::new(ListView::new(vec!["one", "two", "three"])));
+        let mut ui = Ui::new();
+        let id = ui.set_root(Frame::new(Rc::clone(&list)).with_title("Numbers"));
+        ui.set_focus(id);
+        let mut surface = Surface::new(14, 5);
+
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "┌─ Numbers ──┐",
+    "│one         │",
+    "│two         │",
+    "│three       │",
+    "└────────────┘",
+]
+"#
+        );
+
+        // Clicking the border does nothing; clicking inside is
+        // translated to the coordinate space of the list
+        click(&mut ui, 0, 3);
+        assert_eq!(list.borrow().selected(), Some(0));
+        click(&mut ui, 1, 3);
+        assert_eq!(list.borrow().selected(), Some(2));
+
+        key(&mut ui, KeyCode::UpArrow, Modifiers::NONE);
+        assert_eq!(list.borrow().selected(), Some(1));
+    }
+
+    #[test]
+    fn frame_constraints_and_cursor() {
+        let mut ui = Ui::new();
+        let id = ui.set_root(Frame::new(TextInput::new()));
+        ui.set_focus(id);
+        let mut surface = Surface::new(8, 5);
+
+        type_text(&mut ui, "hi");
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "┌──────┐",
+    "│hi    │",
+    "└──────┘",
+    "",
+    "",
+]
+"#
+        );
+        assert_eq!(surface.cursor_position(), (3, 1));
+    }
+}
@@ -0,0 +1,300 @@
+//! A scrollable list widget with selection and fuzzy filtering
+use crate::cell::{CellAttributes, Intensity};
+use crate::input::{InputEvent, KeyCode, Modifiers, MouseButtons};
+pub use crate::lineedit::fuzzy_score;
+use crate::surface::{Change, CursorVisibility};
+use crate::widgets::common::{is_key_press, is_left_press, put_text, wheel_delta, ScrollState};
+use crate::widgets::{RenderArgs, UpdateArgs, Widget, WidgetEvent};
+
+/// A vertically scrollable list of items, one per row.
+///
+/// The arrow, page and home/end keys change the selection, as do
+/// clicking on an item and the mouse wheel.  Pressing Enter, or
+/// clicking on the item that is already selected, activates the
+/// selected item; the app can collect that via `ListView::take_activated`.
+///
+/// When filtering is enabled with `ListView::with_type_to_filter`,
+/// typed characters are appended to a filter string and only the items
+/// that fuzzy match it are shown, best matches first.  The filter is
+/// displayed on the top row while it is non-empty.
+pub struct ListView {
+    items: Vec<String>,
+    filter: String,
+    type_to_filter: bool,
+    /// Indices into `items` of the items that match the filter
+    matches: Vec<usize>,
+    scroll: ScrollState,
+    activated: Option<usize>,
+    last_buttons: MouseButtons,
+}
+
+impl ListView {
+    pub fn new<S: Into<String>>(items: Vec<S>) -> Self {
+        let mut list = Self {
+            items: vec![],
+            filter: String::new(),
+            type_to_filter: false,
+            matches: vec![],
+            scroll: ScrollState::default(),
+            activated: None,
+            last_buttons: MouseButtons::NONE,
+        };
+        list.set_items(items);
+        list
+    }
+
+    /// Enables filtering the list by typing characters while it is focused
+    pub fn with_type_to_filter(mut self) -> Self {
+        self.type_to_filter = true;
+        self
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    /// Replaces the items in the list, resetting the selection
+    pub fn set_items<S: Into<String>>(&mut self, items: Vec<S>) {
+        self.items = items.into_iter().map(Into::into).collect();
+        self.apply_filter();
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Changes the filter, resetting the selection to the best match
+    pub fn set_filter(&mut self, filter: &str) {
+        self.filter = filter.to_string();
+        self.apply_filter();
+    }
+
+    /// Returns the index into `items` of the selected item, if any
+    /// item matches the filter.
+    pub fn selected(&self) -> Option<usize> {
+        self.matches.get(self.scroll.selected).copied()
+    }
+
+    /// Selects the item at the specified index into `items`.
+    /// Has no effect if that item is excluded by the filter.
+    pub fn select(&mut self, item: usize) {
+        if let Some(idx) = self.matches.iter().position(|&m| m == item) {
+            self.scroll.select(idx, self.matches.len());
+        }
+    }
+
+    /// Returns the index into `items` of the most recently activated
+    /// item, if any, and clears it.
+    pub fn take_activated(&mut self) -> Option<usize> {
+        self.activated.take()
+    }
+
+    fn apply_filter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| fuzzy_score(&self.filter, item).map(|score| (score, idx)))
+            .collect();
+        // Best score first; the sort is stable so that equally
+        // scored items retain their original order
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, idx)| idx).collect();
+        self.scroll = ScrollState {
+            page: self.scroll.page,
+            ..ScrollState::default()
+        };
+    }
+
+    /// The number of rows at the top of the widget that are used
+    /// to display the filter
+    fn filter_rows(&self) -> usize {
+        if self.filter.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+impl Widget for ListView {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, height) = args.surface.dimensions();
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+        args.cursor.visibility = CursorVisibility::Hidden;
+
+        let filter_rows = self.filter_rows();
+        if filter_rows > 0 {
+            let mut attrs = CellAttributes::default();
+            attrs.set_intensity(Intensity::Bold);
+            put_text(
+                args.surface,
+                0,
+                0,
+                width,
+                &format!("/{}", self.filter),
+                &attrs,
+            );
+        }
+
+        let rows = height.saturating_sub(filter_rows);
+        self.scroll.ensure_visible(rows, self.matches.len());
+
+        for (idx, &item) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll.top)
+            .take(rows)
+        {
+            let mut attrs = CellAttributes::default();
+            if idx == self.scroll.selected {
+                attrs.set_reverse(true);
+            }
+            put_text(
+                args.surface,
+                0,
+                idx - self.scroll.top + filter_rows,
+                width,
+                &self.items[item],
+                &attrs,
+            );
+        }
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, _args: &mut UpdateArgs) -> bool {
+        match event {
+            WidgetEvent::Input(InputEvent::Key(key)) => {
+                if !is_key_press(key) {
+                    return false;
+                }
+                if self.scroll.process_key(key, self.matches.len()) {
+                    return true;
+                }
+                match (&key.key, key.modifiers) {
+                    (KeyCode::Char('p'), Modifiers::CTRL) => {
+                        self.scroll.move_by(-1, self.matches.len())
+                    }
+                    (KeyCode::Char('n'), Modifiers::CTRL) => {
+                        self.scroll.move_by(1, self.matches.len())
+                    }
+                    (KeyCode::Enter, _) => {
+                        self.activated = self.selected();
+                    }
+                    (KeyCode::Escape, _) if self.type_to_filter && !self.filter.is_empty() => {
+                        self.set_filter("");
+                    }
+                    (KeyCode::Backspace, _) if self.type_to_filter && !self.filter.is_empty() => {
+                        let mut filter = self.filter.clone();
+                        filter.pop();
+                        self.set_filter(&filter);
+                    }
+                    (KeyCode::Char(c), Modifiers::NONE) | (KeyCode::Char(c), Modifiers::SHIFT)
+                        if self.type_to_filter =>
+                    {
+                        let mut filter = self.filter.clone();
+                        filter.push(*c);
+                        self.set_filter(&filter);
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            WidgetEvent::Input(InputEvent::Mouse(mouse)) => {
+                let delta = wheel_delta(mouse);
+                if delta != 0 {
+                    self.scroll.move_by(delta, self.matches.len());
+                    return true;
+                }
+                if !is_left_press(mouse, &mut self.last_buttons) {
+                    return false;
+                }
+                let row = match (mouse.y as usize).checked_sub(self.filter_rows()) {
+                    Some(row) => row + self.scroll.top,
+                    None => return true,
+                };
+                if row < self.matches.len() {
+                    if row == self.scroll.selected {
+                        self.activated = self.selected();
+                    } else {
+                        self.scroll.select(row, self.matches.len());
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::{click, key, render, type_text};
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("xyz", "anything"), None);
+        assert!(fuzzy_score("tf", "termwiz-fuzzy").is_some());
+        assert!(fuzzy_score("ter", "termwiz").unwrap() > fuzzy_score("ter", "the error").unwrap());
+    }
+
+    #[test]
+    fn navigate_and_filter() {
+        let list = Rc::new(RefCell::new(
+            ListView::new(vec!["apple", "banana", "cherry", "date", "elderberry"])
+                .with_type_to_filter(),
+        ));
+        let mut ui = Ui::new();
+        let id = ui.set_root(Rc::clone(&list));
+        ui.set_focus(id);
+        let mut surface = Surface::new(12, 3);
+
+        key(&mut ui, KeyCode::End, Modifiers::NONE);
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "cherry",
+    "date",
+    "elderberry",
+]
+"#
+        );
+        assert_eq!(list.borrow().selected(), Some(4));
+        assert!(surface.screen_cells()[2][0].attrs().reverse());
+
+        click(&mut ui, 0, 0);
+        assert_eq!(list.borrow().selected(), Some(2));
+        assert_eq!(list.borrow_mut().take_activated(), None);
+        click(&mut ui, 0, 0);
+        assert_eq!(list.borrow_mut().take_activated(), Some(2));
+
+        type_text(&mut ui, "er");
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "/er",
+    "cherry",
+    "elderberry",
+]
+"#
+        );
+
+        key(&mut ui, KeyCode::DownArrow, Modifiers::NONE);
+        key(&mut ui, KeyCode::Enter, Modifiers::NONE);
+        assert_eq!(list.borrow_mut().take_activated(), Some(4));
+
+        key(&mut ui, KeyCode::Escape, Modifiers::NONE);
+        assert_eq!(list.borrow().filter(), "");
+        assert_eq!(list.borrow().selected(), Some(0));
+    }
+}
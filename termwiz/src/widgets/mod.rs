@@ -6,13 +6,23 @@ use crate::input::InputEvent;
 use crate::surface::{Change, CursorShape, CursorVisibility, Position, SequenceNo, Surface};
 use crate::Result;
 use fnv::FnvHasher;
+use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
 use std::hash::BuildHasherDefault;
+use std::rc::Rc;
 
 /// fnv is a more appropriate hasher for the WidgetIds we use in this module.
 type FnvHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FnvHasher>>;
 
+mod common;
+pub mod dialog;
+pub mod frame;
 pub mod layout;
+pub mod list;
+pub mod progress;
+pub mod table;
+pub mod tabs;
+pub mod text_input;
 
 /// Describes an event that may need to be processed by the widget
 pub enum WidgetEvent {
@@ -75,6 +85,23 @@ pub trait Widget {
     }
 }
 
+/// Allows an app to retain a handle to a widget that it has added to
+/// a `Ui`, so that it can query or update the state of the widget
+/// in between calls to `Ui::process_event_queue`.
+impl<W: Widget> Widget for Rc<RefCell<W>> {
+    fn render(&mut self, args: &mut RenderArgs) {
+        self.borrow_mut().render(args)
+    }
+
+    fn get_size_constraints(&self) -> layout::Constraints {
+        self.borrow().get_size_constraints()
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, args: &mut UpdateArgs) -> bool {
+        self.borrow_mut().process_event(event, args)
+    }
+}
+
 /// Relative to the top left of the parent container
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct ParentRelativeCoords {
@@ -0,0 +1,131 @@
+//! A progress bar widget
+use crate::cell::{unicode_column_width, CellAttributes};
+use crate::surface::{Change, CursorVisibility};
+use crate::widgets::common::put_text;
+use crate::widgets::layout::Constraints;
+use crate::widgets::{RenderArgs, Widget};
+
+/// A single row progress bar.  The completed portion of the bar is
+/// shown in reverse video, overlaid with a centered label that
+/// includes the percentage complete.
+pub struct ProgressBar {
+    progress: f64,
+    label: Option<String>,
+}
+
+impl ProgressBar {
+    pub fn new() -> Self {
+        Self {
+            progress: 0.,
+            label: None,
+        }
+    }
+
+    /// Returns the progress as a fraction in the range 0.0 to 1.0
+    pub fn progress(&self) -> f64 {
+        self.progress
+    }
+
+    /// Sets the progress as a fraction; the value is clamped to
+    /// the range 0.0 to 1.0
+    pub fn set_progress(&mut self, progress: f64) {
+        self.progress = if progress.is_nan() {
+            0.
+        } else {
+            progress.clamp(0., 1.)
+        };
+    }
+
+    /// Sets the text that is displayed before the percentage
+    pub fn set_label<S: Into<String>>(&mut self, label: Option<S>) {
+        self.label = label.map(Into::into);
+    }
+}
+
+impl Widget for ProgressBar {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, _height) = args.surface.dimensions();
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+        args.cursor.visibility = CursorVisibility::Hidden;
+
+        let percent = (self.progress * 100.).round() as usize;
+        let text = match &self.label {
+            Some(label) => format!("{} {}%", label, percent),
+            None => format!("{}%", percent),
+        };
+        let text_width = unicode_column_width(&text, None).min(width);
+        let left_pad = (width - text_width) / 2;
+        let line = format!("{}{}", " ".repeat(left_pad), text);
+
+        // Split the line at the boundary of the completed portion
+        // so that it can be rendered with different attributes
+        let filled = (self.progress * width as f64).round() as usize;
+        let split = line
+            .char_indices()
+            .scan(0, |col, (idx, c)| {
+                let start = *col;
+                *col += unicode_column_width(c.encode_utf8(&mut [0; 4]), None);
+                Some((idx, start))
+            })
+            .find(|&(_, start)| start >= filled)
+            .map(|(idx, _)| idx)
+            .unwrap_or(line.len());
+        let (done, remaining) = line.split_at(split);
+
+        let mut attrs = CellAttributes::default();
+        attrs.set_reverse(true);
+        let done_width = filled.min(width);
+        put_text(args.surface, 0, 0, done_width, done, &attrs);
+        put_text(
+            args.surface,
+            done_width,
+            0,
+            width - done_width,
+            remaining,
+            &CellAttributes::default(),
+        );
+    }
+
+    fn get_size_constraints(&self) -> Constraints {
+        let mut constraints = Constraints::default();
+        constraints.set_fixed_height(1);
+        constraints
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::render;
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn progress() {
+        let bar = Rc::new(RefCell::new(ProgressBar::new()));
+        let mut ui = Ui::new();
+        ui.set_root(Rc::clone(&bar));
+        let mut surface = Surface::new(20, 1);
+
+        bar.borrow_mut().set_label(Some("copying"));
+        bar.borrow_mut().set_progress(0.25);
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "    copying 25%",
+]
+"#
+        );
+        let cells = &surface.screen_cells()[0];
+        assert!(cells[4].attrs().reverse());
+        assert!(!cells[5].attrs().reverse());
+
+        bar.borrow_mut().set_progress(7.);
+        assert_eq!(bar.borrow().progress(), 1.);
+    }
+}
@@ -0,0 +1,259 @@
+//! A table widget that presents rows of data in aligned columns
+use crate::cell::{unicode_column_width, CellAttributes, Intensity};
+use crate::input::{InputEvent, KeyCode, MouseButtons};
+use crate::surface::{Change, CursorVisibility};
+use crate::tabulate::{alignment_padding, compute_column_widths, Alignment, Column};
+use crate::widgets::common::{
+    is_key_press, is_left_press, put_text, truncate_to_width, wheel_delta, ScrollState,
+};
+use crate::widgets::{RenderArgs, UpdateArgs, Widget, WidgetEvent};
+
+/// Displays a header row followed by rows of data, using the same
+/// column width and alignment rules as the `tabout` crate.
+/// Columns are separated by a single space; any that don't fit in
+/// the width of the widget are truncated.
+///
+/// Rows can be selected with the keyboard, mouse and mouse wheel;
+/// pressing Enter, or clicking the row that is already selected,
+/// activates it and makes it available via `Table::take_activated`.
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+    scroll: ScrollState,
+    activated: Option<usize>,
+    last_buttons: MouseButtons,
+}
+
+impl Table {
+    pub fn new(columns: Vec<Column>, rows: Vec<Vec<String>>) -> Self {
+        Self {
+            columns,
+            rows,
+            scroll: ScrollState::default(),
+            activated: None,
+            last_buttons: MouseButtons::NONE,
+        }
+    }
+
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    /// Replaces the rows in the table, retaining the selected index
+    /// if it is still in range.
+    pub fn set_rows(&mut self, rows: Vec<Vec<String>>) {
+        self.rows = rows;
+        self.scroll.select(self.scroll.selected, self.rows.len());
+    }
+
+    /// Returns the index of the selected row, if there are any rows
+    pub fn selected(&self) -> Option<usize> {
+        if self.rows.is_empty() {
+            None
+        } else {
+            Some(self.scroll.selected)
+        }
+    }
+
+    pub fn select(&mut self, row: usize) {
+        self.scroll.select(row, self.rows.len());
+    }
+
+    /// Returns the index of the most recently activated row, if any,
+    /// and clears it.
+    pub fn take_activated(&mut self) -> Option<usize> {
+        self.activated.take()
+    }
+
+    /// Formats a row of cells into a single line of text
+    fn format_row<S: AsRef<str>>(&self, cells: &[S], widths: &[usize]) -> String {
+        let mut line = String::new();
+        for (idx, cell) in cells.iter().enumerate() {
+            if idx > 0 {
+                line.push(' ');
+            }
+            let max_width = widths[idx];
+            let (text, text_width) = truncate_to_width(cell.as_ref(), max_width);
+            let alignment = self
+                .columns
+                .get(idx)
+                .map(|c| c.alignment)
+                .unwrap_or(Alignment::Left);
+            let (left_pad, right_pad) = alignment_padding(text_width, max_width, alignment);
+            line.push_str(&" ".repeat(left_pad));
+            line.push_str(text);
+            line.push_str(&" ".repeat(right_pad));
+        }
+        line
+    }
+}
+
+impl Widget for Table {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, height) = args.surface.dimensions();
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+        args.cursor.visibility = CursorVisibility::Hidden;
+        if height == 0 {
+            return;
+        }
+
+        let widths = compute_column_widths(
+            &self.columns,
+            self.rows
+                .iter()
+                .map(|row| row.iter().map(|cell| unicode_column_width(cell, None))),
+        );
+
+        let names: Vec<&str> = self.columns.iter().map(|c| c.name.as_str()).collect();
+        let mut header_attrs = CellAttributes::default();
+        header_attrs.set_intensity(Intensity::Bold);
+        put_text(
+            args.surface,
+            0,
+            0,
+            width,
+            &self.format_row(&names, &widths),
+            &header_attrs,
+        );
+
+        let rows = height - 1;
+        self.scroll.ensure_visible(rows, self.rows.len());
+        for (idx, row) in self
+            .rows
+            .iter()
+            .enumerate()
+            .skip(self.scroll.top)
+            .take(rows)
+        {
+            let mut attrs = CellAttributes::default();
+            if idx == self.scroll.selected {
+                attrs.set_reverse(true);
+            }
+            put_text(
+                args.surface,
+                0,
+                idx - self.scroll.top + 1,
+                width,
+                &self.format_row(row, &widths),
+                &attrs,
+            );
+        }
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, _args: &mut UpdateArgs) -> bool {
+        match event {
+            WidgetEvent::Input(InputEvent::Key(key)) => {
+                if !is_key_press(key) {
+                    return false;
+                }
+                if self.scroll.process_key(key, self.rows.len()) {
+                    return true;
+                }
+                if key.key == KeyCode::Enter {
+                    self.activated = self.selected();
+                    return true;
+                }
+                false
+            }
+            WidgetEvent::Input(InputEvent::Mouse(mouse)) => {
+                let delta = wheel_delta(mouse);
+                if delta != 0 {
+                    self.scroll.move_by(delta, self.rows.len());
+                    return true;
+                }
+                if !is_left_press(mouse, &mut self.last_buttons) {
+                    return false;
+                }
+                // Row 0 is the header
+                if let Some(row) = (mouse.y as usize).checked_sub(1) {
+                    let row = row + self.scroll.top;
+                    if row < self.rows.len() {
+                        if row == self.scroll.selected {
+                            self.activated = Some(row);
+                        } else {
+                            self.scroll.select(row, self.rows.len());
+                        }
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::Modifiers;
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::{click, key, render};
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn columns_and_selection() {
+        let table = Rc::new(RefCell::new(Table::new(
+            vec![
+                Column {
+                    name: "NAME".to_string(),
+                    alignment: Alignment::Left,
+                },
+                Column {
+                    name: "SIZE".to_string(),
+                    alignment: Alignment::Right,
+                },
+                Column {
+                    name: "KIND".to_string(),
+                    alignment: Alignment::Center,
+                },
+            ],
+            vec![
+                row(&["Cargo.toml", "1234", "file"]),
+                row(&["src", "96", "dir"]),
+                row(&["target", "4096", "dir"]),
+            ],
+        )));
+        let mut ui = Ui::new();
+        let id = ui.set_root(Rc::clone(&table));
+        ui.set_focus(id);
+        let mut surface = Surface::new(24, 3);
+
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "NAME       SIZE KIND",
+    "Cargo.toml 1234 file",
+    "src          96 dir",
+]
+"#
+        );
+
+        key(&mut ui, KeyCode::PageDown, Modifiers::NONE);
+        key(&mut ui, KeyCode::PageDown, Modifiers::NONE);
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "NAME       SIZE KIND",
+    "src          96 dir",
+    "target     4096 dir",
+]
+"#
+        );
+        assert_eq!(table.borrow().selected(), Some(2));
+
+        click(&mut ui, 3, 1);
+        assert_eq!(table.borrow().selected(), Some(1));
+        key(&mut ui, KeyCode::Enter, Modifiers::NONE);
+        assert_eq!(table.borrow_mut().take_activated(), Some(1));
+    }
+}
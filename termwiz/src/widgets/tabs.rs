@@ -0,0 +1,166 @@
+//! A tab bar widget
+use crate::cell::{unicode_column_width, CellAttributes};
+use crate::input::{InputEvent, KeyCode, MouseButtons};
+use crate::surface::{Change, CursorVisibility};
+use crate::widgets::common::{is_key_press, is_left_press, put_text};
+use crate::widgets::layout::Constraints;
+use crate::widgets::{RenderArgs, UpdateArgs, Widget, WidgetEvent};
+
+/// A single row bar of tab titles, with the active tab shown in
+/// reverse video.  The left and right arrow keys and clicking on a
+/// title change the active tab; the app is responsible for showing
+/// the content that corresponds to `Tabs::active`.
+pub struct Tabs {
+    titles: Vec<String>,
+    active: usize,
+    last_buttons: MouseButtons,
+}
+
+impl Tabs {
+    pub fn new<S: Into<String>>(titles: Vec<S>) -> Self {
+        Self {
+            titles: titles.into_iter().map(Into::into).collect(),
+            active: 0,
+            last_buttons: MouseButtons::NONE,
+        }
+    }
+
+    pub fn titles(&self) -> &[String] {
+        &self.titles
+    }
+
+    /// Returns the index of the active tab
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: usize) {
+        self.active = active.min(self.titles.len().saturating_sub(1));
+    }
+
+    /// Returns the text of each tab label along with the column
+    /// at which it starts
+    fn labels(&self) -> impl Iterator<Item = (usize, String)> + '_ {
+        let mut x = 0;
+        self.titles.iter().map(move |title| {
+            let label = format!(" {} ", title);
+            let start = x;
+            x += unicode_column_width(&label, None) + 1;
+            (start, label)
+        })
+    }
+}
+
+impl Widget for Tabs {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, _height) = args.surface.dimensions();
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+        args.cursor.visibility = CursorVisibility::Hidden;
+
+        let labels: Vec<(usize, String)> = self.labels().collect();
+        for (idx, (x, label)) in labels.into_iter().enumerate() {
+            if x >= width {
+                break;
+            }
+            let mut attrs = CellAttributes::default();
+            if idx == self.active {
+                attrs.set_reverse(true);
+            }
+            let label_width = unicode_column_width(&label, None).min(width - x);
+            put_text(args.surface, x, 0, label_width, &label, &attrs);
+            if x + label_width < width {
+                put_text(
+                    args.surface,
+                    x + label_width,
+                    0,
+                    1,
+                    "│",
+                    &CellAttributes::default(),
+                );
+            }
+        }
+    }
+
+    fn get_size_constraints(&self) -> Constraints {
+        let mut constraints = Constraints::default();
+        constraints.set_fixed_height(1);
+        constraints
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, _args: &mut UpdateArgs) -> bool {
+        match event {
+            WidgetEvent::Input(InputEvent::Key(key)) => {
+                if !is_key_press(key) || self.titles.is_empty() {
+                    return false;
+                }
+                match key.key {
+                    KeyCode::LeftArrow | KeyCode::ApplicationLeftArrow => {
+                        self.active = self.active.checked_sub(1).unwrap_or(self.titles.len() - 1)
+                    }
+                    KeyCode::RightArrow | KeyCode::ApplicationRightArrow => {
+                        self.active = (self.active + 1) % self.titles.len()
+                    }
+                    _ => return false,
+                }
+                true
+            }
+            WidgetEvent::Input(InputEvent::Mouse(mouse)) => {
+                if !is_left_press(mouse, &mut self.last_buttons) {
+                    return false;
+                }
+                let x = mouse.x as usize;
+                let clicked = self.labels().position(|(start, label)| {
+                    x >= start && x < start + unicode_column_width(&label, None)
+                });
+                if let Some(idx) = clicked {
+                    self.active = idx;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::Modifiers;
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::{click, key, render};
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn switch_tabs() {
+        let tabs = Rc::new(RefCell::new(Tabs::new(vec!["one", "two", "three"])));
+        let mut ui = Ui::new();
+        let id = ui.set_root(Rc::clone(&tabs));
+        ui.set_focus(id);
+        let mut surface = Surface::new(20, 1);
+
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    " one │ two │ three │",
+]
+"#
+        );
+        assert!(surface.screen_cells()[0][1].attrs().reverse());
+
+        key(&mut ui, KeyCode::LeftArrow, Modifiers::NONE);
+        assert_eq!(tabs.borrow().active(), 2);
+        key(&mut ui, KeyCode::RightArrow, Modifiers::NONE);
+        assert_eq!(tabs.borrow().active(), 0);
+
+        click(&mut ui, 8, 0);
+        assert_eq!(tabs.borrow().active(), 1);
+        render(&mut ui, &mut surface);
+        assert!(!surface.screen_cells()[0][1].attrs().reverse());
+        assert!(surface.screen_cells()[0][7].attrs().reverse());
+    }
+}
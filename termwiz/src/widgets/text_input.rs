@@ -0,0 +1,263 @@
+//! A single line text input widget
+use crate::cell::{unicode_column_width, CellAttributes, Intensity};
+use crate::input::{InputEvent, KeyCode, Modifiers, MouseButtons};
+use crate::lineedit::{LineEditBuffer, Movement};
+use crate::surface::{Change, CursorShape, CursorVisibility};
+use crate::widgets::common::{is_key_press, is_left_press, put_text, truncate_to_width};
+use crate::widgets::layout::Constraints;
+use crate::widgets::{RenderArgs, UpdateArgs, Widget, WidgetEvent};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A single line text input field.
+/// The editing keys are a subset of those supported by `LineEditor`;
+/// pressing Enter makes the current text available via
+/// `TextInput::take_submitted`.
+pub struct TextInput {
+    buffer: LineEditBuffer,
+    placeholder: Option<String>,
+    submitted: Option<String>,
+    /// The column at which the visible portion of the line starts
+    scroll: usize,
+    last_buttons: MouseButtons,
+}
+
+impl TextInput {
+    pub fn new() -> Self {
+        Self {
+            buffer: LineEditBuffer::default(),
+            placeholder: None,
+            submitted: None,
+            scroll: 0,
+            last_buttons: MouseButtons::NONE,
+        }
+    }
+
+    /// Sets the text that is displayed while the input is empty
+    pub fn with_placeholder<S: Into<String>>(mut self, placeholder: S) -> Self {
+        self.placeholder = Some(placeholder.into());
+        self
+    }
+
+    /// Returns the current text
+    pub fn text(&self) -> &str {
+        self.buffer.get_line()
+    }
+
+    /// Replaces the current text and places the cursor at its end
+    pub fn set_text(&mut self, text: &str) {
+        self.buffer.set_line_and_cursor(text, text.len());
+    }
+
+    /// Returns the text that was most recently submitted by
+    /// pressing Enter, if any, and clears it.
+    pub fn take_submitted(&mut self) -> Option<String> {
+        self.submitted.take()
+    }
+
+    /// Moves the cursor to the grapheme that occupies the
+    /// specified column of the line.
+    fn set_cursor_column(&mut self, column: usize) {
+        let line = self.buffer.get_line();
+        let mut cursor = line.len();
+        let mut used = 0;
+        for (idx, grapheme) in line.grapheme_indices(true) {
+            let width = unicode_column_width(grapheme, None);
+            if used + width > column {
+                cursor = idx;
+                break;
+            }
+            used += width;
+        }
+        let line = line.to_string();
+        self.buffer.set_line_and_cursor(&line, cursor);
+    }
+}
+
+impl Widget for TextInput {
+    fn render(&mut self, args: &mut RenderArgs) {
+        let (width, _height) = args.surface.dimensions();
+        args.surface
+            .add_change(Change::ClearScreen(Default::default()));
+
+        let line = self.buffer.get_line();
+        if line.is_empty() {
+            if let Some(placeholder) = &self.placeholder {
+                let mut attrs = CellAttributes::default();
+                attrs.set_intensity(Intensity::Half);
+                put_text(args.surface, 0, 0, width, placeholder, &attrs);
+            }
+        }
+
+        // Scroll horizontally so that the cursor remains visible
+        let cursor_col = unicode_column_width(&line[..self.buffer.get_cursor()], None);
+        if cursor_col < self.scroll {
+            self.scroll = cursor_col;
+        } else if width > 0 && cursor_col >= self.scroll + width {
+            self.scroll = cursor_col + 1 - width;
+        }
+
+        if !line.is_empty() {
+            let mut col = 0;
+            let mut start = line.len();
+            for (idx, grapheme) in line.grapheme_indices(true) {
+                if col >= self.scroll {
+                    start = idx;
+                    break;
+                }
+                col += unicode_column_width(grapheme, None);
+            }
+            let (visible, _) = truncate_to_width(&line[start..], width);
+            put_text(
+                args.surface,
+                0,
+                0,
+                width,
+                visible,
+                &CellAttributes::default(),
+            );
+        }
+
+        args.cursor.shape = CursorShape::SteadyBar;
+        args.cursor.visibility = if args.is_focused {
+            CursorVisibility::Visible
+        } else {
+            CursorVisibility::Hidden
+        };
+        args.cursor.coords = (cursor_col - self.scroll, 0).into();
+    }
+
+    fn get_size_constraints(&self) -> Constraints {
+        let mut constraints = Constraints::default();
+        constraints.set_fixed_height(1);
+        constraints
+    }
+
+    fn process_event(&mut self, event: &WidgetEvent, _args: &mut UpdateArgs) -> bool {
+        match event {
+            WidgetEvent::Input(InputEvent::Key(key)) => {
+                if !is_key_press(key) {
+                    return false;
+                }
+                match (&key.key, key.modifiers) {
+                    (KeyCode::Char(c), Modifiers::NONE) | (KeyCode::Char(c), Modifiers::SHIFT) => {
+                        self.buffer.insert_char(*c)
+                    }
+                    (KeyCode::Enter, Modifiers::NONE) => {
+                        self.submitted = Some(self.buffer.get_line().to_string());
+                    }
+                    (KeyCode::Backspace, _) => self
+                        .buffer
+                        .kill_text(Movement::BackwardChar(1), Movement::BackwardChar(1)),
+                    (KeyCode::Delete, _) => self
+                        .buffer
+                        .kill_text(Movement::ForwardChar(1), Movement::None),
+                    (KeyCode::LeftArrow, Modifiers::CTRL)
+                    | (KeyCode::Char('b'), Modifiers::ALT) => {
+                        self.buffer.exec_movement(Movement::BackwardWord(1))
+                    }
+                    (KeyCode::RightArrow, Modifiers::CTRL)
+                    | (KeyCode::Char('f'), Modifiers::ALT) => {
+                        self.buffer.exec_movement(Movement::ForwardWord(1))
+                    }
+                    (KeyCode::LeftArrow, _) | (KeyCode::Char('b'), Modifiers::CTRL) => {
+                        self.buffer.exec_movement(Movement::BackwardChar(1))
+                    }
+                    (KeyCode::RightArrow, _) | (KeyCode::Char('f'), Modifiers::CTRL) => {
+                        self.buffer.exec_movement(Movement::ForwardChar(1))
+                    }
+                    (KeyCode::Home, _) | (KeyCode::Char('a'), Modifiers::CTRL) => {
+                        self.buffer.exec_movement(Movement::StartOfLine)
+                    }
+                    (KeyCode::End, _) | (KeyCode::Char('e'), Modifiers::CTRL) => {
+                        self.buffer.exec_movement(Movement::EndOfLine)
+                    }
+                    (KeyCode::Char('u'), Modifiers::CTRL) => self
+                        .buffer
+                        .kill_text(Movement::StartOfLine, Movement::StartOfLine),
+                    (KeyCode::Char('k'), Modifiers::CTRL) => {
+                        self.buffer.kill_text(Movement::EndOfLine, Movement::None)
+                    }
+                    (KeyCode::Char('w'), Modifiers::CTRL) => self
+                        .buffer
+                        .kill_text(Movement::BackwardWord(1), Movement::BackwardWord(1)),
+                    _ => return false,
+                }
+                true
+            }
+            WidgetEvent::Input(InputEvent::Paste(text)) => {
+                // Only a single line can be edited; fold newlines into spaces
+                let text = text.replace("\r\n", " ").replace(['\r', '\n'], " ");
+                self.buffer.insert_text(&text);
+                true
+            }
+            WidgetEvent::Input(InputEvent::Mouse(mouse))
+                if is_left_press(mouse, &mut self.last_buttons) =>
+            {
+                self.set_cursor_column(self.scroll + mouse.x as usize);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::surface::Surface;
+    use crate::widgets::common::test_util::{click, key, render, type_text};
+    use crate::widgets::Ui;
+    use k9::snapshot;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn edit_and_submit() {
+        let input = Rc::new(RefCell::new(TextInput::new().with_placeholder("name")));
+        let mut ui = Ui::new();
+        let id = ui.set_root(Rc::clone(&input));
+        ui.set_focus(id);
+        let mut surface = Surface::new(10, 1);
+
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "name",
+]
+"#
+        );
+
+        type_text(&mut ui, "hello world");
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "llo world",
+]
+"#
+        );
+        assert_eq!(surface.cursor_position(), (9, 0));
+
+        key(&mut ui, KeyCode::Char('a'), Modifiers::CTRL);
+        key(&mut ui, KeyCode::Char('f'), Modifiers::ALT);
+        type_text(&mut ui, "big ");
+        snapshot!(
+            render(&mut ui, &mut surface),
+            r#"
+[
+    "llo big wo",
+]
+"#
+        );
+
+        click(&mut ui, 0, 0);
+        key(&mut ui, KeyCode::Backspace, Modifiers::NONE);
+        key(&mut ui, KeyCode::Enter, Modifiers::NONE);
+        assert_eq!(
+            input.borrow_mut().take_submitted().as_deref(),
+            Some("hllo big world")
+        );
+        assert_eq!(input.borrow_mut().take_submitted(), None);
+    }
+}
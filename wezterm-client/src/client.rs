@@ -7,7 +7,7 @@ use codec::*;
 use config::{configuration, SshDomain, TlsDomainClient, UnixDomain, UnixTarget};
 use filedescriptor::FileDescriptor;
 use futures::FutureExt;
-use mux::client::ClientId;
+use mux::client::{ClientId, ClientPermission};
 use mux::connui::ConnectionUI;
 use mux::domain::DomainId;
 use mux::pane::PaneId;
@@ -30,6 +30,7 @@ use std::os::unix::process::CommandExt;
 #[cfg(windows)]
 use std::os::windows::io::{AsRawSocket, AsSocket, BorrowedSocket, RawSocket};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use thiserror::Error;
@@ -57,6 +58,10 @@ pub struct Client {
     local_domain_id: Option<DomainId>,
     pub client_id: ClientId,
     client_domain_config: ClientDomainConfig,
+    permission: Arc<Mutex<ClientPermission>>,
+    /// The capabilities that we have in common with the server,
+    /// if we have negotiated them
+    capabilities: Arc<Mutex<Option<Capabilities>>>,
     pub is_reconnectable: bool,
     pub is_local: bool,
 }
@@ -77,6 +82,26 @@ pub struct IncompatibleVersionError {
     pub codec_vers: usize,
 }
 
+impl IncompatibleVersionError {
+    /// Returns an error unless the server uses our codec version.
+    /// Negotiated capabilities only gate new types of Pdu; changes to
+    /// the existing types bump the codec version and are not negotiable.
+    fn check(info: &GetCodecVersionResponse) -> Result<(), Self> {
+        if info.codec_vers == CODEC_VERSION {
+            Ok(())
+        } else {
+            Err(Self {
+                version: info.version_string.clone(),
+                codec_vers: info.codec_vers,
+            })
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("The server does not support {0}; it may need to be upgraded")]
+pub struct UnsupportedByServerError(&'static str);
+
 macro_rules! rpc {
     ($method_name:ident, $request_type:ident, $response_type:ident) => {
         pub async fn $method_name(&self, pdu: $request_type) -> anyhow::Result<$response_type> {
@@ -1033,7 +1058,69 @@ impl Reconnectable {
                 })?,
         ))?);
         ui.output_str("TLS Connected!\n");
-        Ok(stream)
+        match &tls_client.udp_remote_address {
+            Some(udp_remote_address) => self.start_udp_session(stream, udp_remote_address, ui),
+            None => Ok(stream),
+        }
+    }
+
+    /// Asks the server to set up a datagram session using the freshly
+    /// authenticated `stream`, and then switches over to that session.
+    /// If the server doesn't offer datagram sessions, we continue to
+    /// use `stream`.
+    fn start_udp_session(
+        &self,
+        mut stream: Box<dyn AsyncReadAndWrite>,
+        udp_remote_address: &str,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<Box<dyn AsyncReadAndWrite>> {
+        let mut request = |pdu: Pdu, serial: u64| {
+            block_on(async {
+                pdu.encode_async(&mut stream, serial).await?;
+                stream.flush().await?;
+                Pdu::decode_async(&mut stream, Some(serial)).await
+            })
+        };
+
+        let decoded = request(
+            Pdu::NegotiateCapabilities(NegotiateCapabilities {
+                capabilities: Capabilities::UDP_SESSIONS,
+            }),
+            1,
+        )
+        .context("negotiating capabilities")?;
+        let offered = match decoded.pdu {
+            Pdu::NegotiateCapabilitiesResponse(response) => response.capabilities,
+            _ => Capabilities::empty(),
+        };
+        if !offered.contains(Capabilities::UDP_SESSIONS) {
+            log::warn!(
+                "server is not listening for datagrams; continuing over TLS \
+                 rather than {}",
+                udp_remote_address
+            );
+            return Ok(stream);
+        }
+
+        let decoded = request(Pdu::StartUdpSession(StartUdpSession {}), 2)
+            .context("requesting a datagram session")?;
+        match decoded.pdu {
+            Pdu::StartUdpSessionResponse(StartUdpSessionResponse { session_id, key }) => {
+                ui.output_str(&format!(
+                    "Switching to datagrams via {}\n",
+                    udp_remote_address
+                ));
+                Ok(Box::new(crate::udp::connect(
+                    udp_remote_address,
+                    session_id,
+                    key,
+                )?))
+            }
+            Pdu::ErrorResponse(ErrorResponse { reason }) => {
+                bail!("server refused datagram session: {}", reason)
+            }
+            pdu => bail!("unexpected response {} to StartUdpSession", pdu.pdu_name()),
+        }
     }
 }
 
@@ -1138,6 +1225,8 @@ impl Client {
             is_local,
             client_id,
             client_domain_config,
+            permission: Arc::new(Mutex::new(ClientPermission::default())),
+            capabilities: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -1145,6 +1234,76 @@ impl Client {
         self.client_domain_config
     }
 
+    /// Returns the permission that the server granted to this client
+    pub fn permission(&self) -> ClientPermission {
+        *self.permission.lock().unwrap()
+    }
+
+    /// Tell the server who we are and the permission that we'd like
+    /// for this session, recording the permission that it granted.
+    /// This must be repeated after reconnecting, as the server side
+    /// session state is not retained across connections.
+    pub async fn set_client_identity(&self) -> anyhow::Result<ClientPermission> {
+        let requested = if self.client_domain_config.read_only() {
+            ClientPermission::ReadOnly
+        } else {
+            ClientPermission::ReadWrite
+        };
+        let response = self
+            .set_client_id(SetClientId {
+                client_id: self.client_id.clone(),
+                is_proxy: false,
+                permission: requested,
+            })
+            .await?;
+        *self.permission.lock().unwrap() = response.permission;
+        if response.permission != requested {
+            log::warn!(
+                "requested a {} session but the server granted {}",
+                requested,
+                response.permission
+            );
+        }
+        Ok(response.permission)
+    }
+
+    /// Returns the capabilities that we have in common with the server,
+    /// negotiating them if we have not yet done so
+    pub async fn capabilities(&self) -> anyhow::Result<Capabilities> {
+        let capabilities = *self.capabilities.lock().unwrap();
+        match capabilities {
+            Some(capabilities) => Ok(capabilities),
+            None => self.negotiate_capabilities().await,
+        }
+    }
+
+    /// Advertise our capabilities to the server, recording those that
+    /// we have in common.  This must be repeated after reconnecting,
+    /// as the server side session state is not retained.
+    pub async fn negotiate_capabilities(&self) -> anyhow::Result<Capabilities> {
+        let supported = Capabilities::all();
+        let capabilities = match self
+            .send_pdu_unchecked(Pdu::NegotiateCapabilities(NegotiateCapabilities {
+                capabilities: supported,
+            }))
+            .await?
+        {
+            Pdu::NegotiateCapabilitiesResponse(response) => supported & response.capabilities,
+            Pdu::ErrorResponse(ErrorResponse { reason }) => {
+                // The server predates capability negotiation
+                log::debug!("server did not negotiate capabilities: {}", reason);
+                Capabilities::empty()
+            }
+            pdu => bail!(
+                "unexpected response {} to NegotiateCapabilities",
+                pdu.pdu_name()
+            ),
+        };
+        log::trace!("negotiated capabilities {:?}", capabilities);
+        self.capabilities.lock().unwrap().replace(capabilities);
+        Ok(capabilities)
+    }
+
     pub async fn verify_version_compat(
         &self,
         ui: &ConnectionUI,
@@ -1157,28 +1316,21 @@ impl Client {
             })
             .await
         {
-            Ok(info) if info.codec_vers == CODEC_VERSION => {
+            Ok(info) => {
+                if let Err(err) = IncompatibleVersionError::check(&info) {
+                    ui.output_str(&err.to_string());
+                    log::error!("{:?}", err);
+                    return Err(err.into());
+                }
                 log::trace!(
                     "Server version is {} (codec version {})",
                     info.version_string,
                     info.codec_vers
                 );
-                self.set_client_id(SetClientId {
-                    client_id: self.client_id.clone(),
-                    is_proxy: false,
-                })
-                .await?;
+                self.negotiate_capabilities().await?;
+                self.set_client_identity().await?;
                 Ok(info)
             }
-            Ok(info) => {
-                let err = IncompatibleVersionError {
-                    version: info.version_string,
-                    codec_vers: info.codec_vers,
-                };
-                ui.output_str(&err.to_string());
-                log::error!("{:?}", err);
-                return Err(err.into());
-            }
             Err(err) => {
                 log::trace!("{:?}", err);
                 let msg = if err.root_cause().is::<Timeout>() {
@@ -1254,6 +1406,15 @@ impl Client {
         }
     }
 
+    /// Create a client that communicates with a server over a stream
+    /// that is already connected, such as one end of a socket pair.
+    /// `config` describes the domain for the purposes of reconnection
+    /// and credentials; a client created this way has no local domain,
+    /// so it doesn't try to reconnect if the stream is closed.
+    pub fn new_with_stream(config: ClientDomainConfig, stream: Box<dyn AsyncReadAndWrite>) -> Self {
+        Self::new(None, Reconnectable::new(config, Some(stream)))
+    }
+
     pub fn new_default_unix_domain(
         initial: bool,
         ui: &mut ConnectionUI,
@@ -1302,6 +1463,14 @@ impl Client {
     }
 
     pub async fn send_pdu(&self, pdu: Pdu) -> anyhow::Result<Pdu> {
+        let required = pdu.required_capabilities();
+        if !required.is_empty() && !self.capabilities().await?.contains(required) {
+            return Err(UnsupportedByServerError(pdu.pdu_name()).into());
+        }
+        self.send_pdu_unchecked(pdu).await
+    }
+
+    async fn send_pdu_unchecked(&self, pdu: Pdu) -> anyhow::Result<Pdu> {
         let (promise, rx) = bounded(1);
         self.sender
             .send(ReaderMessage::SendPdu { pdu, promise })
@@ -1373,7 +1542,7 @@ impl Client {
         SearchScrollbackResponse
     );
     rpc!(kill_pane, KillPane, UnitResponse);
-    rpc!(set_client_id, SetClientId, UnitResponse);
+    rpc!(set_client_id, SetClientId, SetClientIdResponse);
     rpc!(list_clients, GetClientList = (), GetClientListResponse);
     rpc!(set_window_workspace, SetWindowWorkspace, UnitResponse);
     rpc!(set_focused_pane_id, SetFocusedPane, UnitResponse);
@@ -1389,4 +1558,35 @@ impl Client {
         GetPaneDirectionResponse
     );
     rpc!(adjust_pane_size, AdjustPaneSize, UnitResponse);
+    rpc!(sync_pane, SyncPane, SyncPaneResponse);
+    rpc!(get_metrics, GetMetrics = (), GetMetricsResponse);
+    rpc!(set_pane_output_log, SetPaneOutputLog, UnitResponse);
+    rpc!(set_pane_output_pipe, SetPaneOutputPipe, UnitResponse);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn server_info(codec_vers: usize) -> GetCodecVersionResponse {
+        GetCodecVersionResponse {
+            codec_vers,
+            version_string: "20240203-110809-5046fc22".to_string(),
+            executable_path: "/usr/bin/wezterm-mux-server".into(),
+            config_file_path: None,
+        }
+    }
+
+    #[test]
+    fn codec_version_must_match() {
+        assert!(IncompatibleVersionError::check(&server_info(CODEC_VERSION)).is_ok());
+
+        // Negotiating capabilities doesn't make up for changes to the
+        // existing types, so any other version is refused
+        for codec_vers in [CODEC_VERSION - 1, CODEC_VERSION + 1] {
+            let err = IncompatibleVersionError::check(&server_info(codec_vers)).unwrap_err();
+            assert_eq!(err.codec_vers, codec_vers);
+            assert_eq!(err.version, "20240203-110809-5046fc22");
+        }
+    }
 }
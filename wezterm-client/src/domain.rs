@@ -29,6 +29,12 @@ pub struct ClientInner {
 }
 
 impl ClientInner {
+    /// Returns true if the server granted only read-only access,
+    /// in which case input, resizing and killing are not sent
+    pub fn is_read_only(&self) -> bool {
+        self.client.permission().is_read_only()
+    }
+
     fn remote_to_local_window(&self, remote_window_id: WindowId) -> Option<WindowId> {
         let map = self.remote_to_local_window.lock().unwrap();
         map.get(&remote_window_id).cloned()
@@ -228,6 +234,14 @@ impl ClientDomainConfig {
             ClientDomainConfig::Ssh(ssh) => ssh.connect_automatically,
         }
     }
+
+    pub fn read_only(&self) -> bool {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.read_only,
+            ClientDomainConfig::Tls(tls) => tls.read_only,
+            ClientDomainConfig::Ssh(ssh) => ssh.read_only,
+        }
+    }
 }
 
 impl ClientInner {
@@ -466,8 +480,36 @@ impl ClientDomain {
     pub async fn reattach(domain_id: DomainId, ui: ConnectionUI) -> anyhow::Result<()> {
         let inner = Self::get_client_inner_for_domain(domain_id)?;
 
+        inner.client.negotiate_capabilities().await?;
+        inner.client.set_client_identity().await?;
         let panes = inner.client.list_panes().await?;
-        Self::process_pane_list(inner, panes, None)?;
+        Self::process_pane_list(Arc::clone(&inner), panes, None)?;
+
+        for pane in Mux::get().iter_panes() {
+            if pane.domain_id() != inner.local_domain_id {
+                continue;
+            }
+            if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                // The server discarded the sizes that we requested in the
+                // prior session, so tell it about them again
+                client_pane.resend_requested_size();
+
+                // Catch up on whatever happened while we were disconnected
+                // in a single round trip, rather than lazily fetching lines
+                // as they are rendered
+                promise::spawn::spawn(async move {
+                    if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                        if let Err(err) = client_pane.sync_with_server().await {
+                            log::error!(
+                                "failed to sync pane {} after reconnecting: {err:#}",
+                                client_pane.remote_pane_id
+                            );
+                        }
+                    }
+                })
+                .detach();
+            }
+        }
 
         ui.close();
         Ok(())
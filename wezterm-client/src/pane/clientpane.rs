@@ -5,7 +5,7 @@ use anyhow::bail;
 use async_trait::async_trait;
 use codec::*;
 use config::configuration;
-use config::keyassignment::ScrollbackEraseMode;
+use config::keyassignment::{PaneOutputLog, PaneOutputPipe, ScrollbackEraseMode};
 use mux::domain::DomainId;
 use mux::pane::{
     alloc_pane_id, CachePolicy, CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId,
@@ -21,8 +21,8 @@ use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::ops::Range;
 use std::sync::Arc;
-use termwiz::input::KeyEvent;
-use termwiz::surface::SequenceNo;
+use termwiz::input::{KeyEvent, KeyEventType};
+use termwiz::surface::{SequenceNo, SEQ_ZERO};
 use url::Url;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
@@ -49,6 +49,12 @@ pub struct ClientPane {
     config: Mutex<Option<Arc<dyn TerminalConfiguration>>>,
     unseen_output: Mutex<bool>,
     progress: Mutex<Progress>,
+    /// The size that we most recently asked the server to use.
+    /// The server may choose a different size when multiple clients
+    /// are attached, so we track this separately from the dimensions
+    /// reported by the server in order to avoid re-requesting our
+    /// size every time it reports something different.
+    requested_size: Mutex<Option<TerminalSize>>,
 }
 
 impl ClientPane {
@@ -128,6 +134,7 @@ impl ClientPane {
             mouse_grabbed: Mutex::new(false),
             ignore_next_kill: Mutex::new(false),
             unseen_output: Mutex::new(false),
+            requested_size: Mutex::new(None),
             user_vars: Mutex::new(HashMap::new()),
             config: Mutex::new(None),
             progress: Mutex::new(Progress::default()),
@@ -169,15 +176,7 @@ impl ClientPane {
                 }
             },
             Pdu::SetPalette(SetPalette { palette, .. }) => {
-                *self.application_palette.lock() = palette != *self.configured_palette.lock();
-
-                *self.palette.lock() = palette;
-                let mux = Mux::get();
-                self.renderable.lock().inner.borrow_mut().make_all_stale();
-                mux.notify(MuxNotification::Alert {
-                    pane_id: self.local_pane_id,
-                    alert: Alert::PaletteChanged,
-                });
+                self.apply_palette(palette);
             }
             Pdu::NotifyAlert(NotifyAlert { alert, .. }) => {
                 let mux = Mux::get();
@@ -236,6 +235,58 @@ impl ClientPane {
         Ok(())
     }
 
+    fn apply_palette(&self, palette: ColorPalette) {
+        *self.application_palette.lock() = palette != *self.configured_palette.lock();
+
+        *self.palette.lock() = palette;
+        let mux = Mux::get();
+        self.renderable.lock().inner.borrow_mut().make_all_stale();
+        mux.notify(MuxNotification::Alert {
+            pane_id: self.local_pane_id,
+            alert: Alert::PaletteChanged,
+        });
+    }
+
+    /// Fetch the state of the pane from the server in a single round
+    /// trip.  If we have already received updates for this pane, only
+    /// the lines that have changed since then are transferred.
+    pub async fn sync_with_server(&self) -> anyhow::Result<()> {
+        let capabilities = self.client.client.capabilities().await?;
+        if !capabilities.contains(Capabilities::SYNC_PANE) {
+            // Fetch the lines lazily as they are rendered instead
+            self.renderable.lock().inner.borrow_mut().make_all_stale();
+            return Ok(());
+        }
+
+        let seqno = self.renderable.lock().inner.borrow().seqno;
+        let since_seqno = if seqno == SEQ_ZERO { None } else { Some(seqno) };
+
+        let mut sync = self
+            .client
+            .client
+            .sync_pane(SyncPane {
+                pane_id: self.remote_pane_id,
+                scrollback_lines: configuration().mux_sync_scrollback_lines,
+                since_seqno,
+            })
+            .await?;
+
+        *self.mouse_grabbed.lock() = sync.mouse_grabbed;
+        if sync.palette != *self.palette.lock() {
+            self.apply_palette(sync.palette.clone());
+        }
+
+        let lines = std::mem::take(&mut sync.lines);
+        let lines = hydrate_lines(Arc::clone(&self.client), sync.pane_id, lines).await;
+
+        self.renderable
+            .lock()
+            .inner
+            .borrow_mut()
+            .apply_sync(sync, lines);
+        Ok(())
+    }
+
     pub fn remote_pane_id(&self) -> TabId {
         self.remote_pane_id
     }
@@ -250,6 +301,16 @@ impl ClientPane {
     pub fn ignore_next_kill(&self) {
         *self.ignore_next_kill.lock() = true;
     }
+
+    /// Send the most recently requested size to the server again.
+    /// Used after reconnecting, as the server forgets the sizes
+    /// requested by a session when it disconnects.
+    pub fn resend_requested_size(&self) {
+        let size = self.requested_size.lock().take();
+        if let Some(size) = size {
+            self.resize(size).ok();
+        }
+    }
 }
 
 #[async_trait(?Send)]
@@ -271,6 +332,10 @@ impl Pane for ClientPane {
             Value::String("since_last_response_ms".to_string()),
             Value::U64(inner.last_recv_time.elapsed().as_millis() as u64),
         );
+        map.insert(
+            Value::String("is_read_only".to_string()),
+            Value::Bool(self.client.is_read_only()),
+        );
 
         Value::Object(map.into())
     }
@@ -292,7 +357,9 @@ impl Pane for ClientPane {
         lines: Range<StableRowIndex>,
         for_line: &mut dyn ForEachPaneLogicalLine,
     ) {
-        mux::pane::impl_for_each_logical_line_via_get_logical_lines(self, lines, for_line);
+        self.renderable
+            .lock()
+            .for_each_logical_line_mut(lines, for_line);
     }
 
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
@@ -330,6 +397,9 @@ impl Pane for ClientPane {
     }
 
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
+        if self.client.is_read_only() {
+            return Ok(());
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         self.renderable
@@ -387,17 +457,20 @@ impl Pane for ClientPane {
     }
 
     fn resize(&self, size: TerminalSize) -> anyhow::Result<()> {
-        let render = self.renderable.lock();
-        let mut inner = render.inner.borrow_mut();
+        if self.client.is_read_only() {
+            // The size is dictated by the read-write clients
+            return Ok(());
+        }
+        let mut requested_size = self.requested_size.lock();
+        if *requested_size != Some(size) {
+            requested_size.replace(size);
 
-        let cols = size.cols as usize;
-        let rows = size.rows as usize;
+            let render = self.renderable.lock();
+            let mut inner = render.inner.borrow_mut();
+
+            let cols = size.cols as usize;
+            let rows = size.rows as usize;
 
-        if inner.dimensions.cols != cols
-            || inner.dimensions.viewport_rows != rows
-            || inner.dimensions.pixel_width != size.pixel_width
-            || inner.dimensions.pixel_height != size.pixel_height
-        {
             inner.dimensions.cols = cols;
             inner.dimensions.viewport_rows = rows;
             inner.dimensions.pixel_width = size.pixel_width;
@@ -448,6 +521,9 @@ impl Pane for ClientPane {
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        if self.client.is_read_only() {
+            return Ok(());
+        }
         let input_serial;
         {
             let renderable = self.renderable.lock();
@@ -466,6 +542,8 @@ impl Pane for ClientPane {
                     event: KeyEvent {
                         key,
                         modifiers: mods,
+                        event_type: KeyEventType::Press,
+                        kitty: None,
                     },
                     input_serial,
                 })
@@ -497,7 +575,8 @@ impl Pane for ClientPane {
         // in the domain, so we need to check here whether the domain is
         // in the detached state; if so then we must skip sending the
         // kill to the server.
-        let mut send_kill = true;
+        // Read-only clients are not permitted to kill remote panes.
+        let mut send_kill = !self.client.is_read_only();
 
         {
             let mux = Mux::get();
@@ -522,6 +601,9 @@ impl Pane for ClientPane {
     }
 
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
+        if self.client.is_read_only() {
+            return Ok(());
+        }
         self.mouse.lock().append(event);
         if MouseState::next(Arc::clone(&self.mouse)) {
             self.renderable.lock().inner.borrow_mut().update_last_send();
@@ -562,6 +644,9 @@ impl Pane for ClientPane {
     }
 
     fn erase_scrollback(&self, erase_mode: ScrollbackEraseMode) {
+        if self.client.is_read_only() {
+            return;
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         promise::spawn::spawn(async move {
@@ -576,6 +661,56 @@ impl Pane for ClientPane {
         .detach();
     }
 
+    /// The log is written by the server, as that is where the
+    /// output of the pane is read
+    fn set_output_log(&self, log: Option<&PaneOutputLog>) -> anyhow::Result<()> {
+        if self.client.is_read_only() {
+            anyhow::bail!("logging pane output is not permitted for a read-only client");
+        }
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        let log = log.cloned();
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .set_pane_output_log(SetPaneOutputLog {
+                    pane_id: remote_pane_id,
+                    log,
+                })
+                .await
+            {
+                log::error!("pane {}: {:#}", remote_pane_id, err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
+    /// The command is run by the server, as that is where the
+    /// output of the pane is read
+    fn set_output_pipe(&self, pipe: Option<&PaneOutputPipe>) -> anyhow::Result<()> {
+        if self.client.is_read_only() {
+            anyhow::bail!("piping pane output is not permitted for a read-only client");
+        }
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        let pipe = pipe.cloned();
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .set_pane_output_pipe(SetPaneOutputPipe {
+                    pane_id: remote_pane_id,
+                    pipe,
+                })
+                .await
+            {
+                log::error!("pane {}: {:#}", remote_pane_id, err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
     fn advise_focus(&self) {
         let mut focused_pane = self.client.focused_remote_pane_id.lock().unwrap();
         if *focused_pane != Some(self.remote_pane_id) {
@@ -648,6 +783,12 @@ struct PaneWriter {
 
 impl std::io::Write for PaneWriter {
     fn write(&mut self, data: &[u8]) -> Result<usize, std::io::Error> {
+        if self.client.is_read_only() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "this client is read-only",
+            ));
+        }
         promise::spawn::block_on(self.client.client.write_to_pane(WriteToPane {
             pane_id: self.remote_pane_id,
             data: data.to_vec(),
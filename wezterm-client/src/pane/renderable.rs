@@ -2,9 +2,10 @@ use crate::domain::ClientInner;
 use crate::pane::clientpane::ClientPane;
 use anyhow::anyhow;
 use codec::*;
-use config::{configuration, ConfigHandle};
+use config::configuration;
 use lru::LruCache;
-use mux::pane::PaneId;
+use mux::localecho::LocalEcho;
+use mux::pane::{ForEachPaneLogicalLine, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::Mux;
 use promise::BrokenPromise;
@@ -17,7 +18,7 @@ use std::ops::Range;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use termwiz::cell::{Cell, CellAttributes, Underline};
+use termwiz::cell::CellAttributes;
 use termwiz::color::AnsiColor;
 use termwiz::image::{ImageCell, ImageData};
 use termwiz::surface::{SequenceNo, SEQ_ZERO};
@@ -43,6 +44,20 @@ enum LineEntry {
 }
 
 impl LineEntry {
+    fn line(&self) -> Option<&Line> {
+        match self {
+            Self::Line(line) | Self::LineAndFetching(line, _) | Self::Stale(line) => Some(line),
+            Self::Fetching(_) => None,
+        }
+    }
+
+    fn line_mut(&mut self) -> Option<&mut Line> {
+        match self {
+            Self::Line(line) | Self::LineAndFetching(line, _) | Self::Stale(line) => Some(line),
+            Self::Fetching(_) => None,
+        }
+    }
+
     fn kind(&self) -> (&'static str, Option<Instant>) {
         match self {
             Self::Line(_) => ("Line", None),
@@ -75,7 +90,7 @@ pub struct RenderableInner {
     last_send_time: Instant,
     pub last_recv_time: Instant,
     last_late_dirty: Instant,
-    last_input_rtt: u64,
+    local_echo: LocalEcho,
 
     pub input_serial: InputSerial,
 }
@@ -114,7 +129,7 @@ impl RenderableInner {
             last_send_time: now,
             last_recv_time: now,
             last_late_dirty: now,
-            last_input_rtt: 0,
+            local_echo: LocalEcho::new(client.local_echo_threshold_ms.map(Duration::from_millis)),
             input_serial: InputSerial::empty(),
             seqno: SEQ_ZERO,
         }
@@ -133,168 +148,66 @@ impl RenderableInner {
         }
     }
 
-    /// Predictive echo can be noisy when the link is working well,
-    /// so we only employ it when it looks like the latency is high.
-    fn should_predict(&self) -> bool {
-        self.client
-            .local_echo_threshold_ms
-            .map(|thresh| self.last_input_rtt >= thresh)
-            .unwrap_or(false)
-    }
-
-    /// Compute a "prediction" and apply it to the line data that we
-    /// have available, marking it as dirty so that it gets rendered.
-    /// The prediction is basically just local echo.
-    /// Open questions:
-    /// how do we tell if the intent is to suppress local echo during eg:
-    ///  * password prompt?  One option is to look back and see if the line
-    ///                      looks like a password prompt.
-    ///  * normal mode in vim: letter presses are typically movement or
-    ///                        other editor commands
-    /// There are bound to be a number of other edge cases that we should
-    /// handle.
-    fn apply_prediction(&mut self, c: KeyCode, line: &mut Line) {
-        let text = line.as_str();
-        if text.contains("sword") {
-            // This line might be a password prompt.  Don't force
-            // on local echo here, as we don't want to reveal content
-            // from their password
-            return;
-        }
-
-        match c {
-            KeyCode::Enter => {
-                self.cursor_position.x = 0;
-                self.cursor_position.y += 1;
-            }
-            KeyCode::UpArrow => {
-                self.cursor_position.y = self.cursor_position.y.saturating_sub(1);
-            }
-            KeyCode::DownArrow => {
-                self.cursor_position.y += 1;
-            }
-            KeyCode::RightArrow => {
-                self.cursor_position.x += 1;
-            }
-            KeyCode::LeftArrow => {
-                self.cursor_position.x = self.cursor_position.x.saturating_sub(1);
-            }
-            KeyCode::Delete => {
-                line.erase_cell(self.cursor_position.x, SEQ_ZERO);
-            }
-            KeyCode::Backspace => {
-                if self.cursor_position.x > 0 {
-                    line.erase_cell(self.cursor_position.x - 1, SEQ_ZERO);
-                    self.cursor_position.x -= 1;
-                }
-            }
-            KeyCode::Char(c) => {
-                let cell = Cell::new(
-                    c,
-                    CellAttributes::default()
-                        .set_underline(Underline::Double)
-                        .clone(),
-                );
-
-                let width = cell.width();
-                line.set_cell(self.cursor_position.x, cell, SEQ_ZERO);
-                // Adjust the cursor to reflect the width of this new cell
-                self.cursor_position.x += width;
-            }
-            _ => {}
-        }
-    }
-
-    /// Based on a keypress, apply a "prediction" of what the terminal
-    /// content will look like once we receive the response from the
-    /// remote system.  The prediction helps to reduce perceived latency
-    /// when a user is typing at any reasonable velocity.
+    /// Based on a keypress, predict what the terminal content will
+    /// look like once we receive the response from the remote system.
+    /// The prediction helps to reduce perceived latency when a user is
+    /// typing at any reasonable velocity.
     pub fn predict_from_key_event(&mut self, key: KeyCode, mods: KeyModifiers) {
-        if !self.should_predict() {
-            return;
-        }
-
-        let c = match key {
-            KeyCode::LeftArrow
-            | KeyCode::RightArrow
-            | KeyCode::UpArrow
-            | KeyCode::DownArrow
-            | KeyCode::Delete
-            | KeyCode::Backspace
-            | KeyCode::Enter
-            | KeyCode::Char(_) => key,
-            _ => return,
+        let row = self.cursor_position.y;
+        let predicted = match self.lines.peek(&row).and_then(LineEntry::line) {
+            Some(line) => self.local_echo.predict_key(
+                key,
+                mods,
+                &self.cursor_position,
+                line,
+                self.dimensions.cols,
+            ),
+            None => false,
         };
-        if mods != KeyModifiers::NONE && mods != KeyModifiers::SHIFT {
-            return;
+        if predicted {
+            self.schedule_prediction_expiry();
         }
+    }
 
+    pub fn predict_from_paste(&mut self, text: &str) {
         let row = self.cursor_position.y;
-        match self.lines.pop(&row) {
-            Some(LineEntry::Stale(mut line)) | Some(LineEntry::Line(mut line)) => {
-                self.apply_prediction(c, &mut line);
-                self.lines.put(row, LineEntry::Line(line));
-            }
-            Some(LineEntry::LineAndFetching(mut line, instant)) => {
-                self.apply_prediction(c, &mut line);
-                self.lines
-                    .put(row, LineEntry::LineAndFetching(line, instant));
-            }
-            Some(entry) => {
-                self.lines.put(row, entry);
-            }
-            None => {}
+        let predicted = match self.lines.peek(&row).and_then(LineEntry::line) {
+            Some(line) => self.local_echo.predict_paste(
+                text,
+                &self.cursor_position,
+                line,
+                self.dimensions.cols,
+            ),
+            None => false,
+        };
+        if predicted {
+            self.schedule_prediction_expiry();
         }
     }
 
-    fn apply_paste_prediction(&mut self, row: usize, text: &str, line: &mut Line) {
-        let attrs = CellAttributes::default()
-            .set_underline(Underline::Double)
-            .clone();
-
-        let text_line = Line::from_text(text, &attrs, SEQ_ZERO, None);
-
-        if row == 0 {
-            for cell in text_line.visible_cells() {
-                line.set_cell(self.cursor_position.x, cell.as_cell(), SEQ_ZERO);
-                self.cursor_position.x += cell.width();
-            }
-        } else {
-            // The pasted line replaces the data for the existing line
-            line.resize_and_clear(0, SEQ_ZERO, CellAttributes::default());
-            line.append_line(text_line, SEQ_ZERO);
-            self.cursor_position.x = line.len();
-        }
+    /// Render the newly predicted content, and arrange to render again
+    /// once the prediction should have been confirmed, so that it will
+    /// be rolled back if the server didn't do what we expected
+    fn schedule_prediction_expiry(&self) {
+        let local_pane_id = self.local_pane_id;
+        let timeout = self.local_echo.timeout();
+        Mux::get().notify(mux::MuxNotification::PaneOutput(local_pane_id));
+        promise::spawn::spawn(async move {
+            smol::Timer::after(timeout).await;
+            Mux::get().notify(mux::MuxNotification::PaneOutput(local_pane_id));
+        })
+        .detach();
     }
 
-    pub fn predict_from_paste(&mut self, text: &str) {
-        if !self.should_predict() {
-            return;
-        }
-
-        let text = textwrap::fill(text, self.dimensions.cols);
-        let lines: Vec<&str> = text.split("\n").collect();
-
-        for (idx, paste_line) in lines.iter().enumerate() {
-            let row = self.cursor_position.y + idx as StableRowIndex;
-
-            match self.lines.pop(&row) {
-                Some(LineEntry::Stale(mut line)) | Some(LineEntry::Line(mut line)) => {
-                    self.apply_paste_prediction(idx, paste_line, &mut line);
-                    self.lines.put(row, LineEntry::Line(line));
-                }
-                Some(LineEntry::LineAndFetching(mut line, instant)) => {
-                    self.apply_paste_prediction(idx, paste_line, &mut line);
-                    self.lines
-                        .put(row, LineEntry::LineAndFetching(line, instant));
-                }
-                Some(entry) => {
-                    self.lines.put(row, entry);
-                }
-                None => {}
-            }
-        }
-        self.cursor_position.y += lines.len().saturating_sub(1) as StableRowIndex;
+    /// Drop predictions that the lines received from the server have
+    /// confirmed, or that have been outstanding for too long
+    fn validate_predictions(&mut self) {
+        let lines = &self.lines;
+        self.local_echo
+            .validate(&self.cursor_position, |row| match lines.peek(&row) {
+                Some(LineEntry::Line(line)) => Some(line),
+                _ => None,
+            });
     }
 
     pub fn update_last_send(&mut self) {
@@ -330,7 +243,8 @@ impl RenderableInner {
         // Keep track of the approximate round trip time by recording how
         // long it took for this response to come back
         if let Some(serial) = delta.input_serial {
-            self.last_input_rtt = serial.elapsed_millis();
+            self.local_echo
+                .record_rtt(Duration::from_millis(serial.elapsed_millis()));
         }
 
         // When it comes to updating the cursor position, if the update was tagged
@@ -360,12 +274,12 @@ impl RenderableInner {
         );
         self.seqno = delta.seqno;
 
-        let config = configuration();
         for (stable_row, line) in bonus_lines {
             log::trace!("bonus line {} seqno={}", stable_row, line.current_seqno());
-            self.put_line(stable_row, line, &config, None);
+            self.put_line(stable_row, line, None);
             dirty.remove(stable_row);
         }
+        self.validate_predictions();
 
         log::trace!(
             "apply_changes_to_surface: Generate PaneOutput event for local={}",
@@ -422,6 +336,52 @@ impl RenderableInner {
         }
     }
 
+    /// Apply a snapshot of the pane that was obtained via SyncPane
+    pub fn apply_sync(&mut self, sync: SyncPaneResponse, lines: Vec<(StableRowIndex, Line)>) {
+        log::trace!(
+            "apply_sync local={} remote={} rows={:?} is_delta={} seqno {} -> {}",
+            self.local_pane_id,
+            self.remote_pane_id,
+            sync.rows,
+            sync.is_delta,
+            self.seqno,
+            sync.seqno
+        );
+        self.poll_interval = BASE_POLL_INTERVAL;
+        self.last_recv_time = Instant::now();
+
+        let rows = sync.rows;
+        self.cursor_position = sync.cursor_position;
+        self.dimensions = sync.dimensions;
+        self.title = sync.title;
+        self.working_dir = sync.working_dir.map(Into::into);
+        self.seqno = sync.seqno;
+
+        if sync.is_delta {
+            // Lines within the snapshot that weren't sent are unchanged,
+            // but we know nothing about those outside of it, so arrange
+            // for them to be fetched again if they are needed
+            let outside: Vec<StableRowIndex> = self
+                .lines
+                .iter()
+                .map(|(stable_row, _)| *stable_row)
+                .filter(|stable_row| !rows.contains(stable_row))
+                .collect();
+            for stable_row in outside {
+                self.make_stale(stable_row);
+            }
+        } else {
+            self.lines.clear();
+        }
+
+        for (stable_row, line) in lines {
+            self.put_line(stable_row, line, None);
+        }
+        self.validate_predictions();
+
+        Mux::get().notify(mux::MuxNotification::PaneOutput(self.local_pane_id));
+    }
+
     pub fn make_all_stale(&mut self) {
         let mut lines = LruCache::unbounded();
         while let Some((stable_row, entry)) = self.lines.pop_lru() {
@@ -449,11 +409,8 @@ impl RenderableInner {
         &mut self,
         stable_row: StableRowIndex,
         mut line: Line,
-        config: &ConfigHandle,
         fetch_start: Option<Instant>,
     ) {
-        line.scan_and_create_hyperlinks(&config.hyperlink_rules);
-
         let entry = if let Some(fetch_start) = fetch_start {
             // If we're completing a fetch, only replace entries that were
             // set to fetching as part of our fetch.  If they are now longer
@@ -546,12 +503,11 @@ impl RenderableInner {
 
             match result {
                 Ok(lines) => {
-                    let config = configuration();
-
                     log::trace!("fetch complete for {:?} at {:?}", to_fetch, now);
                     for (stable_row, line) in lines.into_iter() {
-                        inner.put_line(stable_row, line, &config, Some(now));
+                        inner.put_line(stable_row, line, Some(now));
                     }
+                    inner.validate_predictions();
                 }
                 Err(err) => {
                     log::error!("get_lines failed: {}", err);
@@ -721,7 +677,107 @@ pub(crate) async fn hydrate_lines(
 
 impl RenderableState {
     pub fn get_cursor_position(&self) -> StableCursorPosition {
-        self.inner.borrow().cursor_position
+        let inner = self.inner.borrow();
+        inner.local_echo.cursor_position(&inner.cursor_position)
+    }
+
+    /// Groups the locally cached lines in the specified range into
+    /// logical lines and passes them to `for_line` so that they can be
+    /// modified in place.  Rows that have not yet been fetched from
+    /// the server end the logical line that precedes them.
+    pub fn for_each_logical_line_mut(
+        &self,
+        lines: Range<StableRowIndex>,
+        for_line: &mut dyn ForEachPaneLogicalLine,
+    ) {
+        // Matches the constraint used when walking the logical lines
+        // of a local pane
+        const MAX_LOGICAL_LINE_LEN: usize = 1024;
+
+        let mut inner = self.inner.borrow_mut();
+
+        // Look backwards to find the start of the first logical line
+        let mut start = lines.start;
+        let mut back_len = 0;
+        while start > 0 {
+            match inner.lines.peek(&(start - 1)).and_then(LineEntry::line) {
+                Some(prior)
+                    if prior.last_cell_was_wrapped()
+                        && prior.len() + back_len <= MAX_LOGICAL_LINE_LEN =>
+                {
+                    back_len += prior.len();
+                    start -= 1;
+                }
+                _ => break,
+            }
+        }
+
+        // Look forwards to find the end of the last logical line
+        let mut end = lines.end;
+        while end > start {
+            let last_wrapped = inner
+                .lines
+                .peek(&(end - 1))
+                .and_then(LineEntry::line)
+                .map(|line| line.last_cell_was_wrapped())
+                .unwrap_or(false);
+            if !last_wrapped || inner.lines.peek(&end).and_then(LineEntry::line).is_none() {
+                break;
+            }
+            end += 1;
+        }
+
+        let mut entries: Vec<(StableRowIndex, Option<LineEntry>)> = (start..end)
+            .map(|row| (row, inner.lines.pop(&row)))
+            .collect();
+
+        let mut idx = 0;
+        while idx < entries.len() {
+            let first_row = entries[idx].0;
+            let mut total_len = match entries[idx].1.as_ref().and_then(LineEntry::line) {
+                Some(line) => line.len(),
+                None => {
+                    idx += 1;
+                    continue;
+                }
+            };
+
+            let mut next = idx + 1;
+            while next < entries.len() {
+                let prior_wrapped = entries[next - 1]
+                    .1
+                    .as_ref()
+                    .and_then(LineEntry::line)
+                    .map(|line| line.last_cell_was_wrapped())
+                    .unwrap_or(false);
+                match entries[next].1.as_ref().and_then(LineEntry::line) {
+                    Some(line)
+                        if prior_wrapped && total_len + line.len() <= MAX_LOGICAL_LINE_LEN =>
+                    {
+                        total_len += line.len();
+                        next += 1;
+                    }
+                    _ => break,
+                }
+            }
+
+            let mut line_refs: Vec<&mut Line> = entries[idx..next]
+                .iter_mut()
+                .filter_map(|(_, entry)| entry.as_mut().and_then(LineEntry::line_mut))
+                .collect();
+            let num_lines = line_refs.len() as StableRowIndex;
+            idx = next;
+
+            if !for_line.with_logical_line_mut(first_row..first_row + num_lines, &mut line_refs) {
+                break;
+            }
+        }
+
+        for (row, entry) in entries {
+            if let Some(entry) = entry {
+                inner.lines.put(row, entry);
+            }
+        }
     }
 
     pub fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
@@ -730,6 +786,8 @@ impl RenderableState {
         let mut to_fetch = RangeSet::new();
         let now = Instant::now();
 
+        inner.validate_predictions();
+
         for idx in lines.clone() {
             let entry = match inner.lines.pop(&idx) {
                 Some(LineEntry::Line(line)) => {
@@ -761,6 +819,12 @@ impl RenderableState {
                 }
             };
 
+            if inner.local_echo.has_predictions_for(idx) {
+                inner
+                    .local_echo
+                    .apply_to_line(idx, result.last_mut().unwrap());
+            }
+
             if inner.client.overlay_lag_indicator && idx == inner.dimensions.physical_top {
                 if inner.is_tardy() {
                     let status = format!(
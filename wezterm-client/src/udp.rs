@@ -0,0 +1,892 @@
+//! A datagram transport for the mux protocol, for clients that move
+//! between networks.  A client authenticates over TLS and obtains a
+//! session id and key using `StartUdpSession`, and then exchanges the
+//! usual stream of encoded `Pdu`s with the server as a sequence of
+//! encrypted, numbered segments carried in UDP datagrams.  Lost
+//! segments are retransmitted, and the server sends its datagrams to
+//! wherever the most recent authentic datagram from the client came
+//! from, so the session carries on when the client's address changes.
+//!
+//! Each datagram has the form:
+//!
+//! ```text
+//! session_id: u64 | counter: u64 | AES-256-GCM(frame) | tag: [u8; 16]
+//! frame := kind: u8 | ack: u64 | seq: u64 | data
+//! ```
+//!
+//! where `counter` increments with every datagram sent in a given
+//! direction, and together with the direction forms the nonce.
+//! `ack` is the sequence number of the next segment that the sender
+//! of the datagram expects to receive.
+use crate::client::AsyncReadAndWrite;
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use smol::channel::{unbounded, Receiver, Sender};
+use smol::net::UdpSocket;
+use smol::prelude::*;
+use smol::Async;
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use wezterm_uds::UnixStream;
+
+pub type UdpSessionId = u64;
+pub type UdpSessionKey = [u8; 32];
+
+/// The largest amount of stream data carried by a single datagram;
+/// small enough to avoid IP fragmentation on typical paths
+const MAX_SEGMENT_LEN: usize = 1200;
+/// The number of segments that may be awaiting acknowledgement
+const MAX_IN_FLIGHT: usize = 512;
+const INITIAL_RTO: Duration = Duration::from_millis(300);
+const MIN_RTO: Duration = Duration::from_millis(50);
+const MAX_RTO: Duration = Duration::from_secs(3);
+/// How often to send something, even if there is nothing to say,
+/// so that the server promptly learns about a change of address
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(3);
+/// How many duplicate acknowledgements indicate that a segment was
+/// lost, rather than merely delayed
+const DUP_ACK_THRESHOLD: usize = 3;
+/// How long the client waits to hear from the server before giving
+/// up on the session and reconnecting
+pub const CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long the server retains a session for a client that it has
+/// not heard from
+pub const SERVER_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+const HEADER_LEN: usize = 16;
+const TAG_LEN: usize = 16;
+const FRAME_HEADER_LEN: usize = 17;
+
+const FROM_CLIENT: u32 = 0;
+const FROM_SERVER: u32 = 1;
+
+const KIND_DATA: u8 = 0;
+const KIND_ACK: u8 = 1;
+const KIND_CLOSE: u8 = 2;
+
+fn nonce(direction: u32, counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..4].copy_from_slice(&direction.to_le_bytes());
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    nonce
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn encode_frame(kind: u8, ack: u64, seq: u64, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + data.len());
+    frame.push(kind);
+    frame.extend_from_slice(&ack.to_le_bytes());
+    frame.extend_from_slice(&seq.to_le_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+struct Segment {
+    seq: u64,
+    data: Vec<u8>,
+    sent_at: Instant,
+    retransmitted: bool,
+}
+
+/// The state of one end of a datagram session.  This doesn't perform
+/// any I/O itself; `run_session` drives it using a socket.
+pub struct Session {
+    session_id: UdpSessionId,
+    key: UdpSessionKey,
+    send_direction: u32,
+    next_counter: u64,
+    /// The highest counter seen in an authentic datagram from the peer
+    highest_counter: Option<u64>,
+    peer: SocketAddr,
+
+    next_seq: u64,
+    /// Segments that have been sent but not yet acknowledged
+    unacked: VecDeque<Segment>,
+    /// Segments that have yet to be sent
+    queued: VecDeque<Vec<u8>>,
+    last_ack: u64,
+    dup_acks: usize,
+    fast_retransmit: bool,
+
+    next_expected: u64,
+    out_of_order: BTreeMap<u64, Vec<u8>>,
+    ack_pending: bool,
+
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+    last_send: Option<Instant>,
+    last_receive: Instant,
+    peer_closed: bool,
+}
+
+impl Session {
+    pub fn new(
+        session_id: UdpSessionId,
+        key: UdpSessionKey,
+        is_server: bool,
+        peer: SocketAddr,
+    ) -> Self {
+        Self {
+            session_id,
+            key,
+            send_direction: if is_server { FROM_SERVER } else { FROM_CLIENT },
+            next_counter: 0,
+            highest_counter: None,
+            peer,
+            next_seq: 0,
+            unacked: VecDeque::new(),
+            queued: VecDeque::new(),
+            last_ack: 0,
+            dup_acks: 0,
+            fast_retransmit: false,
+            next_expected: 0,
+            out_of_order: BTreeMap::new(),
+            ack_pending: false,
+            srtt: None,
+            rttvar: Duration::ZERO,
+            rto: INITIAL_RTO,
+            last_send: None,
+            last_receive: Instant::now(),
+            peer_closed: false,
+        }
+    }
+
+    /// Returns the session id of a datagram, without authenticating it
+    pub fn session_id_of(datagram: &[u8]) -> Option<UdpSessionId> {
+        if datagram.len() < HEADER_LEN + TAG_LEN + FRAME_HEADER_LEN {
+            return None;
+        }
+        Some(read_u64(datagram))
+    }
+
+    /// Returns the address to which datagrams should be sent
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Returns true if the peer has closed the session
+    pub fn is_closed(&self) -> bool {
+        self.peer_closed
+    }
+
+    /// Returns how long it has been since we heard from the peer
+    pub fn idle_time(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_receive)
+    }
+
+    /// Returns true if there is room to accept more data to send
+    pub fn has_capacity(&self) -> bool {
+        self.unacked.len() + self.queued.len() < MAX_IN_FLIGHT
+    }
+
+    /// Queue up stream data to send to the peer
+    pub fn write(&mut self, data: &[u8]) {
+        for chunk in data.chunks(MAX_SEGMENT_LEN) {
+            self.queued.push_back(chunk.to_vec());
+        }
+    }
+
+    fn seal(&mut self, frame: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let counter = self.next_counter;
+        self.next_counter += 1;
+
+        let mut datagram = Vec::with_capacity(HEADER_LEN + frame.len() + TAG_LEN);
+        datagram.extend_from_slice(&self.session_id.to_le_bytes());
+        datagram.extend_from_slice(&counter.to_le_bytes());
+
+        let mut tag = [0u8; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce(self.send_direction, counter)),
+            &datagram,
+            frame,
+            &mut tag,
+        )
+        .context("encrypting datagram")?;
+        datagram.extend_from_slice(&ciphertext);
+        datagram.extend_from_slice(&tag);
+        Ok(datagram)
+    }
+
+    /// Authenticates and decrypts a datagram from the peer, returning
+    /// its counter and frame
+    fn open(&self, datagram: &[u8]) -> anyhow::Result<(u64, Vec<u8>)> {
+        let session_id =
+            Self::session_id_of(datagram).ok_or_else(|| anyhow!("datagram is too short"))?;
+        if session_id != self.session_id {
+            anyhow::bail!("datagram is for session {session_id}");
+        }
+        let counter = read_u64(&datagram[8..]);
+        let (header, rest) = datagram.split_at(HEADER_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+        let frame = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce(self.send_direction ^ 1, counter)),
+            header,
+            ciphertext,
+            tag,
+        )
+        .map_err(|_| anyhow!("datagram failed authentication"))?;
+        Ok((counter, frame))
+    }
+
+    /// Returns true if `datagram` was sent by the peer of this session
+    pub fn authenticate(&self, datagram: &[u8]) -> bool {
+        self.open(datagram).is_ok()
+    }
+
+    fn record_rtt(&mut self, sample: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(sample);
+                self.rttvar = sample / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.abs_diff(sample);
+                self.rttvar = (self.rttvar * 3 + delta) / 4;
+                self.srtt = Some((srtt * 7 + sample) / 8);
+            }
+        }
+        self.rto = (self.srtt.unwrap_or_default() + self.rttvar * 4).clamp(MIN_RTO, MAX_RTO);
+    }
+
+    /// Processes a datagram received from `from`, returning the stream
+    /// data that it made available, in order.  Returns an error if the
+    /// datagram is not an authentic part of this session, in which case
+    /// it should be ignored.
+    pub fn receive(
+        &mut self,
+        datagram: &[u8],
+        from: SocketAddr,
+        now: Instant,
+    ) -> anyhow::Result<Vec<u8>> {
+        let (counter, frame) = self.open(datagram)?;
+        if frame.len() < FRAME_HEADER_LEN {
+            anyhow::bail!("frame is too short");
+        }
+
+        // Only a datagram that is newer than any that we've seen can
+        // move the session to a new address, so that replaying an old
+        // datagram from elsewhere cannot hijack it
+        if self.highest_counter.map(|c| counter > c).unwrap_or(true) {
+            self.highest_counter.replace(counter);
+            if from != self.peer {
+                log::info!(
+                    "udp session {:x} peer moved from {} to {}",
+                    self.session_id,
+                    self.peer,
+                    from
+                );
+                self.peer = from;
+            }
+        }
+        self.last_receive = now;
+
+        let kind = frame[0];
+        let ack = read_u64(&frame[1..]);
+        let seq = read_u64(&frame[9..]);
+        let data = &frame[FRAME_HEADER_LEN..];
+
+        if ack > self.last_ack {
+            self.last_ack = ack;
+            self.dup_acks = 0;
+            while let Some(segment) = self.unacked.front() {
+                if segment.seq >= ack {
+                    break;
+                }
+                if let Some(segment) = self.unacked.pop_front() {
+                    if segment.seq + 1 == ack && !segment.retransmitted {
+                        self.record_rtt(now.saturating_duration_since(segment.sent_at));
+                    }
+                }
+            }
+            // The peer is making progress, so undo any backoff
+            if let Some(srtt) = self.srtt {
+                self.rto = (srtt + self.rttvar * 4).clamp(MIN_RTO, MAX_RTO);
+            }
+        } else if kind == KIND_ACK && !self.unacked.is_empty() {
+            // The peer acknowledges each segment that it receives, so
+            // repeatedly acknowledging the same one means that the
+            // segments after it are arriving but it isn't
+            self.dup_acks += 1;
+            if self.dup_acks == DUP_ACK_THRESHOLD {
+                self.fast_retransmit = true;
+            }
+        }
+
+        let mut delivered = vec![];
+        match kind {
+            KIND_DATA => {
+                // Always acknowledge, even duplicates, in case it was
+                // our acknowledgement that got lost
+                self.ack_pending = true;
+                if seq == self.next_expected {
+                    delivered.extend_from_slice(data);
+                    self.next_expected += 1;
+                    while let Some(data) = self.out_of_order.remove(&self.next_expected) {
+                        delivered.extend_from_slice(&data);
+                        self.next_expected += 1;
+                    }
+                } else if seq > self.next_expected
+                    && seq < self.next_expected + 2 * MAX_IN_FLIGHT as u64
+                {
+                    self.out_of_order.insert(seq, data.to_vec());
+                }
+            }
+            KIND_ACK => {}
+            KIND_CLOSE => {
+                self.peer_closed = true;
+            }
+            kind => anyhow::bail!("unknown frame kind {kind}"),
+        }
+
+        Ok(delivered)
+    }
+
+    /// Returns the datagrams that should be sent to the peer now:
+    /// retransmissions of segments that haven't been acknowledged in
+    /// time, newly queued segments, and acknowledgements
+    pub fn poll_transmit(&mut self, now: Instant) -> anyhow::Result<Vec<Vec<u8>>> {
+        let ack = self.next_expected;
+        let mut frames = vec![];
+
+        if self.fast_retransmit {
+            self.fast_retransmit = false;
+            if let Some(segment) = self.unacked.front_mut() {
+                frames.push(encode_frame(KIND_DATA, ack, segment.seq, &segment.data));
+                segment.sent_at = now;
+                segment.retransmitted = true;
+            }
+        }
+
+        let mut retransmitted = false;
+        for segment in self.unacked.iter_mut() {
+            if now.saturating_duration_since(segment.sent_at) >= self.rto {
+                frames.push(encode_frame(KIND_DATA, ack, segment.seq, &segment.data));
+                segment.sent_at = now;
+                segment.retransmitted = true;
+                retransmitted = true;
+            }
+        }
+        if retransmitted {
+            self.rto = (self.rto * 2).min(MAX_RTO);
+        }
+
+        while self.unacked.len() < MAX_IN_FLIGHT {
+            let data = match self.queued.pop_front() {
+                Some(data) => data,
+                None => break,
+            };
+            let seq = self.next_seq;
+            self.next_seq += 1;
+            frames.push(encode_frame(KIND_DATA, ack, seq, &data));
+            self.unacked.push_back(Segment {
+                seq,
+                data,
+                sent_at: now,
+                retransmitted: false,
+            });
+        }
+
+        let keepalive_due = self
+            .last_send
+            .map(|last| now.saturating_duration_since(last) >= KEEPALIVE_INTERVAL)
+            .unwrap_or(true);
+        if frames.is_empty() && (self.ack_pending || keepalive_due) {
+            frames.push(encode_frame(KIND_ACK, ack, 0, &[]));
+        }
+
+        if frames.is_empty() {
+            return Ok(vec![]);
+        }
+        self.ack_pending = false;
+        self.last_send.replace(now);
+        frames.iter().map(|frame| self.seal(frame)).collect()
+    }
+
+    /// Returns a datagram that tells the peer that we are closing
+    /// the session
+    pub fn close(&mut self) -> anyhow::Result<Vec<u8>> {
+        let frame = encode_frame(KIND_CLOSE, self.next_expected, 0, &[]);
+        self.seal(&frame)
+    }
+
+    /// Returns how long until `poll_transmit` next has something to do
+    pub fn next_wakeup(&self, now: Instant) -> Duration {
+        if self.ack_pending
+            || self.fast_retransmit
+            || (!self.queued.is_empty() && self.unacked.len() < MAX_IN_FLIGHT)
+        {
+            return Duration::ZERO;
+        }
+        let mut deadline = match self.last_send {
+            Some(last) => last + KEEPALIVE_INTERVAL,
+            None => return Duration::ZERO,
+        };
+        if let Some(oldest) = self.unacked.iter().map(|s| s.sent_at).min() {
+            deadline = deadline.min(oldest + self.rto);
+        }
+        deadline.saturating_duration_since(now)
+    }
+}
+
+enum Event {
+    Datagram(Vec<u8>, SocketAddr),
+    Local(usize),
+    Timer,
+}
+
+/// Relays data between `local` and the peer of `session`, until one
+/// of them closes the session or the peer has been silent for longer
+/// than `idle_timeout`.  `datagrams` supplies the datagrams received
+/// from the peer; datagrams are sent to the peer using `socket`.
+pub async fn run_session(
+    mut session: Session,
+    socket: UdpSocket,
+    datagrams: Receiver<(Vec<u8>, SocketAddr)>,
+    mut local: Async<UnixStream>,
+    idle_timeout: Duration,
+) -> anyhow::Result<()> {
+    let mut buf = vec![0u8; MAX_SEGMENT_LEN * 16];
+    loop {
+        let now = Instant::now();
+        for datagram in session.poll_transmit(now)? {
+            if let Err(err) = socket.send_to(&datagram, session.peer()).await {
+                // The network may be temporarily unavailable while
+                // moving between networks; we'll retransmit later
+                log::debug!("sending datagram to {}: {:#}", session.peer(), err);
+            }
+        }
+        if session.is_closed() {
+            return Ok(());
+        }
+        let idle = session.idle_time(now);
+        if idle >= idle_timeout {
+            anyhow::bail!("no response from {} for {:?}", session.peer(), idle);
+        }
+        let wait = session.next_wakeup(now).min(idle_timeout - idle);
+
+        let datagram = async {
+            let (datagram, from) = datagrams.recv().await?;
+            Ok::<Event, anyhow::Error>(Event::Datagram(datagram, from))
+        };
+        let timer = async {
+            smol::Timer::after(wait).await;
+            Ok::<Event, anyhow::Error>(Event::Timer)
+        };
+        let event = if session.has_capacity() {
+            let read =
+                async { Ok::<Event, anyhow::Error>(Event::Local(local.read(&mut buf).await?)) };
+            datagram.or(read).or(timer).await?
+        } else {
+            datagram.or(timer).await?
+        };
+
+        match event {
+            Event::Datagram(datagram, from) => {
+                match session.receive(&datagram, from, Instant::now()) {
+                    Ok(data) => {
+                        if !data.is_empty() {
+                            local.write_all(&data).await?;
+                            local.flush().await?;
+                        }
+                    }
+                    Err(err) => log::trace!("ignoring datagram from {}: {:#}", from, err),
+                }
+            }
+            Event::Local(0) => {
+                let datagram = session.close()?;
+                socket.send_to(&datagram, session.peer()).await.ok();
+                return Ok(());
+            }
+            Event::Local(len) => session.write(&buf[..len]),
+            Event::Timer => {}
+        }
+    }
+}
+
+/// Receives datagrams from `socket` and passes them to `tx`
+pub async fn receive_datagrams(
+    socket: UdpSocket,
+    tx: Sender<(Vec<u8>, SocketAddr)>,
+) -> anyhow::Result<()> {
+    let mut buf = vec![0u8; 65536];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buf).await {
+            Ok(result) => result,
+            // Some systems report ICMP errors for earlier sends here
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionRefused
+                ) =>
+            {
+                continue;
+            }
+            Err(err) => return Err(err).context("receiving datagram"),
+        };
+        tx.send((buf[..len].to_vec(), from)).await?;
+    }
+}
+
+/// Generates the identifier and key for a new session
+pub fn generate_session_credentials() -> anyhow::Result<(UdpSessionId, UdpSessionKey)> {
+    let mut id = [0u8; 8];
+    let mut key = [0u8; 32];
+    openssl::rand::rand_bytes(&mut id)?;
+    openssl::rand::rand_bytes(&mut key)?;
+    Ok((u64::from_le_bytes(id), key))
+}
+
+/// Returns a connected pair of streams
+pub fn stream_pair() -> anyhow::Result<(UnixStream, UnixStream)> {
+    let (a, b) = filedescriptor::socketpair()?;
+    #[cfg(unix)]
+    unsafe {
+        use std::os::unix::io::{FromRawFd, IntoRawFd};
+        Ok((
+            UnixStream::from_raw_fd(a.into_raw_fd()),
+            UnixStream::from_raw_fd(b.into_raw_fd()),
+        ))
+    }
+    #[cfg(windows)]
+    unsafe {
+        use std::os::windows::io::{FromRawSocket, IntoRawSocket};
+        Ok((
+            UnixStream::from_raw_socket(a.into_raw_socket()),
+            UnixStream::from_raw_socket(b.into_raw_socket()),
+        ))
+    }
+}
+
+/// The client end of a datagram session.  If the session fails, reads
+/// report the reason as an error rather than as end of file, so that
+/// the client will try to reconnect.
+#[derive(Debug)]
+pub struct UdpStream {
+    inner: Async<UnixStream>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl AsyncRead for UdpStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match Pin::new(&mut self.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(0)) if !buf.is_empty() => {
+                Poll::Ready(match self.failure.lock().unwrap().as_ref() {
+                    Some(reason) => Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        reason.to_string(),
+                    )),
+                    None => Ok(0),
+                })
+            }
+            result => result,
+        }
+    }
+}
+
+impl AsyncWrite for UdpStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+#[async_trait(?Send)]
+impl AsyncReadAndWrite for UdpStream {
+    async fn wait_for_readable(&self) -> anyhow::Result<()> {
+        Ok(self.inner.readable().await?)
+    }
+}
+
+/// Starts the client end of the datagram session identified by
+/// `session_id` and `key` with the server at `remote_address`
+pub fn connect(
+    remote_address: &str,
+    session_id: UdpSessionId,
+    key: UdpSessionKey,
+) -> anyhow::Result<UdpStream> {
+    let peer = remote_address
+        .to_socket_addrs()
+        .with_context(|| format!("resolving {}", remote_address))?
+        .next()
+        .ok_or_else(|| anyhow!("{} did not resolve to an address", remote_address))?;
+    // Bind to the unspecified address so that the system picks the
+    // appropriate source address for each datagram, which may change
+    // as we move between networks
+    let bind_address = if peer.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = std::net::UdpSocket::bind(bind_address)
+        .with_context(|| format!("binding udp socket to {}", bind_address))?;
+    let socket = UdpSocket::try_from(socket)?;
+
+    let (client_end, session_end) = stream_pair()?;
+    let session_end = Async::new(session_end)?;
+    let failure = Arc::new(Mutex::new(None));
+
+    std::thread::Builder::new()
+        .name(format!("udp session {session_id:x}"))
+        .spawn({
+            let failure = Arc::clone(&failure);
+            move || {
+                let (tx, rx) = unbounded();
+                let session = Session::new(session_id, key, false, peer);
+                let result = smol::block_on(
+                    run_session(
+                        session,
+                        socket.clone(),
+                        rx,
+                        session_end,
+                        CLIENT_IDLE_TIMEOUT,
+                    )
+                    .or(receive_datagrams(socket, tx)),
+                );
+                if let Err(err) = result {
+                    log::error!("udp session with {}: {:#}", peer, err);
+                    failure.lock().unwrap().replace(format!("{:#}", err));
+                }
+            }
+        })?;
+
+    Ok(UdpStream {
+        inner: Async::new(client_end)?,
+        failure,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const KEY: UdpSessionKey = [42; 32];
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// Transfers data in both directions between a pair of sessions
+    /// over a simulated link that loses every `nth` datagram
+    fn transfer_with_loss(nth: usize) {
+        let mut client = Session::new(1, KEY, false, addr(1000));
+        let mut server = Session::new(1, KEY, true, addr(2000));
+
+        let to_server: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        let to_client: Vec<u8> = (0..50_000).map(|i| (i % 241) as u8).collect();
+        client.write(&to_server);
+        server.write(&to_client);
+
+        let mut received_by_server = vec![];
+        let mut received_by_client = vec![];
+        let mut sent = 0;
+        let mut now = Instant::now();
+
+        for _ in 0..10_000 {
+            for datagram in client.poll_transmit(now).unwrap() {
+                sent += 1;
+                if sent % nth != 0 {
+                    received_by_server.extend(server.receive(&datagram, addr(2000), now).unwrap());
+                }
+            }
+            for datagram in server.poll_transmit(now).unwrap() {
+                sent += 1;
+                if sent % nth != 0 {
+                    received_by_client.extend(client.receive(&datagram, addr(1000), now).unwrap());
+                }
+            }
+            if received_by_server.len() == to_server.len()
+                && received_by_client.len() == to_client.len()
+            {
+                break;
+            }
+            now += Duration::from_millis(20);
+        }
+
+        assert!(received_by_server == to_server);
+        assert!(received_by_client == to_client);
+    }
+
+    #[test]
+    fn transfer_without_loss() {
+        transfer_with_loss(usize::MAX);
+    }
+
+    #[test]
+    fn transfer_with_heavy_loss() {
+        transfer_with_loss(3);
+    }
+
+    #[test]
+    fn rejects_tampered_and_foreign_datagrams() {
+        let mut client = Session::new(1, KEY, false, addr(1000));
+        let mut server = Session::new(1, KEY, true, addr(2000));
+        let mut other = Session::new(1, [1; 32], false, addr(1000));
+        let now = Instant::now();
+
+        client.write(b"hello");
+        let datagram = client.poll_transmit(now).unwrap().remove(0);
+
+        let mut tampered = datagram.clone();
+        let last = tampered.len() - TAG_LEN - 1;
+        tampered[last] ^= 1;
+        assert!(server.receive(&tampered, addr(2000), now).is_err());
+
+        other.write(b"hello");
+        let foreign = other.poll_transmit(now).unwrap().remove(0);
+        assert!(server.receive(&foreign, addr(2000), now).is_err());
+
+        // A datagram can't be reflected back to its sender
+        assert!(client.receive(&datagram, addr(1000), now).is_err());
+
+        assert_eq!(
+            server.receive(&datagram, addr(2000), now).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn peer_moves_only_for_newer_datagrams() {
+        let mut client = Session::new(1, KEY, false, addr(1000));
+        let mut server = Session::new(1, KEY, true, addr(2000));
+        let now = Instant::now();
+
+        client.write(b"one");
+        let first = client.poll_transmit(now).unwrap().remove(0);
+        client.write(b"two");
+        let second = client.poll_transmit(now).unwrap().remove(0);
+
+        server.receive(&first, addr(2000), now).unwrap();
+        assert_eq!(server.peer(), addr(2000));
+
+        // The client changed address
+        assert_eq!(server.receive(&second, addr(3000), now).unwrap(), b"two");
+        assert_eq!(server.peer(), addr(3000));
+
+        // Replaying an older datagram from elsewhere doesn't move it
+        server.receive(&first, addr(4000), now).unwrap();
+        assert_eq!(server.peer(), addr(3000));
+    }
+
+    /// Runs a server end of a session on loopback, echoing back
+    /// whatever the client sends
+    fn spawn_echo_server(session_id: UdpSessionId) -> SocketAddr {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = vec![0u8; 65536];
+            let (len, from) = socket.recv_from(&mut buf).unwrap();
+            let session = Session::new(session_id, KEY, true, from);
+            assert!(session.authenticate(&buf[..len]));
+
+            let (tx, rx) = unbounded();
+            tx.try_send((buf[..len].to_vec(), from)).unwrap();
+
+            let (mut app_end, session_end) = stream_pair().unwrap();
+            std::thread::spawn(move || {
+                use std::io::{Read, Write};
+                let mut buf = [0u8; 4096];
+                loop {
+                    match app_end.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(len) => app_end.write_all(&buf[..len]).unwrap(),
+                    }
+                }
+            });
+
+            let socket = UdpSocket::try_from(socket).unwrap();
+            smol::block_on(
+                run_session(
+                    session,
+                    socket.clone(),
+                    rx,
+                    Async::new(session_end).unwrap(),
+                    SERVER_IDLE_TIMEOUT,
+                )
+                .or(receive_datagrams(socket, tx)),
+            )
+            .ok();
+        });
+        address
+    }
+
+    /// Relays datagrams between a client and `server`, dropping
+    /// every `nth` one
+    fn spawn_lossy_proxy(server: SocketAddr, nth: usize) -> SocketAddr {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let mut buf = vec![0u8; 65536];
+            let mut client = None;
+            let mut relayed = 0;
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                let to = if from == server {
+                    match client {
+                        Some(client) => client,
+                        None => continue,
+                    }
+                } else {
+                    client.replace(from);
+                    server
+                };
+                relayed += 1;
+                if relayed % nth != 0 {
+                    socket.send_to(&buf[..len], to).ok();
+                }
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn echo_over_lossy_loopback() {
+        let server = spawn_echo_server(7);
+        let proxy = spawn_lossy_proxy(server, 10);
+        let mut stream = connect(&proxy.to_string(), 7, KEY).unwrap();
+
+        let sent: Vec<u8> = (0..200_000).map(|i| (i % 253) as u8).collect();
+        let received = smol::block_on(async {
+            let mut received = vec![];
+            for chunk in sent.chunks(10_000) {
+                stream.write_all(chunk).await?;
+                stream.flush().await?;
+                let mut echoed = vec![0u8; chunk.len()];
+                stream.read_exact(&mut echoed).await?;
+                received.extend(echoed);
+            }
+            Ok::<Vec<u8>, std::io::Error>(received)
+        })
+        .unwrap();
+        assert!(received == sent);
+    }
+}
@@ -55,6 +55,17 @@ impl Hyperlink {
         }
     }
 
+    pub fn new_implicit_with_params<S: Into<String>>(
+        uri: S,
+        params: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            uri: uri.into(),
+            params,
+            implicit: true,
+        }
+    }
+
     pub fn new_with_id<S: Into<String>, S2: Into<String>>(uri: S, id: S2) -> Self {
         let mut params = HashMap::new();
         params.insert("id".into(), id.into());
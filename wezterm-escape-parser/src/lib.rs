@@ -11,7 +11,6 @@
 use crate::tmux_cc::Event;
 use core::fmt::{Display, Formatter, Result as FmtResult, Write as FmtWrite};
 use num_derive::*;
-use wezterm_color_types::LinearRgba;
 
 #[cfg_attr(not(feature = "std"), macro_use)]
 extern crate alloc;
@@ -445,14 +444,17 @@ impl Display for SixelData {
                 write!(f, "!{}{}", repeat_count, (data + 0x3f) as char)
             }
             Self::DefineColorMapRGB { color_number, rgb } => {
-                let LinearRgba(r, g, b, _) = rgb.to_linear_tuple_rgba();
+                // The components are percentages of the sRGB values,
+                // matching the way that the parser interprets them
+                let (r, g, b) = rgb.to_tuple_rgb8();
+                let percent = |v: u8| (v as u32 * 100 + 127) / 255;
                 write!(
                     f,
                     "#{};2;{};{};{}",
                     color_number,
-                    (r * 100.) as u8,
-                    (g * 100.) as u8,
-                    (b * 100.0) as u8
+                    percent(r),
+                    percent(g),
+                    percent(b)
                 )
             }
             Self::DefineColorMapHSL {
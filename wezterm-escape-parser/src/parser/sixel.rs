@@ -302,4 +302,13 @@ mod test {
             actions
         );
     }
+
+    #[test]
+    fn sixel_color_map_round_trip() {
+        // Intermediate values must survive being parsed and formatted,
+        // rather than being skewed by a linear conversion
+        let mut p = Parser::new();
+        let actions = p.parse_as_vec(b"\x1bPq#0;2;50;25;75#0~\x1b\\");
+        assert_eq!(format!("{}", actions[0]), "\x1bP0;0q#0;2;50;25;75#0~");
+    }
 }
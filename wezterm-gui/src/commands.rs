@@ -774,6 +774,22 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Edit"],
             icon: Some("cod_clear_all"),
         },
+        StartPaneOutputLog(log) => CommandDef {
+            brief: format!("Log the output of the current pane to {}", log.path.display()).into(),
+            doc: "Appends the output of the current pane to a file".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: None,
+        },
+        StopPaneOutputLog => CommandDef {
+            brief: "Stop logging the output of the current pane".into(),
+            doc: "Stops logging the output of the current pane".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: None,
+        },
         Search(Pattern::CurrentSelectionOrEmptyString) => CommandDef {
             brief: "Search pane output".into(),
             doc: "Enters the search mode UI for the current pane".into(),
@@ -1410,6 +1426,56 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
                 icon: Some("oct_terminal"),
             }
         }
+        SelectCommandOutput => CommandDef {
+            brief: "Select command output".into(),
+            doc: "Selects the output of the most recent command \
+                  that is at or above the viewport"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: Some("oct_terminal"),
+        },
+        CopyCommandOutput(destination) => CommandDef {
+            brief: match destination {
+                ClipboardCopyDestination::Clipboard => "Copy command output to clipboard",
+                ClipboardCopyDestination::PrimarySelection => {
+                    "Copy command output to primary selection"
+                }
+                ClipboardCopyDestination::ClipboardAndPrimarySelection => {
+                    "Copy command output to clipboard and primary selection"
+                }
+            }
+            .into(),
+            doc: "Copies the output of the most recent command \
+                  that is at or above the viewport"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: Some("md_content_copy"),
+        },
+        ToggleCommandOutputFold => CommandDef {
+            brief: "Fold/unfold command output".into(),
+            doc: "Folds away the output of the most recent command \
+                  that is at or above the viewport, or unfolds it \
+                  if it is already folded"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["View"],
+            icon: Some("md_unfold_less_horizontal"),
+        },
+        RerunCommand => CommandDef {
+            brief: "Rerun command".into(),
+            doc: "Sends the command line of the most recent command \
+                  that is at or above the viewport to the pane again"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Shell"],
+            icon: Some("md_replay"),
+        },
         ScrollByCurrentEventWheelDelta => CommandDef {
             brief: "Scrolls based on the mouse wheel position \
                 in the current mouse event"
@@ -1969,6 +2035,175 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Edit", "Copy Mode"],
             icon: None,
         },
+        LauncherMode(assignment) => {
+            let (brief, doc) = match assignment {
+                LauncherModeAssignment::Close => (
+                    "Close the launcher",
+                    "Closes the launcher without activating an entry",
+                ),
+                LauncherModeAssignment::MoveUp => (
+                    "Move up in the launcher",
+                    "Selects the previous entry in the launcher",
+                ),
+                LauncherModeAssignment::MoveDown => (
+                    "Move down in the launcher",
+                    "Selects the next entry in the launcher",
+                ),
+                LauncherModeAssignment::PageUp => (
+                    "Page up in the launcher",
+                    "Moves the launcher selection up by a page",
+                ),
+                LauncherModeAssignment::PageDown => (
+                    "Page down in the launcher",
+                    "Moves the launcher selection down by a page",
+                ),
+                LauncherModeAssignment::Accept => (
+                    "Activate the selected launcher entry",
+                    "Activates the selected entry and closes the launcher",
+                ),
+                LauncherModeAssignment::StartFilter => (
+                    "Filter the launcher entries",
+                    "Starts filtering the launcher entries by typing, \
+                     rather than activating them by their accelerator keys",
+                ),
+                LauncherModeAssignment::DeleteBackward => (
+                    "Delete a character from the launcher filter",
+                    "Removes the last character from the launcher filter",
+                ),
+                LauncherModeAssignment::ClearInput => (
+                    "Clear the launcher filter",
+                    "Removes all of the text from the launcher filter",
+                ),
+            };
+            CommandDef {
+                brief: brief.into(),
+                doc: doc.into(),
+                keys: vec![],
+                args: &[],
+                menubar: &["Shell", "Launcher"],
+                icon: None,
+            }
+        }
+        PaletteMode(assignment) => {
+            let (brief, doc) = match assignment {
+                PaletteModeAssignment::Close => (
+                    "Close the command palette",
+                    "Closes the command palette without running a command",
+                ),
+                PaletteModeAssignment::MoveUp => (
+                    "Move up in the command palette",
+                    "Selects the previous command in the palette",
+                ),
+                PaletteModeAssignment::MoveDown => (
+                    "Move down in the command palette",
+                    "Selects the next command in the palette",
+                ),
+                PaletteModeAssignment::PageUp => (
+                    "Page up in the command palette",
+                    "Moves the palette selection up by a page",
+                ),
+                PaletteModeAssignment::PageDown => (
+                    "Page down in the command palette",
+                    "Moves the palette selection down by a page",
+                ),
+                PaletteModeAssignment::Accept => (
+                    "Run the selected palette command",
+                    "Runs the selected command and closes the palette",
+                ),
+                PaletteModeAssignment::DeleteBackward => (
+                    "Delete a character from the palette query",
+                    "Removes the last character from the palette query",
+                ),
+                PaletteModeAssignment::ClearInput => (
+                    "Clear the palette query",
+                    "Removes all of the text from the palette query",
+                ),
+            };
+            CommandDef {
+                brief: brief.into(),
+                doc: doc.into(),
+                keys: vec![],
+                args: &[],
+                menubar: &["Edit", "Command Palette"],
+                icon: None,
+            }
+        }
+        CharSelectorMode(assignment) => {
+            let (brief, doc) = match assignment {
+                CharSelectorModeAssignment::Close => (
+                    "Close the character selector",
+                    "Closes the character selector without inserting a character",
+                ),
+                CharSelectorModeAssignment::MoveUp => (
+                    "Move up in the character selector",
+                    "Selects the previous character in the selector",
+                ),
+                CharSelectorModeAssignment::MoveDown => (
+                    "Move down in the character selector",
+                    "Selects the next character in the selector",
+                ),
+                CharSelectorModeAssignment::PageUp => (
+                    "Page up in the character selector",
+                    "Moves the character selection up by a page",
+                ),
+                CharSelectorModeAssignment::PageDown => (
+                    "Page down in the character selector",
+                    "Moves the character selection down by a page",
+                ),
+                CharSelectorModeAssignment::Accept => (
+                    "Insert the selected character",
+                    "Sends the selected character to the pane and closes the selector",
+                ),
+                CharSelectorModeAssignment::DeleteBackward => (
+                    "Delete a character from the character search",
+                    "Removes the last character from the character search",
+                ),
+                CharSelectorModeAssignment::ClearInput => (
+                    "Clear the character search",
+                    "Removes all of the text from the character search",
+                ),
+                CharSelectorModeAssignment::NextGroup => (
+                    "Next character group",
+                    "Shows the next group of characters in the selector",
+                ),
+                CharSelectorModeAssignment::PriorGroup => (
+                    "Previous character group",
+                    "Shows the previous group of characters in the selector",
+                ),
+            };
+            CommandDef {
+                brief: brief.into(),
+                doc: doc.into(),
+                keys: vec![],
+                args: &[],
+                menubar: &["Edit", "Character Selection"],
+                icon: None,
+            }
+        }
+        PaneSelectorMode(assignment) => {
+            let (brief, doc) = match assignment {
+                PaneSelectorModeAssignment::Close => (
+                    "Close the pane selector",
+                    "Closes the pane selector without selecting a pane",
+                ),
+                PaneSelectorModeAssignment::DeleteBackward => (
+                    "Delete a character from the pane label",
+                    "Removes the last character typed towards a pane label",
+                ),
+                PaneSelectorModeAssignment::ClearInput => (
+                    "Clear the pane label",
+                    "Removes all of the characters typed towards a pane label",
+                ),
+            };
+            CommandDef {
+                brief: brief.into(),
+                doc: doc.into(),
+                keys: vec![],
+                args: &[],
+                menubar: &["Window", "Pane Selection"],
+                icon: None,
+            }
+        }
         RotatePanes(direction) => CommandDef {
             brief: format!("Rotate panes {direction:?}").into(),
             doc: format!("Rotate panes {direction:?}").into(),
@@ -2039,6 +2274,7 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         CloseCurrentTab { confirm: true },
         CloseCurrentPane { confirm: true },
         DetachDomain(SpawnTabDomain::CurrentPaneDomain),
+        RerunCommand,
         ResetTerminal,
         // ----------------- Edit
         #[cfg(not(target_os = "macos"))]
@@ -2052,6 +2288,8 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         QuickSelect,
         CharSelect(CharSelectArguments::default()),
         ActivateCopyMode,
+        SelectCommandOutput,
+        CopyCommandOutput(ClipboardCopyDestination::Clipboard),
         ClearKeyTableStack,
         ActivateCommandPalette,
         // ----------------- View
@@ -2063,6 +2301,7 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         ScrollByPage(NotNan::new(1.0).unwrap()),
         ScrollToTop,
         ScrollToBottom,
+        ToggleCommandOutputFold,
         // ----------------- Window
         ToggleFullScreen,
         ToggleAlwaysOnTop,
@@ -381,6 +381,18 @@ impl InputMap {
         keys.by_name
             .entry("search_mode".to_string())
             .or_insert_with(crate::overlay::copy::search_key_table);
+        keys.by_name
+            .entry("launcher_mode".to_string())
+            .or_insert_with(crate::overlay::launcher::launcher_key_table);
+        keys.by_name
+            .entry("palette_mode".to_string())
+            .or_insert_with(crate::termwindow::palette::palette_key_table);
+        keys.by_name
+            .entry("char_selector_mode".to_string())
+            .or_insert_with(crate::termwindow::charselect::char_selector_key_table);
+        keys.by_name
+            .entry("pane_selector_mode".to_string())
+            .or_insert_with(crate::termwindow::paneselect::pane_selector_key_table);
 
         Self {
             keys,
@@ -11,6 +11,7 @@ use clap::{Parser, ValueHint};
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use config::{ConfigHandle, SerialDomain, SshDomain, SshMultiplexing};
 use mux::activity::Activity;
+use mux::client::ClientPermission;
 use mux::domain::{Domain, LocalDomain};
 use mux::Mux;
 use mux_lua::MuxDomain;
@@ -682,7 +683,7 @@ fn setup_mux(
     let mux = Arc::new(mux::Mux::new(Some(local_domain.clone())));
     Mux::set_mux(&mux);
     let client_id = Arc::new(mux::client::ClientId::new());
-    mux.register_client(client_id.clone());
+    mux.register_client(client_id.clone(), ClientPermission::ReadWrite);
     mux.replace_identity(Some(client_id));
     let default_workspace_name = default_workspace_name.unwrap_or(
         config
@@ -11,7 +11,9 @@ use crate::overlay::quickselect;
 use crate::overlay::selector::{matcher_pattern, matcher_score};
 use crate::termwindow::TermWindowNotif;
 use config::configuration;
-use config::keyassignment::{KeyAssignment, SpawnCommand, SpawnTabDomain};
+use config::keyassignment::{
+    KeyAssignment, KeyTable, KeyTableEntry, LauncherModeAssignment, SpawnCommand, SpawnTabDomain,
+};
 use mux::domain::{DomainId, DomainState};
 use mux::pane::PaneId;
 use mux::termwiztermtab::TermWizTerminal;
@@ -25,7 +27,7 @@ use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, Mou
 use termwiz::surface::{Change, Position};
 use termwiz::terminal::Terminal;
 use termwiz_funcs::truncate_right;
-use window::WindowOps;
+use window::{KeyCode as WKeyCode, WindowOps};
 
 pub use config::keyassignment::LauncherFlags;
 
@@ -187,6 +189,7 @@ struct LauncherState {
     alphabet: String,
     selection: String,
     always_fuzzy: bool,
+    input_map: InputMap,
 }
 
 impl LauncherState {
@@ -502,12 +505,72 @@ impl LauncherState {
         }
     }
 
+    fn page_up(&mut self) {
+        for _ in 0..self.max_items {
+            self.move_up();
+        }
+    }
+
+    fn page_down(&mut self) {
+        for _ in 0..self.max_items {
+            self.move_down();
+        }
+    }
+
+    fn lookup_key(&self, key: &KeyEvent) -> Option<LauncherModeAssignment> {
+        lookup_launcher_key(&self.input_map, key, self.filtering, &self.alphabet)
+    }
+
+    /// Returns true if the launcher should close
+    fn perform_assignment(&mut self, assignment: LauncherModeAssignment) -> bool {
+        match assignment {
+            LauncherModeAssignment::Close => return true,
+            LauncherModeAssignment::MoveUp => self.move_up(),
+            LauncherModeAssignment::MoveDown => self.move_down(),
+            LauncherModeAssignment::PageUp => self.page_up(),
+            LauncherModeAssignment::PageDown => self.page_down(),
+            LauncherModeAssignment::Accept => return self.launch(self.active_idx),
+            LauncherModeAssignment::StartFilter => {
+                self.filtering = true;
+            }
+            LauncherModeAssignment::DeleteBackward => {
+                if !self.filtering {
+                    self.selection.pop();
+                } else {
+                    if self.filter_term.pop().is_none() && !self.always_fuzzy {
+                        self.filtering = false;
+                    }
+                    self.update_filter();
+                }
+            }
+            LauncherModeAssignment::ClearInput => {
+                if !self.filtering {
+                    self.selection.clear();
+                } else {
+                    self.filter_term.clear();
+                    self.update_filter();
+                }
+            }
+        }
+        false
+    }
+
     fn run_loop(&mut self, term: &mut TermWizTerminal) -> anyhow::Result<()> {
         while let Ok(Some(event)) = term.poll_input(None) {
+            if let InputEvent::Key(key) = &event {
+                if let Some(assignment) = self.lookup_key(key) {
+                    if self.perform_assignment(assignment) {
+                        break;
+                    }
+                    self.render(term)?;
+                    continue;
+                }
+            }
             match event {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char(c),
                     modifiers: Modifiers::NONE,
+                    ..
                 }) if !self.filtering && self.alphabet.contains(c) => {
                     self.selection.push(c);
                     if let Some(pos) = self.labels.iter().position(|x| *x == self.selection) {
@@ -520,59 +583,6 @@ impl LauncherState {
                         }
                     }
                 }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Char('j'),
-                    ..
-                }) if !self.filtering => {
-                    self.move_down();
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Char('k'),
-                    ..
-                }) if !self.filtering => {
-                    self.move_up();
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Char('P' | 'K'),
-                    modifiers: Modifiers::CTRL,
-                }) => {
-                    self.move_up();
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Char('N' | 'J'),
-                    modifiers: Modifiers::CTRL,
-                }) => {
-                    self.move_down();
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Char('/'),
-                    ..
-                }) if !self.filtering => {
-                    self.filtering = true;
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Backspace,
-                    ..
-                }) => {
-                    if !self.filtering {
-                        self.selection.pop();
-                    } else {
-                        if self.filter_term.pop().is_none() && !self.always_fuzzy {
-                            self.filtering = false;
-                        }
-                        self.update_filter();
-                    }
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Char('G') | KeyCode::Char('['),
-                    modifiers: Modifiers::CTRL,
-                })
-                | InputEvent::Key(KeyEvent {
-                    key: KeyCode::Escape,
-                    ..
-                }) => {
-                    break;
-                }
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char(c),
                     ..
@@ -580,18 +590,6 @@ impl LauncherState {
                     self.filter_term.push(c);
                     self.update_filter();
                 }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::UpArrow,
-                    ..
-                }) => {
-                    self.move_up();
-                }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::DownArrow,
-                    ..
-                }) => {
-                    self.move_down();
-                }
                 InputEvent::Mouse(MouseEvent {
                     y, mouse_buttons, ..
                 }) if mouse_buttons.contains(MouseButtons::VERT_WHEEL) => {
@@ -627,14 +625,6 @@ impl LauncherState {
                         break;
                     }
                 }
-                InputEvent::Key(KeyEvent {
-                    key: KeyCode::Enter,
-                    ..
-                }) => {
-                    if self.launch(self.active_idx) {
-                        break;
-                    }
-                }
                 _ => {}
             }
             self.render(term)?;
@@ -644,6 +634,152 @@ impl LauncherState {
     }
 }
 
+/// Resolve a key press against the `launcher_mode` key table.
+/// Printable characters are left for the alphabet labels and the
+/// fuzzy filter so that they can still be typed.
+fn lookup_launcher_key(
+    input_map: &InputMap,
+    key: &KeyEvent,
+    filtering: bool,
+    alphabet: &str,
+) -> Option<LauncherModeAssignment> {
+    let code = match key.key {
+        KeyCode::Char(c)
+            if !c.is_control()
+                && (key.modifiers == Modifiers::NONE || key.modifiers == Modifiers::SHIFT)
+                && (filtering || alphabet.contains(c)) =>
+        {
+            return None;
+        }
+        // TermWizTerminal upper cases CTRL-letter; undo that so
+        // that it matches the way that the key tables are written
+        KeyCode::Char(c) if key.modifiers == Modifiers::CTRL => {
+            WKeyCode::Char(c.to_ascii_lowercase())
+        }
+        KeyCode::Char(c) => WKeyCode::Char(c),
+        KeyCode::Enter => WKeyCode::Char('\r'),
+        KeyCode::Escape => WKeyCode::Char('\x1b'),
+        KeyCode::Backspace => WKeyCode::Char('\x08'),
+        KeyCode::Tab => WKeyCode::Char('\t'),
+        KeyCode::UpArrow => WKeyCode::UpArrow,
+        KeyCode::DownArrow => WKeyCode::DownArrow,
+        KeyCode::LeftArrow => WKeyCode::LeftArrow,
+        KeyCode::RightArrow => WKeyCode::RightArrow,
+        KeyCode::PageUp => WKeyCode::PageUp,
+        KeyCode::PageDown => WKeyCode::PageDown,
+        KeyCode::Home => WKeyCode::Home,
+        KeyCode::End => WKeyCode::End,
+        KeyCode::Function(n) => WKeyCode::Function(n),
+        _ => return None,
+    };
+    match input_map
+        .lookup_key(&code, key.modifiers, Some("launcher_mode"))?
+        .action
+    {
+        KeyAssignment::LauncherMode(assignment) => Some(assignment),
+        _ => None,
+    }
+}
+
+pub fn launcher_key_table() -> KeyTable {
+    let mut table = KeyTable::default();
+    for (key, mods, action) in [
+        (
+            WKeyCode::Char('\x1b'),
+            Modifiers::NONE,
+            LauncherModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('g'),
+            Modifiers::CTRL,
+            LauncherModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('['),
+            Modifiers::CTRL,
+            LauncherModeAssignment::Close,
+        ),
+        (
+            WKeyCode::UpArrow,
+            Modifiers::NONE,
+            LauncherModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('k'),
+            Modifiers::NONE,
+            LauncherModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('p'),
+            Modifiers::CTRL,
+            LauncherModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('k'),
+            Modifiers::CTRL,
+            LauncherModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::DownArrow,
+            Modifiers::NONE,
+            LauncherModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('j'),
+            Modifiers::NONE,
+            LauncherModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('n'),
+            Modifiers::CTRL,
+            LauncherModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('j'),
+            Modifiers::CTRL,
+            LauncherModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::PageUp,
+            Modifiers::NONE,
+            LauncherModeAssignment::PageUp,
+        ),
+        (
+            WKeyCode::PageDown,
+            Modifiers::NONE,
+            LauncherModeAssignment::PageDown,
+        ),
+        (
+            WKeyCode::Char('\r'),
+            Modifiers::NONE,
+            LauncherModeAssignment::Accept,
+        ),
+        (
+            WKeyCode::Char('/'),
+            Modifiers::NONE,
+            LauncherModeAssignment::StartFilter,
+        ),
+        (
+            WKeyCode::Char('\x08'),
+            Modifiers::NONE,
+            LauncherModeAssignment::DeleteBackward,
+        ),
+        (
+            WKeyCode::Char('u'),
+            Modifiers::CTRL,
+            LauncherModeAssignment::ClearInput,
+        ),
+    ] {
+        table.insert(
+            (key, mods),
+            KeyTableEntry {
+                action: KeyAssignment::LauncherMode(action),
+            },
+        );
+    }
+    table
+}
+
 pub fn launcher(
     args: LauncherArgs,
     mut term: TermWizTerminal,
@@ -667,6 +803,7 @@ pub fn launcher(
         selection: String::new(),
         alphabet: args.alphabet.clone(),
         always_fuzzy: filtering,
+        input_map: InputMap::new(&configuration()),
     };
 
     term.set_raw_mode()?;
@@ -676,3 +813,96 @@ pub fn launcher(
     state.render(&mut term)?;
     state.run_loop(&mut term)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(key: KeyCode, modifiers: Modifiers) -> KeyEvent {
+        KeyEvent {
+            key,
+            modifiers,
+            event_type: termwiz::input::KeyEventType::Press,
+            kitty: None,
+        }
+    }
+
+    #[test]
+    fn default_key_table() {
+        let input_map = InputMap::new(&config::configuration());
+        let lookup = |k, mods| lookup_launcher_key(&input_map, &key(k, mods), false, "1234");
+
+        assert_eq!(
+            lookup(KeyCode::Escape, Modifiers::NONE),
+            Some(LauncherModeAssignment::Close)
+        );
+        assert_eq!(
+            lookup(KeyCode::Enter, Modifiers::NONE),
+            Some(LauncherModeAssignment::Accept)
+        );
+        assert_eq!(
+            lookup(KeyCode::Char('j'), Modifiers::NONE),
+            Some(LauncherModeAssignment::MoveDown)
+        );
+        // TermWizTerminal reports CTRL-letters in upper case
+        assert_eq!(
+            lookup(KeyCode::Char('P'), Modifiers::CTRL),
+            Some(LauncherModeAssignment::MoveUp)
+        );
+        assert_eq!(
+            lookup(KeyCode::Char('/'), Modifiers::NONE),
+            Some(LauncherModeAssignment::StartFilter)
+        );
+        assert_eq!(lookup(KeyCode::Char('z'), Modifiers::ALT), None);
+    }
+
+    #[test]
+    fn printable_keys_pass_through() {
+        let input_map = InputMap::new(&config::configuration());
+
+        // While filtering, keys that are otherwise bound are typed
+        for c in ['j', 'k', '/', 'J'] {
+            let mods = if c.is_uppercase() {
+                Modifiers::SHIFT
+            } else {
+                Modifiers::NONE
+            };
+            assert_eq!(
+                lookup_launcher_key(&input_map, &key(KeyCode::Char(c), mods), true, ""),
+                None,
+                "{c}"
+            );
+        }
+        // Modified keys and non-printable keys still resolve
+        assert_eq!(
+            lookup_launcher_key(
+                &input_map,
+                &key(KeyCode::Char('U'), Modifiers::CTRL),
+                true,
+                ""
+            ),
+            Some(LauncherModeAssignment::ClearInput)
+        );
+        assert_eq!(
+            lookup_launcher_key(
+                &input_map,
+                &key(KeyCode::Backspace, Modifiers::NONE),
+                true,
+                ""
+            ),
+            Some(LauncherModeAssignment::DeleteBackward)
+        );
+
+        // Without filtering, the alphabet labels take precedence
+        // over the bindings for the same keys
+        assert_eq!(
+            lookup_launcher_key(
+                &input_map,
+                &key(KeyCode::Char('j'), Modifiers::NONE),
+                false,
+                "jkl"
+            ),
+            None
+        );
+    }
+}
@@ -245,6 +245,7 @@ impl SelectorState {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char(c),
                     modifiers: Modifiers::NONE,
+                    ..
                 }) if !self.filtering && self.args.alphabet.contains(c) => {
                     self.selection.push(c);
                     if let Some(pos) = self.labels.iter().position(|x| *x == self.selection) {
@@ -272,12 +273,14 @@ impl SelectorState {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char('P' | 'K'),
                     modifiers: Modifiers::CTRL,
+                    ..
                 }) => {
                     self.move_up();
                 }
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char('N' | 'J'),
                     modifiers: Modifiers::CTRL,
+                    ..
                 }) => {
                     self.move_down();
                 }
@@ -303,6 +306,7 @@ impl SelectorState {
                 InputEvent::Key(KeyEvent {
                     key: KeyCode::Char('G' | 'C'),
                     modifiers: Modifiers::CTRL,
+                    ..
                 })
                 | InputEvent::Key(KeyEvent {
                     key: KeyCode::Escape,
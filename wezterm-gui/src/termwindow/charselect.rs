@@ -9,7 +9,8 @@ use crate::termwindow::DimensionContext;
 use crate::utilsprites::RenderMetrics;
 use crate::TermWindow;
 use config::keyassignment::{
-    CharSelectArguments, CharSelectGroup, ClipboardCopyDestination, KeyAssignment,
+    CharSelectArguments, CharSelectGroup, CharSelectorModeAssignment, ClipboardCopyDestination,
+    KeyAssignment, KeyTable, KeyTableEntry,
 };
 use config::Dimension;
 use emojis::{Emoji, Group};
@@ -20,9 +21,9 @@ use std::borrow::Cow;
 use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use termwiz::input::Modifiers;
-use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use wezterm_term::{KeyCode, KeyModifiers, MouseButton, MouseEvent};
 use window::color::LinearRgba;
+use window::{KeyCode as WKeyCode, Modifiers};
 
 struct MatchResults {
     selection: String,
@@ -552,6 +553,36 @@ impl CharSelector {
         *self.top_row.borrow_mut() = 0;
     }
 
+    fn accept(&self, term_window: &mut TermWindow) {
+        // Enter the selected character to the current pane
+        let selected_idx = *self.selected_row.borrow();
+        let alias_idx = match self.matches.borrow().as_ref() {
+            None => return,
+            Some(results) => match results.matches.get(selected_idx) {
+                Some(i) => *i,
+                None => return,
+            },
+        };
+        let item = &self.aliases[alias_idx];
+        if let Err(err) = save_recent(item) {
+            log::error!("Error while saving recents: {err:#}");
+        }
+        let glyph = item.glyph();
+        log::trace!(
+            "selected: {glyph}. copy_on_select={} -> {:?}",
+            self.copy_on_select,
+            self.copy_to
+        );
+
+        if self.copy_on_select {
+            term_window.copy_to_clipboard(self.copy_to, glyph.clone());
+        }
+        if let Some(pane) = term_window.get_active_pane_or_overlay() {
+            pane.writer().write_all(glyph.as_bytes()).ok();
+        }
+        term_window.cancel_modal();
+    }
+
     fn do_move(&self, how: Move) {
         let page_size = *self.max_rows_on_screen.borrow();
         let current_row = *self.selected_row.borrow();
@@ -589,104 +620,78 @@ impl CharSelector {
 }
 
 impl Modal for CharSelector {
-    fn perform_assignment(
-        &self,
-        _assignment: &KeyAssignment,
-        _term_window: &mut TermWindow,
-    ) -> bool {
-        false
-    }
-
-    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
-        Ok(())
+    fn key_table_name(&self) -> Option<&'static str> {
+        Some("char_selector_mode")
     }
 
-    fn key_down(
-        &self,
-        key: KeyCode,
-        mods: KeyModifiers,
-        term_window: &mut TermWindow,
-    ) -> anyhow::Result<bool> {
-        const CTRL_AND_SHIFT: Modifiers = KeyModifiers::CTRL.union(KeyModifiers::SHIFT);
-
-        match (key, mods) {
-            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+    fn perform_assignment(&self, assignment: &KeyAssignment, term_window: &mut TermWindow) -> bool {
+        let assignment = match assignment {
+            KeyAssignment::CharSelectorMode(assignment) => assignment,
+            _ => return false,
+        };
+        match assignment {
+            CharSelectorModeAssignment::Close => {
                 term_window.cancel_modal();
+                return true;
             }
-            (KeyCode::Char('r'), KeyModifiers::CTRL) => {
+            CharSelectorModeAssignment::NextGroup => {
                 // Cycle the selected group
                 let mut group = self.group.borrow_mut();
                 *group = group.next();
                 self.selection.borrow_mut().clear();
                 self.updated_input();
             }
-            (KeyCode::Char('R'), KeyModifiers::CTRL) | (KeyCode::Char('r'), CTRL_AND_SHIFT) => {
+            CharSelectorModeAssignment::PriorGroup => {
                 // Cycle the selected group in reverse direction
                 let mut group = self.group.borrow_mut();
                 *group = group.previous();
                 self.selection.borrow_mut().clear();
                 self.updated_input();
             }
-            (KeyCode::PageUp, KeyModifiers::NONE) => {
-                self.do_move(Move::PageUp);
-            }
-            (KeyCode::PageDown, KeyModifiers::NONE) => {
-                self.do_move(Move::PageDown);
+            CharSelectorModeAssignment::PageUp => self.do_move(Move::PageUp),
+            CharSelectorModeAssignment::PageDown => self.do_move(Move::PageDown),
+            CharSelectorModeAssignment::MoveUp => self.do_move(Move::Up(1)),
+            CharSelectorModeAssignment::MoveDown => self.do_move(Move::Down(1)),
+            CharSelectorModeAssignment::DeleteBackward => {
+                self.selection.borrow_mut().pop();
+                self.updated_input();
             }
-            (KeyCode::UpArrow, KeyModifiers::NONE) => {
-                self.do_move(Move::Up(1));
+            CharSelectorModeAssignment::ClearInput => {
+                self.selection.borrow_mut().clear();
+                self.updated_input();
             }
-            (KeyCode::DownArrow, KeyModifiers::NONE) => {
-                self.do_move(Move::Down(1));
+            CharSelectorModeAssignment::Accept => {
+                self.accept(term_window);
+                return true;
             }
+        }
+        term_window.invalidate_modal();
+        true
+    }
+
+    fn mouse_event(&self, event: MouseEvent, term_window: &mut TermWindow) -> anyhow::Result<()> {
+        let assignment = match event.button {
+            MouseButton::WheelUp(_) => CharSelectorModeAssignment::MoveUp,
+            MouseButton::WheelDown(_) => CharSelectorModeAssignment::MoveDown,
+            _ => return Ok(()),
+        };
+        self.perform_assignment(&KeyAssignment::CharSelectorMode(assignment), term_window);
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<bool> {
+        match (key, mods) {
             (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
                 // Type to add to the selection
                 let mut selection = self.selection.borrow_mut();
                 selection.push(c);
                 self.updated_input();
             }
-            (KeyCode::Backspace, KeyModifiers::NONE) => {
-                // Backspace to edit the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.pop();
-                self.updated_input();
-            }
-            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
-                // CTRL-u to clear the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.clear();
-                self.updated_input();
-            }
-            (KeyCode::Enter, KeyModifiers::NONE) => {
-                // Enter the selected character to the current pane
-                let selected_idx = *self.selected_row.borrow();
-                let alias_idx = match self.matches.borrow().as_ref() {
-                    None => return Ok(true),
-                    Some(results) => match results.matches.get(selected_idx) {
-                        Some(i) => *i,
-                        None => return Ok(true),
-                    },
-                };
-                let item = &self.aliases[alias_idx];
-                if let Err(err) = save_recent(item) {
-                    log::error!("Error while saving recents: {err:#}");
-                }
-                let glyph = item.glyph();
-                log::trace!(
-                    "selected: {glyph}. copy_on_select={} -> {:?}",
-                    self.copy_on_select,
-                    self.copy_to
-                );
-
-                if self.copy_on_select {
-                    term_window.copy_to_clipboard(self.copy_to, glyph.clone());
-                }
-                if let Some(pane) = term_window.get_active_pane_or_overlay() {
-                    pane.writer().write_all(glyph.as_bytes()).ok();
-                }
-                term_window.cancel_modal();
-                return Ok(true);
-            }
             _ => return Ok(false),
         }
         term_window.invalidate_modal();
@@ -750,3 +755,92 @@ impl Modal for CharSelector {
         self.element.borrow_mut().take();
     }
 }
+
+pub fn char_selector_key_table() -> KeyTable {
+    let mut table = KeyTable::default();
+    for (key, mods, action) in [
+        (
+            WKeyCode::Char('\x1b'),
+            Modifiers::NONE,
+            CharSelectorModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('g'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('r'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::NextGroup,
+        ),
+        (
+            WKeyCode::Char('R'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::PriorGroup,
+        ),
+        (
+            WKeyCode::UpArrow,
+            Modifiers::NONE,
+            CharSelectorModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('p'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('k'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::DownArrow,
+            Modifiers::NONE,
+            CharSelectorModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('n'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('j'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::PageUp,
+            Modifiers::NONE,
+            CharSelectorModeAssignment::PageUp,
+        ),
+        (
+            WKeyCode::PageDown,
+            Modifiers::NONE,
+            CharSelectorModeAssignment::PageDown,
+        ),
+        (
+            WKeyCode::Char('\r'),
+            Modifiers::NONE,
+            CharSelectorModeAssignment::Accept,
+        ),
+        (
+            WKeyCode::Char('\x08'),
+            Modifiers::NONE,
+            CharSelectorModeAssignment::DeleteBackward,
+        ),
+        (
+            WKeyCode::Char('u'),
+            Modifiers::CTRL,
+            CharSelectorModeAssignment::ClearInput,
+        ),
+    ] {
+        table.insert(
+            (key, mods),
+            KeyTableEntry {
+                action: KeyAssignment::CharSelectorMode(action),
+            },
+        );
+    }
+    table
+}
@@ -271,6 +271,26 @@ impl super::TermWindow {
         if is_down {
             if only_key_bindings == OnlyKeyBindings::No {
                 if let Some(modal) = self.get_modal() {
+                    if let Some(table_name) = modal.key_table_name() {
+                        if let Some(entry) =
+                            self.input_map
+                                .lookup_key(keycode, raw_modifiers, Some(table_name))
+                        {
+                            if self.config.debug_key_events {
+                                log::info!(
+                                    "table:{} {:?} {:?} -> perform {:?}",
+                                    table_name,
+                                    keycode,
+                                    raw_modifiers,
+                                    entry.action,
+                                );
+                            }
+                            if modal.perform_assignment(&entry.action, self) {
+                                context.invalidate();
+                                return true;
+                            }
+                        }
+                    }
                     if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(keycode) {
                         match modal.key_down(term_key, raw_modifiers.remove_positional_mods(), self)
                         {
@@ -66,7 +66,9 @@ use wezterm_dynamic::Value;
 use wezterm_font::FontConfiguration;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
-use wezterm_term::{Alert, Progress, StableRowIndex, TerminalConfiguration, TerminalSize};
+use wezterm_term::{
+    Alert, CommandBlock, Progress, StableRowIndex, TerminalConfiguration, TerminalSize,
+};
 
 pub mod background;
 pub mod box_model;
@@ -160,6 +162,14 @@ pub enum UIItemType {
     ScrollThumb,
     BelowScrollThumb,
     Split(PositionedSplit),
+    CommandBlockMarker(CommandBlockMarker),
+}
+
+/// A gutter marker drawn alongside a shell-integration prompt
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandBlockMarker {
+    pub pane_id: PaneId,
+    pub block: CommandBlock,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -186,6 +196,12 @@ pub struct SemanticZoneCache {
     zones: Vec<StableRowIndex>,
 }
 
+#[derive(Clone, Default)]
+pub struct CommandBlockCache {
+    seqno: SequenceNo,
+    blocks: Vec<CommandBlock>,
+}
+
 pub struct OverlayState {
     pub pane: Arc<dyn Pane>,
     pub key_table_state: KeyTableState,
@@ -407,6 +423,7 @@ pub struct TermWindow {
     tab_state: RefCell<HashMap<TabId, TabState>>,
     pane_state: RefCell<HashMap<PaneId, PaneState>>,
     semantic_zones: HashMap<PaneId, SemanticZoneCache>,
+    command_blocks: HashMap<PaneId, CommandBlockCache>,
 
     window_background: Vec<LoadedBackgroundLayer>,
 
@@ -782,6 +799,7 @@ impl TermWindow {
             scheduled_animation: RefCell::new(None),
             allow_images: AllowImage::Yes,
             semantic_zones: HashMap::new(),
+            command_blocks: HashMap::new(),
             ui_items: vec![],
             dragging: None,
             last_ui_item: None,
@@ -1434,6 +1452,9 @@ impl TermWindow {
 
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         metrics::histogram!("mux.pane_output_event.rate").record(1.);
+        // Folding command output doesn't change the seqno,
+        // so we can't rely on it to invalidate the cache
+        self.command_blocks.remove(&pane_id);
         if self.is_pane_visible(pane_id) {
             if let Some(ref win) = self.window {
                 win.invalidate();
@@ -2045,12 +2066,27 @@ impl TermWindow {
             Some(title) => title,
             None => {
                 if let (Some(pos), Some(tab)) = (active_pane, active_tab) {
+                    let is_read_only = mux
+                        .get_pane(pos.pane_id)
+                        .map(|pane| match pane.get_metadata() {
+                            Value::Object(obj) => matches!(
+                                obj.get(&Value::String("is_read_only".to_string())),
+                                Some(Value::Bool(true))
+                            ),
+                            _ => false,
+                        })
+                        .unwrap_or(false);
+                    let badges = format!(
+                        "{}{}",
+                        if is_read_only { "[read-only] " } else { "" },
+                        if pos.is_zoomed { "[Z] " } else { "" }
+                    );
                     if num_tabs == 1 {
-                        format!("{}{}", if pos.is_zoomed { "[Z] " } else { "" }, pos.title)
+                        format!("{}{}", badges, pos.title)
                     } else {
                         format!(
                             "{}[{}/{}] {}",
-                            if pos.is_zoomed { "[Z] " } else { "" },
+                            badges,
                             tab.tab_index + 1,
                             num_tabs,
                             pos.title
@@ -2488,6 +2524,126 @@ impl TermWindow {
         &cache.zones
     }
 
+    /// Applies the configured hyperlink rules to the specified lines
+    /// of the pane.  Rules that are scoped to particular domains or
+    /// foreground processes are only applied if they match the pane.
+    pub fn apply_hyperlinks(&self, pane: &Arc<dyn Pane>, lines: std::ops::Range<StableRowIndex>) {
+        let rules = &self.config.hyperlink_rules;
+        if !rules.iter().any(|rule| rule.is_scoped()) {
+            pane.apply_hyperlinks(lines, rules);
+            return;
+        }
+
+        let domain_name = Mux::get()
+            .get_domain(pane.domain_id())
+            .map(|domain| domain.domain_name().to_string())
+            .unwrap_or_default();
+        let process = pane
+            .get_foreground_process_name(CachePolicy::AllowStale)
+            .and_then(|name| {
+                std::path::Path::new(&name)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            });
+
+        let rules: Vec<_> = rules
+            .iter()
+            .filter(|rule| rule.applies_to(&domain_name, process.as_deref()))
+            .cloned()
+            .collect();
+        pane.apply_hyperlinks(lines, &rules);
+    }
+
+    /// Returns the command blocks for the pane, recomputing them
+    /// only when the pane content has changed
+    fn get_command_blocks(&mut self, pane: &Arc<dyn Pane>) -> &[CommandBlock] {
+        let cache = self
+            .command_blocks
+            .entry(pane.pane_id())
+            .or_insert_with(CommandBlockCache::default);
+
+        let seqno = pane.get_current_seqno();
+        if cache.seqno != seqno {
+            cache.blocks = pane.get_command_blocks().unwrap_or_else(|_| vec![]);
+            cache.seqno = seqno;
+        }
+        &cache.blocks
+    }
+
+    /// Returns the stable row index that is displayed on each row
+    /// of the pane, skipping over the rows of folded command output.
+    /// The result can be shorter than the viewport if there are
+    /// not enough rows to fill it.
+    pub fn visible_stable_rows(&mut self, pane: &Arc<dyn Pane>) -> Vec<StableRowIndex> {
+        let dims = pane.get_dimensions();
+        let viewport = self.get_viewport(pane.pane_id());
+        let top = viewport.unwrap_or(dims.physical_top);
+        let rows = dims.viewport_rows as StableRowIndex;
+
+        if !pane.has_folded_command_output() {
+            return (top..top + rows).collect();
+        }
+
+        // Never hide the cursor, so that a command that is still
+        // running remains visible
+        let cursor_y = pane.get_cursor_position().y;
+        let hidden: Vec<_> = self
+            .get_command_blocks(pane)
+            .iter()
+            .filter_map(|block| block.folded_rows())
+            .filter(|range| !range.contains(&cursor_y))
+            .collect();
+        if hidden.is_empty() {
+            return (top..top + rows).collect();
+        }
+        let is_visible = |row: &StableRowIndex| !hidden.iter().any(|range| range.contains(row));
+
+        let last_row = dims.physical_top + rows - 1;
+        match viewport {
+            Some(top) => (top..=last_row)
+                .filter(is_visible)
+                .take(dims.viewport_rows)
+                .collect(),
+            None => {
+                // Anchor the bottom of the screen, pulling in rows
+                // from the scrollback to fill the space left by
+                // the folded rows
+                let mut result: Vec<_> = (dims.scrollback_top..=last_row)
+                    .rev()
+                    .filter(is_visible)
+                    .take(dims.viewport_rows)
+                    .collect();
+                result.reverse();
+                result
+            }
+        }
+    }
+
+    /// Maps a row of the pane to the stable row index that is displayed
+    /// on it.  The row may lie outside of the pane while dragging a
+    /// selection, in which case it is extrapolated from the nearest
+    /// visible row.
+    pub fn pane_row_to_stable_row(&mut self, pane: &Arc<dyn Pane>, row: i64) -> StableRowIndex {
+        let rows = self.visible_stable_rows(pane);
+        match (rows.first(), rows.last()) {
+            (Some(&first), Some(&last)) => {
+                if row < 0 {
+                    first + row as StableRowIndex
+                } else if let Some(&stable_row) = rows.get(row as usize) {
+                    stable_row
+                } else {
+                    last + (row - rows.len() as i64 + 1) as StableRowIndex
+                }
+            }
+            _ => {
+                let dims = pane.get_dimensions();
+                self.get_viewport(pane.pane_id())
+                    .unwrap_or(dims.physical_top)
+                    + row as StableRowIndex
+            }
+        }
+    }
+
     fn scroll_to_prompt(&mut self, amount: isize, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
         let dims = pane.get_dimensions();
         let position = self
@@ -2764,6 +2920,38 @@ impl TermWindow {
             ScrollByLine(n) => self.scroll_by_line(*n, pane)?,
             ScrollByCurrentEventWheelDelta => self.scroll_by_current_event_wheel_delta(pane)?,
             ScrollToPrompt(n) => self.scroll_to_prompt(*n, pane)?,
+            SelectCommandOutput => {
+                if let Some(block) = self.command_block_for_viewport(pane) {
+                    self.select_command_output(pane, &block);
+                }
+            }
+            CopyCommandOutput(destination) => {
+                if let Some(zone) = self
+                    .command_block_for_viewport(pane)
+                    .and_then(|block| block.output)
+                {
+                    let text = pane.get_text_from_semantic_zone(&zone);
+                    self.copy_to_clipboard(*destination, text);
+                }
+            }
+            ToggleCommandOutputFold => {
+                if let Some(block) = self.command_output_block_for_viewport(pane) {
+                    pane.set_command_output_folded(block.start_y(), !block.folded)?;
+                }
+            }
+            RerunCommand => {
+                if let Some(zone) = self
+                    .command_block_for_viewport(pane)
+                    .and_then(|block| block.input)
+                {
+                    let text = pane.get_text_from_semantic_zone(&zone);
+                    if !text.is_empty() {
+                        pane.send_paste(&text)?;
+                        pane.writer().write_all(b"\r")?;
+                        self.scroll_to_bottom(pane);
+                    }
+                }
+            }
             ScrollToTop => self.scroll_to_top(pane),
             ScrollToBottom => self.scroll_to_bottom(pane),
             ShowTabNavigator => self.show_tab_navigator(),
@@ -2848,6 +3036,16 @@ impl TermWindow {
                 let window = self.window.as_ref().unwrap();
                 window.invalidate();
             }
+            StartPaneOutputLog(log) => {
+                if let Err(err) = pane.set_output_log(Some(log)) {
+                    log::error!("StartPaneOutputLog: {:#}", err);
+                }
+            }
+            StopPaneOutputLog => {
+                if let Err(err) = pane.set_output_log(None) {
+                    log::error!("StopPaneOutputLog: {:#}", err);
+                }
+            }
             Search(pattern) => {
                 if let Some(pane) = self.get_active_pane_or_overlay() {
                     let mut replace_current = false;
@@ -3085,7 +3283,8 @@ impl TermWindow {
                 })
                 .detach();
             }
-            CopyMode(_) => {
+            CopyMode(_) | LauncherMode(_) | PaletteMode(_) | CharSelectorMode(_)
+            | PaneSelectorMode(_) => {
                 // NOP here; handled by the overlay directly
             }
             RotatePanes(direction) => {
@@ -3165,29 +3364,41 @@ impl TermWindow {
         // We get that assurance for free as part of the async dispatch that we
         // perform below; here we allow the user to define an `open-uri` event
         // handler that can bypass the normal `open_url` functionality.
+        // Links produced by a hyperlink rule that specifies an `event`
+        // emit that event instead, and are never opened as a URL.
         if let Some(link) = self.current_highlight.as_ref().cloned() {
             let window = GuiWin::new(self);
             let pane = MuxPane(pane.pane_id());
+            let rule_event = if link.is_implicit() {
+                link.params()
+                    .get(termwiz::hyperlink::HYPERLINK_EVENT_PARAM)
+                    .cloned()
+            } else {
+                None
+            };
 
             async fn open_uri(
                 lua: Option<Rc<mlua::Lua>>,
                 window: GuiWin,
                 pane: MuxPane,
                 link: String,
+                rule_event: Option<String>,
             ) -> anyhow::Result<()> {
+                let is_open_uri = rule_event.is_none();
+                let event_name = rule_event.unwrap_or_else(|| "open-uri".to_string());
                 let default_click = match lua {
                     Some(lua) => {
                         let args = lua.pack_multi((window, pane, link.clone()))?;
-                        config::lua::emit_event(&lua, ("open-uri".to_string(), args))
+                        config::lua::emit_event(&lua, (event_name.clone(), args))
                             .await
                             .map_err(|e| {
-                                log::error!("while processing open-uri event: {:#}", e);
+                                log::error!("while processing {} event: {:#}", event_name, e);
                                 e
                             })?
                     }
                     None => true,
                 };
-                if default_click {
+                if default_click && is_open_uri {
                     log::info!("clicking {}", link);
                     wezterm_open_url::open_url(&link);
                 }
@@ -3195,7 +3406,7 @@ impl TermWindow {
             }
 
             promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                open_uri(lua, window, pane, link.uri().to_string())
+                open_uri(lua, window, pane, link.uri().to_string(), rule_event)
             }))
             .detach();
         }
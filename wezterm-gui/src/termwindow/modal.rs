@@ -6,6 +6,11 @@ use std::cell::Ref;
 use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
 
 pub trait Modal: Downcast {
+    /// The name of the key table that is consulted for key presses
+    /// while this modal is active, before falling back to `key_down`.
+    fn key_table_name(&self) -> Option<&'static str> {
+        None
+    }
     fn perform_assignment(
         &self,
         _assignment: &KeyAssignment,
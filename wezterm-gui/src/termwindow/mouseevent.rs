@@ -1,6 +1,7 @@
 use crate::tabbar::TabBarItem;
 use crate::termwindow::{
-    GuiWin, MouseCapture, PositionedSplit, ScrollHit, TermWindowNotif, UIItem, UIItemType, TMB,
+    CommandBlockMarker, GuiWin, MouseCapture, PositionedSplit, ScrollHit, TermWindowNotif, UIItem,
+    UIItemType, TMB,
 };
 use ::window::{
     MouseButtons as WMB, MouseCursor, MouseEvent, MouseEventKind as WMEK, MousePress,
@@ -43,7 +44,8 @@ impl super::TermWindow {
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::CommandBlockMarker(_) => {}
         }
     }
 
@@ -54,7 +56,8 @@ impl super::TermWindow {
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::CommandBlockMarker(_) => {}
         }
     }
 
@@ -184,6 +187,32 @@ impl super::TermWindow {
                     return;
                 }
             }
+
+            WMEK::VertWheel(amount) => {
+                if let Some(modal) = self.get_modal() {
+                    // Let the modal scroll its own content rather than
+                    // scrolling the pane underneath it
+                    let button = match amount {
+                        0 => return,
+                        1.. => MouseButton::WheelUp(amount as usize),
+                        _ => MouseButton::WheelDown(-amount as usize),
+                    };
+                    let event = wezterm_term::MouseEvent {
+                        kind: TMEK::Press,
+                        x,
+                        y,
+                        x_pixel_offset,
+                        y_pixel_offset,
+                        button,
+                        modifiers: event.modifiers,
+                    };
+                    if let Err(err) = modal.mouse_event(event, self) {
+                        log::error!("Error dispatching mouse event to modal: {err:#}");
+                    }
+                    context.invalidate();
+                    return;
+                }
+            }
             _ => {}
         }
 
@@ -382,6 +411,9 @@ impl super::TermWindow {
             UIItemType::CloseTab(idx) => {
                 self.mouse_event_close_tab(idx, event, context);
             }
+            UIItemType::CommandBlockMarker(marker) => {
+                self.mouse_event_command_block_marker(marker, event, context);
+            }
         }
     }
 
@@ -645,6 +677,26 @@ impl super::TermWindow {
         }
     }
 
+    pub fn mouse_event_command_block_marker(
+        &mut self,
+        marker: CommandBlockMarker,
+        event: MouseEvent,
+        context: &dyn WindowOps,
+    ) {
+        context.set_cursor(Some(if marker.block.output.is_some() {
+            MouseCursor::Hand
+        } else {
+            MouseCursor::Arrow
+        }));
+
+        if event.kind == WMEK::Press(MousePress::Left) {
+            if let Some(pane) = Mux::get().get_pane(marker.pane_id) {
+                self.select_command_output(&pane, &marker.block);
+                context.invalidate();
+            }
+        }
+    }
+
     fn mouse_event_terminal(
         &mut self,
         mut pane: Arc<dyn Pane>,
@@ -767,11 +819,7 @@ impl super::TermWindow {
             event
         );
 
-        let dims = pane.get_dimensions();
-        let stable_row = self
-            .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top)
-            + row as StableRowIndex;
+        let stable_row = self.pane_row_to_stable_row(&pane, row);
 
         self.pane_state(pane.pane_id())
             .mouse_terminal_coords
@@ -785,7 +833,7 @@ impl super::TermWindow {
                 stable_row,
             ));
 
-        pane.apply_hyperlinks(stable_row..stable_row + 1, &self.config.hyperlink_rules);
+        self.apply_hyperlinks(&pane, stable_row..stable_row + 1);
 
         struct FindCurrentLink {
             current: Option<Arc<Hyperlink>>,
@@ -8,23 +8,31 @@ use crate::termwindow::render::corners::{
 };
 use crate::termwindow::{DimensionContext, GuiWin, TermWindow};
 use crate::utilsprites::RenderMetrics;
-use config::keyassignment::KeyAssignment;
-use config::Dimension;
+use config::keyassignment::{
+    KeyAssignment, KeyTable, KeyTableEntry, PaletteModeAssignment, SpawnCommand, SpawnTabDomain,
+};
+use config::{CommandPaletteProvider, ConfigHandle, Dimension};
 use frecency::Frecency;
 use luahelper::{from_lua_value_dynamic, impl_lua_conversion_dynamic};
+use mux::domain::DomainState;
+use mux::pane::{CachePolicy, Pane};
+use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
 use mux_lua::MuxPane;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::cell::{Ref, RefCell};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Arc;
+use termwiz::cell::SemanticType;
 use termwiz::nerdfonts::NERD_FONTS;
 use wezterm_dynamic::{FromDynamic, ToDynamic};
-use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use wezterm_term::{KeyCode, KeyModifiers, MouseButton, MouseEvent};
 use window::color::LinearRgba;
-use window::Modifiers;
+use window::{KeyCode as WKeyCode, Modifiers};
 
 struct MatchResults {
     selection: String,
@@ -38,9 +46,19 @@ pub struct CommandPalette {
     selected_row: RefCell<usize>,
     top_row: RefCell<usize>,
     max_rows_on_screen: RefCell<usize>,
-    commands: Vec<ExpandedCommand>,
+    entries: Vec<PaletteEntry>,
+}
+
+/// A command along with the provider that contributed it
+struct PaletteEntry {
+    provider: CommandPaletteProvider,
+    command: ExpandedCommand,
 }
 
+/// The number of OSC 133 input zones to consider when
+/// building the RecentCommands provider entries
+const MAX_RECENT_COMMANDS: usize = 100;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct Recent {
     brief: String,
@@ -88,12 +106,15 @@ pub struct UserPaletteEntry {
 }
 impl_lua_conversion_dynamic!(UserPaletteEntry);
 
-fn build_commands(
+/// Builds the key assignment entries, including those added by
+/// the augment-command-palette event
+fn command_entries(
+    config: &ConfigHandle,
     gui_window: GuiWin,
     pane: Option<MuxPane>,
     filter_copy_mode: bool,
 ) -> Vec<ExpandedCommand> {
-    let mut commands = CommandDef::actions_for_palette_and_menubar(&config::configuration());
+    let mut commands = CommandDef::actions_for_palette_and_menubar(config);
 
     match config::run_immediate_with_lua_config(|lua| {
         let mut entries: Vec<UserPaletteEntry> = vec![];
@@ -131,14 +152,279 @@ fn build_commands(
         }
     }
 
-    commands.retain(|cmd| {
-        if filter_copy_mode {
-            !matches!(cmd.action, KeyAssignment::CopyMode(_))
-        } else {
-            true
-        }
+    commands.retain(|cmd| match cmd.action {
+        KeyAssignment::CopyMode(_) => !filter_copy_mode,
+        // These only make sense while their overlay is active
+        KeyAssignment::LauncherMode(_)
+        | KeyAssignment::PaletteMode(_)
+        | KeyAssignment::CharSelectorMode(_)
+        | KeyAssignment::PaneSelectorMode(_) => false,
+        _ => true,
     });
 
+    commands
+}
+
+fn provider_entry(
+    group: &'static [&'static str],
+    brief: String,
+    doc: String,
+    action: KeyAssignment,
+    icon: &'static str,
+) -> ExpandedCommand {
+    ExpandedCommand {
+        brief: brief.into(),
+        doc: doc.into(),
+        action,
+        keys: vec![],
+        menubar: group,
+        icon: Some(Cow::Borrowed(icon)),
+    }
+}
+
+fn launch_menu_entries(config: &ConfigHandle) -> Vec<ExpandedCommand> {
+    config
+        .launch_menu
+        .iter()
+        .map(|item| {
+            let brief = match item.label.as_ref() {
+                Some(label) => label.to_string(),
+                None => match item.args.as_ref() {
+                    Some(args) => args.join(" "),
+                    None => "(default shell)".to_string(),
+                },
+            };
+            provider_entry(
+                &["Launch Menu"],
+                brief,
+                String::new(),
+                KeyAssignment::SpawnCommandInNewTab(item.clone()),
+                "md_rocket_launch",
+            )
+        })
+        .collect()
+}
+
+fn pane_entries(mux_window_id: MuxWindowId) -> Vec<ExpandedCommand> {
+    let mux = Mux::get();
+    let window = match mux.get_window(mux_window_id) {
+        Some(window) => window,
+        None => return vec![],
+    };
+
+    let mut entries = vec![];
+    for (tab_idx, tab) in window.iter().enumerate() {
+        for pos in tab.iter_panes_ignoring_zoom() {
+            let mut details = vec![format!("tab {} pane {}", tab_idx + 1, pos.index)];
+            if let Some(name) = pos
+                .pane
+                .get_foreground_process_name(CachePolicy::AllowStale)
+            {
+                let name = std::path::Path::new(&name)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or(name);
+                details.push(name);
+            }
+            if let Some(cwd) = pos.pane.get_current_working_dir(CachePolicy::AllowStale) {
+                details.push(cwd.path().to_string());
+            }
+
+            entries.push(provider_entry(
+                &["Pane"],
+                pos.pane.get_title(),
+                details.join(" "),
+                KeyAssignment::Multiple(vec![
+                    KeyAssignment::ActivateTab(tab_idx as isize),
+                    KeyAssignment::ActivatePaneByIndex(pos.index),
+                ]),
+                "cod_split_horizontal",
+            ));
+        }
+    }
+    entries
+}
+
+fn tab_entries(mux_window_id: MuxWindowId) -> Vec<ExpandedCommand> {
+    let mux = Mux::get();
+    let window = match mux.get_window(mux_window_id) {
+        Some(window) => window,
+        None => return vec![],
+    };
+
+    window
+        .iter()
+        .enumerate()
+        .map(|(tab_idx, tab)| {
+            let title = tab.get_title();
+            let title = if title.is_empty() {
+                tab.get_active_pane()
+                    .map(|pane| pane.get_title())
+                    .unwrap_or_default()
+            } else {
+                title
+            };
+            let doc = match tab.count_panes() {
+                Some(count) => format!("tab {} with {count} panes", tab_idx + 1),
+                None => format!("tab {}", tab_idx + 1),
+            };
+            provider_entry(
+                &["Tab"],
+                title,
+                doc,
+                KeyAssignment::ActivateTab(tab_idx as isize),
+                "md_tab",
+            )
+        })
+        .collect()
+}
+
+fn workspace_entries() -> Vec<ExpandedCommand> {
+    let mux = Mux::get();
+    let active = mux.active_workspace();
+    mux.iter_workspaces()
+        .into_iter()
+        .filter(|ws| *ws != active)
+        .map(|ws| {
+            provider_entry(
+                &["Workspace"],
+                format!("Switch to workspace `{ws}`"),
+                String::new(),
+                KeyAssignment::SwitchToWorkspace {
+                    name: Some(ws),
+                    spawn: None,
+                },
+                "md_briefcase",
+            )
+        })
+        .collect()
+}
+
+fn domain_entries() -> Vec<ExpandedCommand> {
+    let mux = Mux::get();
+    let mut domains = mux.iter_domains();
+    domains.retain(|dom| dom.spawnable());
+    domains.sort_by_key(|dom| dom.domain_id());
+
+    domains
+        .into_iter()
+        .map(|dom| {
+            let name = dom.domain_name().to_string();
+            if dom.state() == DomainState::Attached {
+                provider_entry(
+                    &["Domain"],
+                    format!("New tab in domain `{name}`"),
+                    String::new(),
+                    KeyAssignment::SpawnCommandInNewTab(SpawnCommand {
+                        domain: SpawnTabDomain::DomainName(name),
+                        ..SpawnCommand::default()
+                    }),
+                    "cod_remote",
+                )
+            } else {
+                provider_entry(
+                    &["Domain"],
+                    format!("Attach domain `{name}`"),
+                    String::new(),
+                    KeyAssignment::AttachDomain(name),
+                    "cod_remote",
+                )
+            }
+        })
+        .collect()
+}
+
+/// Recovers the command lines that were entered at the shell prompt,
+/// as delimited by OSC 133 input zones, most recent first.
+fn recent_command_entries(pane: &Arc<dyn Pane>) -> Vec<ExpandedCommand> {
+    let zones = match pane.get_semantic_zones() {
+        Ok(zones) => zones,
+        Err(err) => {
+            log::debug!("recent commands: {err:#}");
+            return vec![];
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut entries = vec![];
+    for zone in zones
+        .iter()
+        .rev()
+        .filter(|zone| zone.semantic_type == SemanticType::Input)
+        .take(MAX_RECENT_COMMANDS)
+    {
+        let text = pane.get_text_from_semantic_zone(zone);
+        let text = text.trim();
+        // Sending a line break would run the command as soon as it is
+        // picked, so multi-line commands are not offered at all
+        if text.is_empty() || text.contains(['\n', '\r']) || !seen.insert(text.to_string()) {
+            continue;
+        }
+        entries.push(provider_entry(
+            &["Recent Command"],
+            text.to_string(),
+            String::new(),
+            KeyAssignment::SendString(text.to_string()),
+            "md_history",
+        ));
+    }
+    entries
+}
+
+fn build_entries(
+    config: &ConfigHandle,
+    gui_window: GuiWin,
+    mux_window_id: MuxWindowId,
+    pane: Option<Arc<dyn Pane>>,
+    mux_pane: Option<MuxPane>,
+    filter_copy_mode: bool,
+) -> Vec<PaletteEntry> {
+    let mut providers = config.command_palette_providers.clone();
+    providers.sort();
+    providers.dedup();
+
+    let mut entries: Vec<PaletteEntry> = vec![];
+    for &provider in &providers {
+        let commands = match provider {
+            // Added below, once we know what the other providers offer
+            CommandPaletteProvider::Commands => continue,
+            CommandPaletteProvider::LaunchMenu => launch_menu_entries(config),
+            CommandPaletteProvider::Panes => pane_entries(mux_window_id),
+            CommandPaletteProvider::Tabs => tab_entries(mux_window_id),
+            CommandPaletteProvider::Workspaces => workspace_entries(),
+            CommandPaletteProvider::Domains => domain_entries(),
+            CommandPaletteProvider::RecentCommands => match &pane {
+                Some(pane) => recent_command_entries(pane),
+                None => vec![],
+            },
+        };
+        entries.extend(
+            commands
+                .into_iter()
+                .map(|command| PaletteEntry { provider, command }),
+        );
+    }
+
+    if providers.contains(&CommandPaletteProvider::Commands) {
+        let mut commands = command_entries(config, gui_window, mux_pane, filter_copy_mode);
+        // The launch menu, domain and workspace items that are generated
+        // for the menubar belong to their own providers when enabled
+        commands.retain(|cmd| {
+            !entries.iter().any(|entry| {
+                matches!(
+                    entry.provider,
+                    CommandPaletteProvider::LaunchMenu
+                        | CommandPaletteProvider::Workspaces
+                        | CommandPaletteProvider::Domains
+                ) && entry.command.action == cmd.action
+            })
+        });
+        entries.extend(commands.into_iter().map(|command| PaletteEntry {
+            provider: CommandPaletteProvider::Commands,
+            command,
+        }));
+    }
+
     let mut scores: HashMap<&str, f64> = HashMap::new();
     let recents = load_recents();
     if let Ok(recents) = &recents {
@@ -147,8 +433,8 @@ fn build_commands(
         }
     }
 
-    commands.sort_by(|a, b| {
-        match (scores.get(&*a.brief), scores.get(&*b.brief)) {
+    entries.sort_by(|a, b| {
+        match (scores.get(&*a.command.brief), scores.get(&*b.command.brief)) {
             // Want descending frecency score, so swap a<->b
             // for the compare here
             (Some(a), Some(b)) => match b.partial_cmp(a) {
@@ -160,13 +446,24 @@ fn build_commands(
             (None, None) => {}
         }
 
-        match a.menubar.cmp(&b.menubar) {
-            Ordering::Equal => a.brief.cmp(&b.brief),
+        match a.provider.cmp(&b.provider) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+
+        // Entries from the other providers are already in a
+        // meaningful order, so only sort the commands
+        if a.provider != CommandPaletteProvider::Commands {
+            return Ordering::Equal;
+        }
+
+        match a.command.menubar.cmp(b.command.menubar) {
+            Ordering::Equal => a.command.brief.cmp(&b.command.brief),
             ordering => ordering,
         }
     });
 
-    commands
+    entries
 }
 
 #[derive(Debug)]
@@ -176,10 +473,10 @@ struct MatchResult {
 }
 
 impl MatchResult {
-    fn new(row_idx: usize, score: u32, selection: &str, commands: &[ExpandedCommand]) -> Self {
+    fn new(row_idx: usize, score: u32, selection: &str, entries: &[PaletteEntry]) -> Self {
         Self {
             row_idx,
-            score: if commands[row_idx].brief == selection {
+            score: if entries[row_idx].command.brief == selection {
                 // Pump up the score for an exact match, otherwise
                 // the order may be undesirable if there are a lot
                 // of candidates with the same score
@@ -191,21 +488,39 @@ impl MatchResult {
     }
 }
 
-fn compute_matches(selection: &str, commands: &[ExpandedCommand]) -> Vec<usize> {
+fn compute_matches(selection: &str, entries: &[PaletteEntry]) -> Vec<usize> {
+    let (scope, selection) = CommandPaletteProvider::split_scope(selection);
+    let in_scope =
+        |entry: &PaletteEntry| scope.map(|c| entry.provider.prefix() == c).unwrap_or(true);
+
     if selection.is_empty() {
-        commands.iter().enumerate().map(|(idx, _)| idx).collect()
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| in_scope(entry))
+            .map(|(idx, _)| idx)
+            .collect()
     } else {
         let pattern = matcher_pattern(selection);
 
         let start = std::time::Instant::now();
-        let mut scores: Vec<MatchResult> = commands
+        let mut scores: Vec<MatchResult> = entries
             .par_iter()
             .enumerate()
+            .filter(|(_, entry)| in_scope(entry))
             .filter_map(|(row_idx, entry)| {
-                let group = entry.menubar.join(" ");
-                let text = format!("{group}: {}. {} {:?}", entry.brief, entry.doc, entry.action);
+                let command = &entry.command;
+                let group = command.menubar.join(" ");
+                let text = if entry.provider == CommandPaletteProvider::Commands {
+                    format!(
+                        "{group}: {}. {} {:?}",
+                        command.brief, command.doc, command.action
+                    )
+                } else {
+                    format!("{group}: {}. {}", command.brief, command.doc)
+                };
                 matcher_score(&pattern, &text)
-                    .map(|score| MatchResult::new(row_idx, score, selection, commands))
+                    .map(|score| MatchResult::new(row_idx, score, selection, entries))
             })
             .collect();
         scores.sort_by(|a, b| a.score.cmp(&b.score).reverse());
@@ -219,7 +534,7 @@ impl CommandPalette {
     pub fn new(term_window: &mut TermWindow) -> Self {
         // Showing the CopyMode actions in the palette is useless
         // if the CopyOverlay isn't active, so figure out if that
-        // is the case so that we can filter them out in build_commands.
+        // is the case so that we can filter them out in command_entries.
         let filter_copy_mode = term_window
             .get_active_pane_or_overlay()
             .map(|pane| {
@@ -232,12 +547,19 @@ impl CommandPalette {
             .get_active_pane_or_overlay()
             .map(|pane| MuxPane(pane.pane_id()));
 
-        let commands = build_commands(GuiWin::new(term_window), mux_pane, filter_copy_mode);
+        let entries = build_entries(
+            &term_window.config,
+            GuiWin::new(term_window),
+            term_window.mux_window_id,
+            term_window.get_active_pane_no_overlay(),
+            mux_pane,
+            filter_copy_mode,
+        );
 
         Self {
             element: RefCell::new(None),
             selection: RefCell::new(String::new()),
-            commands,
+            entries,
             matches: RefCell::new(None),
             selected_row: RefCell::new(0),
             top_row: RefCell::new(0),
@@ -248,7 +570,7 @@ impl CommandPalette {
     fn compute(
         term_window: &mut TermWindow,
         selection: &str,
-        commands: &[ExpandedCommand],
+        entries: &[PaletteEntry],
         matches: &MatchResults,
         max_rows_on_screen: usize,
         selected_row: usize,
@@ -269,25 +591,29 @@ impl CommandPalette {
         let border = term_window.get_os_border();
         let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
 
-        let mut elements =
-            vec![
-                Element::new(&font, ElementContent::Text(format!("> {selection}_")))
-                    .colors(ElementColors {
-                        border: BorderColor::default(),
-                        bg: LinearRgba::TRANSPARENT.into(),
-                        text: term_window
-                            .config
-                            .command_palette_fg_color
-                            .to_linear()
-                            .into(),
-                    })
-                    .display(DisplayType::Block),
-            ];
+        // When the input already starts with a provider prefix,
+        // don't show the default prompt in front of it
+        let prompt = match CommandPaletteProvider::split_scope(selection) {
+            (Some(_), _) => format!("{selection}_"),
+            (None, _) => format!("> {selection}_"),
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(prompt))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: term_window
+                    .config
+                    .command_palette_fg_color
+                    .to_linear()
+                    .into(),
+            })
+            .display(DisplayType::Block)];
 
         for (display_idx, command) in matches
             .matches
             .iter()
-            .map(|&idx| &commands[idx])
+            .map(|&idx| &entries[idx].command)
             .enumerate()
             .skip(top_row)
             .take(max_rows_on_screen)
@@ -544,6 +870,32 @@ impl CommandPalette {
         *self.top_row.borrow_mut() = 0;
     }
 
+    fn page_size(&self) -> usize {
+        self.max_rows_on_screen.borrow().saturating_sub(1).max(1)
+    }
+
+    fn accept(&self, term_window: &mut TermWindow) {
+        let selected_idx = *self.selected_row.borrow();
+        let alias_idx = match self.matches.borrow().as_ref() {
+            None => return,
+            Some(results) => match results.matches.get(selected_idx) {
+                Some(i) => *i,
+                None => return,
+            },
+        };
+        let item = &self.entries[alias_idx].command;
+        if let Err(err) = save_recent(item) {
+            log::error!("Error while saving recents: {err:#}");
+        }
+        term_window.cancel_modal();
+
+        if let Some(pane) = term_window.get_active_pane_or_overlay() {
+            if let Err(err) = term_window.perform_key_assignment(&pane, &item.action) {
+                log::error!("Error while performing {item:?}: {err:#}");
+            }
+        }
+    }
+
     fn move_up(&self) {
         let mut row = self.selected_row.borrow_mut();
         *row = row.saturating_sub(1);
@@ -561,7 +913,7 @@ impl CommandPalette {
             .borrow()
             .as_ref()
             .map(|m| m.matches.len())
-            .unwrap_or_else(|| self.commands.len())
+            .unwrap_or_else(|| self.entries.len())
             .saturating_sub(1);
         let mut row = self.selected_row.borrow_mut();
         *row = row.saturating_add(1).min(limit);
@@ -573,15 +925,56 @@ impl CommandPalette {
 }
 
 impl Modal for CommandPalette {
-    fn perform_assignment(
-        &self,
-        _assignment: &KeyAssignment,
-        _term_window: &mut TermWindow,
-    ) -> bool {
-        false
+    fn key_table_name(&self) -> Option<&'static str> {
+        Some("palette_mode")
     }
 
-    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+    fn perform_assignment(&self, assignment: &KeyAssignment, term_window: &mut TermWindow) -> bool {
+        let assignment = match assignment {
+            KeyAssignment::PaletteMode(assignment) => assignment,
+            _ => return false,
+        };
+        match assignment {
+            PaletteModeAssignment::Close => {
+                term_window.cancel_modal();
+                return true;
+            }
+            PaletteModeAssignment::MoveUp => self.move_up(),
+            PaletteModeAssignment::MoveDown => self.move_down(),
+            PaletteModeAssignment::PageUp => {
+                for _ in 0..self.page_size() {
+                    self.move_up();
+                }
+            }
+            PaletteModeAssignment::PageDown => {
+                for _ in 0..self.page_size() {
+                    self.move_down();
+                }
+            }
+            PaletteModeAssignment::DeleteBackward => {
+                self.selection.borrow_mut().pop();
+                self.updated_input();
+            }
+            PaletteModeAssignment::ClearInput => {
+                self.selection.borrow_mut().clear();
+                self.updated_input();
+            }
+            PaletteModeAssignment::Accept => {
+                self.accept(term_window);
+                return true;
+            }
+        }
+        term_window.invalidate_modal();
+        true
+    }
+
+    fn mouse_event(&self, event: MouseEvent, term_window: &mut TermWindow) -> anyhow::Result<()> {
+        let assignment = match event.button {
+            MouseButton::WheelUp(_) => PaletteModeAssignment::MoveUp,
+            MouseButton::WheelDown(_) => PaletteModeAssignment::MoveDown,
+            _ => return Ok(()),
+        };
+        self.perform_assignment(&KeyAssignment::PaletteMode(assignment), term_window);
         Ok(())
     }
 
@@ -592,56 +985,12 @@ impl Modal for CommandPalette {
         term_window: &mut TermWindow,
     ) -> anyhow::Result<bool> {
         match (key, mods) {
-            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
-                term_window.cancel_modal();
-            }
-            (KeyCode::UpArrow, KeyModifiers::NONE) | (KeyCode::Char('p'), KeyModifiers::CTRL) => {
-                self.move_up();
-            }
-            (KeyCode::DownArrow, KeyModifiers::NONE) | (KeyCode::Char('n'), KeyModifiers::CTRL) => {
-                self.move_down();
-            }
             (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
                 // Type to add to the selection
                 let mut selection = self.selection.borrow_mut();
                 selection.push(c);
                 self.updated_input();
             }
-            (KeyCode::Backspace, KeyModifiers::NONE) => {
-                // Backspace to edit the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.pop();
-                self.updated_input();
-            }
-            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
-                // CTRL-u to clear the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.clear();
-                self.updated_input();
-            }
-            (KeyCode::Enter, KeyModifiers::NONE) => {
-                // Enter the selected character to the current pane
-                let selected_idx = *self.selected_row.borrow();
-                let alias_idx = match self.matches.borrow().as_ref() {
-                    None => return Ok(true),
-                    Some(results) => match results.matches.get(selected_idx) {
-                        Some(i) => *i,
-                        None => return Ok(true),
-                    },
-                };
-                let item = &self.commands[alias_idx];
-                if let Err(err) = save_recent(item) {
-                    log::error!("Error while saving recents: {err:#}");
-                }
-                term_window.cancel_modal();
-
-                if let Some(pane) = term_window.get_active_pane_or_overlay() {
-                    if let Err(err) = term_window.perform_key_assignment(&pane, &item.action) {
-                        log::error!("Error while performing {item:?}: {err:#}");
-                    }
-                }
-                return Ok(true);
-            }
             _ => return Ok(false),
         }
         term_window.invalidate_modal();
@@ -678,7 +1027,7 @@ impl Modal for CommandPalette {
         if rebuild_matches {
             results.replace(MatchResults {
                 selection: selection.to_string(),
-                matches: compute_matches(selection, &self.commands),
+                matches: compute_matches(selection, &self.entries),
             });
         };
         let matches = results.as_ref().unwrap();
@@ -687,7 +1036,7 @@ impl Modal for CommandPalette {
             let element = Self::compute(
                 term_window,
                 selection,
-                &self.commands,
+                &self.entries,
                 matches,
                 max_rows_on_screen,
                 *self.selected_row.borrow(),
@@ -704,3 +1053,82 @@ impl Modal for CommandPalette {
         self.element.borrow_mut().take();
     }
 }
+
+pub fn palette_key_table() -> KeyTable {
+    let mut table = KeyTable::default();
+    for (key, mods, action) in [
+        (
+            WKeyCode::Char('\x1b'),
+            Modifiers::NONE,
+            PaletteModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('g'),
+            Modifiers::CTRL,
+            PaletteModeAssignment::Close,
+        ),
+        (
+            WKeyCode::UpArrow,
+            Modifiers::NONE,
+            PaletteModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('p'),
+            Modifiers::CTRL,
+            PaletteModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::Char('k'),
+            Modifiers::CTRL,
+            PaletteModeAssignment::MoveUp,
+        ),
+        (
+            WKeyCode::DownArrow,
+            Modifiers::NONE,
+            PaletteModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('n'),
+            Modifiers::CTRL,
+            PaletteModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::Char('j'),
+            Modifiers::CTRL,
+            PaletteModeAssignment::MoveDown,
+        ),
+        (
+            WKeyCode::PageUp,
+            Modifiers::NONE,
+            PaletteModeAssignment::PageUp,
+        ),
+        (
+            WKeyCode::PageDown,
+            Modifiers::NONE,
+            PaletteModeAssignment::PageDown,
+        ),
+        (
+            WKeyCode::Char('\r'),
+            Modifiers::NONE,
+            PaletteModeAssignment::Accept,
+        ),
+        (
+            WKeyCode::Char('\x08'),
+            Modifiers::NONE,
+            PaletteModeAssignment::DeleteBackward,
+        ),
+        (
+            WKeyCode::Char('u'),
+            Modifiers::CTRL,
+            PaletteModeAssignment::ClearInput,
+        ),
+    ] {
+        table.insert(
+            (key, mods),
+            KeyTableEntry {
+                action: KeyAssignment::PaletteMode(action),
+            },
+        );
+    }
+    table
+}
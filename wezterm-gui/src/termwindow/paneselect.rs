@@ -7,11 +7,15 @@ use crate::termwindow::render::corners::{
 use crate::termwindow::DimensionContext;
 use crate::utilsprites::RenderMetrics;
 use crate::TermWindow;
-use config::keyassignment::{KeyAssignment, PaneSelectArguments, PaneSelectMode};
+use config::keyassignment::{
+    KeyAssignment, KeyTable, KeyTableEntry, PaneSelectArguments, PaneSelectMode,
+    PaneSelectorModeAssignment,
+};
 use config::Dimension;
 use mux::Mux;
 use std::cell::{Ref, RefCell};
 use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::{KeyCode as WKeyCode, Modifiers};
 
 pub struct PaneSelector {
     element: RefCell<Option<Vec<ComputedElement>>>,
@@ -226,12 +230,25 @@ impl PaneSelector {
 }
 
 impl Modal for PaneSelector {
-    fn perform_assignment(
-        &self,
-        _assignment: &KeyAssignment,
-        _term_window: &mut TermWindow,
-    ) -> bool {
-        false
+    fn key_table_name(&self) -> Option<&'static str> {
+        Some("pane_selector_mode")
+    }
+
+    fn perform_assignment(&self, assignment: &KeyAssignment, term_window: &mut TermWindow) -> bool {
+        let assignment = match assignment {
+            KeyAssignment::PaneSelectorMode(assignment) => assignment,
+            _ => return false,
+        };
+        match assignment {
+            PaneSelectorModeAssignment::Close => term_window.cancel_modal(),
+            PaneSelectorModeAssignment::DeleteBackward => {
+                self.selection.borrow_mut().pop();
+            }
+            PaneSelectorModeAssignment::ClearInput => {
+                self.selection.borrow_mut().clear();
+            }
+        }
+        true
     }
 
     fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
@@ -245,9 +262,6 @@ impl Modal for PaneSelector {
         term_window: &mut TermWindow,
     ) -> anyhow::Result<bool> {
         match (key, mods) {
-            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
-                term_window.cancel_modal();
-            }
             (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
                 // Type to add to the selection
                 let mut selection = self.selection.borrow_mut();
@@ -260,16 +274,6 @@ impl Modal for PaneSelector {
                     return Ok(true);
                 }
             }
-            (KeyCode::Backspace, KeyModifiers::NONE) => {
-                // Backspace to edit the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.pop();
-            }
-            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
-                // CTRL-u to clear the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.clear();
-            }
             _ => return Ok(false),
         }
         Ok(true)
@@ -293,3 +297,37 @@ impl Modal for PaneSelector {
         self.element.borrow_mut().take();
     }
 }
+
+pub fn pane_selector_key_table() -> KeyTable {
+    let mut table = KeyTable::default();
+    for (key, mods, action) in [
+        (
+            WKeyCode::Char('\x1b'),
+            Modifiers::NONE,
+            PaneSelectorModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('g'),
+            Modifiers::CTRL,
+            PaneSelectorModeAssignment::Close,
+        ),
+        (
+            WKeyCode::Char('\x08'),
+            Modifiers::NONE,
+            PaneSelectorModeAssignment::DeleteBackward,
+        ),
+        (
+            WKeyCode::Char('u'),
+            Modifiers::CTRL,
+            PaneSelectorModeAssignment::ClearInput,
+        ),
+    ] {
+        table.insert(
+            (key, mods),
+            KeyTableEntry {
+                action: KeyAssignment::PaneSelectorMode(action),
+            },
+        );
+    }
+    table
+}
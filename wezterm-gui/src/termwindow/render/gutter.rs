@@ -0,0 +1,163 @@
+use crate::quad::{QuadTrait, TripleLayerQuadAllocator};
+use crate::termwindow::{CommandBlockMarker, UIItem, UIItemType};
+use anyhow::Context;
+use mux::tab::PositionedPane;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::CommandBlock;
+use window::color::LinearRgba;
+
+impl crate::TermWindow {
+    /// Draws a marker in the padding to the left of each prompt that
+    /// is visible in the pane, colored according to the exit status
+    /// of the associated command.
+    /// The markers are drawn outside of the cell grid so that they
+    /// don't affect the layout of the terminal content.
+    pub fn paint_command_gutter(
+        &mut self,
+        pos: &PositionedPane,
+        layers: &mut TripleLayerQuadAllocator,
+        top_pixel_y: f32,
+        left_pixel_x: f32,
+    ) -> anyhow::Result<()> {
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+
+        let gutter_width = if pos.left == 0 {
+            let (padding_left, _) = self.padding_left_top();
+            padding_left
+        } else {
+            cell_width / 2.0
+        };
+        let marker_width = (gutter_width / 2.0).min(cell_width / 4.0).floor();
+        if marker_width < 1.0 {
+            return Ok(());
+        }
+        let marker_x = (left_pixel_x - (gutter_width + marker_width) / 2.0).floor();
+
+        let pane_id = pos.pane.pane_id();
+        let visible_rows = self.visible_stable_rows(&pos.pane);
+        let (top, bottom) = match (visible_rows.first(), visible_rows.last()) {
+            (Some(&first), Some(&last)) => (first, last + 1),
+            _ => return Ok(()),
+        };
+        let palette = pos.pane.palette();
+
+        let blocks: Vec<CommandBlock> = self
+            .get_command_blocks(&pos.pane)
+            .iter()
+            .filter(|block| block.prompt.is_some() || block.input.is_some())
+            .filter(|block| block.start_y() < bottom && block.end_y() >= top)
+            .cloned()
+            .collect();
+
+        for block in blocks {
+            let color = match command_marker_color(&block, &palette) {
+                Some(color) => color,
+                None => continue,
+            };
+
+            // The marker spans the prompt and the command line that
+            // was entered at it.  Those rows are never folded away,
+            // so they are displayed contiguously.
+            let first_row = block.start_y();
+            let last_row = block
+                .input
+                .or(block.prompt)
+                .map(|zone| zone.end_y)
+                .unwrap_or(first_row);
+            let first_idx = visible_rows.iter().position(|&row| row >= first_row);
+            let last_idx = visible_rows.iter().rposition(|&row| row <= last_row);
+            let (first_idx, last_idx) = match (first_idx, last_idx) {
+                (Some(first), Some(last)) if first <= last => (first, last),
+                _ => continue,
+            };
+
+            let y = top_pixel_y + (pos.top + first_idx) as f32 * cell_height;
+            let height = (last_idx - first_idx + 1) as f32 * cell_height;
+
+            let mut quad = self
+                .filled_rectangle(
+                    layers,
+                    2,
+                    euclid::rect(marker_x, y, marker_width, height),
+                    color,
+                )
+                .context("filled_rectangle")?;
+            quad.set_hsv(if pos.is_active {
+                None
+            } else {
+                Some(self.config.inactive_pane_hsb)
+            });
+
+            // Make the whole gutter width clickable, as the marker
+            // itself is quite narrow
+            self.ui_items.push(UIItem {
+                x: (left_pixel_x - gutter_width).max(0.) as usize,
+                width: gutter_width as usize,
+                y: y as usize,
+                height: height as usize,
+                item_type: UIItemType::CommandBlockMarker(CommandBlockMarker { pane_id, block }),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// If the mouse is hovering over a command gutter marker, draws
+    /// a tooltip describing the exit status and duration of the command
+    pub fn paint_command_marker_tooltip(&mut self) -> anyhow::Result<()> {
+        let (marker, item_right, item_top) = match &self.last_ui_item {
+            Some(UIItem {
+                item_type: UIItemType::CommandBlockMarker(marker),
+                x,
+                y,
+                width,
+                ..
+            }) if self.current_mouse_event.is_some() => {
+                (marker.clone(), (x + width) as f32, *y as f32)
+            }
+            _ => return Ok(()),
+        };
+
+        let mut lines = vec![match marker.block.exit_status {
+            Some(0) => "Succeeded".to_string(),
+            Some(status) => format!("Exited with status {status}"),
+            None => "Running".to_string(),
+        }];
+        if let Some(ms) = marker.block.duration_ms {
+            lines.push(format!("Took {}", format_duration_ms(ms)));
+        }
+        if marker.block.folded {
+            lines.push("Output is folded".to_string());
+        }
+        if marker.block.output.is_some() {
+            lines.push("Click to select output".to_string());
+        }
+
+        self.paint_tooltip(lines, item_right, item_top)
+    }
+}
+
+/// Returns the color used for the gutter marker of a command block,
+/// or None if no marker should be drawn because no command has
+/// been run at that prompt yet
+fn command_marker_color(block: &CommandBlock, palette: &ColorPalette) -> Option<LinearRgba> {
+    match block.exit_status {
+        // Use the ANSI green and red palette entries so that the
+        // markers harmonize with the color scheme
+        Some(0) => Some(palette.colors.0[2].to_linear()),
+        Some(_) => Some(palette.colors.0[1].to_linear()),
+        None if block.output.is_some() => Some(palette.scrollbar_thumb.to_linear()),
+        None => None,
+    }
+}
+
+fn format_duration_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{ms}ms")
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.)
+    } else {
+        format!("{}m{}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
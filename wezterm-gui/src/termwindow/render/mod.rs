@@ -38,11 +38,13 @@ pub mod borders;
 pub mod corners;
 pub mod draw;
 pub mod fancy_tab_bar;
+pub mod gutter;
 pub mod paint;
 pub mod pane;
 pub mod screen_line;
 pub mod split;
 pub mod tab_bar;
+pub mod tooltip;
 pub mod window_buttons;
 
 /// The data that we associate with a line; we use this to cache it shape hash
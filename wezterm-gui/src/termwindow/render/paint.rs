@@ -272,6 +272,10 @@ impl crate::TermWindow {
         self.paint_window_borders(&mut layers)
             .context("paint_window_borders")?;
         drop(layers);
+        self.paint_command_marker_tooltip()
+            .context("paint_command_marker_tooltip")?;
+        self.paint_hyperlink_preview()
+            .context("paint_hyperlink_preview")?;
         self.paint_modal().context("paint_modal")?;
 
         Ok(())
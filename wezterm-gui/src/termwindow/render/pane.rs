@@ -302,14 +302,21 @@ impl crate::TermWindow {
         let cursor_is_default_color =
             palette.cursor_fg == global_cursor_fg && palette.cursor_bg == global_cursor_bg;
 
+        let visible_rows = self.visible_stable_rows(&pos.pane);
+        let mut fold_rows = vec![];
+
         {
-            let stable_range = match current_viewport {
-                Some(top) => top..top + dims.viewport_rows as StableRowIndex,
-                None => dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
+            let stable_range = match (visible_rows.first(), visible_rows.last()) {
+                (Some(&first), Some(&last)) => first..last + 1,
+                _ => match current_viewport {
+                    Some(top) => top..top + dims.viewport_rows as StableRowIndex,
+                    None => {
+                        dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex
+                    }
+                },
             };
 
-            pos.pane
-                .apply_hyperlinks(stable_range.clone(), &self.config.hyperlink_rules);
+            self.apply_hyperlinks(&pos.pane, stable_range.clone());
 
             struct LineRender<'a, 'b> {
                 term_window: &'a mut crate::TermWindow,
@@ -335,6 +342,10 @@ impl crate::TermWindow {
                 window_is_transparent: bool,
                 layers: &'a mut TripleLayerQuadAllocator<'b>,
                 error: Option<anyhow::Error>,
+                /// The row of the pane on which the run of lines being
+                /// rendered begins.  This is non-zero when command
+                /// output has been folded away above the run.
+                run_visual_top: usize,
             }
 
             let left_pixel_x = padding_left
@@ -365,6 +376,7 @@ impl crate::TermWindow {
                 window_is_transparent,
                 layers,
                 error: None,
+                run_visual_top: 0,
             };
 
             impl<'a, 'b> LineRender<'a, 'b> {
@@ -375,6 +387,7 @@ impl crate::TermWindow {
                     line: &&mut Line,
                 ) -> anyhow::Result<()> {
                     let stable_row = stable_top + line_idx as StableRowIndex;
+                    let visual_idx = self.run_visual_top + line_idx;
                     let selrange = self
                         .selrange
                         .map_or(0..0, |sel| sel.cols_for_row(stable_row, self.rectangular));
@@ -435,10 +448,10 @@ impl crate::TermWindow {
                         cursor,
                         shape_hash,
                         top_pixel_y: NotNan::new(self.top_pixel_y).unwrap()
-                            + (line_idx + self.pos.top) as f32
+                            + (visual_idx + self.pos.top) as f32
                                 * self.term_window.render_metrics.cell_size.height as f32,
                         left_pixel_x: NotNan::new(self.left_pixel_x).unwrap(),
-                        phys_line_idx: line_idx,
+                        phys_line_idx: visual_idx,
                         reverse_video: self.dims.reverse_video,
                     };
 
@@ -565,12 +578,60 @@ impl crate::TermWindow {
                 }
             }
 
-            pos.pane.with_lines_mut(stable_range.clone(), &mut render);
-            if let Some(error) = render.error.take() {
-                return Err(error).context("error while calling with_lines_mut");
+            // Render each contiguous run of visible rows; there is
+            // only a single run unless command output has been folded
+            let mut run_visual_top = 0;
+            while run_visual_top < visible_rows.len() {
+                let run_top = visible_rows[run_visual_top];
+                let run_len = visible_rows[run_visual_top..]
+                    .iter()
+                    .enumerate()
+                    .take_while(|(idx, &row)| row == run_top + *idx as StableRowIndex)
+                    .count();
+                render.run_visual_top = run_visual_top;
+                pos.pane
+                    .with_lines_mut(run_top..run_top + run_len as StableRowIndex, &mut render);
+                if let Some(error) = render.error.take() {
+                    return Err(error).context("error while calling with_lines_mut");
+                }
+                if run_visual_top > 0 {
+                    // The row above this run is the first row of
+                    // some folded output
+                    fold_rows.push(run_visual_top - 1);
+                }
+                run_visual_top += run_len;
             }
         }
 
+        // Draw a line beneath the visible row of each folded output
+        // to indicate that there is more to it
+        let left_pixel_x = padding_left + border.left.get() as f32 + (pos.left as f32 * cell_width);
+        let fold_line_height = self.render_metrics.underline_height as f32;
+        for visual_row in fold_rows {
+            let y =
+                top_pixel_y + (pos.top + visual_row + 1) as f32 * cell_height - fold_line_height;
+            self.filled_rectangle(
+                layers,
+                2,
+                euclid::rect(
+                    left_pixel_x,
+                    y,
+                    pos.width as f32 * cell_width,
+                    fold_line_height,
+                ),
+                palette.scrollbar_thumb.to_linear(),
+            )
+            .context("filled_rectangle")?;
+        }
+
+        if config.show_command_gutter_markers {
+            let left_pixel_x = padding_left
+                + border.left.get() as f32
+                + (pos.left as f32 * self.render_metrics.cell_size.width as f32);
+            self.paint_command_gutter(pos, layers, top_pixel_y, left_pixel_x)
+                .context("paint_command_gutter")?;
+        }
+
         /*
         if let Some(zone) = zone {
             // TODO: render a thingy to jump to prior prompt
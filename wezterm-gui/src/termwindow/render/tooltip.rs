@@ -0,0 +1,110 @@
+use crate::termwindow::box_model::*;
+use crate::termwindow::render::corners::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use config::Dimension;
+use termwiz::hyperlink::HYPERLINK_PREVIEW_PARAM;
+
+impl crate::TermWindow {
+    /// Draws a small box containing the specified lines of text
+    /// with its top left corner at the specified pixel position.
+    pub fn paint_tooltip(&mut self, lines: Vec<String>, x: f32, y: f32) -> anyhow::Result<()> {
+        let font = self.fonts.title_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let fg = self.config.command_palette_fg_color.to_linear();
+        let bg = self.config.command_palette_bg_color.to_linear();
+
+        let children = lines
+            .into_iter()
+            .map(|line| Element::new(&font, ElementContent::Text(line)).display(DisplayType::Block))
+            .collect();
+
+        let element = Element::new(&font, ElementContent::Children(children))
+            .colors(ElementColors {
+                border: BorderColor::new(fg),
+                bg: bg.into(),
+                text: fg.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.25),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }));
+
+        let dimensions = self.dimensions;
+        let computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    x,
+                    y,
+                    (dimensions.pixel_width as f32 - x).max(0.),
+                    (dimensions.pixel_height as f32 - y).max(0.),
+                ),
+                metrics: &metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        self.render_element(&computed, gl_state, None)
+    }
+
+    /// If the mouse is hovering over an implicit hyperlink whose rule
+    /// defines a `preview`, draws that preview text below the pointer
+    pub fn paint_hyperlink_preview(&mut self) -> anyhow::Result<()> {
+        let preview = match self.current_highlight.as_ref() {
+            Some(link) if link.is_implicit() => match link.params().get(HYPERLINK_PREVIEW_PARAM) {
+                Some(preview) => preview.clone(),
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        let (x, y) = match &self.current_mouse_event {
+            Some(event) => (event.coords.x.max(0) as f32, event.coords.y.max(0) as f32),
+            None => return Ok(()),
+        };
+
+        let lines = preview.lines().map(|line| line.to_string()).collect();
+        self.paint_tooltip(lines, x, y + self.render_metrics.cell_size.height as f32)
+    }
+}
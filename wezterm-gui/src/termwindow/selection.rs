@@ -4,7 +4,7 @@ use mux::pane::{Pane, PaneId};
 use std::cell::RefMut;
 use std::sync::Arc;
 use termwiz::surface::Line;
-use wezterm_term::StableRowIndex;
+use wezterm_term::{CommandBlock, StableRowIndex};
 
 impl super::TermWindow {
     pub fn selection(&self, pane_id: PaneId) -> RefMut<Selection> {
@@ -280,4 +280,66 @@ impl super::TermWindow {
         self.selection(pane.pane_id()).seqno = pane.get_current_seqno();
         self.window.as_ref().unwrap().invalidate();
     }
+
+    /// Returns the most recent command block that has been run and
+    /// that begins at or above the bottom of the viewport
+    pub fn command_block_for_viewport(&self, pane: &Arc<dyn Pane>) -> Option<CommandBlock> {
+        self.find_command_block_for_viewport(pane, |block| {
+            block.output.is_some() || block.exit_status.is_some()
+        })
+    }
+
+    /// Returns the most recent command block that produced output
+    /// and that begins at or above the bottom of the viewport
+    pub fn command_output_block_for_viewport(&self, pane: &Arc<dyn Pane>) -> Option<CommandBlock> {
+        self.find_command_block_for_viewport(pane, |block| block.output.is_some())
+    }
+
+    fn find_command_block_for_viewport(
+        &self,
+        pane: &Arc<dyn Pane>,
+        predicate: impl Fn(&CommandBlock) -> bool,
+    ) -> Option<CommandBlock> {
+        let dims = pane.get_dimensions();
+        let bottom = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top)
+            + dims.viewport_rows as StableRowIndex;
+        let blocks = pane.get_command_blocks().ok()?;
+        blocks
+            .into_iter()
+            .rev()
+            .find(|block| predicate(block) && block.start_y() < bottom)
+    }
+
+    /// Selects the output of the given command block, scrolling
+    /// the viewport so that the start of the output is visible
+    pub fn select_command_output(&mut self, pane: &Arc<dyn Pane>, block: &CommandBlock) {
+        let zone = match block.output {
+            Some(zone) => zone,
+            None => return,
+        };
+        let range = SelectionRange {
+            start: SelectionCoordinate::x_y(zone.start_x, zone.start_y),
+            end: SelectionCoordinate::x_y(zone.end_x, zone.end_y),
+        };
+        {
+            let mut selection = self.selection(pane.pane_id());
+            selection.origin = Some(range.start);
+            selection.range = Some(range);
+            selection.rectangular = false;
+            selection.seqno = pane.get_current_seqno();
+        }
+
+        let dims = pane.get_dimensions();
+        let top = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        if zone.start_y < top || zone.start_y >= top + dims.viewport_rows as StableRowIndex {
+            self.set_viewport(pane.pane_id(), Some(zone.start_y), dims);
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
 }
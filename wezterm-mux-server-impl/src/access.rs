@@ -0,0 +1,609 @@
+//! Access control for clients connecting to the mux server.
+//! Each listener may have a set of `access_rules`; the first rule
+//! that matches a connecting peer determines the access granted to it.
+//! When no rule matches, only the user running the server is permitted.
+use anyhow::anyhow;
+use codec::*;
+use config::MuxAccessRule;
+use mux::client::ClientInfo;
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::window::WindowId;
+use mux::{Mux, MuxNotification};
+use promise::spawn::spawn_into_main_thread;
+use wezterm_dynamic::ToDynamic;
+use wezterm_uds::UnixStream;
+
+/// Identifies the peer on the other end of a mux connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Peer {
+    /// A unix domain socket peer.  The uid is not available on
+    /// all systems.
+    Unix { uid: Option<u32> },
+    /// A TLS peer, identified by the CN of its client certificate
+    Tls { common_name: String },
+}
+
+impl std::fmt::Display for Peer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unix { uid: Some(uid) } => write!(f, "unix peer uid {uid}"),
+            Self::Unix { uid: None } => write!(f, "unix peer"),
+            Self::Tls { common_name } => write!(f, "tls peer CN `{common_name}`"),
+        }
+    }
+}
+
+/// The access granted to a connected client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAccess {
+    pub peer: Peer,
+    /// If true, the client is only granted read-only access
+    pub read_only: bool,
+    /// If set, the client may only see and interact with windows
+    /// in these workspaces
+    pub workspaces: Option<Vec<String>>,
+}
+
+impl ClientAccess {
+    pub fn unrestricted(peer: Peer) -> Self {
+        Self {
+            peer,
+            read_only: false,
+            workspaces: None,
+        }
+    }
+
+    fn with_rule(peer: Peer, rule: &MuxAccessRule) -> Self {
+        Self {
+            peer,
+            read_only: rule.read_only,
+            workspaces: if rule.workspaces.is_empty() {
+                None
+            } else {
+                Some(rule.workspaces.clone())
+            },
+        }
+    }
+
+    /// Determine the access for a peer connected to a unix domain socket,
+    /// given its uid if that is known
+    pub fn for_unix_peer(uid: Option<u32>, rules: &[MuxAccessRule]) -> anyhow::Result<Self> {
+        let peer = Peer::Unix { uid };
+        if rules.is_empty() {
+            return Ok(Self::unrestricted(peer));
+        }
+
+        let uid = uid.ok_or_else(|| {
+            anyhow!("peer credentials are unavailable, so access_rules cannot be applied")
+        })?;
+        if let Some(rule) = rules.iter().find(|rule| rule.matches_uid(uid)) {
+            return Ok(Self::with_rule(peer, rule));
+        }
+        if Some(uid) == server_uid() {
+            return Ok(Self::unrestricted(peer));
+        }
+        anyhow::bail!("uid {uid} does not match any of the access_rules")
+    }
+
+    /// Determine the access for a peer connected via TLS, given the CN
+    /// of its (already verified) client certificate
+    pub fn for_tls_peer(common_name: &str, rules: &[MuxAccessRule]) -> anyhow::Result<Self> {
+        let peer = Peer::Tls {
+            common_name: common_name.to_string(),
+        };
+        if let Some(rule) = rules
+            .iter()
+            .find(|rule| rule.matches_common_name(common_name))
+        {
+            return Ok(Self::with_rule(peer, rule));
+        }
+
+        let wanted_unix_name = std::env::var("USER")?;
+
+        if wanted_unix_name == common_name {
+            log::trace!(
+                "Peer certificate CN `{}` == $USER `{}`",
+                common_name,
+                wanted_unix_name
+            );
+            Ok(Self::unrestricted(peer))
+        } else {
+            // Some environments that are used by the author of this
+            // program encode the CN in the form `user:unixname/DATA`
+            let maybe_encoded = format!("user:{}/", wanted_unix_name);
+            if common_name.starts_with(&maybe_encoded) {
+                log::trace!(
+                    "Peer certificate CN `{}` matches $USER `{}`",
+                    common_name,
+                    wanted_unix_name
+                );
+                Ok(Self::unrestricted(peer))
+            } else if rules.is_empty() {
+                anyhow::bail!(
+                    "CN `{}` did not match $USER `{}`",
+                    common_name,
+                    wanted_unix_name
+                );
+            } else {
+                anyhow::bail!(
+                    "CN `{}` did not match $USER `{}` or any of the access_rules",
+                    common_name,
+                    wanted_unix_name
+                );
+            }
+        }
+    }
+
+    pub fn allows_workspace(&self, workspace: &str) -> bool {
+        match &self.workspaces {
+            None => true,
+            Some(workspaces) => workspaces.iter().any(|w| w == workspace),
+        }
+    }
+
+    /// Returns true if the client is permitted to know about another
+    /// client, which is the case when that client is using one of the
+    /// permitted workspaces
+    pub fn allows_client(&self, client: &ClientInfo) -> bool {
+        if self.workspaces.is_none() {
+            return true;
+        }
+        match &client.active_workspace {
+            Some(workspace) => self.allows_workspace(workspace),
+            None => false,
+        }
+    }
+
+    // Ids that are not known to the mux are permitted by the methods
+    // below, so that the request fails in the usual way rather than
+    // reporting an access violation for a window, tab or pane that
+    // has just been closed.
+
+    pub fn allows_window(&self, window_id: WindowId) -> bool {
+        if self.workspaces.is_none() {
+            return true;
+        }
+        match Mux::get().get_window(window_id) {
+            Some(window) => self.allows_workspace(window.get_workspace()),
+            None => true,
+        }
+    }
+
+    pub fn allows_tab(&self, tab_id: TabId) -> bool {
+        if self.workspaces.is_none() {
+            return true;
+        }
+        let mux = Mux::get();
+        match mux.window_containing_tab(tab_id) {
+            Some(window_id) => self.allows_window(window_id),
+            None => mux.get_tab(tab_id).is_none(),
+        }
+    }
+
+    pub fn allows_pane(&self, pane_id: PaneId) -> bool {
+        if self.workspaces.is_none() {
+            return true;
+        }
+        let mux = Mux::get();
+        match mux.resolve_pane_id(pane_id) {
+            Some((_domain_id, window_id, _tab_id)) => self.allows_window(window_id),
+            None => mux.get_pane(pane_id).is_none(),
+        }
+    }
+
+    /// Returns an error if the client is not permitted to make the
+    /// request because it refers to something outside of the
+    /// workspaces to which the client is restricted
+    pub fn check_pdu(&self, pdu: &Pdu) -> anyhow::Result<()> {
+        // The issued certificate identifies its holder as the user
+        // running the server, which would grant unrestricted access
+        // to a subsequent TLS connection
+        if matches!(pdu, Pdu::GetTlsCreds(_)) && (self.read_only || self.workspaces.is_some()) {
+            anyhow::bail!("GetTlsCreds is not permitted for a client restricted by access_rules");
+        }
+
+        let workspaces = match &self.workspaces {
+            None => return Ok(()),
+            Some(workspaces) => workspaces,
+        };
+
+        let permitted = match pdu {
+            Pdu::WriteToPane(WriteToPane { pane_id, .. })
+            | Pdu::SendKeyDown(SendKeyDown { pane_id, .. })
+            | Pdu::SendMouseEvent(SendMouseEvent { pane_id, .. })
+            | Pdu::SendPaste(SendPaste { pane_id, .. })
+            | Pdu::Resize(Resize { pane_id, .. })
+            | Pdu::SetPaneZoomed(SetPaneZoomed { pane_id, .. })
+            | Pdu::KillPane(KillPane { pane_id })
+            | Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id })
+            | Pdu::GetLines(GetLines { pane_id, .. })
+            | Pdu::SyncPane(SyncPane { pane_id, .. })
+            | Pdu::GetImageCell(GetImageCell { pane_id, .. })
+            | Pdu::SearchScrollbackRequest(SearchScrollbackRequest { pane_id, .. })
+            | Pdu::SetFocusedPane(SetFocusedPane { pane_id })
+            | Pdu::EraseScrollbackRequest(EraseScrollbackRequest { pane_id, .. })
+            | Pdu::GetPaneDirection(GetPaneDirection { pane_id, .. })
+            | Pdu::ActivatePaneDirection(ActivatePaneDirection { pane_id, .. })
+            | Pdu::GetPaneRenderableDimensions(GetPaneRenderableDimensions { pane_id })
+            | Pdu::SetPalette(SetPalette { pane_id, .. })
+            | Pdu::SetPaneOutputLog(SetPaneOutputLog { pane_id, .. })
+            | Pdu::SetPaneOutputPipe(SetPaneOutputPipe { pane_id, .. })
+            | Pdu::AdjustPaneSize(AdjustPaneSize { pane_id, .. }) => self.allows_pane(*pane_id),
+            Pdu::SplitPane(SplitPane {
+                pane_id,
+                move_pane_id,
+                ..
+            }) => {
+                self.allows_pane(*pane_id)
+                    && move_pane_id.map(|id| self.allows_pane(id)).unwrap_or(true)
+            }
+            Pdu::MovePaneToNewTab(MovePaneToNewTab {
+                pane_id,
+                window_id,
+                workspace_for_new_window,
+            }) => {
+                self.allows_pane(*pane_id)
+                    && window_id.map(|id| self.allows_window(id)).unwrap_or(true)
+                    && workspace_for_new_window
+                        .as_deref()
+                        .map(|w| self.allows_workspace(w))
+                        .unwrap_or(true)
+            }
+            Pdu::SpawnV2(SpawnV2 {
+                window_id,
+                workspace,
+                ..
+            }) => match window_id {
+                Some(window_id) => self.allows_window(*window_id),
+                None => self.allows_workspace(workspace),
+            },
+            Pdu::SetWindowWorkspace(SetWindowWorkspace {
+                window_id,
+                workspace,
+            }) => self.allows_window(*window_id) && self.allows_workspace(workspace),
+            Pdu::WindowTitleChanged(WindowTitleChanged { window_id, .. }) => {
+                self.allows_window(*window_id)
+            }
+            Pdu::TabTitleChanged(TabTitleChanged { tab_id, .. }) => self.allows_tab(*tab_id),
+            Pdu::RenameWorkspace(RenameWorkspace {
+                old_workspace,
+                new_workspace,
+            }) => self.allows_workspace(old_workspace) && self.allows_workspace(new_workspace),
+            // The metrics describe every pane on the server
+            Pdu::GetMetrics(_) => false,
+            _ => true,
+        };
+
+        if permitted {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} refers to a workspace other than {}",
+                pdu.pdu_name(),
+                workspaces.join(", ")
+            ))
+        }
+    }
+
+    /// Returns true if the notification relates to something that the
+    /// client is permitted to see
+    pub fn allows_notification(&self, notif: &MuxNotification) -> bool {
+        if self.workspaces.is_none() {
+            return true;
+        }
+        match notif {
+            MuxNotification::PaneOutput(pane_id)
+            | MuxNotification::PaneFocused(pane_id)
+            | MuxNotification::Alert { pane_id, .. }
+            | MuxNotification::AssignClipboard { pane_id, .. } => self.allows_pane(*pane_id),
+            MuxNotification::TabAddedToWindow { window_id, .. }
+            | MuxNotification::WindowTitleChanged { window_id, .. } => {
+                self.allows_window(*window_id)
+            }
+            MuxNotification::TabResized(tab_id)
+            | MuxNotification::TabTitleChanged { tab_id, .. } => self.allows_tab(*tab_id),
+            MuxNotification::WorkspaceRenamed {
+                old_workspace,
+                new_workspace,
+            } => self.allows_workspace(old_workspace) || self.allows_workspace(new_workspace),
+            _ => true,
+        }
+    }
+}
+
+/// The information passed to the `mux-access-denied` event
+#[derive(Debug, Clone, ToDynamic)]
+struct AccessDenied {
+    peer: String,
+    uid: Option<u32>,
+    common_name: Option<String>,
+    request: Option<String>,
+    reason: String,
+}
+
+/// Log a denied connection or request, and emit the `mux-access-denied`
+/// event so that it can be recorded by the configuration
+pub fn report_denied(peer: &Peer, request: Option<&str>, reason: &anyhow::Error) {
+    match request {
+        Some(request) => log::warn!("denied {request} from {peer}: {reason:#}"),
+        None => log::error!("denied connection from {peer}: {reason:#}"),
+    }
+
+    let denied = AccessDenied {
+        peer: peer.to_string(),
+        uid: match peer {
+            Peer::Unix { uid } => *uid,
+            Peer::Tls { .. } => None,
+        },
+        common_name: match peer {
+            Peer::Unix { .. } => None,
+            Peer::Tls { common_name } => Some(common_name.clone()),
+        },
+        request: request.map(|r| r.to_string()),
+        reason: format!("{reason:#}"),
+    };
+
+    // The lua state can only be used on the main thread
+    spawn_into_main_thread(async move {
+        promise::spawn::spawn(async move {
+            if let Err(err) = emit_access_denied(denied).await {
+                log::error!("while processing mux-access-denied event: {err:#}");
+            }
+        })
+        .detach();
+    })
+    .detach();
+}
+
+async fn emit_access_denied(denied: AccessDenied) -> anyhow::Result<()> {
+    config::with_lua_config_on_main_thread(|lua| async move {
+        if let Some(lua) = lua {
+            let args = lua.pack_multi(luahelper::to_lua(&lua, denied)?)?;
+            config::lua::emit_event(&lua, ("mux-access-denied".to_string(), args)).await?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[cfg(unix)]
+fn server_uid() -> Option<u32> {
+    Some(unsafe { libc::getuid() })
+}
+
+#[cfg(windows)]
+fn server_uid() -> Option<u32> {
+    None
+}
+
+/// Returns the uid of the process on the other end of a unix domain socket
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn peer_uid(stream: &UnixStream) -> anyhow::Result<u32> {
+    use anyhow::Context;
+    use std::os::fd::AsRawFd;
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("getsockopt SO_PEERCRED");
+    }
+    Ok(cred.uid)
+}
+
+/// Returns the uid of the process on the other end of a unix domain socket
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub fn peer_uid(stream: &UnixStream) -> anyhow::Result<u32> {
+    use anyhow::Context;
+    use std::os::fd::AsRawFd;
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    let res = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("getpeereid");
+    }
+    Ok(uid)
+}
+
+/// Returns the uid of the process on the other end of a unix domain socket
+#[cfg(windows)]
+pub fn peer_uid(_stream: &UnixStream) -> anyhow::Result<u32> {
+    anyhow::bail!("peer credentials are not available on this system")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mux::client::{ClientId, ClientPermission};
+    use mux::tab::Tab;
+    use std::sync::Arc;
+    use wezterm_term::TerminalSize;
+
+    fn rule(uid: Option<u32>, common_name: Option<&str>, workspaces: &[&str]) -> MuxAccessRule {
+        MuxAccessRule {
+            uid,
+            common_name: common_name.map(|cn| cn.to_string()),
+            workspaces: workspaces.iter().map(|w| w.to_string()).collect(),
+            read_only: false,
+        }
+    }
+
+    /// A uid that is neither matched by the rules in these tests
+    /// nor the uid of the user running them
+    fn stranger_uid() -> u32 {
+        server_uid().unwrap_or(0).wrapping_add(4242)
+    }
+
+    #[test]
+    fn unix_rule_resolution() {
+        let stranger = stranger_uid();
+        let rules = vec![
+            MuxAccessRule {
+                read_only: true,
+                ..rule(Some(stranger), None, &[])
+            },
+            rule(Some(stranger), None, &["shadowed"]),
+            rule(Some(stranger + 1), None, &["pairing"]),
+        ];
+
+        // The first matching rule wins
+        let access = ClientAccess::for_unix_peer(Some(stranger), &rules).unwrap();
+        assert!(access.read_only);
+        assert_eq!(access.workspaces, None);
+
+        let access = ClientAccess::for_unix_peer(Some(stranger + 1), &rules).unwrap();
+        assert!(!access.read_only);
+        assert_eq!(access.workspaces, Some(vec!["pairing".to_string()]));
+
+        // Peers that match no rule are refused, as are peers whose
+        // uid can't be determined
+        assert!(ClientAccess::for_unix_peer(Some(stranger + 2), &rules).is_err());
+        assert!(ClientAccess::for_unix_peer(None, &rules).is_err());
+
+        // Without rules, every peer is granted full access
+        assert_eq!(
+            ClientAccess::for_unix_peer(None, &[]).unwrap(),
+            ClientAccess::unrestricted(Peer::Unix { uid: None })
+        );
+
+        // A rule without a uid matches every unix peer, but
+        // not tls peers
+        let rules = vec![
+            rule(None, Some("wez"), &["tls"]),
+            rule(None, None, &["any"]),
+        ];
+        let access = ClientAccess::for_unix_peer(Some(stranger + 2), &rules).unwrap();
+        assert_eq!(access.workspaces, Some(vec!["any".to_string()]));
+    }
+
+    #[test]
+    fn tls_rule_resolution() {
+        let rules = vec![
+            rule(Some(stranger_uid()), None, &["unix"]),
+            rule(None, Some("user:bob/*"), &["bob"]),
+            rule(None, Some("user:bob/laptop"), &["shadowed"]),
+        ];
+
+        // uid rules don't apply to tls peers, and the first matching
+        // rule wins
+        let access = ClientAccess::for_tls_peer("user:bob/laptop", &rules).unwrap();
+        assert_eq!(
+            access.peer,
+            Peer::Tls {
+                common_name: "user:bob/laptop".to_string()
+            }
+        );
+        assert_eq!(access.workspaces, Some(vec!["bob".to_string()]));
+
+        assert!(ClientAccess::for_tls_peer("user:mallory/laptop", &rules).is_err());
+    }
+
+    #[test]
+    fn workspace_and_client_access() {
+        let restricted = ClientAccess {
+            peer: Peer::Unix { uid: None },
+            read_only: false,
+            workspaces: Some(vec!["pairing".to_string()]),
+        };
+        let unrestricted = ClientAccess::unrestricted(Peer::Unix { uid: None });
+
+        assert!(restricted.allows_workspace("pairing"));
+        assert!(!restricted.allows_workspace("default"));
+        assert!(unrestricted.allows_workspace("default"));
+
+        let mut client = ClientInfo::new(Arc::new(ClientId::new()), ClientPermission::ReadWrite);
+        assert!(!restricted.allows_client(&client));
+        assert!(unrestricted.allows_client(&client));
+        client.active_workspace = Some("pairing".to_string());
+        assert!(restricted.allows_client(&client));
+        client.active_workspace = Some("default".to_string());
+        assert!(!restricted.allows_client(&client));
+        assert!(unrestricted.allows_client(&client));
+    }
+
+    #[test]
+    fn restricted_clients_cannot_obtain_tls_creds() {
+        let get_creds = Pdu::GetTlsCreds(GetTlsCreds {});
+        let unrestricted = ClientAccess::unrestricted(Peer::Unix { uid: None });
+        assert!(unrestricted.check_pdu(&get_creds).is_ok());
+
+        let workspace_restricted = ClientAccess {
+            workspaces: Some(vec!["pairing".to_string()]),
+            ..unrestricted.clone()
+        };
+        assert!(workspace_restricted.check_pdu(&get_creds).is_err());
+
+        let read_only = ClientAccess {
+            read_only: true,
+            ..unrestricted.clone()
+        };
+        assert!(read_only.check_pdu(&get_creds).is_err());
+    }
+
+    #[test]
+    fn pane_access() {
+        let mut config = config::Config::default_config();
+        config.mux_enable_ssh_agent = false;
+        config::use_this_configuration(config);
+        // Activity and notifications are queued to the main thread
+        let _executor = promise::spawn::ScopedExecutor::new();
+        let mux = Arc::new(Mux::new(None));
+        Mux::set_mux(&mux);
+
+        let size = TerminalSize::default();
+        let mut panes = vec![];
+        for workspace in ["pairing", "default"] {
+            let window_id = *mux.new_empty_window(Some(workspace.to_string()), None);
+            let (term, pane) =
+                mux::termwiztermtab::allocate(size, Arc::new(config::TermConfig::new()));
+            let tab = Arc::new(Tab::new(&size));
+            tab.assign_pane(&pane);
+            mux.add_tab_and_active_pane(&tab).unwrap();
+            mux.add_tab_to_window(&tab, window_id).unwrap();
+            panes.push((term, pane.pane_id(), tab.tab_id(), window_id));
+        }
+        let (_, pairing_pane, pairing_tab, pairing_window) = &panes[0];
+        let (_, default_pane, default_tab, default_window) = &panes[1];
+
+        let restricted = ClientAccess {
+            peer: Peer::Unix { uid: None },
+            read_only: false,
+            workspaces: Some(vec!["pairing".to_string()]),
+        };
+        assert!(restricted.allows_pane(*pairing_pane));
+        assert!(restricted.allows_tab(*pairing_tab));
+        assert!(restricted.allows_window(*pairing_window));
+        assert!(!restricted.allows_pane(*default_pane));
+        assert!(!restricted.allows_tab(*default_tab));
+        assert!(!restricted.allows_window(*default_window));
+        // Unknown ids are left for the request to reject
+        assert!(restricted.allows_pane(PaneId::MAX));
+
+        let unrestricted = ClientAccess::unrestricted(Peer::Unix { uid: None });
+        assert!(unrestricted.allows_pane(*default_pane));
+
+        assert!(restricted
+            .check_pdu(&Pdu::KillPane(KillPane {
+                pane_id: *pairing_pane
+            }))
+            .is_ok());
+        assert!(restricted
+            .check_pdu(&Pdu::KillPane(KillPane {
+                pane_id: *default_pane
+            }))
+            .is_err());
+        assert!(restricted
+            .check_pdu(&Pdu::GetMetrics(GetMetrics {}))
+            .is_err());
+
+        Mux::shutdown();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::access::ClientAccess;
 use crate::sessionhandler::{PduSender, SessionHandler};
 use anyhow::Context;
 use async_ossl::AsyncSslStream;
@@ -23,7 +24,7 @@ enum Item {
     Readable,
 }
 
-pub async fn process<T>(stream: T) -> anyhow::Result<()>
+pub async fn process<T>(stream: T, access: ClientAccess) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -33,10 +34,10 @@ where
     T: async_io::IoSafe,
 {
     let stream = smol::Async::new(stream)?;
-    process_async(stream).await
+    process_async(stream, access).await
 }
 
-pub async fn process_async<T>(mut stream: Async<T>) -> anyhow::Result<()>
+pub async fn process_async<T>(mut stream: Async<T>, access: ClientAccess) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -56,7 +57,7 @@ where
                 .map_err(|e| anyhow::anyhow!("{:?}", e))
         }
     });
-    let mut handler = SessionHandler::new(pdu_sender);
+    let mut handler = SessionHandler::new(pdu_sender, access);
 
     {
         let mux = Mux::get();
@@ -68,7 +69,14 @@ where
         let rx_msg = item_rx.recv();
         let wait_for_read = stream.readable().map(|_| Ok(Item::Readable));
 
-        match smol::future::or(rx_msg, wait_for_read).await {
+        let item = smol::future::or(rx_msg, wait_for_read).await;
+        if let Ok(Item::Notif(notif)) = &item {
+            if !handler.allows_notification(notif) {
+                continue;
+            }
+        }
+
+        match item {
             Ok(Item::Readable) => {
                 let decoded = match Pdu::decode_async(&mut stream, None).await {
                     Ok(data) => data,
@@ -85,6 +93,9 @@ where
                 handler.process_one(decoded);
             }
             Ok(Item::WritePdu(decoded)) => {
+                if decoded.serial == 0 && !handler.peer_understands(&decoded.pdu) {
+                    continue;
+                }
                 match decoded.pdu.encode_async(&mut stream, decoded.serial).await {
                     Ok(()) => {}
                     Err(err) => {
@@ -113,10 +124,12 @@ where
             }
             Ok(Item::Notif(MuxNotification::PaneAdded(_pane_id))) => {}
             Ok(Item::Notif(MuxNotification::PaneRemoved(pane_id))) => {
-                Pdu::PaneRemoved(codec::PaneRemoved { pane_id })
-                    .encode_async(&mut stream, 0)
-                    .await?;
-                stream.flush().await.context("flushing PDU to client")?;
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::PaneRemoved(codec::PaneRemoved { pane_id }),
+                )
+                .await?;
             }
             Ok(Item::Notif(MuxNotification::Alert { pane_id, alert })) => {
                 {
@@ -132,20 +145,24 @@ where
                 selection,
                 clipboard,
             })) => {
-                Pdu::SetClipboard(codec::SetClipboard {
-                    pane_id,
-                    clipboard,
-                    selection,
-                })
-                .encode_async(&mut stream, 0)
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::SetClipboard(codec::SetClipboard {
+                        pane_id,
+                        clipboard,
+                        selection,
+                    }),
+                )
                 .await?;
-                stream.flush().await.context("flushing PDU to client")?;
             }
             Ok(Item::Notif(MuxNotification::TabAddedToWindow { tab_id, window_id })) => {
-                Pdu::TabAddedToWindow(codec::TabAddedToWindow { tab_id, window_id })
-                    .encode_async(&mut stream, 0)
-                    .await?;
-                stream.flush().await.context("flushing PDU to client")?;
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::TabAddedToWindow(codec::TabAddedToWindow { tab_id, window_id }),
+                )
+                .await?;
             }
             Ok(Item::Notif(MuxNotification::WindowRemoved(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
@@ -157,50 +174,62 @@ where
                         .map(|w| w.get_workspace().to_string())
                 };
                 if let Some(workspace) = workspace {
-                    Pdu::WindowWorkspaceChanged(codec::WindowWorkspaceChanged {
-                        window_id,
-                        workspace,
-                    })
-                    .encode_async(&mut stream, 0)
+                    send_unilateral(
+                        &mut stream,
+                        &handler,
+                        Pdu::WindowWorkspaceChanged(codec::WindowWorkspaceChanged {
+                            window_id,
+                            workspace,
+                        }),
+                    )
                     .await?;
-                    stream.flush().await.context("flushing PDU to client")?;
                 }
             }
             Ok(Item::Notif(MuxNotification::PaneFocused(pane_id))) => {
-                Pdu::PaneFocused(codec::PaneFocused { pane_id })
-                    .encode_async(&mut stream, 0)
-                    .await?;
-                stream.flush().await.context("flushing PDU to client")?;
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::PaneFocused(codec::PaneFocused { pane_id }),
+                )
+                .await?;
             }
             Ok(Item::Notif(MuxNotification::TabResized(tab_id))) => {
-                Pdu::TabResized(codec::TabResized { tab_id })
-                    .encode_async(&mut stream, 0)
-                    .await?;
-                stream.flush().await.context("flushing PDU to client")?;
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::TabResized(codec::TabResized { tab_id }),
+                )
+                .await?;
             }
             Ok(Item::Notif(MuxNotification::TabTitleChanged { tab_id, title })) => {
-                Pdu::TabTitleChanged(codec::TabTitleChanged { tab_id, title })
-                    .encode_async(&mut stream, 0)
-                    .await?;
-                stream.flush().await.context("flushing PDU to client")?;
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::TabTitleChanged(codec::TabTitleChanged { tab_id, title }),
+                )
+                .await?;
             }
             Ok(Item::Notif(MuxNotification::WindowTitleChanged { window_id, title })) => {
-                Pdu::WindowTitleChanged(codec::WindowTitleChanged { window_id, title })
-                    .encode_async(&mut stream, 0)
-                    .await?;
-                stream.flush().await.context("flushing PDU to client")?;
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::WindowTitleChanged(codec::WindowTitleChanged { window_id, title }),
+                )
+                .await?;
             }
             Ok(Item::Notif(MuxNotification::WorkspaceRenamed {
                 old_workspace,
                 new_workspace,
             })) => {
-                Pdu::RenameWorkspace(codec::RenameWorkspace {
-                    old_workspace,
-                    new_workspace,
-                })
-                .encode_async(&mut stream, 0)
+                send_unilateral(
+                    &mut stream,
+                    &handler,
+                    Pdu::RenameWorkspace(codec::RenameWorkspace {
+                        old_workspace,
+                        new_workspace,
+                    }),
+                )
                 .await?;
-                stream.flush().await.context("flushing PDU to client")?;
             }
             Ok(Item::Notif(MuxNotification::ActiveWorkspaceChanged(_))) => {}
             Ok(Item::Notif(MuxNotification::Empty)) => {}
@@ -211,3 +240,22 @@ where
         }
     }
 }
+
+/// Sends a Pdu that the client didn't ask for, provided that the
+/// client has advertised the capabilities needed to understand it
+async fn send_unilateral<T>(
+    stream: &mut Async<T>,
+    handler: &SessionHandler,
+    pdu: Pdu,
+) -> anyhow::Result<()>
+where
+    T: std::io::Write,
+    T: async_io::IoSafe,
+{
+    if !handler.peer_understands(&pdu) {
+        log::trace!("client doesn't understand {}", pdu.pdu_name());
+        return Ok(());
+    }
+    pdu.encode_async(stream, 0).await?;
+    stream.flush().await.context("flushing PDU to client")
+}
@@ -5,10 +5,14 @@ use mux::Mux;
 use std::sync::Arc;
 use wezterm_client::domain::{ClientDomain, ClientDomainConfig};
 
+pub mod access;
 pub mod dispatch;
 pub mod local;
+pub mod metrics;
 pub mod pki;
 pub mod sessionhandler;
+pub mod sizing;
+pub mod udp;
 
 fn client_domains(config: &config::ConfigHandle) -> Vec<ClientDomainConfig> {
     let mut domains = vec![];
@@ -1,28 +1,50 @@
+use crate::access::{peer_uid, report_denied, ClientAccess, Peer};
 use anyhow::{anyhow, Context as _};
-use config::{create_user_owned_dirs, UnixDomain};
+use config::{create_user_owned_dirs, MuxAccessRule, UnixDomain};
 use promise::spawn::spawn_into_main_thread;
 use wezterm_uds::UnixListener;
 
 pub struct LocalListener {
     listener: UnixListener,
+    access_rules: Vec<MuxAccessRule>,
 }
 
 impl LocalListener {
     pub fn new(listener: UnixListener) -> Self {
-        Self { listener }
+        Self {
+            listener,
+            access_rules: vec![],
+        }
     }
 
     pub fn with_domain(unix_dom: &UnixDomain) -> anyhow::Result<Self> {
         let listener = safely_create_sock_path(unix_dom)?;
-        Ok(Self::new(listener))
+        Ok(Self {
+            listener,
+            access_rules: unix_dom.access_rules.clone(),
+        })
     }
 
     pub fn run(&mut self) {
         for stream in self.listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    let uid = match peer_uid(&stream) {
+                        Ok(uid) => Some(uid),
+                        Err(err) => {
+                            log::trace!("unable to determine peer uid: {err:#}");
+                            None
+                        }
+                    };
+                    let access = match ClientAccess::for_unix_peer(uid, &self.access_rules) {
+                        Ok(access) => access,
+                        Err(err) => {
+                            report_denied(&Peer::Unix { uid }, None, &err);
+                            continue;
+                        }
+                    };
                     spawn_into_main_thread(async move {
-                        crate::dispatch::process(stream).await.map_err(|e| {
+                        crate::dispatch::process(stream, access).await.map_err(|e| {
                             log::error!("{:#}", e);
                             e
                         })
@@ -88,6 +110,16 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
     let listener = UnixListener::bind(sock_path)
         .with_context(|| format!("Failed to bind to {}", sock_path.display()))?;
 
+    #[cfg(unix)]
+    if !unix_dom.access_rules.is_empty() {
+        // Connections are vetted against the access_rules, so allow
+        // other users to open the socket.  They will still need to be
+        // able to traverse the directory that contains it.
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(sock_path, std::fs::Permissions::from_mode(0o666))
+            .with_context(|| format!("Failed to set permissions on {}", sock_path.display()))?;
+    }
+
     config::set_sticky_bit(&sock_path);
 
     Ok(listener)
@@ -0,0 +1,507 @@
+//! Collects the values recorded via the `metrics` crate and renders
+//! them in the Prometheus text exposition format, either in response
+//! to `GetMetrics` (eg: `wezterm cli metrics`) or via the HTTP endpoint
+//! served on `mux_metrics_socket_path`.
+//!
+//! Histograms are rendered as summaries.  Those with names ending in
+//! `.rate` are used for throughput by `wezterm-gui/src/stats.rs`; here
+//! they are rendered as counters of the total of the recorded values.
+use anyhow::Context;
+use config::create_user_owned_dirs;
+use hdrhistogram::Histogram;
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, HistogramFn, Key, KeyName, Label, Metadata, Recorder,
+    SharedString, Unit,
+};
+use mux::pane::PaneId;
+use mux::Mux;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use wezterm_uds::{UnixListener, UnixStream};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+lazy_static::lazy_static! {
+    static ref INNER: Arc<Mutex<Inner>> = Arc::new(Mutex::new(Inner::default()));
+}
+
+/// The original name of a metric, its Prometheus type, and the
+/// rendered lines of each of its series, keyed by their labels
+type Family = (String, &'static str, Vec<(String, Vec<String>)>);
+
+const QUANTILES: &[f64] = &[0.5, 0.75, 0.95, 0.99];
+
+/// A counter or gauge value, stored as the bits of an f64
+#[derive(Default)]
+struct Value {
+    bits: AtomicU64,
+}
+
+impl Value {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: f64) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    fn add(&self, delta: f64) {
+        self.bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + delta).to_bits())
+            })
+            .ok();
+    }
+}
+
+impl CounterFn for Value {
+    fn increment(&self, value: u64) {
+        self.add(value as f64);
+    }
+
+    fn absolute(&self, value: u64) {
+        self.set(value as f64);
+    }
+}
+
+impl GaugeFn for Value {
+    fn increment(&self, value: f64) {
+        self.add(value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.add(-value);
+    }
+
+    fn set(&self, value: f64) {
+        Value::set(self, value);
+    }
+}
+
+/// Accumulates the values recorded for a `.rate` histogram
+impl HistogramFn for Value {
+    fn record(&self, value: f64) {
+        self.add(value);
+    }
+}
+
+struct SummaryInner {
+    hist: Histogram<u64>,
+    sum: f64,
+    count: u64,
+}
+
+struct Summary {
+    inner: Mutex<SummaryInner>,
+    /// hdrhistogram records integers, so values are multiplied
+    /// by this before they are recorded
+    scale: f64,
+}
+
+impl Summary {
+    fn new(name: &str) -> Self {
+        let scale = if name.ends_with(".size") || name.ends_with(".bytes") {
+            1.0
+        } else {
+            // Assume seconds; record nanoseconds
+            1_000_000_000.0
+        };
+        Self {
+            inner: Mutex::new(SummaryInner {
+                hist: Histogram::new(2).expect("failed to create histogram"),
+                sum: 0.,
+                count: 0,
+            }),
+            scale,
+        }
+    }
+}
+
+impl HistogramFn for Summary {
+    fn record(&self, value: f64) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.hist.record((value * self.scale) as u64).ok();
+        inner.sum += value;
+        inner.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    counters: HashMap<Key, Arc<Value>>,
+    gauges: HashMap<Key, Arc<Value>>,
+    summaries: HashMap<Key, Arc<Summary>>,
+    descriptions: HashMap<String, SharedString>,
+}
+
+impl Inner {
+    /// Forgets the metrics that are labelled with a pane that no
+    /// longer exists, so that they don't accumulate on a long
+    /// running server
+    fn prune_removed_panes(&mut self) {
+        let mux = match Mux::try_get() {
+            Some(mux) => mux,
+            None => return,
+        };
+        let is_live = |key: &Key| {
+            key.labels().all(|label| {
+                label.key() != "pane"
+                    || label
+                        .value()
+                        .parse::<PaneId>()
+                        .map(|pane_id| mux.get_pane(pane_id).is_some())
+                        .unwrap_or(true)
+            })
+        };
+        self.counters.retain(|key, _| is_live(key));
+        self.gauges.retain(|key, _| is_live(key));
+        self.summaries.retain(|key, _| is_live(key));
+    }
+
+    fn render(&self) -> String {
+        // Group the series into families so that each family is
+        // described once, in a stable order
+        let mut families: BTreeMap<String, Family> = BTreeMap::new();
+        let mut add = |name: String, key: &Key, kind, lines: Vec<String>| {
+            families
+                .entry(name)
+                .or_insert_with(|| (key.name().to_string(), kind, vec![]))
+                .2
+                .push((labels(key, None), lines));
+        };
+
+        for (key, value) in &self.counters {
+            let mut name = prometheus_name(key.name().trim_end_matches(".rate"));
+            if !name.ends_with("_total") {
+                name.push_str("_total");
+            }
+            let line = format!("{}{} {}", name, labels(key, None), value.get());
+            add(name, key, "counter", vec![line]);
+        }
+
+        for (key, value) in &self.gauges {
+            let name = prometheus_name(key.name());
+            let line = format!("{}{} {}", name, labels(key, None), value.get());
+            add(name, key, "gauge", vec![line]);
+        }
+
+        for (key, summary) in &self.summaries {
+            let name = prometheus_name(key.name());
+            let inner = summary.inner.lock().unwrap();
+            let mut lines = vec![];
+            for q in QUANTILES {
+                let value = if inner.count == 0 {
+                    0.
+                } else {
+                    inner.hist.value_at_quantile(*q) as f64 / summary.scale
+                };
+                lines.push(format!(
+                    "{}{} {}",
+                    name,
+                    labels(key, Some(&q.to_string())),
+                    value
+                ));
+            }
+            lines.push(format!("{}_sum{} {}", name, labels(key, None), inner.sum));
+            lines.push(format!(
+                "{}_count{} {}",
+                name,
+                labels(key, None),
+                inner.count
+            ));
+            add(name, key, "summary", lines);
+        }
+
+        let mut text = String::new();
+        for (name, (metric, kind, mut series)) in families {
+            if let Some(description) = self.descriptions.get(&metric) {
+                writeln!(text, "# HELP {} {}", name, escape(description, false)).ok();
+            }
+            writeln!(text, "# TYPE {} {}", name, kind).ok();
+            series.sort();
+            for line in series.into_iter().flat_map(|(_, lines)| lines) {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+        text
+    }
+}
+
+/// Maps a metric name like `pdu.encode.size` to a valid
+/// Prometheus name like `wezterm_pdu_encode_size`
+fn prometheus_name(name: &str) -> String {
+    let mut result = "wezterm_".to_string();
+    result.extend(name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+        _ => '_',
+    }));
+    result
+}
+
+fn escape(value: &str, quote: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '"' if quote => result.push_str("\\\""),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn labels(key: &Key, quantile: Option<&str>) -> String {
+    let mut labels: Vec<String> = key
+        .labels()
+        .map(|label: &Label| {
+            format!(
+                "{}=\"{}\"",
+                prometheus_name(label.key()).trim_start_matches("wezterm_"),
+                escape(label.value(), true)
+            )
+        })
+        .collect();
+    if let Some(quantile) = quantile {
+        labels.push(format!("quantile=\"{}\"", quantile));
+    }
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// Returns true if `install` has been called in this process
+pub fn is_recording() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Installs the recorder; metrics recorded before this is called
+/// are discarded
+pub fn install() -> anyhow::Result<()> {
+    metrics::set_global_recorder(MetricsRecorder {})
+        .map_err(|e| anyhow::anyhow!("Failed to set metrics recorder: {}", e))?;
+    INSTALLED.store(true, Ordering::Relaxed);
+
+    metrics::describe_gauge!("mux.clients", "Number of connected clients");
+    metrics::describe_counter!("pane.output.bytes", "Bytes read from the pty of each pane");
+    metrics::describe_histogram!(
+        "parse_buffered_data.parse.latency",
+        Unit::Seconds,
+        "Time taken to parse a chunk of pane output"
+    );
+    metrics::describe_histogram!(
+        "pdu.latency",
+        Unit::Seconds,
+        "Time from receiving each type of request to queueing its response"
+    );
+    Ok(())
+}
+
+/// Returns the current values of the metrics in the
+/// Prometheus text exposition format
+pub fn render() -> anyhow::Result<String> {
+    if !is_recording() {
+        anyhow::bail!("metrics are only collected by wezterm-mux-server");
+    }
+    let mut inner = INNER.lock().unwrap();
+    inner.prune_removed_panes();
+    Ok(inner.render())
+}
+
+struct MetricsRecorder {}
+
+impl Recorder for MetricsRecorder {
+    fn describe_counter(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        let mut inner = INNER.lock().unwrap();
+        inner
+            .descriptions
+            .insert(key.as_str().to_string(), description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        let mut inner = INNER.lock().unwrap();
+        inner
+            .descriptions
+            .insert(key.as_str().to_string(), description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        let mut inner = INNER.lock().unwrap();
+        inner
+            .descriptions
+            .insert(key.as_str().to_string(), description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata) -> Counter {
+        let mut inner = INNER.lock().unwrap();
+        Counter::from_arc(Arc::clone(inner.counters.entry(key.clone()).or_default()))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata) -> Gauge {
+        let mut inner = INNER.lock().unwrap();
+        Gauge::from_arc(Arc::clone(inner.gauges.entry(key.clone()).or_default()))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata) -> metrics::Histogram {
+        let mut inner = INNER.lock().unwrap();
+        if key.name().ends_with(".rate") {
+            metrics::Histogram::from_arc(Arc::clone(inner.counters.entry(key.clone()).or_default()))
+        } else {
+            metrics::Histogram::from_arc(Arc::clone(
+                inner
+                    .summaries
+                    .entry(key.clone())
+                    .or_insert_with(|| Arc::new(Summary::new(key.name()))),
+            ))
+        }
+    }
+}
+
+/// Serves the metrics over HTTP on a unix domain socket, so that
+/// they can be scraped, for example with
+/// `curl --unix-socket PATH http://localhost/metrics`
+pub struct MetricsListener {
+    listener: UnixListener,
+}
+
+impl MetricsListener {
+    pub fn bind(sock_path: &Path) -> anyhow::Result<Self> {
+        if let Some(sock_dir) = sock_path.parent() {
+            create_user_owned_dirs(sock_dir)?;
+        }
+        match std::fs::remove_file(sock_path) {
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(err).context(format!("Unable to remove {}", sock_path.display()))
+            }
+        }
+        let listener = UnixListener::bind(sock_path)
+            .with_context(|| format!("Failed to bind to {}", sock_path.display()))?;
+        Ok(Self { listener })
+    }
+
+    pub fn run(&mut self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    std::thread::spawn(move || {
+                        if let Err(err) = serve(stream) {
+                            log::trace!("metrics request failed: {:#}", err);
+                        }
+                    });
+                }
+                Err(err) => {
+                    log::error!("metrics accept failed: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Reads the request header and then responds with the metrics,
+/// as we don't have any other resources to serve
+fn serve(mut stream: UnixStream) -> anyhow::Result<()> {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let len = stream.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..len]);
+        if request.len() > 16 * 1024 {
+            anyhow::bail!("request header is too large");
+        }
+    }
+
+    let (status, body) = match render() {
+        Ok(text) => ("200 OK", text),
+        Err(err) => ("503 Service Unavailable", format!("{:#}\n", err)),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_prometheus_text() {
+        let mut inner = Inner::default();
+        let key = Key::from_parts("pane.output.bytes", vec![Label::new("pane", "1")]);
+        let counter = Arc::new(Value::default());
+        CounterFn::increment(&*counter, 42);
+        inner.counters.insert(key, counter);
+
+        let key = Key::from_name("read_from_pane_pty.bytes.rate");
+        let rate = Arc::new(Value::default());
+        HistogramFn::record(&*rate, 10.);
+        HistogramFn::record(&*rate, 5.);
+        inner.counters.insert(key, rate);
+
+        let key = Key::from_name("mux.clients");
+        let gauge = Arc::new(Value::default());
+        GaugeFn::set(&*gauge, 2.);
+        inner.gauges.insert(key, gauge);
+
+        let key = Key::from_parts("pdu.size", vec![Label::new("pdu", "Ping")]);
+        let summary = Arc::new(Summary::new(key.name()));
+        summary.record(10.);
+        summary.record(30.);
+        // Recorded with 2 significant figures of precision
+        summary.record(5000.);
+        inner.summaries.insert(key, summary);
+        inner
+            .descriptions
+            .insert("mux.clients".to_string(), "Connected clients".into());
+
+        assert_eq!(
+            inner.render(),
+            "# HELP wezterm_mux_clients Connected clients\n\
+             # TYPE wezterm_mux_clients gauge\n\
+             wezterm_mux_clients 2\n\
+             # TYPE wezterm_pane_output_bytes_total counter\n\
+             wezterm_pane_output_bytes_total{pane=\"1\"} 42\n\
+             # TYPE wezterm_pdu_size summary\n\
+             wezterm_pdu_size{pdu=\"Ping\",quantile=\"0.5\"} 30\n\
+             wezterm_pdu_size{pdu=\"Ping\",quantile=\"0.75\"} 5023\n\
+             wezterm_pdu_size{pdu=\"Ping\",quantile=\"0.95\"} 5023\n\
+             wezterm_pdu_size{pdu=\"Ping\",quantile=\"0.99\"} 5023\n\
+             wezterm_pdu_size_sum{pdu=\"Ping\"} 5040\n\
+             wezterm_pdu_size_count{pdu=\"Ping\"} 3\n\
+             # TYPE wezterm_read_from_pane_pty_bytes_total counter\n\
+             wezterm_read_from_pane_pty_bytes_total 15\n"
+        );
+    }
+
+    #[test]
+    fn prometheus_names() {
+        assert_eq!(
+            prometheus_name("pdu.encode.compressed.size"),
+            "wezterm_pdu_encode_compressed_size"
+        );
+        assert_eq!(escape("a\"b\\c\nd", true), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -1,14 +1,18 @@
+use crate::access::{report_denied, ClientAccess};
+use crate::sizing::{self, SessionId};
+use crate::udp;
 use crate::PKI;
 use anyhow::{anyhow, Context};
 use codec::*;
 use config::TermConfig;
-use mux::client::ClientId;
+use mux::client::{ClientId, ClientPermission};
 use mux::domain::SplitSource;
 use mux::pane::{CachePolicy, Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::{Mux, MuxNotification};
 use promise::spawn::spawn_into_main_thread;
+use rangeset::RangeSet;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
@@ -141,6 +145,70 @@ impl PerPane {
             seqno: self.seqno,
         })
     }
+
+    /// Produce a snapshot of the pane for a SyncPane request.
+    /// Our record of what the client knows is updated to match,
+    /// so that subsequent pushes only describe what changed after
+    /// the snapshot was taken.
+    fn snapshot(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        scrollback_lines: usize,
+        since_seqno: Option<SequenceNo>,
+    ) -> SyncPaneResponse {
+        let dims = pane.get_dimensions();
+        let seqno = pane.get_current_seqno();
+        let end = dims.physical_top + dims.viewport_rows as StableRowIndex;
+        let start = dims
+            .physical_top
+            .saturating_sub(scrollback_lines as StableRowIndex)
+            .max(dims.scrollback_top);
+        let rows = start..end;
+
+        // A seqno from the future means that the client is confused
+        // about which pane it is talking about; send everything
+        let since_seqno = since_seqno.filter(|since| *since <= seqno);
+        let to_send = match since_seqno {
+            Some(since) => pane.get_changed_since(rows.clone(), since),
+            None => {
+                let mut all = RangeSet::new();
+                all.add_range(rows.clone());
+                all
+            }
+        };
+
+        let mut lines = vec![];
+        for range in to_send.iter() {
+            let (first_row, range_lines) = pane.get_lines(range.clone());
+            for (idx, mut line) in range_lines.into_iter().enumerate() {
+                line.compress_for_scrollback();
+                lines.push((first_row + idx as StableRowIndex, line));
+            }
+        }
+
+        self.cursor_position = pane.get_cursor_position();
+        self.title = pane.get_title();
+        self.working_dir = pane.get_current_working_dir(CachePolicy::AllowStale);
+        self.dimensions = dims;
+        self.mouse_grabbed = pane.is_mouse_grabbed();
+        self.seqno = seqno;
+        self.sent_initial_palette = true;
+        self.config_generation = config::configuration().generation();
+
+        SyncPaneResponse {
+            pane_id: pane.pane_id(),
+            mouse_grabbed: self.mouse_grabbed,
+            cursor_position: self.cursor_position,
+            dimensions: dims,
+            title: self.title.clone(),
+            working_dir: self.working_dir.clone().map(Into::into),
+            palette: pane.palette(),
+            rows,
+            is_delta: since_seqno.is_some(),
+            lines: lines.into(),
+            seqno,
+        }
+    }
 }
 
 fn maybe_push_pane_changes(
@@ -201,6 +269,11 @@ pub struct SessionHandler {
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
     client_id: Option<Arc<ClientId>>,
     proxy_client_id: Option<ClientId>,
+    permission: ClientPermission,
+    access: Arc<ClientAccess>,
+    session_id: SessionId,
+    /// The capabilities advertised by both the client and ourselves
+    capabilities: Capabilities,
 }
 
 impl Drop for SessionHandler {
@@ -209,19 +282,40 @@ impl Drop for SessionHandler {
             let mux = Mux::get();
             mux.unregister_client(&client_id);
         }
+        sizing::forget_session(self.session_id);
     }
 }
 
 impl SessionHandler {
-    pub fn new(to_write_tx: PduSender) -> Self {
+    pub fn new(to_write_tx: PduSender, access: ClientAccess) -> Self {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
             client_id: None,
             proxy_client_id: None,
+            permission: if access.read_only {
+                ClientPermission::ReadOnly
+            } else {
+                ClientPermission::default()
+            },
+            access: Arc::new(access),
+            session_id: sizing::alloc_session_id(),
+            capabilities: Capabilities::empty(),
         }
     }
 
+    /// Returns true if the client has advertised the capabilities
+    /// needed to understand `pdu`
+    pub fn peer_understands(&self, pdu: &Pdu) -> bool {
+        self.capabilities.contains(pdu.required_capabilities())
+    }
+
+    /// Returns true if the notification relates to something
+    /// that this client is permitted to see
+    pub fn allows_notification(&self, notif: &MuxNotification) -> bool {
+        self.access.allows_notification(notif)
+    }
+
     pub(crate) fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
         Arc::clone(
             self.per_pane
@@ -248,10 +342,26 @@ impl SessionHandler {
         let start = Instant::now();
         let sender = self.to_write_tx.clone();
         let serial = decoded.serial;
+        let pdu_name = decoded.pdu.pdu_name();
+        let refused = if self.permission.is_read_only() && decoded.pdu.requires_write_permission() {
+            Some(anyhow!(
+                "{} is not permitted for a read-only client",
+                decoded.pdu.pdu_name()
+            ))
+        } else if let Err(err) = self.access.check_pdu(&decoded.pdu) {
+            report_denied(&self.access.peer, Some(decoded.pdu.pdu_name()), &err);
+            Some(err)
+        } else {
+            None
+        };
 
         if let Some(client_id) = &self.client_id {
-            if decoded.pdu.is_user_input() {
+            if decoded.pdu.is_user_input() && refused.is_none() {
                 Mux::get().client_had_input(client_id);
+                if !matches!(decoded.pdu, Pdu::Resize(_)) {
+                    // Resize updates the sizing state itself
+                    sizing::session_had_input(self.session_id);
+                }
             }
         }
 
@@ -262,7 +372,9 @@ impl SessionHandler {
                     reason: format!("Error: {err:#}"),
                 }),
             };
-            log::trace!("{} processing time {:?}", serial, start.elapsed());
+            let elapsed = start.elapsed();
+            log::trace!("{} processing time {:?}", serial, elapsed);
+            metrics::histogram!("pdu.latency", "pdu" => pdu_name).record(elapsed);
             sender.send(DecodedPdu { pdu, serial }).ok();
         };
 
@@ -274,6 +386,11 @@ impl SessionHandler {
             send_response(f());
         }
 
+        if let Some(err) = refused {
+            send_response(Err(err));
+            return;
+        }
+
         match decoded.pdu {
             Pdu::Ping(Ping {}) => send_response(Ok(Pdu::Pong(Pong {}))),
             Pdu::SetWindowWorkspace(SetWindowWorkspace {
@@ -298,7 +415,15 @@ impl SessionHandler {
             Pdu::SetClientId(SetClientId {
                 mut client_id,
                 is_proxy,
+                permission,
             }) => {
+                // A session that has been made read-only, either by the
+                // access rules or an earlier request, cannot be upgraded
+                let permission = if self.permission.is_read_only() {
+                    ClientPermission::ReadOnly
+                } else {
+                    permission
+                };
                 if is_proxy {
                     if self.proxy_client_id.is_none() {
                         // Copy proxy identity, but don't assign it to the mux;
@@ -320,13 +445,16 @@ impl SessionHandler {
 
                     let client_id = Arc::new(client_id);
                     self.client_id.replace(client_id.clone());
+                    self.permission = permission;
                     spawn_into_main_thread(async move {
                         let mux = Mux::get();
-                        mux.register_client(client_id);
+                        mux.register_client(client_id, permission);
                     })
                     .detach();
                 }
-                send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
+                send_response(Ok(Pdu::SetClientIdResponse(SetClientIdResponse {
+                    permission,
+                })))
             }
             Pdu::SetFocusedPane(SetFocusedPane { pane_id }) => {
                 let client_id = self.client_id.clone();
@@ -371,11 +499,13 @@ impl SessionHandler {
                 .detach();
             }
             Pdu::GetClientList(GetClientList) => {
+                let access = Arc::clone(&self.access);
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
                             let mux = Mux::get();
-                            let clients = mux.iter_clients();
+                            let mut clients = mux.iter_clients();
+                            clients.retain(|client| access.allows_client(client));
                             Ok(Pdu::GetClientListResponse(GetClientListResponse {
                                 clients,
                             }))
@@ -386,6 +516,7 @@ impl SessionHandler {
                 .detach();
             }
             Pdu::ListPanes(ListPanes {}) => {
+                let access = Arc::clone(&self.access);
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -395,6 +526,9 @@ impl SessionHandler {
                             let mut window_titles = HashMap::new();
                             for window_id in mux.iter_windows().into_iter() {
                                 let window = mux.get_window(window_id).unwrap();
+                                if !access.allows_workspace(window.get_workspace()) {
+                                    continue;
+                                }
                                 window_titles.insert(window_id, window.get_title().to_string());
                                 for tab in window.iter() {
                                     tabs.push(tab.codec_pane_tree());
@@ -469,6 +603,38 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::SetPaneOutputLog(SetPaneOutputLog { pane_id, log }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.set_output_log(log.as_ref())?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::SetPaneOutputPipe(SetPaneOutputPipe { pane_id, pipe }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.set_output_pipe(pipe.as_ref())?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
             Pdu::KillPane(KillPane { pane_id }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -635,18 +801,23 @@ impl SessionHandler {
                 pane_id,
                 size,
             }) => {
+                let session_id = self.session_id;
+                let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
                             let mux = Mux::get();
-                            let pane = mux
-                                .get_pane(pane_id)
+                            mux.get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
-                            pane.resize(size)?;
-                            let tab = mux
-                                .get_tab(containing_tab_id)
+                            mux.get_tab(containing_tab_id)
                                 .ok_or_else(|| anyhow!("no such tab {}", containing_tab_id))?;
-                            tab.rebuild_splits_sizes_from_contained_panes();
+                            sizing::request_size(
+                                session_id,
+                                client_id,
+                                containing_tab_id,
+                                pane_id,
+                                size,
+                            )?;
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
                         send_response,
@@ -811,6 +982,32 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SyncPane(SyncPane {
+                pane_id,
+                scrollback_lines,
+                since_seqno,
+            }) => {
+                let per_pane = self.per_pane(pane_id);
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let mut per_pane = per_pane.lock().unwrap();
+                            Ok(Pdu::SyncPaneResponse(per_pane.snapshot(
+                                &pane,
+                                scrollback_lines,
+                                since_seqno,
+                            )))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::GetImageCell(GetImageCell {
                 pane_id,
                 line_idx,
@@ -879,6 +1076,34 @@ impl SessionHandler {
                     send_response,
                 );
             }
+            Pdu::NegotiateCapabilities(NegotiateCapabilities { capabilities }) => {
+                let offered = offered_capabilities();
+                self.capabilities = offered & capabilities;
+                send_response(Ok(Pdu::NegotiateCapabilitiesResponse(
+                    NegotiateCapabilitiesResponse {
+                        capabilities: offered,
+                    },
+                )));
+            }
+
+            Pdu::GetMetrics(GetMetrics {}) => {
+                catch(
+                    || {
+                        Ok(Pdu::GetMetricsResponse(GetMetricsResponse {
+                            text: crate::metrics::render()?,
+                        }))
+                    },
+                    send_response,
+                );
+            }
+
+            Pdu::StartUdpSession(StartUdpSession {}) => {
+                let access = Arc::clone(&self.access);
+                catch(
+                    move || Ok(Pdu::StartUdpSessionResponse(udp::start_session(&access)?)),
+                    send_response,
+                );
+            }
             Pdu::WindowTitleChanged(WindowTitleChanged { window_id, title }) => {
                 spawn_into_main_thread(async move {
                     catch(
@@ -999,10 +1224,15 @@ impl SessionHandler {
             | Pdu::GetPaneDirectionResponse { .. }
             | Pdu::SearchScrollbackResponse { .. }
             | Pdu::GetLinesResponse { .. }
+            | Pdu::SyncPaneResponse { .. }
+            | Pdu::StartUdpSessionResponse { .. }
+            | Pdu::NegotiateCapabilitiesResponse { .. }
+            | Pdu::GetMetricsResponse { .. }
             | Pdu::GetCodecVersionResponse { .. }
             | Pdu::WindowWorkspaceChanged { .. }
             | Pdu::GetTlsCredsResponse { .. }
             | Pdu::GetClientListResponse { .. }
+            | Pdu::SetClientIdResponse { .. }
             | Pdu::PaneRemoved { .. }
             | Pdu::PaneFocused { .. }
             | Pdu::TabResized { .. }
@@ -1017,6 +1247,15 @@ impl SessionHandler {
     }
 }
 
+/// Returns the capabilities that we advertise to clients
+fn offered_capabilities() -> Capabilities {
+    let mut capabilities = Capabilities::all();
+    if !udp::is_listening() {
+        capabilities.remove(Capabilities::UDP_SESSIONS);
+    }
+    capabilities
+}
+
 // Dancing around a little bit here; we can't directly spawn_into_main_thread the domain_spawn
 // function below because the compiler thinks that all of its locals then need to be Send.
 // We need to shimmy through this helper to break that aspect of the compiler flow
@@ -0,0 +1,384 @@
+//! When multiple clients are attached to the same pane, each of them
+//! asks for the pane to match the size of its own window.  Rather than
+//! letting whichever `Resize` arrived last win, which causes the pane to
+//! thrash between sizes, we remember the size requested by each session
+//! and resolve the effective size using `mux_client_size_policy`.
+//! Clients whose window is larger than the effective size render the
+//! pane at that size and leave the rest of their window blank.
+use chrono::{DateTime, Utc};
+use config::{configuration, MuxClientSizePolicy};
+use mux::client::{ClientId, ClientInfo};
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::Mux;
+use promise::spawn::spawn_into_main_thread;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use wezterm_term::TerminalSize;
+
+/// Identifies a client session for the purposes of size tracking.
+/// Not all sessions identify themselves with a `ClientId`, so we
+/// allocate our own identifier for each of them.
+pub type SessionId = usize;
+
+pub fn alloc_session_id() -> SessionId {
+    static NEXT_SESSION_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+struct SizeRequest {
+    session: SessionId,
+    client_id: Option<Arc<ClientId>>,
+    tab_id: TabId,
+    size: TerminalSize,
+    requested_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct ClientSizes {
+    by_pane: HashMap<PaneId, Vec<SizeRequest>>,
+    /// The session that most recently sent input; used to avoid
+    /// re-evaluating sizes on every keypress
+    active_session: Option<SessionId>,
+}
+
+impl ClientSizes {
+    /// Records the size requested by a session, replacing any earlier
+    /// request that it made for the same pane, and returns all of the
+    /// requests for that pane
+    fn record(&mut self, pane_id: PaneId, request: SizeRequest) -> &[SizeRequest] {
+        // A Resize counts as input from this session
+        self.active_session.replace(request.session);
+        let requests = self.by_pane.entry(pane_id).or_default();
+        requests.retain(|r| r.session != request.session);
+        requests.push(request);
+        requests
+    }
+
+    /// Discards the requests made by a session, returning the panes
+    /// that it had requested sizes for
+    fn forget(&mut self, session: SessionId) -> Vec<PaneId> {
+        if self.active_session == Some(session) {
+            self.active_session.take();
+        }
+        let mut panes = vec![];
+        self.by_pane.retain(|pane_id, requests| {
+            let before = requests.len();
+            requests.retain(|r| r.session != session);
+            if requests.len() != before {
+                panes.push(*pane_id);
+            }
+            !requests.is_empty()
+        });
+        panes
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref SIZES: Mutex<ClientSizes> = Mutex::new(ClientSizes::default());
+}
+
+/// Returns the time of the most recent activity for the session
+/// that made a request.  Sessions that identified themselves have
+/// their input tracked by the mux; for the others, the best we can
+/// do is to use the time at which they requested the size.
+fn last_activity(request: &SizeRequest, clients: &[ClientInfo]) -> DateTime<Utc> {
+    request
+        .client_id
+        .as_ref()
+        .and_then(|id| clients.iter().find(|info| info.client_id == *id))
+        .map(|info| info.last_input.max(request.requested_at))
+        .unwrap_or(request.requested_at)
+}
+
+/// Resolves the effective size of a pane using the configured policy
+fn resolve(requests: &[SizeRequest]) -> Option<TerminalSize> {
+    let policy = configuration().mux_client_size_policy;
+    let clients = match policy {
+        MuxClientSizePolicy::MostRecentlyActive => Mux::get().iter_clients(),
+        MuxClientSizePolicy::Smallest | MuxClientSizePolicy::Largest => vec![],
+    };
+    resolve_with_policy(policy, requests, &clients)
+}
+
+fn resolve_with_policy(
+    policy: MuxClientSizePolicy,
+    requests: &[SizeRequest],
+    clients: &[ClientInfo],
+) -> Option<TerminalSize> {
+    match policy {
+        MuxClientSizePolicy::MostRecentlyActive => requests
+            .iter()
+            .max_by_key(|r| last_activity(r, clients))
+            .map(|r| r.size),
+        MuxClientSizePolicy::Smallest => {
+            let widest = requests.iter().min_by_key(|r| r.size.cols)?;
+            let tallest = requests.iter().min_by_key(|r| r.size.rows)?;
+            Some(TerminalSize {
+                cols: widest.size.cols,
+                pixel_width: widest.size.pixel_width,
+                dpi: widest.size.dpi,
+                rows: tallest.size.rows,
+                pixel_height: tallest.size.pixel_height,
+            })
+        }
+        MuxClientSizePolicy::Largest => {
+            let widest = requests.iter().max_by_key(|r| r.size.cols)?;
+            let tallest = requests.iter().max_by_key(|r| r.size.rows)?;
+            Some(TerminalSize {
+                cols: widest.size.cols,
+                pixel_width: widest.size.pixel_width,
+                dpi: widest.size.dpi,
+                rows: tallest.size.rows,
+                pixel_height: tallest.size.pixel_height,
+            })
+        }
+    }
+}
+
+/// Resizes the pane if its current dimensions differ from `size`.
+/// Returns true if the pane was resized.
+fn apply_size(pane_id: PaneId, size: TerminalSize) -> anyhow::Result<bool> {
+    let mux = Mux::get();
+    let pane = match mux.get_pane(pane_id) {
+        Some(pane) => pane,
+        None => {
+            SIZES.lock().unwrap().by_pane.remove(&pane_id);
+            return Ok(false);
+        }
+    };
+    let dims = pane.get_dimensions();
+    if dims.cols == size.cols
+        && dims.viewport_rows == size.rows
+        && dims.pixel_width == size.pixel_width
+        && dims.pixel_height == size.pixel_height
+        && dims.dpi == size.dpi
+    {
+        return Ok(false);
+    }
+    pane.resize(size)?;
+    Ok(true)
+}
+
+/// Re-evaluates the effective size of the specified panes, resizing
+/// them and their containing tabs as needed.  Must be called on the
+/// main thread.
+fn reconcile(panes: Vec<PaneId>) {
+    let mux = Mux::get();
+    for pane_id in panes {
+        let resolved = {
+            let sizes = SIZES.lock().unwrap();
+            sizes.by_pane.get(&pane_id).and_then(|requests| {
+                let tab_id = requests.last()?.tab_id;
+                Some((tab_id, resolve(requests)?))
+            })
+        };
+        let (tab_id, size) = match resolved {
+            Some(resolved) => resolved,
+            None => continue,
+        };
+        match apply_size(pane_id, size) {
+            Ok(true) => {
+                if let Some(tab) = mux.get_tab(tab_id) {
+                    tab.rebuild_splits_sizes_from_contained_panes();
+                }
+            }
+            Ok(false) => {}
+            Err(err) => log::error!("resizing pane {pane_id} to {size:?}: {err:#}"),
+        }
+    }
+}
+
+/// Records the size that `session` would like the pane to have, and
+/// resizes the pane to the size resolved by the configured policy.
+/// Must be called on the main thread.
+pub fn request_size(
+    session: SessionId,
+    client_id: Option<Arc<ClientId>>,
+    tab_id: TabId,
+    pane_id: PaneId,
+    size: TerminalSize,
+) -> anyhow::Result<()> {
+    let size = {
+        let mut sizes = SIZES.lock().unwrap();
+        let requests = sizes.record(
+            pane_id,
+            SizeRequest {
+                session,
+                client_id,
+                tab_id,
+                size,
+                requested_at: Utc::now(),
+            },
+        );
+        resolve(requests).unwrap_or(size)
+    };
+
+    apply_size(pane_id, size)?;
+
+    let mux = Mux::get();
+    if let Some(tab) = mux.get_tab(tab_id) {
+        // Always rebuild, even if the size didn't change, so that the
+        // requesting client resyncs with the effective size
+        tab.rebuild_splits_sizes_from_contained_panes();
+    }
+    Ok(())
+}
+
+/// Called when `session` has sent input to the mux.  When using the
+/// `MostRecentlyActive` policy, the panes that it has requested sizes
+/// for are switched over to its preferred size.
+pub fn session_had_input(session: SessionId) {
+    if configuration().mux_client_size_policy != MuxClientSizePolicy::MostRecentlyActive {
+        return;
+    }
+    let panes: Vec<PaneId> = {
+        let mut sizes = SIZES.lock().unwrap();
+        if sizes.active_session == Some(session) {
+            return;
+        }
+        sizes.active_session.replace(session);
+        sizes
+            .by_pane
+            .iter()
+            .filter(|(_, requests)| requests.iter().any(|r| r.session == session))
+            .map(|(pane_id, _)| *pane_id)
+            .collect()
+    };
+    if !panes.is_empty() {
+        spawn_into_main_thread(async move { reconcile(panes) }).detach();
+    }
+}
+
+/// Discards the sizes requested by a session that has disconnected,
+/// and resizes the panes that it was attached to in accordance with
+/// the remaining clients.
+pub fn forget_session(session: SessionId) {
+    let panes = SIZES.lock().unwrap().forget(session);
+    if !panes.is_empty() {
+        spawn_into_main_thread(async move { reconcile(panes) }).detach();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::Duration;
+    use mux::client::ClientPermission;
+
+    fn size(cols: usize, rows: usize) -> TerminalSize {
+        TerminalSize {
+            cols,
+            rows,
+            pixel_width: cols * 10,
+            pixel_height: rows * 20,
+            dpi: 96,
+        }
+    }
+
+    fn request(
+        session: SessionId,
+        client_id: Option<&Arc<ClientId>>,
+        size: TerminalSize,
+        requested_at: DateTime<Utc>,
+    ) -> SizeRequest {
+        SizeRequest {
+            session,
+            client_id: client_id.cloned(),
+            tab_id: 0,
+            size,
+            requested_at,
+        }
+    }
+
+    #[test]
+    fn resolve_policies() {
+        let start = Utc::now();
+        let busy = Arc::new(ClientId::new());
+        let idle = Arc::new(ClientId::new());
+
+        let mut busy_info = ClientInfo::new(Arc::clone(&busy), ClientPermission::ReadWrite);
+        busy_info.last_input = start + Duration::seconds(10);
+        let mut idle_info = ClientInfo::new(Arc::clone(&idle), ClientPermission::ReadWrite);
+        idle_info.last_input = start;
+        let clients = vec![busy_info, idle_info];
+
+        // The idle client requested its size most recently, but the
+        // busy client has sent input since then
+        let requests = vec![
+            request(1, Some(&busy), size(80, 40), start),
+            request(2, Some(&idle), size(120, 20), start + Duration::seconds(5)),
+            request(3, None, size(100, 30), start + Duration::seconds(1)),
+        ];
+
+        for (policy, expected) in [
+            (MuxClientSizePolicy::MostRecentlyActive, size(80, 40)),
+            (MuxClientSizePolicy::Smallest, size(80, 20)),
+            (MuxClientSizePolicy::Largest, size(120, 40)),
+        ] {
+            assert_eq!(
+                resolve_with_policy(policy, &requests, &clients),
+                Some(expected),
+                "{policy:?}"
+            );
+        }
+
+        // Without input, the most recent request wins
+        assert_eq!(
+            resolve_with_policy(MuxClientSizePolicy::MostRecentlyActive, &requests, &[]),
+            Some(size(120, 20))
+        );
+
+        for policy in [
+            MuxClientSizePolicy::MostRecentlyActive,
+            MuxClientSizePolicy::Smallest,
+            MuxClientSizePolicy::Largest,
+        ] {
+            assert_eq!(
+                resolve_with_policy(policy, &[], &clients),
+                None,
+                "{policy:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn forget_session() {
+        let now = Utc::now();
+        let mut sizes = ClientSizes::default();
+        sizes.record(1, request(1, None, size(80, 24), now));
+        sizes.record(1, request(2, None, size(120, 40), now));
+        sizes.record(2, request(2, None, size(100, 30), now));
+        // A later request from the same session replaces the earlier one
+        let requests = sizes.record(1, request(2, None, size(132, 50), now));
+        assert_eq!(requests.len(), 2);
+        assert_eq!(sizes.active_session, Some(2));
+
+        let mut panes = sizes.forget(2);
+        panes.sort();
+        assert_eq!(panes, vec![1, 2]);
+        assert_eq!(sizes.active_session, None);
+
+        // The pane now follows the remaining session, and panes that
+        // have no remaining requests are no longer tracked
+        let requests = &sizes.by_pane[&1];
+        for policy in [
+            MuxClientSizePolicy::MostRecentlyActive,
+            MuxClientSizePolicy::Smallest,
+            MuxClientSizePolicy::Largest,
+        ] {
+            assert_eq!(
+                resolve_with_policy(policy, requests, &[]),
+                Some(size(80, 24)),
+                "{policy:?}"
+            );
+        }
+        assert!(!sizes.by_pane.contains_key(&2));
+
+        assert!(sizes.forget(2).is_empty());
+        assert_eq!(sizes.forget(1), vec![1]);
+        assert!(sizes.by_pane.is_empty());
+    }
+}
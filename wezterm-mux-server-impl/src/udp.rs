@@ -0,0 +1,191 @@
+//! The server end of the datagram transport implemented by
+//! `wezterm_client::udp`.  A client that has authenticated itself over
+//! TLS sends `StartUdpSession` to obtain the credentials for a session,
+//! and then continues the conversation over datagrams.  Each session is
+//! bridged to a local stream which is processed in the same way as any
+//! other connection, with the access granted to the TLS connection.
+use crate::access::ClientAccess;
+use anyhow::Context;
+use codec::StartUdpSessionResponse;
+use promise::spawn::spawn_into_main_thread;
+use smol::channel::{unbounded, Sender};
+use smol::net::UdpSocket;
+use smol::{Async, LocalExecutor};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wezterm_client::udp::{self, Session, UdpSessionId, UdpSessionKey, SERVER_IDLE_TIMEOUT};
+
+/// How long a client has to start using a session after requesting it
+const PENDING_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct PendingSession {
+    key: UdpSessionKey,
+    access: ClientAccess,
+    issued: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref PENDING: Mutex<HashMap<UdpSessionId, PendingSession>> = Mutex::new(HashMap::new());
+}
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Passes the datagrams for a session to the task running it
+type DatagramSender = Sender<(Vec<u8>, SocketAddr)>;
+
+/// Returns true if a `UdpListener` has been bound
+pub fn is_listening() -> bool {
+    LISTENING.load(Ordering::Relaxed)
+}
+
+/// Issues the credentials for a new session, which will be granted
+/// the same access as the connection that requested it
+pub fn start_session(access: &ClientAccess) -> anyhow::Result<StartUdpSessionResponse> {
+    if !is_listening() {
+        anyhow::bail!("no tls_servers have a udp_bind_address");
+    }
+    let (session_id, key) = udp::generate_session_credentials()?;
+    let mut pending = PENDING.lock().unwrap();
+    pending.retain(|_, session| session.issued.elapsed() < PENDING_TIMEOUT);
+    pending.insert(
+        session_id,
+        PendingSession {
+            key,
+            access: access.clone(),
+            issued: Instant::now(),
+        },
+    );
+    Ok(StartUdpSessionResponse { session_id, key })
+}
+
+/// Looks up the pending session for `datagram`, and if the datagram
+/// was sent by the client that requested it, claims it
+fn claim_session(datagram: &[u8], from: SocketAddr) -> Option<(Session, ClientAccess)> {
+    let session_id = Session::session_id_of(datagram)?;
+    let mut pending = PENDING.lock().unwrap();
+    let entry = pending.get(&session_id)?;
+    if entry.issued.elapsed() >= PENDING_TIMEOUT {
+        pending.remove(&session_id);
+        return None;
+    }
+    let session = Session::new(session_id, entry.key, true, from);
+    if !session.authenticate(datagram) {
+        return None;
+    }
+    let entry = pending.remove(&session_id)?;
+    Some((session, entry.access))
+}
+
+pub struct UdpListener {
+    socket: std::net::UdpSocket,
+}
+
+impl UdpListener {
+    pub fn bind(address: &str) -> anyhow::Result<Self> {
+        let socket = std::net::UdpSocket::bind(address)
+            .with_context(|| format!("binding udp socket to {}", address))?;
+        LISTENING.store(true, Ordering::Relaxed);
+        Ok(Self { socket })
+    }
+
+    pub fn run(self) {
+        let address = self.socket.local_addr();
+        if let Err(err) = smol::block_on(self.serve()) {
+            log::error!("udp listener on {:?}: {:#}", address, err);
+        }
+    }
+
+    async fn serve(self) -> anyhow::Result<()> {
+        let socket = UdpSocket::try_from(self.socket)?;
+        let executor = LocalExecutor::new();
+        let sessions: Rc<RefCell<HashMap<UdpSessionId, DatagramSender>>> =
+            Rc::new(RefCell::new(HashMap::new()));
+        let mut buf = vec![0u8; 65536];
+
+        executor
+            .run(async {
+                loop {
+                    let (len, from) = match socket.recv_from(&mut buf).await {
+                        Ok(result) => result,
+                        Err(err)
+                            if matches!(
+                                err.kind(),
+                                std::io::ErrorKind::ConnectionReset
+                                    | std::io::ErrorKind::ConnectionRefused
+                            ) =>
+                        {
+                            continue;
+                        }
+                        Err(err) => return Err(err).context("receiving datagram"),
+                    };
+                    let datagram = buf[..len].to_vec();
+                    let session_id = match Session::session_id_of(&datagram) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+
+                    if let Some(tx) = sessions.borrow().get(&session_id) {
+                        tx.try_send((datagram, from)).ok();
+                        continue;
+                    }
+
+                    let (session, access) = match claim_session(&datagram, from) {
+                        Some(claimed) => claimed,
+                        None => {
+                            log::trace!("ignoring datagram for unknown session from {}", from);
+                            continue;
+                        }
+                    };
+                    log::info!("udp session {:x} started by {}", session_id, access.peer);
+
+                    let local = match udp::stream_pair().and_then(|(server_end, session_end)| {
+                        Ok((server_end, Async::new(session_end)?))
+                    }) {
+                        Ok((server_end, session_end)) => {
+                            spawn_into_main_thread(async move {
+                                crate::dispatch::process(server_end, access)
+                                    .await
+                                    .map_err(|e| {
+                                        log::error!("{:#}", e);
+                                        e
+                                    })
+                            })
+                            .detach();
+                            session_end
+                        }
+                        Err(err) => {
+                            log::error!("udp session {:x}: {:#}", session_id, err);
+                            continue;
+                        }
+                    };
+
+                    let (tx, rx) = unbounded();
+                    tx.try_send((datagram, from)).ok();
+                    sessions.borrow_mut().insert(session_id, tx);
+
+                    let sessions = Rc::clone(&sessions);
+                    let socket = socket.clone();
+                    executor
+                        .spawn(async move {
+                            let result =
+                                udp::run_session(session, socket, rx, local, SERVER_IDLE_TIMEOUT)
+                                    .await;
+                            sessions.borrow_mut().remove(&session_id);
+                            match result {
+                                Ok(()) => log::info!("udp session {:x} closed", session_id),
+                                Err(err) => {
+                                    log::error!("udp session {:x}: {:#}", session_id, err)
+                                }
+                            }
+                        })
+                        .detach();
+                }
+            })
+            .await
+    }
+}
@@ -0,0 +1,176 @@
+//! Exercises the refusal of mutating requests from a read-only client
+//! by a real server session connected by a socket pair.
+//! This lives in its own test binary because it installs the global
+//! mux and executors.
+#![cfg(unix)]
+
+use codec::{ErrorResponse, GetTlsCreds, Pdu, Resize, SetPaneOutputPipe, WriteToPane};
+use config::keyassignment::PaneOutputPipe;
+use config::UnixDomain;
+use mux::client::ClientPermission;
+use mux::pane::Pane;
+use mux::tab::Tab;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use promise::spawn::{spawn_into_main_thread, ScopedExecutor, SimpleExecutor};
+use smol::Async;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::surface::Change;
+use termwiz::terminal::Terminal;
+use wezterm_client::client::Client;
+use wezterm_client::domain::ClientDomainConfig;
+use wezterm_mux_server_impl::access::{ClientAccess, Peer};
+use wezterm_term::TerminalSize;
+
+fn viewport_text(pane: &dyn Pane) -> Vec<String> {
+    let dims = pane.get_dimensions();
+    let (_, lines) =
+        pane.get_lines(dims.physical_top..dims.physical_top + dims.viewport_rows as isize);
+    lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect()
+}
+
+/// Renders text to the server pane, and waits for the mux to
+/// process it, which happens on another thread
+async fn render(term: &mut TermWizTerminal, pane: &Arc<dyn Pane>, text: &str) {
+    term.render(&[
+        Change::Text(text.to_string()),
+        Change::Title(text.to_string()),
+    ])
+    .unwrap();
+    term.flush().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while pane.get_title() != text {
+        assert!(Instant::now() < deadline, "timed out waiting for output");
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+}
+
+async fn assert_refused(client: &Client, pdu: Pdu) {
+    let name = pdu.pdu_name();
+    match client.send_pdu(pdu).await.unwrap() {
+        Pdu::ErrorResponse(ErrorResponse { reason }) => {
+            assert!(
+                reason.contains("not permitted for a read-only client"),
+                "{} was refused for another reason: {}",
+                name,
+                reason
+            );
+        }
+        pdu => panic!("{} was answered with {}", name, pdu.pdu_name()),
+    }
+}
+
+#[test]
+fn read_only_client_is_refused_writes() {
+    // The session and client futures are too large for the default
+    // test thread stack in debug builds
+    std::thread::Builder::new()
+        .stack_size(16 * 1024 * 1024)
+        .spawn(refuse_writes)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn refuse_writes() {
+    let mut config = config::Config::default_config();
+    config.mux_enable_ssh_agent = false;
+    config::use_this_configuration(config);
+
+    let _local = SimpleExecutor::new();
+    // The server session and mux notifications run here
+    let executor = ScopedExecutor::new();
+    let mux = Arc::new(Mux::new(None));
+    Mux::set_mux(&mux);
+
+    smol::block_on(executor.run(async {
+        let size = TerminalSize {
+            rows: 4,
+            cols: 20,
+            pixel_width: 200,
+            pixel_height: 80,
+            dpi: 96,
+        };
+        let (mut term, server_pane) =
+            mux::termwiztermtab::allocate(size, Arc::new(config::TermConfig::new()));
+        let tab = Arc::new(Tab::new(&size));
+        tab.assign_pane(&server_pane);
+        mux.add_tab_and_active_pane(&tab).unwrap();
+        render(&mut term, &server_pane, "hello").await;
+
+        let (server_stream, client_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        let server_stream =
+            unsafe { wezterm_uds::UnixStream::from_raw_fd(server_stream.into_raw_fd()) };
+        let client_stream =
+            unsafe { wezterm_uds::UnixStream::from_raw_fd(client_stream.into_raw_fd()) };
+        spawn_into_main_thread(wezterm_mux_server_impl::dispatch::process(
+            server_stream,
+            ClientAccess::unrestricted(Peer::Unix { uid: None }),
+        ))
+        .detach();
+
+        let client = Client::new_with_stream(
+            ClientDomainConfig::Unix(UnixDomain {
+                read_only: true,
+                ..UnixDomain::default()
+            }),
+            Box::new(Async::new(client_stream).unwrap()),
+        );
+        assert_eq!(
+            client.set_client_identity().await.unwrap(),
+            ClientPermission::ReadOnly
+        );
+
+        let pane_id = server_pane.pane_id();
+        assert_refused(
+            &client,
+            Pdu::WriteToPane(WriteToPane {
+                pane_id,
+                data: b"typed".to_vec(),
+            }),
+        )
+        .await;
+        assert_refused(
+            &client,
+            Pdu::Resize(Resize {
+                containing_tab_id: tab.tab_id(),
+                pane_id,
+                size: TerminalSize {
+                    rows: 10,
+                    cols: 40,
+                    ..size
+                },
+            }),
+        )
+        .await;
+        assert_refused(
+            &client,
+            Pdu::SetPaneOutputPipe(SetPaneOutputPipe {
+                pane_id,
+                pipe: Some(PaneOutputPipe {
+                    args: vec!["cat".to_string()],
+                    cwd: None,
+                    inject_output: false,
+                }),
+            }),
+        )
+        .await;
+        assert_refused(&client, Pdu::GetTlsCreds(GetTlsCreds {})).await;
+
+        // The pane is untouched by the refused requests
+        render(&mut term, &server_pane, "done").await;
+        assert_eq!(viewport_text(&*server_pane)[0], "hellodone");
+        let dims = server_pane.get_dimensions();
+        assert_eq!((dims.cols, dims.viewport_rows), (20, 4));
+    }));
+
+    // The session may still be pending; it needs the mux when dropped
+    drop(executor);
+    Mux::shutdown();
+}
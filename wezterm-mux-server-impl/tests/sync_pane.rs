@@ -0,0 +1,163 @@
+//! Exercises the SyncPane catch-up path between a real client session
+//! and a real server session connected by a socket pair.
+//! This lives in its own test binary because it installs the global
+//! mux and executors.
+#![cfg(unix)]
+
+use config::UnixDomain;
+use mux::pane::Pane;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use promise::spawn::{spawn_into_main_thread, ScopedExecutor, SimpleExecutor};
+use smol::Async;
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use wezterm_client::client::Client;
+use wezterm_client::domain::{ClientDomainConfig, ClientInner};
+use wezterm_client::pane::ClientPane;
+use wezterm_mux_server_impl::access::{ClientAccess, Peer};
+use wezterm_term::TerminalSize;
+
+fn viewport_text(pane: &dyn Pane) -> Vec<String> {
+    let dims = pane.get_dimensions();
+    let (_, lines) =
+        pane.get_lines(dims.physical_top..dims.physical_top + dims.viewport_rows as isize);
+    lines
+        .iter()
+        .map(|line| line.as_str().trim_end().to_string())
+        .collect()
+}
+
+/// Renders changes to the server pane, and waits for the mux to
+/// process them, which happens on another thread
+async fn render(term: &mut TermWizTerminal, pane: &Arc<dyn Pane>, changes: Vec<Change>) {
+    let title = changes
+        .iter()
+        .rev()
+        .find_map(|change| match change {
+            Change::Title(title) => Some(title.clone()),
+            _ => None,
+        })
+        .expect("each render sets the title, which marks its end");
+    term.render(&changes).unwrap();
+    term.flush().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while pane.get_title() != title {
+        assert!(Instant::now() < deadline, "timed out waiting for output");
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+}
+
+fn assert_converged(server: &Arc<dyn Pane>, client: &ClientPane) {
+    assert_eq!(viewport_text(client), viewport_text(&**server));
+    assert_eq!(client.get_cursor_position(), server.get_cursor_position());
+    assert_eq!(client.get_title(), server.get_title());
+    assert_eq!(client.get_current_seqno(), server.get_current_seqno());
+}
+
+#[test]
+fn sync_pane_catches_up_from_stale_seqno() {
+    // The session and client futures are too large for the default
+    // test thread stack in debug builds
+    std::thread::Builder::new()
+        .stack_size(16 * 1024 * 1024)
+        .spawn(catch_up_from_stale_seqno)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn catch_up_from_stale_seqno() {
+    let mut config = config::Config::default_config();
+    config.mux_enable_ssh_agent = false;
+    config::use_this_configuration(config);
+
+    // Local spawns, such as the client pane advising the server of its
+    // palette, are queued here and never run; the sync doesn't need them
+    let _local = SimpleExecutor::new();
+    // The server session and mux notifications run here
+    let executor = ScopedExecutor::new();
+    let mux = Arc::new(Mux::new(None));
+    Mux::set_mux(&mux);
+
+    smol::block_on(executor.run(async {
+        let size = TerminalSize {
+            rows: 4,
+            cols: 20,
+            pixel_width: 200,
+            pixel_height: 80,
+            dpi: 96,
+        };
+        let (mut term, server_pane) =
+            mux::termwiztermtab::allocate(size, Arc::new(config::TermConfig::new()));
+
+        let (server_stream, client_stream) = std::os::unix::net::UnixStream::pair().unwrap();
+        let server_stream =
+            unsafe { wezterm_uds::UnixStream::from_raw_fd(server_stream.into_raw_fd()) };
+        let client_stream =
+            unsafe { wezterm_uds::UnixStream::from_raw_fd(client_stream.into_raw_fd()) };
+        spawn_into_main_thread(wezterm_mux_server_impl::dispatch::process(
+            server_stream,
+            ClientAccess::unrestricted(Peer::Unix { uid: None }),
+        ))
+        .detach();
+
+        let client = Client::new_with_stream(
+            ClientDomainConfig::Unix(UnixDomain::default()),
+            Box::new(Async::new(client_stream).unwrap()),
+        );
+        let client = Arc::new(ClientInner::new(0, client, None, false));
+        let client_pane = ClientPane::new(&client, 0, server_pane.pane_id(), size, "");
+
+        render(
+            &mut term,
+            &server_pane,
+            vec![
+                Change::Text("one\r\ntwo\r\nthree".to_string()),
+                Change::Title("first".to_string()),
+            ],
+        )
+        .await;
+
+        // The initial sync transfers everything
+        client_pane.sync_with_server().await.unwrap();
+        assert_converged(&server_pane, &client_pane);
+        let stale_seqno = client_pane.get_current_seqno();
+
+        // While the client is "disconnected", the pane changes
+        render(
+            &mut term,
+            &server_pane,
+            vec![
+                Change::CursorPosition {
+                    x: Position::Absolute(0),
+                    y: Position::Absolute(1),
+                },
+                Change::Text("TWO".to_string()),
+                Change::CursorPosition {
+                    x: Position::Absolute(2),
+                    y: Position::Absolute(3),
+                },
+                Change::Text("four".to_string()),
+                Change::Title("second".to_string()),
+            ],
+        )
+        .await;
+        assert_ne!(server_pane.get_current_seqno(), stale_seqno);
+        assert_ne!(viewport_text(&client_pane), viewport_text(&*server_pane));
+
+        // Catching up from the stale seqno brings the client back in line
+        client_pane.sync_with_server().await.unwrap();
+        assert_converged(&server_pane, &client_pane);
+        assert_eq!(
+            viewport_text(&client_pane),
+            vec!["one", "TWO", "three", "  four"]
+        );
+    }));
+
+    Mux::shutdown();
+}
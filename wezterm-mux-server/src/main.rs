@@ -76,6 +76,7 @@ fn run() -> anyhow::Result<()> {
     env_bootstrap::bootstrap();
 
     //stats::Stats::init()?;
+    wezterm_mux_server_impl::metrics::install()?;
     config::designate_this_as_the_main_thread();
     let _saver = umask::UmaskSaver::new();
 
@@ -319,6 +320,19 @@ pub fn spawn_listener() -> anyhow::Result<()> {
 
     for tls_server in &config.tls_servers {
         ossl::spawn_tls_listener(tls_server)?;
+        if let Some(address) = &tls_server.udp_bind_address {
+            let listener = wezterm_mux_server_impl::udp::UdpListener::bind(address)?;
+            thread::spawn(move || {
+                listener.run();
+            });
+        }
+    }
+
+    if let Some(path) = &config.mux_metrics_socket_path {
+        let mut listener = wezterm_mux_server_impl::metrics::MetricsListener::bind(path)?;
+        thread::spawn(move || {
+            listener.run();
+        });
     }
 
     Ok(())
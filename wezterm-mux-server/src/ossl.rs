@@ -1,24 +1,31 @@
 use anyhow::{anyhow, Context, Error};
 use async_ossl::AsyncSslStream;
-use config::TlsDomainServer;
+use config::{MuxAccessRule, TlsDomainServer};
 use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
 use openssl::x509::X509;
 use promise::spawn::spawn_into_main_thread;
 use std::net::TcpListener;
 use std::path::Path;
 use std::sync::Arc;
+use wezterm_mux_server_impl::access::{report_denied, ClientAccess, Peer};
 use wezterm_mux_server_impl::PKI;
 
 struct OpenSSLNetListener {
     acceptor: Arc<SslAcceptor>,
     listener: TcpListener,
+    access_rules: Vec<MuxAccessRule>,
 }
 
 impl OpenSSLNetListener {
-    pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> Self {
+    pub fn new(
+        listener: TcpListener,
+        acceptor: SslAcceptor,
+        access_rules: Vec<MuxAccessRule>,
+    ) -> Self {
         Self {
             listener,
             acceptor: Arc::new(acceptor),
+            access_rules,
         }
     }
 
@@ -26,12 +33,13 @@ impl OpenSSLNetListener {
     /// The requirements are:
     /// * The peer must have a certificate
     /// * The peer certificate must be trusted
-    /// * The peer certificate must include a CN string that is
-    ///   either an exact match for the unix username of the
-    ///   user running this mux server instance, or must match
-    ///   a special encoded prefix set up by a proprietary PKI
-    ///   infrastructure in an environment used by the author.
-    fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<()> {
+    /// * The peer certificate must include a CN string that either
+    ///   matches one of the `access_rules` for this server, or is
+    ///   an exact match for the unix username of the user running
+    ///   this mux server instance, or matches a special encoded
+    ///   prefix set up by a proprietary PKI infrastructure in an
+    ///   environment used by the author.
+    fn verify_peer_cert<T>(&self, stream: &SslStream<T>) -> anyhow::Result<ClientAccess> {
         let cert = stream
             .ssl()
             .peer_certificate()
@@ -43,30 +51,17 @@ impl OpenSSLNetListener {
             .ok_or_else(|| anyhow!("cert has no CN"))?;
         let cn_str = cn.data().as_utf8()?.to_string();
 
-        let wanted_unix_name = std::env::var("USER")?;
-
-        if wanted_unix_name == cn_str {
-            log::trace!(
-                "Peer certificate CN `{}` == $USER `{}`",
-                cn_str,
-                wanted_unix_name
+        let access = ClientAccess::for_tls_peer(&cn_str, &self.access_rules);
+        if let Err(err) = &access {
+            report_denied(
+                &Peer::Tls {
+                    common_name: cn_str,
+                },
+                None,
+                err,
             );
-            Ok(())
-        } else {
-            // Some environments that are used by the author of this
-            // program encode the CN in the form `user:unixname/DATA`
-            let maybe_encoded = format!("user:{}/", wanted_unix_name);
-            if cn_str.starts_with(&maybe_encoded) {
-                log::trace!(
-                    "Peer certificate CN `{}` matches $USER `{}`",
-                    cn_str,
-                    wanted_unix_name
-                );
-                Ok(())
-            } else {
-                anyhow::bail!("CN `{}` did not match $USER `{}`", cn_str, wanted_unix_name);
-            }
         }
+        access
     }
 
     fn run(&mut self) {
@@ -78,15 +73,19 @@ impl OpenSSLNetListener {
 
                     match acceptor.accept(stream) {
                         Ok(stream) => {
-                            if let Err(err) = Self::verify_peer_cert(&stream) {
-                                log::error!("problem with peer cert: {}", err);
-                                break;
-                            }
+                            let access = match self.verify_peer_cert(&stream) {
+                                Ok(access) => access,
+                                Err(err) => {
+                                    log::error!("problem with peer cert: {}", err);
+                                    continue;
+                                }
+                            };
                             spawn_into_main_thread(async move {
                                 log::error!("Making new AsyncSslStream");
-                                wezterm_mux_server_impl::dispatch::process(AsyncSslStream::new(
-                                    stream,
-                                ))
+                                wezterm_mux_server_impl::dispatch::process(
+                                    AsyncSslStream::new(stream),
+                                    access,
+                                )
                                 .await
                                 .map_err(|e| {
                                     log::error!("process: {:?}", e);
@@ -180,6 +179,7 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
             )
         })?,
         acceptor,
+        tls_server.access_rules.clone(),
     );
     std::thread::spawn(move || {
         net_listener.run();
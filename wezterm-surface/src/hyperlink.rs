@@ -59,8 +59,41 @@ pub struct Rule {
     /// Which capture to highlight
     #[dynamic(default)]
     pub highlight: usize,
+
+    /// If non-empty, the rule only applies to panes that belong
+    /// to one of the named domains
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    pub domains: Vec<String>,
+
+    /// If non-empty, the rule only applies to panes whose foreground
+    /// process has one of these names.  The names are compared against
+    /// the final component of the path to the executable.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    pub processes: Vec<String>,
+
+    /// If set, clicking on the link emits an event with this name
+    /// rather than opening the URL.  The event receives the expanded
+    /// `format` string in place of the URL.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    pub event: Option<String>,
+
+    /// An optional format string, expanded in the same way as `format`,
+    /// that produces text to display while hovering over the link
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    #[dynamic(default)]
+    pub preview: Option<String>,
 }
 
+/// The hyperlink parameter used to record the event that should
+/// be emitted when an implicit link is clicked
+pub const HYPERLINK_EVENT_PARAM: &str = "event";
+/// The hyperlink parameter used to record the hover preview text
+/// of an implicit link
+pub const HYPERLINK_PREVIEW_PARAM: &str = "preview";
+
 struct RegexWrap(Regex);
 
 impl FromDynamic for RegexWrap {
@@ -146,7 +179,11 @@ impl<'t> Match<'t> {
     /// Expand replacements in the format string to yield the URL
     /// The replacement is as described on Rule::format.
     fn expand(&self) -> String {
-        let mut result = self.rule.format.clone();
+        self.expand_format(&self.rule.format)
+    }
+
+    fn expand_format(&self, format: &str) -> String {
+        let mut result = format.to_owned();
         // Start with the highest numbered capture and decrement.
         // This avoids ambiguity when replacing $11 vs $1.
         for n in (0..self.captures.len()).rev() {
@@ -179,9 +216,34 @@ impl Rule {
             regex: Regex::new(regex)?,
             format: format.to_owned(),
             highlight,
+            domains: Vec::new(),
+            processes: Vec::new(),
+            event: None,
+            preview: None,
         })
     }
 
+    /// Returns true if the rule applies to a pane in the named domain
+    /// whose foreground process is `process`
+    pub fn applies_to(&self, domain: &str, process: Option<&str>) -> bool {
+        if !self.domains.is_empty() && !self.domains.iter().any(|d| d == domain) {
+            return false;
+        }
+        if !self.processes.is_empty() {
+            return match process {
+                Some(process) => self.processes.iter().any(|p| p == process),
+                None => false,
+            };
+        }
+        true
+    }
+
+    /// Returns true if the rule is restricted to particular domains
+    /// or processes
+    pub fn is_scoped(&self) -> bool {
+        !self.domains.is_empty() || !self.processes.is_empty()
+    }
+
     /// Given a line of text from the terminal screen, and a set of
     /// rules, return the set of RuleMatches.
     pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
@@ -205,7 +267,24 @@ impl Rule {
             .into_iter()
             .map(|m| {
                 let url = m.expand();
-                let link = Arc::new(Hyperlink::new_implicit(url));
+                let link = if m.rule.event.is_some() || m.rule.preview.is_some() {
+                    let params = m
+                        .rule
+                        .event
+                        .as_ref()
+                        .map(|event| (HYPERLINK_EVENT_PARAM.to_string(), event.clone()))
+                        .into_iter()
+                        .chain(m.rule.preview.as_ref().map(|preview| {
+                            (
+                                HYPERLINK_PREVIEW_PARAM.to_string(),
+                                m.expand_format(preview),
+                            )
+                        }))
+                        .collect();
+                    Arc::new(Hyperlink::new_implicit_with_params(url, params))
+                } else {
+                    Arc::new(Hyperlink::new_implicit(url))
+                };
                 RuleMatch {
                     link,
                     range: m.range(),
@@ -250,6 +329,47 @@ mod test {
         );
     }
 
+    #[test]
+    fn rule_preview_and_event() {
+        let mut rule =
+            Rule::new(r"\b([A-Z]+)-(\d+)\b", "https://issues.example.com/$1/$2").unwrap();
+        rule.event = Some("open-issue".to_string());
+        rule.preview = Some("Issue $2 in project $1".to_string());
+
+        let matches = Rule::match_hyperlinks("see ABC-123", &[rule]);
+        assert_eq!(matches.len(), 1);
+        let link = &matches[0].link;
+        assert!(link.is_implicit());
+        assert_eq!(link.uri(), "https://issues.example.com/ABC/123");
+        assert_eq!(
+            link.params().get(HYPERLINK_EVENT_PARAM).map(String::as_str),
+            Some("open-issue")
+        );
+        assert_eq!(
+            link.params()
+                .get(HYPERLINK_PREVIEW_PARAM)
+                .map(String::as_str),
+            Some("Issue 123 in project ABC")
+        );
+    }
+
+    #[test]
+    fn rule_scope() {
+        let mut rule = Rule::new(r"\w+", "$0").unwrap();
+        assert!(!rule.is_scoped());
+        assert!(rule.applies_to("local", None));
+
+        rule.domains = vec!["SSH:host".to_string()];
+        assert!(rule.is_scoped());
+        assert!(!rule.applies_to("local", Some("bash")));
+        assert!(rule.applies_to("SSH:host", None));
+
+        rule.processes = vec!["git".to_string()];
+        assert!(!rule.applies_to("SSH:host", None));
+        assert!(!rule.applies_to("SSH:host", Some("bash")));
+        assert!(rule.applies_to("SSH:host", Some("git")));
+    }
+
     #[test]
     fn parse_with_parentheses() {
         fn assert_helper(test_uri: &str, expected_uri: &str, msg: &str) {
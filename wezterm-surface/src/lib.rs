@@ -287,6 +287,18 @@ impl Surface {
         seq
     }
 
+    /// Apply a series of changes without retaining them, as though they
+    /// had been added by `add_changes` and then pruned by
+    /// `flush_changes_older_than`.  This avoids copying the changes when
+    /// only the resulting contents of the surface are of interest.
+    pub fn apply_changes_unlogged(&mut self, changes: &[Change]) {
+        for change in changes {
+            self.apply_change(change);
+        }
+        self.seqno += changes.len();
+        self.changes.clear();
+    }
+
     /// Apply a change and return the sequence number at the end of the change.
     pub fn add_change<C: Into<Change>>(&mut self, change: C) -> SequenceNo {
         let seq = self.seqno;
@@ -803,6 +815,70 @@ impl Surface {
         self.diff_region(0, 0, self.width, self.height, other, 0, 0)
     }
 
+    /// Looks for a vertical shift of the whole screen that would make
+    /// more of the lines of `self` match those of `other` than already
+    /// do; for example, because `other` holds the same output after
+    /// a few more lines were appended.
+    /// Returns the `ScrollRegionUp` or `ScrollRegionDown` change that
+    /// performs the shift.  Applying it followed by the result of
+    /// `diff_screens` is usually much cheaper than repainting `other`.
+    /// Returns None if the surfaces have different dimensions, or if
+    /// no shift would preserve at least half of the non-blank lines
+    /// of `other`.
+    pub fn detect_scroll(&self, other: &Surface) -> Option<Change> {
+        if self.width != other.width || self.height != other.height || self.height < 2 {
+            return None;
+        }
+        let height = self.height;
+        let blank: Vec<bool> = other.lines.iter().map(Line::is_whitespace).collect();
+        let non_blank = blank.iter().filter(|b| !**b).count();
+
+        // For each shift, count the non-blank lines of other that match
+        // the line of self that is that many rows below them.  Index
+        // `height - 1` corresponds to no shift.
+        let mut shift_matches = vec![0usize; 2 * height - 1];
+        for (row, other_line) in other.lines.iter().enumerate() {
+            if blank[row] {
+                continue;
+            }
+            for (src, line) in self.lines.iter().enumerate() {
+                if same_line_contents(line, other_line) {
+                    shift_matches[src + height - 1 - row] += 1;
+                }
+            }
+        }
+
+        let mut best_shift = 0;
+        let mut best_matches = shift_matches[height - 1];
+        for count in 1..height as isize {
+            for shift in [count, -count] {
+                let num_matches = shift_matches[(shift + height as isize - 1) as usize];
+                if num_matches > best_matches {
+                    best_shift = shift;
+                    best_matches = num_matches;
+                }
+            }
+        }
+
+        if best_shift == 0 || best_matches * 2 < non_blank {
+            return None;
+        }
+        let scroll_count = best_shift.unsigned_abs();
+        Some(if best_shift > 0 {
+            Change::ScrollRegionUp {
+                first_row: 0,
+                region_size: height,
+                scroll_count,
+            }
+        } else {
+            Change::ScrollRegionDown {
+                first_row: 0,
+                region_size: height,
+                scroll_count,
+            }
+        })
+    }
+
     /// Draw the contents of `other` into self at the specified coordinates.
     /// The required updates are recorded as Change entries as well as stored
     /// in the screen line/cell data.
@@ -842,6 +918,13 @@ impl Surface {
     }
 }
 
+/// Returns true if the two lines display the same cells, ignoring
+/// their sequence numbers.
+/// Lines that use different storage are considered to be different.
+fn same_line_contents(line: &Line, other: &Line) -> bool {
+    line.cells == other.cells
+}
+
 /// Populate `diff_state` with changes to replace contents of `line` in range [x,x+width)
 /// with the contents of `other_line` in range [other_x,other_x+width).
 fn diff_line(
@@ -1626,6 +1709,52 @@ mod test {
         assert_eq!(s.screen_chars_to_string(), "abcd\n");
     }
 
+    #[test]
+    fn detect_scroll() {
+        let mut s = Surface::new(4, 4);
+        s.add_change("one\r\ntwo\r\nthr\r\nfour");
+
+        let mut scrolled = Surface::new(4, 4);
+        scrolled.add_change("two\r\nthr\r\nfour\r\nfive");
+        let scroll = s.detect_scroll(&scrolled).unwrap();
+        assert_eq!(
+            scroll,
+            Change::ScrollRegionUp {
+                first_row: 0,
+                region_size: 4,
+                scroll_count: 1,
+            }
+        );
+
+        // After scrolling, only the new line needs to be painted
+        s.add_change(scroll);
+        let changes = s.diff_screens(&scrolled);
+        s.add_changes(changes.clone());
+        assert_eq!(
+            s.screen_chars_to_string(),
+            scrolled.screen_chars_to_string()
+        );
+        assert!(changes.contains(&Change::Text("five".into())));
+        assert!(!changes.contains(&Change::Text("two ".into())));
+
+        let mut reversed = Surface::new(4, 4);
+        reversed.add_change("new\r\ntwo\r\nthr\r\nfour");
+        assert_eq!(
+            s.detect_scroll(&reversed),
+            Some(Change::ScrollRegionDown {
+                first_row: 0,
+                region_size: 4,
+                scroll_count: 1,
+            })
+        );
+
+        // Unrelated content doesn't scroll
+        let mut other = Surface::new(4, 4);
+        other.add_change("a\r\nb\r\nc\r\nd");
+        assert_eq!(s.detect_scroll(&other), None);
+        assert_eq!(s.detect_scroll(&Surface::new(4, 3)), None);
+    }
+
     #[test]
     fn diff_cursor_double_width() {
         let mut s = Surface::new(3, 1);
@@ -57,6 +57,10 @@ impl ListClientsCommand {
                         name: "FOCUS".to_string(),
                         alignment: Alignment::Right,
                     },
+                    Column {
+                        name: "MODE".to_string(),
+                        alignment: Alignment::Left,
+                    },
                     Column {
                         name: "SSH_AUTH_SOCK".to_string(),
                         alignment: Alignment::Left,
@@ -99,6 +103,7 @@ impl ListClientsCommand {
                         info.focused_pane_id
                             .map(|id| id.to_string())
                             .unwrap_or_else(String::new),
+                        info.permission.to_string(),
                         info.client_id
                             .ssh_auth_sock
                             .as_deref()
@@ -128,6 +133,7 @@ struct CliListClientsResultItem {
     workspace: String,
     focused_pane_id: Option<mux::pane::PaneId>,
     ssh_auth_sock: Option<String>,
+    mode: String,
 }
 
 impl From<mux::client::ClientInfo> for CliListClientsResultItem {
@@ -140,6 +146,7 @@ impl From<mux::client::ClientInfo> for CliListClientsResultItem {
             active_workspace,
             focused_pane_id,
             client_id,
+            permission,
             ..
         } = client_info;
 
@@ -165,6 +172,7 @@ impl From<mux::client::ClientInfo> for CliListClientsResultItem {
             workspace: active_workspace.as_deref().unwrap_or("").to_string(),
             focused_pane_id,
             ssh_auth_sock: ssh_auth_sock.as_ref().map(|s| s.to_string()),
+            mode: permission.to_string(),
         }
     }
 }
@@ -0,0 +1,13 @@
+use clap::Parser;
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone)]
+pub struct MetricsCommand {}
+
+impl MetricsCommand {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let metrics = client.get_metrics().await?;
+        print!("{}", metrics.text);
+        Ok(())
+    }
+}
@@ -12,7 +12,10 @@ mod get_text;
 mod kill_pane;
 mod list;
 mod list_clients;
+mod metrics;
 mod move_pane_to_new_tab;
+mod output_log;
+mod output_pipe;
 mod proxy;
 mod rename_workspace;
 mod send_text;
@@ -136,6 +139,26 @@ Outputs the pane-id for the newly created pane on success"
     #[command(name = "kill-pane", rename_all = "kebab")]
     KillPane(kill_pane::KillPane),
 
+    /// Start logging the output of a pane to a file
+    #[command(name = "start-output-log", rename_all = "kebab")]
+    StartOutputLog(output_log::StartOutputLog),
+
+    /// Stop logging the output of a pane
+    #[command(name = "stop-output-log", rename_all = "kebab")]
+    StopOutputLog(output_log::StopOutputLog),
+
+    /// Start piping the output of a pane through a command
+    #[command(
+        name = "start-output-pipe",
+        rename_all = "kebab",
+        trailing_var_arg = true
+    )]
+    StartOutputPipe(output_pipe::StartOutputPipe),
+
+    /// Stop piping the output of a pane through a command
+    #[command(name = "stop-output-pipe", rename_all = "kebab")]
+    StopOutputPipe(output_pipe::StopOutputPipe),
+
     /// Activate (focus) a pane
     #[command(name = "activate-pane", rename_all = "kebab")]
     ActivatePane(activate_pane::ActivatePane),
@@ -160,6 +183,11 @@ Outputs the pane-id for the newly created pane on success"
     #[command(name = "rename-workspace", rename_all = "kebab")]
     RenameWorkspace(rename_workspace::RenameWorkspace),
 
+    /// Print the metrics collected by wezterm-mux-server in the
+    /// Prometheus text format
+    #[command(name = "metrics", rename_all = "kebab")]
+    Metrics(metrics::MetricsCommand),
+
     /// Zoom, unzoom, or toggle zoom state
     #[command(name = "zoom-pane", rename_all = "kebab")]
     ZoomPane(zoom_pane::ZoomPane),
@@ -192,12 +220,17 @@ async fn run_cli_async(opts: &crate::Opt, cli: CliCommand) -> anyhow::Result<()>
         CliSubCommand::ActivatePaneDirection(cmd) => cmd.run(client).await,
         CliSubCommand::GetPaneDirection(cmd) => cmd.run(client).await,
         CliSubCommand::KillPane(cmd) => cmd.run(client).await,
+        CliSubCommand::StartOutputLog(cmd) => cmd.run(client).await,
+        CliSubCommand::StopOutputLog(cmd) => cmd.run(client).await,
+        CliSubCommand::StartOutputPipe(cmd) => cmd.run(client).await,
+        CliSubCommand::StopOutputPipe(cmd) => cmd.run(client).await,
         CliSubCommand::ActivatePane(cmd) => cmd.run(client).await,
         CliSubCommand::AdjustPaneSize(cmd) => cmd.run(client).await,
         CliSubCommand::ActivateTab(cmd) => cmd.run(client).await,
         CliSubCommand::SetTabTitle(cmd) => cmd.run(client).await,
         CliSubCommand::SetWindowTitle(cmd) => cmd.run(client).await,
         CliSubCommand::RenameWorkspace(cmd) => cmd.run(client).await,
+        CliSubCommand::Metrics(cmd) => cmd.run(client).await,
         CliSubCommand::ZoomPane(cmd) => cmd.run(client).await,
     }
 }
@@ -0,0 +1,77 @@
+use clap::Parser;
+use config::keyassignment::PaneOutputLog;
+use mux::pane::PaneId;
+use std::path::PathBuf;
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone)]
+pub struct StartOutputLog {
+    /// Specify the target pane.
+    /// The default is to use the current pane based on the
+    /// environment variable WEZTERM_PANE.
+    #[arg(long)]
+    pane_id: Option<PaneId>,
+
+    /// Remove escape sequences from the output, leaving only
+    /// printable text, tabs and line breaks
+    #[arg(long)]
+    strip_ansi: bool,
+
+    /// Rotate the log file before it would grow larger than
+    /// this many bytes
+    #[arg(long)]
+    max_size: Option<u64>,
+
+    /// Rotate the log file once it has been written to for
+    /// this many seconds
+    #[arg(long)]
+    rotate_interval_seconds: Option<u64>,
+
+    /// How many rotated log files to keep
+    #[arg(long, default_value = "5")]
+    max_files: usize,
+
+    /// The file that the output is appended to.
+    /// It is opened by the mux server that hosts the pane.
+    #[arg(value_hint=clap::ValueHint::FilePath)]
+    path: PathBuf,
+}
+
+impl StartOutputLog {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let pane_id = client.resolve_pane_id(self.pane_id).await?;
+        let path = std::env::current_dir()?.join(&self.path);
+        client
+            .set_pane_output_log(codec::SetPaneOutputLog {
+                pane_id,
+                log: Some(PaneOutputLog {
+                    path,
+                    strip_ansi: self.strip_ansi,
+                    max_size: self.max_size,
+                    rotate_interval_seconds: self.rotate_interval_seconds,
+                    max_files: self.max_files,
+                }),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct StopOutputLog {
+    /// Specify the target pane.
+    /// The default is to use the current pane based on the
+    /// environment variable WEZTERM_PANE.
+    #[arg(long)]
+    pane_id: Option<PaneId>,
+}
+
+impl StopOutputLog {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let pane_id = client.resolve_pane_id(self.pane_id).await?;
+        client
+            .set_pane_output_log(codec::SetPaneOutputLog { pane_id, log: None })
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use crate::cli::resolve_relative_cwd;
+use clap::{Parser, ValueHint};
+use config::keyassignment::PaneOutputPipe;
+use mux::pane::PaneId;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone)]
+pub struct StartOutputPipe {
+    /// Specify the target pane.
+    /// The default is to use the current pane based on the
+    /// environment variable WEZTERM_PANE.
+    #[arg(long)]
+    pane_id: Option<PaneId>,
+
+    /// Parse the output of the command and inject it into the
+    /// pane, as though the pane had output it
+    #[arg(long)]
+    inject_output: bool,
+
+    /// Specify the current working directory for the command.
+    /// The default is the working directory of the mux server.
+    #[arg(long, value_parser, value_hint=ValueHint::DirPath)]
+    cwd: Option<OsString>,
+
+    /// The command to pipe the output of the pane through.
+    /// For example: `wezterm cli start-output-pipe -- grep --line-buffered ERROR`.
+    /// It is run by the mux server that hosts the pane.
+    #[arg(value_parser, value_hint=ValueHint::CommandWithArguments, num_args=1.., required=true)]
+    prog: Vec<OsString>,
+}
+
+impl StartOutputPipe {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let pane_id = client.resolve_pane_id(self.pane_id).await?;
+        let args = self
+            .prog
+            .iter()
+            .map(|arg| {
+                arg.to_str()
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| anyhow::anyhow!("argument {:?} is not valid UTF-8", arg))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        client
+            .set_pane_output_pipe(codec::SetPaneOutputPipe {
+                pane_id,
+                pipe: Some(PaneOutputPipe {
+                    args,
+                    cwd: resolve_relative_cwd(self.cwd.clone())?.map(PathBuf::from),
+                    inject_output: self.inject_output,
+                }),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct StopOutputPipe {
+    /// Specify the target pane.
+    /// The default is to use the current pane based on the
+    /// environment variable WEZTERM_PANE.
+    #[arg(long)]
+    pane_id: Option<PaneId>,
+}
+
+impl StopOutputPipe {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let pane_id = client.resolve_pane_id(self.pane_id).await?;
+        client
+            .set_pane_output_pipe(codec::SetPaneOutputPipe {
+                pane_id,
+                pipe: None,
+            })
+            .await?;
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use clap::Parser;
 use codec::{Pdu, SetClientId};
 use config::ConfigHandle;
 use mux::activity::Activity;
-use mux::client::ClientId;
+use mux::client::{ClientId, ClientPermission};
 use mux::Mux;
 use std::io::{Read, Write};
 use std::sync::Arc;
@@ -33,6 +33,7 @@ impl ProxyCommand {
         let pdu = Pdu::SetClientId(SetClientId {
             client_id: ClientId::new(),
             is_proxy: true,
+            permission: ClientPermission::ReadWrite,
         });
         let serial = 1;
         pdu.encode(&mut stream, serial)?;
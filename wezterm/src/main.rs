@@ -594,10 +594,12 @@ impl ImgCatCommand {
                         KeyEvent {
                             key: KeyCode::Enter | KeyCode::Escape,
                             modifiers: _,
+                            ..
                         }
                         | KeyEvent {
                             key: KeyCode::Char('c') | KeyCode::Char('d'),
                             modifiers: Modifiers::CTRL,
+                            ..
                         },
                     ) => {
                         break;